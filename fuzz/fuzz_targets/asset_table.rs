@@ -0,0 +1,11 @@
+#![no_main]
+use bk_asset_tool::banjo_kazooie::AssetTable;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(table) = AssetTable::try_new(data) {
+        for e in table.entries().filter(|e| !e.compressed) {
+            let _ = table.bytes(e.uid);
+        }
+    }
+});
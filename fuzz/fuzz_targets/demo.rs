@@ -0,0 +1,7 @@
+#![no_main]
+use bk_asset_tool::banjo_kazooie::demo::DemoButtonFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = DemoButtonFile::try_from_bytes(data);
+});
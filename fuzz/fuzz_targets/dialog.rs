@@ -0,0 +1,7 @@
+#![no_main]
+use bk_asset_tool::banjo_kazooie::asset::Dialog;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Dialog::try_from_bytes(data);
+});
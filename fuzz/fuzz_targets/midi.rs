@@ -0,0 +1,9 @@
+#![no_main]
+use bk_asset_tool::banjo_kazooie::sequence::Sequence;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(seq) = Sequence::from_bytes(data){
+        let _ = seq.to_midi();
+    }
+});
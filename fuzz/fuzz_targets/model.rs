@@ -0,0 +1,10 @@
+#![no_main]
+use bk_asset_tool::banjo_kazooie::model::Model;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(model) = Model::try_from_bytes(data){
+        let _ = model.triangles();
+        let _ = model.validate();
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+use bk_asset_tool::banjo_kazooie::asset::{GruntyQuestion, QuizQuestion};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = QuizQuestion::try_from_bytes(data);
+    let _ = GruntyQuestion::try_from_bytes(data);
+});
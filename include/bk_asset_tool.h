@@ -0,0 +1,89 @@
+#ifndef BK_ASSET_TOOL_H
+#define BK_ASSET_TOOL_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * an asset bin opened with `bk_bin_open` or `bk_bin_from_bytes`
+ */
+typedef struct BkAssetBin BkAssetBin;
+
+typedef struct BkAssetInfo {
+  bool compressed;
+  uint16_t flags;
+  uintptr_t segment;
+} BkAssetInfo;
+
+#if defined(BK_ASSET_TOOL_FS)
+/**
+ * NULL if the file can't be read or decoded. Free with `bk_bin_free`
+ */
+BkAssetBin *bk_bin_open(const char *path);
+#endif
+
+/**
+ * copies what it needs, `bytes` can be freed after
+ */
+BkAssetBin *bk_bin_from_bytes(const uint8_t *bytes, uintptr_t len);
+
+void bk_bin_free(BkAssetBin *bin);
+
+/**
+ * number of table entries, uids go from 0 up to it
+ */
+uintptr_t bk_bin_asset_count(const BkAssetBin *bin);
+
+/**
+ * 0 on success, -1 if the uid is outside the table
+ */
+int bk_bin_asset_info(const BkAssetBin *bin, uintptr_t uid, BkAssetInfo *out);
+
+/**
+ * decompressed bytes of an entry, NULL for empty entries. The length goes in
+ * `out_len`. Free with `bk_bytes_free`
+ */
+uint8_t *bk_bin_asset_bytes(const BkAssetBin *bin, uintptr_t uid, uintptr_t *out_len);
+
+/**
+ * a buffer to pass bytes in with, for hosts like wasm that can't hand over
+ * their own memory. Free with `bk_bytes_free`
+ */
+uint8_t *bk_alloc(uintptr_t len);
+
+void bk_bytes_free(uint8_t *bytes, uintptr_t len);
+
+/**
+ * replaces a non-empty entry with decompressed bytes, keeping its flags. 0 on
+ * success, -1 if the uid is empty or outside the table
+ */
+int bk_bin_replace_asset(BkAssetBin *bin, uintptr_t uid, const uint8_t *bytes, uintptr_t len);
+
+#if defined(BK_ASSET_TOOL_FS)
+/**
+ * builds the bin the same way construct does. 0 on success, -1 on failure
+ */
+int bk_bin_write(BkAssetBin *bin, const char *path);
+#endif
+
+/**
+ * the whole bin, built the same way construct does. Free with `bk_bytes_free`
+ */
+uint8_t *bk_bin_to_bytes(BkAssetBin *bin, uintptr_t *out_len);
+
+/**
+ * a dialog or question as the same utf-8 JSON document extract writes, NULL
+ * for other types. Free with `bk_bytes_free`
+ */
+uint8_t *bk_bin_asset_json(const BkAssetBin *bin, uintptr_t uid, uintptr_t *out_len);
+
+/**
+ * one frame of a sprite as png file bytes, NULL if it isn't a sprite or has
+ * no such frame. Free with `bk_bytes_free`
+ */
+uint8_t *bk_bin_sprite_png(const BkAssetBin *bin, uintptr_t uid, uintptr_t frame, uintptr_t *out_len);
+
+#endif /* BK_ASSET_TOOL_H */
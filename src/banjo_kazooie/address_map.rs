@@ -0,0 +1,21 @@
+use std::io::{self, Write};
+
+use super::AssetTable;
+
+// where each entry's data is, from the start of the bin and in the rom, for
+// finding an asset in an emulator's memory viewer or writing a patch. Entries
+// are loaded into heap memory at run time, so they have no fixed ram address.
+
+pub fn write(out: &mut dyn Write, in_bytes: &[u8], rom_base: usize) -> io::Result<()>{
+    let table = AssetTable::new(in_bytes);
+    let data_start = table.data_start();
+    writeln!(out, "rom_base: 0x{:X}", rom_base)?;
+    writeln!(out, "table: {{offset: 0x0, rom: 0x{:X}, size: 0x{:X}}}", rom_base, data_start)?;
+    writeln!(out, "assets:")?;
+    for e in table.entries().filter(|e|{e.flags != 4}){
+        let offset = data_start + e.offset;
+        writeln!(out, "  - {{uid: 0x{:04X}, offset: 0x{:X}, rom: 0x{:X}, size: 0x{:X}, compressed: {:5}, segment: {}}}",
+            e.uid, offset, rom_base + offset, e.size, e.compressed, e.segment)?;
+    }
+    return Ok(())
+}
@@ -0,0 +1,254 @@
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::asset::{Asset, AssetType, ExtractOptions};
+use super::deps;
+use super::error::{ByteReader, ParseError};
+
+// bone animations: a frame range, then one element per animated bone channel,
+// each a list of keyframes. Values are 1/64ths of a degree, unit or scale.
+
+pub const TRANSFORM_NAMES : [&str; 9] = [
+    "rotate_x", "rotate_y", "rotate_z",
+    "scale_x", "scale_y", "scale_z",
+    "translate_x", "translate_y", "translate_z",
+];
+
+/// one keyframe, packed as 2 interpolation bits over a 14 bit frame, then the value
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimKey{
+    pub frame : u16,
+    pub interpolation : u8,
+    pub value : i16,
+}
+
+/// the keyframes of one channel of one bone
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimElement{
+    pub bone : u16, //12 bits
+    pub transform : u8, //4 bits, see TRANSFORM_NAMES
+    pub keys : Vec<AnimKey>,
+}
+
+impl AnimElement{
+    /// the channel at `frame` in degrees, units or scale, straight between keys
+    /// and held before the first and after the last
+    pub fn value_at(&self, frame: u16) -> f32{
+        let after = self.keys.iter().position(|k|{k.frame > frame});
+        let value = match after{
+            _ if self.keys.is_empty() => 0.0,
+            Some(0) => self.keys[0].value as f32,
+            None => self.keys.last().unwrap().value as f32,
+            Some(i) => {
+                let (a, b) = (&self.keys[i - 1], &self.keys[i]);
+                let t = (frame - a.frame) as f32/(b.frame - a.frame) as f32;
+                a.value as f32 + t*(b.value as f32 - a.value as f32)
+            },
+        };
+        return value/64.0
+    }
+}
+
+pub struct Animation{
+    pub start_frame : u16,
+    pub end_frame : u16,
+    pub elements : Vec<AnimElement>,
+    padding : usize, //zero bytes after the last element
+    raw : Option<Vec<u8>>, //set for files that don't decode back to the same bytes
+}
+
+impl Animation{
+    pub fn from_bytes(in_bytes: &[u8])->Animation{
+        Animation::try_from_bytes(in_bytes).unwrap_or_else(|_|{
+            Animation{start_frame: 0, end_frame: 0, elements: Vec::new(), padding: 0, raw: Some(in_bytes.to_vec())}
+        })
+    }
+
+    /// the decoded animation, or why `in_bytes` isn't one that encodes back to the same bytes
+    pub fn try_from_bytes(in_bytes: &[u8]) -> Result<Animation, ParseError>{
+        let anim = Animation::decode(in_bytes)?;
+        if anim.to_bytes() != in_bytes{
+            return Err(ParseError::Invalid("animation doesn't encode back to the same bytes".to_string()))
+        }
+        return Ok(anim)
+    }
+
+    fn decode(in_bytes: &[u8]) -> Result<Animation, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let start_frame = r.u16()?;
+        let end_frame = r.u16()?;
+        let element_cnt = r.u16()? as usize;
+        if r.u16()? != 0{
+            return Err(ParseError::Invalid("animation header padding isn't zero".to_string()))
+        }
+        let mut elements : Vec<AnimElement> = Vec::new();
+        for _ in 0..element_cnt{
+            let id = r.u16()?;
+            let key_cnt = r.u16()? as usize;
+            let mut keys : Vec<AnimKey> = Vec::new();
+            for _ in 0..key_cnt{
+                let packed = r.u16()?;
+                keys.push(AnimKey{frame: packed & 0x3FFF, interpolation: (packed >> 14) as u8, value: r.u16()? as i16});
+            }
+            elements.push(AnimElement{bone: id >> 4, transform: (id & 0xF) as u8, keys: keys});
+        }
+        let tail = r.rest();
+        if tail.iter().any(|b|{*b != 0}){
+            return Err(ParseError::Invalid(format!("animation has nonzero bytes after its last element, at 0x{:X}", r.offset)))
+        }
+        Ok(Animation{start_frame: start_frame, end_frame: end_frame, elements: elements, padding: tail.len(), raw: None})
+    }
+
+    /// a `.anim.yaml`, or a raw `.anim.bin` from older extractions
+    pub fn read(path: &Path) -> Animation{
+        if path.extension().unwrap() == "bin"{
+            return Animation::from_bytes(&deps::read(path).unwrap())
+        }
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        assert_eq!(doc["type"].as_str(), Some("Animation"), "{:?} is not an animation", path);
+        if let Some(bin) = doc["bin"].as_str(){
+            return Animation::from_bytes(&deps::read(path.parent().unwrap().join(bin)).unwrap())
+        }
+        let int = |y: &Yaml, what: &str, max: i64| -> i64{
+            let i = y.as_i64().unwrap_or_else(||{panic!("{:?}: {} has to be an integer", path, what)});
+            assert!(0 <= i && i <= max, "{:?}: {} {} is outside 0..={}", path, what, i, max);
+            i
+        };
+        let elements = doc["elements"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter()
+            .map(|e|{
+                let transform = match &e["channel"]{
+                    Yaml::String(name) => TRANSFORM_NAMES.iter().position(|n|{n == name})
+                        .unwrap_or_else(||{panic!("{:?}: unknown channel \"{}\", try one of {:?}", path, name, TRANSFORM_NAMES)}) as u8,
+                    y => int(y, "channel", 0xF) as u8,
+                };
+                let keys = e["keys"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter()
+                    .map(|k|{
+                        let k = k.as_vec().filter(|k|{k.len() == 3}).unwrap_or_else(||{panic!("{:?}: keys are [frame, value, interpolation]", path)});
+                        let value = k[1].as_i64().unwrap_or_else(||{panic!("{:?}: key values have to be integers", path)});
+                        assert!(i16::MIN as i64 <= value && value <= i16::MAX as i64, "{:?}: key value {} doesn't fit 16 bits", path, value);
+                        AnimKey{frame: int(&k[0], "frame", 0x3FFF) as u16, interpolation: int(&k[2], "interpolation", 3) as u8, value: value as i16}
+                    })
+                    .collect();
+                AnimElement{bone: int(&e["bone"], "bone", 0xFFF) as u16, transform: transform, keys: keys}
+            })
+            .collect();
+        Animation{
+            start_frame: int(&doc["start_frame"], "start_frame", 0xFFFF) as u16,
+            end_frame: int(&doc["end_frame"], "end_frame", 0xFFFF) as u16,
+            elements: elements,
+            padding: doc["padding"].as_i64().unwrap_or(0) as usize,
+            raw: None,
+        }
+    }
+
+    /// stretch (scale > 1) or compress the timing around the start frame. Keys that
+    /// land on the same frame are merged, keeping the later one; returns how many were dropped.
+    pub fn retime(&mut self, scale: f32) -> usize{
+        assert!(self.raw.is_none(), "this animation doesn't decode, it can't be retimed");
+        assert!(scale > 0.0, "retime scale has to be positive");
+        let start = self.start_frame as f32;
+        let map = |f : u16|->u16{
+            let new_f = (start + (f as f32 - start)*scale).round();
+            assert!(0.0 <= new_f && new_f <= 0x3FFF as f32, "frame {} would move to {}, outside the 14 bit frame range", f, new_f);
+            new_f as u16
+        };
+        self.end_frame = map(self.end_frame);
+        let mut dropped = 0;
+        for e in self.elements.iter_mut(){
+            let mut keys : Vec<AnimKey> = Vec::new();
+            for k in e.keys.iter(){
+                let k = AnimKey{frame: map(k.frame), ..k.clone()};
+                match keys.last_mut(){
+                    Some(last) if last.frame == k.frame => {*last = k; dropped += 1;},
+                    _ => keys.push(k),
+                }
+            }
+            e.keys = keys;
+        }
+        return dropped
+    }
+
+    /// one `bone_N.csv` per animated bone in `dir`, with a row for every frame
+    /// from the start to the end frame. Returns how many were written
+    pub fn write_curves(&self, dir: &Path) -> usize{
+        assert!(self.raw.is_none(), "this animation doesn't decode, it has no curves");
+        deps::create_dir_all(dir).unwrap_or_else(|e|{panic!("{:?}: {}", dir, e)});
+        let mut bones : Vec<u16> = self.elements.iter().map(|e|{e.bone}).collect();
+        bones.sort();
+        bones.dedup();
+        //rotate then translate, in TRANSFORM_NAMES order
+        let columns : [u8; 6] = [0, 1, 2, 6, 7, 8];
+        for bone in bones.iter(){
+            let path = dir.join(format!("bone_{}.csv", bone));
+            let mut out = deps::create(&path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+            writeln!(out, "frame,rx,ry,rz,tx,ty,tz").unwrap();
+            let channels : Vec<Option<&AnimElement>> = columns.iter()
+                .map(|t|{self.elements.iter().find(|e|{e.bone == *bone && e.transform == *t})})
+                .collect();
+            for frame in self.start_frame..=self.end_frame{
+                let values : Vec<String> = channels.iter().map(|e|{format!("{}", e.map_or(0.0, |e|{e.value_at(frame)}))}).collect();
+                writeln!(out, "{},{}", frame, values.join(",")).unwrap();
+            }
+        }
+        return bones.len()
+    }
+
+    pub fn transform_name(transform: u8) -> Option<&'static str>{
+        TRANSFORM_NAMES.get(transform as usize).cloned()
+    }
+}
+
+impl Asset for Animation{
+    fn to_bytes(&self)->Vec<u8>{
+        if let Some(raw) = self.raw.as_ref(){
+            return raw.clone();
+        }
+        let mut out = self.start_frame.to_be_bytes().to_vec();
+        out.extend_from_slice(&self.end_frame.to_be_bytes());
+        out.extend_from_slice(&(self.elements.len() as u16).to_be_bytes());
+        out.extend_from_slice(&[0; 2]);
+        for e in self.elements.iter(){
+            out.extend_from_slice(&((e.bone << 4) | e.transform as u16).to_be_bytes());
+            out.extend_from_slice(&(e.keys.len() as u16).to_be_bytes());
+            for k in e.keys.iter(){
+                out.extend_from_slice(&(((k.interpolation as u16) << 14) | k.frame).to_be_bytes());
+                out.extend_from_slice(&k.value.to_be_bytes());
+            }
+        }
+        out.resize(out.len() + self.padding, 0);
+        return out
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::Animation;
+    }
+
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let mut anim_f = deps::create(path).unwrap();
+        writeln!(anim_f, "type: Animation").unwrap();
+        if let Some(raw) = self.raw.as_ref(){
+            //keep files that don't decode cleanly as they are
+            let base_name = path.file_name().unwrap().to_str().unwrap().trim_end_matches(".anim.yaml");
+            let bin_name = format!("{}.anim.bin", base_name);
+            deps::write(path.parent().unwrap().join(&bin_name), raw).unwrap();
+            writeln!(anim_f, "bin: {:?}", bin_name).unwrap();
+            return;
+        }
+        writeln!(anim_f, "start_frame: {}", self.start_frame).unwrap();
+        writeln!(anim_f, "end_frame: {}", self.end_frame).unwrap();
+        if self.padding != 0{
+            writeln!(anim_f, "padding: {}", self.padding).unwrap();
+        }
+        writeln!(anim_f, "# keys: [frame, value in 1/64ths, interpolation]").unwrap();
+        writeln!(anim_f, "elements:").unwrap();
+        for e in self.elements.iter(){
+            let channel = match Animation::transform_name(e.transform){
+                Some(name) => String::from(name),
+                None => format!("{}", e.transform),
+            };
+            let keys : Vec<String> = e.keys.iter().map(|k|{format!("[{}, {}, {}]", k.frame, k.value, k.interpolation)}).collect();
+            writeln!(anim_f, "  - {{bone: {}, channel: {}, keys: [{}]}}", e.bone, channel, keys.join(", ")).unwrap();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::path::Path;
+use yaml_rust::Yaml;
+
+use super::deps;
+use super::names::NameMap;
+
+// free-form `notes:` and `tags:` that teams put on assets.yaml entries and in
+// the yaml of each asset. The tool never reads them for anything, it only
+// carries them over when a bin is extracted again into the same folder:
+// entries get theirs back in assets.yaml, and each asset's lines are appended
+// as they were to the file written for it.
+
+pub const KEYS : [&str; 2] = ["notes", "tags"];
+
+#[derive(Clone, Default)]
+pub struct Notes{
+    pub notes : Option<String>, //from the assets.yaml entry
+    pub tags : Vec<String>,
+    pub file_lines : Vec<String>, //from the asset's own yaml, as written
+}
+
+impl Notes{
+    pub fn is_empty(&self) -> bool{
+        return self.notes.is_none() && self.tags.is_empty() && self.file_lines.is_empty()
+    }
+
+    /// `notes:` and `tags:` of an assets.yaml entry, `tags` can be one string
+    pub fn from_entry(yaml: &Yaml) -> Notes{
+        let tags = match &yaml["tags"]{
+            Yaml::Array(tags) => tags.iter().map(scalar).collect(),
+            Yaml::BadValue | Yaml::Null => Vec::new(),
+            t => vec![scalar(t)],
+        };
+        let notes = match &yaml["notes"]{
+            Yaml::BadValue | Yaml::Null => None,
+            n => Some(scalar(n)),
+        };
+        return Notes{notes: notes, tags: tags, file_lines: Vec::new()}
+    }
+
+    /// the entry's notes and tags as more keys of its flow mapping in assets.yaml
+    pub fn entry_keys(&self) -> String{
+        let mut out = String::new();
+        if let Some(n) = self.notes.as_ref(){
+            out += &format!(", notes: {:?}", n);
+        }
+        if !self.tags.is_empty(){
+            let tags : Vec<String> = self.tags.iter().map(|t|{if is_plain(t) {t.clone()} else {format!("{:?}", t)}}).collect();
+            out += &format!(", tags: [{}]", tags.join(", "));
+        }
+        return out
+    }
+}
+
+fn scalar(yaml: &Yaml) -> String{
+    return match yaml{
+        Yaml::String(s) | Yaml::Real(s) => s.clone(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        y => panic!("notes and tags are text, not {:?}", y),
+    }
+}
+
+/// tags that read back as the same string without quotes
+fn is_plain(tag: &str) -> bool{
+    return tag.chars().next().map_or(false, |c|{c.is_ascii_alphabetic()})
+        && tag.chars().all(|c|{c.is_ascii_alphanumeric() || c == '_' || c == '-'})
+        && !["true", "false", "null", "yes", "no", "on", "off"].contains(&tag.to_ascii_lowercase().as_str())
+}
+
+/// the yaml descriptors extract writes for each type, the only files notes are
+/// read from and written back to. Raw copies, pngs, midis, objs and C next to
+/// them, and json text, are left alone
+const DESCRIPTORS : [&str; 9] = [".dialog", ".grunty_q", ".quiz_q", ".demo", ".seq.yaml", ".model.yaml", ".lvl_setup.yaml", ".anim.yaml", ".sprite.yaml"];
+
+pub fn holds_notes(relative_path: &str) -> bool{
+    return DESCRIPTORS.iter().any(|ext|{relative_path.ends_with(ext)})
+}
+
+/// top level `notes:` and `tags:` of an asset's yaml and the lines under them
+pub fn file_lines(text: &str) -> Vec<String>{
+    let mut out : Vec<String> = Vec::new();
+    let mut inside = false;
+    for line in text.lines(){
+        let top_level = !line.is_empty() && !line.starts_with(' ') && !line.starts_with('\t');
+        if top_level{
+            let key = line.split(':').next().unwrap_or("").trim();
+            //a block list under the key can start at column 0
+            inside = KEYS.contains(&key) || (inside && line.starts_with("- "));
+        }
+        if inside{
+            out.push(line.to_string());
+        }
+    }
+    while out.last().map_or(false, |l|{l.trim().is_empty()}){
+        out.pop();
+    }
+    return out
+}
+
+/// the notes of every entry of an earlier extraction's assets.yaml, with the
+/// ones in the files it lists, by uid
+pub fn read(yaml_path: &Path, names: &NameMap) -> HashMap<usize, Notes>{
+    let folder = yaml_path.parent().unwrap();
+    let doc = &super::load_index(yaml_path, names);
+    let mut out : HashMap<usize, Notes> = HashMap::new();
+    for y in doc["files"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter(){
+        let uid = y["uid"].as_i64().unwrap() as usize;
+        let mut notes = Notes::from_entry(y);
+        let path = y["relative_path"].as_str().filter(|p|{holds_notes(p)});
+        if let Some(text) = path.and_then(|p|{deps::read_to_string(folder.join(p)).ok()}){
+            notes.file_lines = file_lines(&text);
+        }
+        if !notes.is_empty(){
+            out.insert(uid, notes);
+        }
+    }
+    return out
+}
@@ -0,0 +1,54 @@
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::path::Path;
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use super::deps;
+
+// a whole extracted folder as one zip, for `extract --archive` and
+// `construct --from-archive`. Files are deflated, every file gets the same
+// 1980-01-01 timestamp and they're in path order, so the same extraction always
+// gives the same zip.
+
+/// `dir` and everything in it as a zip, paths from `dir`. Returns the file count
+pub fn write_dir<W: Write + Seek>(out: W, dir: &Path) -> io::Result<usize>{
+    let paths = deps::walk(dir)?;
+    let mut zip = ZipWriter::new(out);
+    for path in paths.iter(){
+        let name = path.strip_prefix(dir).unwrap().components()
+            .map(|c|{c.as_os_str().to_string_lossy().into_owned()})
+            .collect::<Vec<String>>().join("/");
+        let bytes = deps::read(path)?;
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default())
+            .large_file(bytes.len() >= 0xFFFF_FFFF);
+        zip.start_file(name, options)?;
+        zip.write_all(&bytes)?;
+    }
+    zip.finish()?;
+    return Ok(paths.len())
+}
+
+/// every file of a zip written into `dir`, returns the file count. Names that
+/// would land outside `dir` are an error
+pub fn extract_to(bytes: &[u8], dir: &Path) -> Result<usize, String>{
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(|e|{format!("not a zip, {}", e)})?;
+    let mut written = 0;
+    for i in 0..zip.len(){
+        let mut file = zip.by_index(i).map_err(|e|{e.to_string()})?;
+        if file.is_dir(){
+            continue;
+        }
+        let relative = file.enclosed_name().map(|p|{p.to_path_buf()}).ok_or_else(||{format!("{} would be written outside of {:?}", file.name(), dir)})?;
+        //reading checks the crc
+        let mut data : Vec<u8> = Vec::new();
+        file.read_to_end(&mut data).map_err(|e|{format!("{} is corrupt, {}", file.name(), e)})?;
+        let path = dir.join(relative);
+        deps::create_dir_all(path.parent().unwrap()).map_err(|e|{format!("{:?}: {}", path, e)})?;
+        deps::write(&path, &data).map_err(|e|{format!("{:?}: {}", path, e)})?;
+        written += 1;
+    }
+    return Ok(written)
+}
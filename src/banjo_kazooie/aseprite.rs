@@ -0,0 +1,287 @@
+use std::convert::TryInto;
+use std::path::Path;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+use super::deps;
+
+// reader for Aseprite `.ase`/`.aseprite` files, just enough to flatten a frame
+// into the pixels a sprite frame is built from
+
+const LAYER_CHUNK : u16 = 0x2004;
+const CEL_CHUNK : u16 = 0x2005;
+const PALETTE_CHUNK : u16 = 0x2019;
+const OLD_PALETTE_CHUNK : u16 = 0x0004;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ColorDepth{
+    Indexed,
+    Grayscale,
+    Rgba,
+}
+
+impl ColorDepth{
+    fn bytes_per_pixel(&self) -> usize{
+        match self{
+            ColorDepth::Indexed => 1,
+            ColorDepth::Grayscale => 2,
+            ColorDepth::Rgba => 4,
+        }
+    }
+}
+
+pub struct Layer{
+    pub name : String,
+    pub visible : bool, //includes the visibility of the groups it is in
+    pub background : bool,
+    pub opacity : u8,
+}
+
+#[derive(Clone)]
+struct Cel{
+    layer : usize,
+    x : isize,
+    y : isize,
+    opacity : u8,
+    w : usize,
+    h : usize,
+    pixels : Vec<u8>,
+}
+
+pub struct AseFile{
+    pub w : usize,
+    pub h : usize,
+    pub depth : ColorDepth,
+    pub transparent_index : u8,
+    pub palette : Vec<u8>, //rgba32
+    pub layers : Vec<Layer>,
+    frames : Vec<Vec<Cel>>,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16{
+    u16::from_le_bytes(bytes[offset..offset+2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32{
+    u32::from_le_bytes(bytes[offset..offset+4].try_into().unwrap())
+}
+
+fn string_at(bytes: &[u8], offset: usize) -> String{
+    let len = u16_at(bytes, offset) as usize;
+    String::from_utf8_lossy(&bytes[offset + 2..offset + 2 + len]).to_string()
+}
+
+impl AseFile{
+    pub fn read(path: &Path) -> AseFile{
+        let bytes = deps::read(path).expect("could not read aseprite file");
+        return AseFile::from_bytes(&bytes, path)
+    }
+
+    pub fn from_bytes(in_bytes: &[u8], path: &Path) -> AseFile{
+        assert!(in_bytes.len() >= 128 && u16_at(in_bytes, 4) == 0xA5E0, "{:?} is not an aseprite file", path);
+        let frame_cnt = u16_at(in_bytes, 6) as usize;
+        let depth = match u16_at(in_bytes, 12){
+            8 => ColorDepth::Indexed,
+            16 => ColorDepth::Grayscale,
+            32 => ColorDepth::Rgba,
+            x => panic!("{:?} has unknown color depth {}", path, x),
+        };
+        let mut ase = AseFile{
+            w: u16_at(in_bytes, 8) as usize,
+            h: u16_at(in_bytes, 10) as usize,
+            depth: depth,
+            transparent_index: in_bytes[28],
+            palette: Vec::new(),
+            layers: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        //older files leave layer opacity unset
+        let layer_opacity_valid = u32_at(in_bytes, 14) & 1 != 0;
+        //visibility of the enclosing group at each child level
+        let mut group_visible : Vec<bool> = Vec::new();
+        let mut offset = 128;
+        for _ in 0..frame_cnt{
+            let frame_size = u32_at(in_bytes, offset) as usize;
+            assert_eq!(u16_at(in_bytes, offset + 4), 0xF1FA, "{:?}: bad frame magic at 0x{:X}", path, offset);
+            let chunk_cnt = match u32_at(in_bytes, offset + 12){
+                0 => u16_at(in_bytes, offset + 6) as usize,
+                n => n as usize,
+            };
+            let mut cels : Vec<Cel> = Vec::new();
+            let mut chunk_offset = offset + 16;
+            for _ in 0..chunk_cnt{
+                let chunk_size = u32_at(in_bytes, chunk_offset) as usize;
+                let chunk = &in_bytes[chunk_offset + 6..chunk_offset + chunk_size];
+                match u16_at(in_bytes, chunk_offset + 4){
+                    LAYER_CHUNK => {
+                        let flags = u16_at(chunk, 0);
+                        let layer_type = u16_at(chunk, 2);
+                        let level = u16_at(chunk, 4) as usize;
+                        group_visible.truncate(level);
+                        let visible = flags & 1 != 0 && group_visible.iter().all(|v|{*v});
+                        let name = string_at(chunk, 16);
+                        assert!(layer_type != 2, "{:?}: tilemap layer \"{}\" is not supported", path, name);
+                        if layer_type == 1{
+                            group_visible.push(visible);
+                        }
+                        ase.layers.push(Layer{name: name, visible: visible, background: flags & 8 != 0, opacity: if layer_opacity_valid {chunk[12]} else {0xFF}});
+                    },
+                    CEL_CHUNK => {
+                        let layer = u16_at(chunk, 0) as usize;
+                        let x = u16_at(chunk, 2) as i16 as isize;
+                        let y = u16_at(chunk, 4) as i16 as isize;
+                        let opacity = chunk[6];
+                        let data = &chunk[16..];
+                        match u16_at(chunk, 7){
+                            0 | 2 => {
+                                let (w, h) = (u16_at(data, 0) as usize, u16_at(data, 2) as usize);
+                                let pixels = match u16_at(chunk, 7){
+                                    0 => data[4..].to_vec(),
+                                    _ => decompress_to_vec_zlib(&data[4..]).expect("could not inflate aseprite cel"),
+                                };
+                                assert!(pixels.len() >= w*h*depth.bytes_per_pixel(), "{:?}: cel data is too short", path);
+                                cels.push(Cel{layer: layer, x: x, y: y, opacity: opacity, w: w, h: h, pixels: pixels});
+                            },
+                            1 => {
+                                //linked cel, reuses the cel of an earlier frame
+                                let src_frame = u16_at(data, 0) as usize;
+                                let linked = ase.frames[src_frame].iter().find(|c|{c.layer == layer}).expect("linked cel without a source").clone();
+                                cels.push(Cel{x: x, y: y, opacity: opacity, ..linked});
+                            },
+                            x => panic!("{:?}: cel type {} is not supported", path, x),
+                        }
+                    },
+                    PALETTE_CHUNK => {
+                        let size = u32_at(chunk, 0) as usize;
+                        let (first, last) = (u32_at(chunk, 4) as usize, u32_at(chunk, 8) as usize);
+                        ase.palette.resize(ase.palette.len().max(4*size), 0);
+                        let mut entry = 20;
+                        for i in first..=last{
+                            let flags = u16_at(chunk, entry);
+                            ase.palette[4*i..4*i + 4].copy_from_slice(&chunk[entry + 2..entry + 6]);
+                            entry += 6;
+                            if flags & 1 != 0{
+                                entry += 2 + u16_at(chunk, entry) as usize;
+                            }
+                        }
+                    },
+                    OLD_PALETTE_CHUNK if ase.palette.is_empty() => {
+                        let mut i = 0;
+                        let mut entry = 2;
+                        for _ in 0..u16_at(chunk, 0){
+                            i += chunk[entry] as usize;
+                            let cnt = match chunk[entry + 1]{0 => 256, n => n as usize};
+                            entry += 2;
+                            for c in chunk[entry..entry + 3*cnt].chunks_exact(3){
+                                ase.palette.resize(ase.palette.len().max(4*i + 4), 0);
+                                ase.palette[4*i..4*i + 4].copy_from_slice(&[c[0], c[1], c[2], 0xFF]);
+                                i += 1;
+                            }
+                            entry += 3*cnt;
+                        }
+                    },
+                    _ => {},
+                }
+                chunk_offset += chunk_size;
+            }
+            ase.frames.push(cels);
+            offset += frame_size;
+        }
+        return ase
+    }
+
+    pub fn frame_count(&self) -> usize{
+        return self.frames.len()
+    }
+
+    pub fn layer_index(&self, name: &str) -> Option<usize>{
+        return self.layers.iter().position(|l|{l.name == name})
+    }
+
+    /// cels of a frame that are drawn, either one named layer or every visible one
+    fn drawn_cels(&self, frame: usize, layer: Option<usize>) -> Vec<&Cel>{
+        assert!(frame < self.frames.len(), "aseprite frame {} does not exist, the file has {}", frame, self.frames.len());
+        let mut cels : Vec<&Cel> = self.frames[frame].iter()
+            .filter(|c|{
+                match layer{
+                    Some(l) => c.layer == l,
+                    None => self.layers[c.layer].visible,
+                }
+            })
+            .collect();
+        cels.sort_by_key(|c|{c.layer});
+        return cels
+    }
+
+    /// canvas coordinates covered by a cel, paired with the cel pixel index
+    fn cel_pixels<'a>(&self, cel: &'a Cel) -> impl Iterator<Item=(usize, usize)> + 'a{
+        let (w, h) = (self.w as isize, self.h as isize);
+        (0..cel.h)
+            .map(move |cy|{(0..cel.w).map(move |cx|{(cx, cy)})})
+            .flatten()
+            .filter_map(move |(cx, cy)|{
+                let (x, y) = (cel.x + cx as isize, cel.y + cy as isize);
+                match (0..w).contains(&x) && (0..h).contains(&y){
+                    true => Some(((y*w + x) as usize, cy*cel.w + cx)),
+                    false => None,
+                }
+            })
+    }
+
+    /// palette indices and rgba32 palette colors of a frame, None unless the file is indexed
+    pub fn frame_indexed(&self, frame: usize, layer: Option<usize>) -> Option<(usize, usize, Vec<u8>, Vec<u8>)>{
+        if self.depth != ColorDepth::Indexed{
+            return None;
+        }
+        let mut indices = vec![self.transparent_index; self.w*self.h];
+        for cel in self.drawn_cels(frame, layer){
+            let opaque = self.layers[cel.layer].background;
+            for (dst, src) in self.cel_pixels(cel){
+                let i = cel.pixels[src];
+                if opaque || i != self.transparent_index{
+                    indices[dst] = i;
+                }
+            }
+        }
+        let mut colors = self.palette.clone();
+        let has_background = self.layers.iter().any(|l|{l.background && l.visible});
+        if !has_background && colors.len() > 4*self.transparent_index as usize{
+            colors[4*self.transparent_index as usize + 3] = 0;
+        }
+        return Some((self.w, self.h, indices, colors))
+    }
+
+    /// a frame flattened to 8 bit rgba
+    pub fn frame_rgba32(&self, frame: usize, layer: Option<usize>) -> (usize, usize, Vec<u8>){
+        let mut canvas = vec![0u8; 4*self.w*self.h];
+        for cel in self.drawn_cels(frame, layer){
+            let opacity = cel.opacity as u32 * self.layers[cel.layer].opacity as u32 / 0xFF;
+            for (dst, src) in self.cel_pixels(cel){
+                let px : [u8; 4] = match self.depth{
+                    ColorDepth::Rgba => cel.pixels[4*src..4*src + 4].try_into().unwrap(),
+                    ColorDepth::Grayscale => [cel.pixels[2*src], cel.pixels[2*src], cel.pixels[2*src], cel.pixels[2*src + 1]],
+                    ColorDepth::Indexed => {
+                        let i = cel.pixels[src];
+                        match (i == self.transparent_index && !self.layers[cel.layer].background, self.palette.get(4*i as usize..4*i as usize + 4)){
+                            (false, Some(c)) => c.try_into().unwrap(),
+                            _ => [0; 4],
+                        }
+                    },
+                };
+                //source over
+                let src_a = px[3] as u32 * opacity / 0xFF;
+                let dst_px = &mut canvas[4*dst..4*dst + 4];
+                let dst_a = dst_px[3] as u32 * (0xFF - src_a) / 0xFF;
+                let out_a = src_a + dst_a;
+                if out_a == 0{
+                    continue;
+                }
+                for c in 0..3{
+                    dst_px[c] = ((px[c] as u32 * src_a + dst_px[c] as u32 * dst_a) / out_a) as u8;
+                }
+                dst_px[3] = out_a as u8;
+            }
+        }
+        return (self.w, self.h, canvas)
+    }
+}
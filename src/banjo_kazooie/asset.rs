@@ -1,40 +1,151 @@
-use std::fs::{self, File, DirBuilder};
 use std::io::{Write, Read, BufWriter};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use yaml_rust::{Yaml, YamlLoader};
 use png;
 
+use super::aseprite::AseFile;
+use super::deps;
+use super::log;
+use super::error::{ByteReader, ParseError};
+use super::names::NameMap;
+pub use super::animation::Animation;
+pub use super::demo::DemoButtonFile;
+pub use super::level_setup::LevelSetup;
+pub use super::sequence::MidiSeqFile;
+pub use super::model::Model;
+use super::texture::{self, Dither, ImgFmt, Texture};
+use super::type_rules;
+
+/// decodes as the type the detection rules give, see `type_rules`
 pub fn from_seg_indx_and_bytes(segment :usize, i :usize, in_bytes: &[u8]) -> Box<dyn Asset>{
-    return match segment{
-        0 => Box::new(Animation::from_bytes(in_bytes)),
-        1 | 3 => match in_bytes { //models and sprites
-            [0x00, 0x00, 0x00, 0x0B, ..] => Box::new(Model::from_bytes(in_bytes)),
-            _ => Box::new(Sprite::from_bytes(in_bytes)),
-        }, //sprites
-        2 => Box::new(LevelSetup::from_bytes(in_bytes)),
-        4 => match in_bytes { //Dialog, GruntyQuestions, QuizQuestions, DemoButtonFiles
-                [0x01, 0x01, 0x02, 0x05, 0x00, ..] => Box::new(QuizQuestion::from_bytes(in_bytes)),
-                [0x01, 0x03, 0x00, 0x05, 0x00, ..] => Box::new(GruntyQuestion::from_bytes(in_bytes)),
-                [0x01, 0x03, 0x00,..] => Box::new(Dialog::from_bytes(in_bytes)),
-                _ => Box::new(DemoButtonFile::from_bytes(in_bytes)),
-            },
-        5 => Box::new(Model::from_bytes(in_bytes)),
-        6 => Box::new(MidiSeqFile::from_bytes(in_bytes)),
+    return from_type_name_and_bytes(&type_rules::detect(segment, in_bytes), in_bytes)
+}
+
+/// types an assets.yaml `type_override` can name. Sprites can also be named
+/// with their format, as extract writes them
+pub const TYPE_NAMES : [&str; 10] = ["Animation", "Binary", "DemoInput", "Dialog", "GruntyQuestion", "LevelSetup", "Midi", "Model", "QuizQuestion", "Sprite"];
+
+/// `name`, if `from_type_name_and_bytes` knows it
+pub fn check_type_name(name: &str) -> &str{
+    assert!(TYPE_NAMES.contains(&name) || name.starts_with("Sprite_"), "unknown type \"{}\"\n try: {}", name, TYPE_NAMES.join(", "));
+    return name
+}
+
+/// whether the asset is of the type `name` names
+pub fn is_type_name(asset_type: &AssetType, name: &str) -> bool{
+    return match asset_type{
+        AssetType::Sprite(_) => name.starts_with("Sprite"),
+        t => super::type_name(t) == name,
+    }
+}
+
+/// decodes as the named type instead of detecting it
+pub fn from_type_name_and_bytes(name: &str, in_bytes: &[u8]) -> Box<dyn Asset>{
+    return match check_type_name(name){
+        "Animation" => Box::new(Animation::from_bytes(in_bytes)),
+        "DemoInput" => Box::new(DemoButtonFile::from_bytes(in_bytes)),
+        "Dialog" => Box::new(Dialog::from_bytes(in_bytes)),
+        "GruntyQuestion" => Box::new(GruntyQuestion::from_bytes(in_bytes)),
+        "LevelSetup" => Box::new(LevelSetup::from_bytes(in_bytes)),
+        "Midi" => Box::new(MidiSeqFile::from_bytes(in_bytes)),
+        "Model" => Box::new(Model::from_bytes(in_bytes)),
+        "QuizQuestion" => Box::new(QuizQuestion::from_bytes(in_bytes)),
+        x if x.starts_with("Sprite") => Box::new(Sprite::from_bytes(in_bytes)),
         _ => Box::new(Binary::from_bytes(in_bytes)),
     }
 }
 
+/// `from_type_name_and_bytes`, with bytes the named type can't read as an error
+pub fn try_from_type_name_and_bytes(name: &str, in_bytes: &[u8]) -> Result<Box<dyn Asset>, ParseError>{
+    if !TYPE_NAMES.contains(&name) && !name.starts_with("Sprite_"){
+        return Err(ParseError::Invalid(format!("unknown type \"{}\"", name)))
+    }
+    return Ok(match name{
+        "Animation" => Box::new(Animation::from_bytes(in_bytes)),
+        "DemoInput" => Box::new(DemoButtonFile::try_from_bytes(in_bytes)?),
+        "Dialog" => Box::new(Dialog::try_from_bytes(in_bytes)?),
+        "GruntyQuestion" => Box::new(GruntyQuestion::try_from_bytes(in_bytes)?),
+        "LevelSetup" => Box::new(LevelSetup::from_bytes(in_bytes)),
+        "Midi" => Box::new(MidiSeqFile::from_bytes(in_bytes)),
+        "Model" => Box::new(Model::try_from_bytes(in_bytes)?),
+        "QuizQuestion" => Box::new(QuizQuestion::try_from_bytes(in_bytes)?),
+        x if x.starts_with("Sprite") => Box::new(Sprite::try_from_bytes(in_bytes)?),
+        _ => Box::new(Binary::from_bytes(in_bytes)),
+    })
+}
+
+/// `from_seg_indx_and_bytes`, with bytes the detected type can't read as an error
+pub fn try_from_seg_indx_and_bytes(segment :usize, in_bytes: &[u8]) -> Result<Box<dyn Asset>, ParseError>{
+    return try_from_type_name_and_bytes(&type_rules::detect(segment, in_bytes), in_bytes)
+}
+
+/// whether `from_seg_indx_and_bytes` can give this type in this segment
+pub fn segment_holds(segment: usize, asset_type: &AssetType) -> bool{
+    return matches!(asset_type, AssetType::Binary) || type_rules::rules().iter().any(|r|{r.segments.contains(&segment) && is_type_name(asset_type, &r.type_name)})
+}
+
 #[derive(PartialEq, Debug, Copy, Clone)]
-pub enum ImgFmt{
-    CI4,
-    CI8,
-    I4,
-    I8,
-    RGBA16,
-    RGBA32,
-    IA4,
-    IA8,
-    Unknown(u16),
+pub enum TextFormat{
+    Yaml,
+    Json,
+}
+
+/// how construct compresses compressed entries
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Compression{
+    Fast,
+    Match, //byte matching
+    Max,
+}
+
+impl Compression{
+    pub fn from_str(s: &str) -> Compression{
+        return match s{
+            "fast" => Compression::Fast,
+            "match" => Compression::Match,
+            "max" => Compression::Max,
+            x => panic!("invalid compression \"{}\" provided\n try: fast, match, or max", x),
+        }
+    }
+}
+
+/// extraction settings handed to each asset's write
+#[derive(Clone)]
+pub struct ExtractOptions{
+    pub text_format: TextFormat,
+    pub apng_delay_ms: Option<u16>, //also write multi frame sprites as one animated png
+    pub c_arrays: bool, //also write textures as .inc.c arrays
+    pub actor_names: Option<PathBuf>, //uid: name table for level setup node ids
+    pub names: NameMap, //asset names for file names and assets.yaml
+    pub split_index: bool, //list the entries in a yaml per type that assets.yaml includes
+    pub threads: usize, //encoding a sprite's frame pngs in parallel
+}
+
+impl ExtractOptions{
+    pub fn new() -> ExtractOptions{
+        ExtractOptions{text_format: TextFormat::Yaml, apng_delay_ms: None, c_arrays: false, actor_names: None, names: NameMap::new(), split_index: false, threads: 1}
+    }
+}
+
+/// construction settings, sprites can override the texture ones in their descriptor
+#[derive(Clone)]
+pub struct ImportOptions{
+    pub strict_colors: bool,
+    pub alpha_threshold: u8, //formats with 1 bit alpha treat anything below as transparent
+    pub dither: Dither,
+    pub patches: Vec<PathBuf>, //level setup patches, applied to the setup their uid names
+    pub asset_align: Option<usize>, //overrides the alignment in assets.yaml
+    pub bin_align: Option<usize>,
+    pub fill: Option<u8>,
+    pub compression: Compression, //entries can override it in assets.yaml
+    pub threads: usize, //compressing entries in parallel
+    pub names: NameMap, //for assets.yaml entries given by name instead of uid
+}
+
+impl ImportOptions{
+    pub fn new() -> ImportOptions{
+        ImportOptions{strict_colors: false, alpha_threshold: 128, dither: Dither::None, patches: Vec::new(), asset_align: None, bin_align: None, fill: None, compression: Compression::Match, threads: 1, names: NameMap::new()}
+    }
 }
 
 pub enum AssetType{
@@ -60,7 +171,7 @@ impl Binary{
     }
 
     pub fn read(path: &Path) -> Binary{
-        Binary{bytes: fs::read(path).unwrap()}
+        Binary{bytes: deps::read(path).unwrap()}
     }
 }
 
@@ -69,12 +180,20 @@ impl Asset for Binary{
         return self.bytes.clone();
     }
 
+    fn write_bytes(&self, out: &mut dyn Write) -> std::io::Result<()>{
+        return out.write_all(&self.bytes);
+    }
+
+    fn known_len(&self) -> Option<usize>{
+        return Some(self.bytes.len());
+    }
+
     fn get_type(&self)->AssetType{
         return AssetType::Binary;
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let mut bin_file = deps::create(path).unwrap();
         bin_file.write_all(&self.bytes).unwrap();
     }
 }
@@ -86,12 +205,44 @@ struct BKString{
 }
 
 impl BKString{
+    /// `cnt` strings of a cmd byte, a length byte and the text
+    fn read_list(r: &mut ByteReader, cnt: u8) -> Result<Vec<BKString>, ParseError>{
+        return (0..cnt).map(|_|{
+            let cmd = r.u8()?;
+            let str_size = r.u8()?;
+            Ok(BKString{cmd : cmd, string : r.take(str_size as usize)?.to_vec()})
+        })
+        .collect()
+    }
+
     pub fn from_yaml(yaml: &Yaml) -> BKString{
         let cmd = yaml["cmd"].as_i64().unwrap() as u8;
         let string = string_to_vecu8(&yaml["string"].as_str().unwrap());            
         
         BKString{cmd : cmd, string: string}
     }
+
+    fn to_json(&self) -> String{
+        format!("{{\"cmd\": {}, \"string\": \"{}\"}}", self.cmd, vecu8_to_json_string(&self.string))
+    }
+
+    fn text_line(&self, section: &'static str) -> TextLine{
+        TextLine{section: section, cmd: self.cmd, text: vecu8_to_string(&self.string)}
+    }
+}
+
+/// one decoded string of a text asset, escaped the same way as the yaml
+#[derive(Clone, PartialEq, Eq)]
+pub struct TextLine{
+    pub section : &'static str, //bottom, top, question or option
+    pub cmd : u8,
+    pub text : String,
+}
+
+fn bkstrings_to_json(texts: &[BKString]) -> String{
+    let items : Vec<String> = texts.iter().map(|t|{format!("    {}", t.to_json())}).collect();
+    if items.is_empty() { return String::from("[]"); }
+    return format!("[\n{}\n  ]", items.join(",\n"))
 }
 
 pub struct Dialog{
@@ -101,37 +252,22 @@ pub struct Dialog{
 
 impl Dialog{
     pub fn from_bytes(in_bytes: &[u8])->Dialog{
-        let mut offset : usize = 3;
-            
-        let mut bottom = Vec::new();
-        let bottom_size : u8 = in_bytes[offset];
-        offset += 1;
-        let mut i = 0;
-        for i in 0..bottom_size{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            bottom.push(i_string);
-            offset += 2 + str_size as usize;
-        }
-
-        let mut top = Vec::new();
-        let top_size : u8 = in_bytes[offset];
-        offset += 1;
-        let mut i = 0;
-        for i in 0..top_size{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            top.push(i_string);
-            offset += 2 + str_size as usize;
-        }
-
-        return Dialog{ bottom: bottom, top: top,};
+        return Dialog::try_from_bytes(in_bytes).unwrap_or_else(|e| panic!("bad dialog: {}", e))
+    }
+
+    pub fn try_from_bytes(in_bytes: &[u8])->Result<Dialog, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        r.offset = 3;
+        let bottom_size = r.u8()?;
+        let bottom = BKString::read_list(&mut r, bottom_size)?;
+        let top_size = r.u8()?;
+        let top = BKString::read_list(&mut r, top_size)?;
+        return Ok(Dialog{ bottom: bottom, top: top,});
     }
 
     pub fn read(path: &Path) -> Dialog{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        //json documents are valid yaml, so this also loads --text-format json output
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
         let doc_type = doc["type"].as_str().unwrap();
         assert_eq!(doc_type, "Dialog");
         let bottom_obj = doc["bottom"].as_vec().unwrap();
@@ -170,8 +306,16 @@ impl Asset for Dialog{
         return AssetType::Dialog;
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
+    fn text_lines(&self) -> Option<Vec<TextLine>>{
+        Some(self.bottom.iter().map(|t|{t.text_line("bottom")}).chain(self.top.iter().map(|t|{t.text_line("top")})).collect())
+    }
+
+    fn to_json(&self) -> Option<String>{
+        Some(format!("{{\n  \"type\": \"Dialog\",\n  \"bottom\": {},\n  \"top\": {}\n}}\n", bkstrings_to_json(&self.bottom), bkstrings_to_json(&self.top)))
+    }
+
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let mut bin_file = deps::create(path).unwrap();
         
         writeln!(bin_file, "type: Dialog").unwrap();
         writeln!(bin_file, "bottom:").unwrap();
@@ -185,31 +329,112 @@ impl Asset for Dialog{
     }
 }
 
+/// the 5 bytes before a question's string count. Extract tells the question
+/// types apart by `kind`, 0x01 for quiz and 0x03 for Grunty questions. What
+/// the first and last bytes do isn't known
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct QuestionHeader{
+    pub unknown_0: u8,
+    pub kind: u8, //0x01 for quiz, 0x03 for Grunty questions
+    pub answer: u8, //index of the correct option
+    pub category: u8, //which set of questions it's drawn from
+    pub unknown_4: u8,
+}
+
+impl QuestionHeader{
+    pub const QUIZ : QuestionHeader = QuestionHeader{unknown_0: 0x01, kind: 0x01, answer: 0x02, category: 0x05, unknown_4: 0x00};
+    pub const GRUNTY : QuestionHeader = QuestionHeader{unknown_0: 0x01, kind: 0x03, answer: 0x00, category: 0x05, unknown_4: 0x00};
+
+    /// yaml keys, and what older files call them: `version`, `kind`, `answer`,
+    /// `section` and `flags` at first, then `unknown_0` to `unknown_4`
+    const KEYS : [(&'static str, &'static [&'static str]); 5] = [
+        ("unknown_0", &["version"]),
+        ("kind", &["unknown_1"]),
+        ("answer", &["unknown_2"]),
+        ("category", &["unknown_3", "section"]),
+        ("unknown_4", &["flags"]),
+    ];
+
+    fn from_bytes(r: &mut ByteReader) -> Result<QuestionHeader, ParseError>{
+        return Ok(QuestionHeader{unknown_0: r.u8()?, kind: r.u8()?, answer: r.u8()?, category: r.u8()?, unknown_4: r.u8()?})
+    }
+
+    fn to_bytes(&self) -> [u8; 5]{
+        return [self.unknown_0, self.kind, self.answer, self.category, self.unknown_4]
+    }
+
+    /// yaml key of each byte
+    pub fn key(i: usize) -> &'static str{
+        return QuestionHeader::KEYS[i].0
+    }
+
+    /// `header:` of a question's yaml or json, `default` for files from before
+    /// it was written. Bytes extract tells the type by have to stay the default's
+    fn from_yaml(doc: &Yaml, default: QuestionHeader, path: &Path) -> QuestionHeader{
+        let y = &doc["header"];
+        let type_name = doc["type"].as_str().unwrap_or("question");
+        let mut bytes = default.to_bytes();
+        for (i, (key, old_keys)) in QuestionHeader::KEYS.iter().enumerate(){
+            let given : Vec<&str> = std::iter::once(*key).chain(old_keys.iter().copied())
+                .filter(|k|{!y[*k].is_badvalue()})
+                .collect();
+            let v = match given.as_slice(){
+                [] => continue,
+                [k] => y[*k].as_i64().unwrap_or_else(||{panic!("{:?}: header {} should be a number", path, k)}),
+                _ => panic!("{:?}: header {} is given as {}", path, key, given.join(" and ")),
+            };
+            assert!((0..=0xFF).contains(&v), "{:?}: header {} is {}, it's a byte", path, key, v);
+            bytes[i] = v as u8;
+        }
+        let detected = type_rules::detect(4, &bytes);
+        let hex = |b: &[u8]|{b.iter().map(|b|{format!("{:02X}", b)}).collect::<Vec<String>>().join(" ")};
+        assert!(detected == type_name, "{:?}: header {} would extract as a {}, every vanilla {} has {}", path, hex(&bytes), detected, type_name, hex(&default.to_bytes()));
+        return QuestionHeader{unknown_0: bytes[0], kind: bytes[1], answer: bytes[2], category: bytes[3], unknown_4: bytes[4]}
+    }
+
+    fn to_yaml(&self) -> String{
+        let fields : Vec<String> = QuestionHeader::KEYS.iter().zip(self.to_bytes().iter()).map(|((k, _), b)|{format!("{}: 0x{:02X}", k, b)}).collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    fn to_json(&self) -> String{
+        let fields : Vec<String> = QuestionHeader::KEYS.iter().zip(self.to_bytes().iter()).map(|((k, _), b)|{format!("\"{}\": {}", k, b)}).collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
 pub struct QuizQuestion{
+    header: QuestionHeader,
     question: Vec<BKString>,
     options: [BKString; 3],
 }
 
 impl QuizQuestion{
     pub fn from_bytes(in_bytes: &[u8])->QuizQuestion{
-        let mut texts = Vec::new();
-        let mut str_cnt = in_bytes[5];
-        let mut offset : usize = 6;
-        for _i in 0..str_cnt{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            texts.push(i_string);
-            offset += 2 + str_size as usize;
+        return QuizQuestion::try_from_bytes(in_bytes).unwrap_or_else(|e| panic!("bad question: {}", e))
+    }
+
+    /// the question's strings, then its 3 options
+    pub fn try_from_bytes(in_bytes: &[u8])->Result<QuizQuestion, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let header = QuestionHeader::from_bytes(&mut r)?;
+        let str_cnt = r.u8()?;
+        let texts = BKString::read_list(&mut r, str_cnt)?;
+        if texts.len() < 3{
+            return Err(ParseError::Invalid(format!("question has {} strings, it needs at least the 3 options", texts.len())));
         }
         let (q_text, o_text) = texts.split_at(texts.len() - 3); 
 
         let options : [BKString; 3] = [o_text[0].clone(), o_text[1].clone(), o_text[2].clone()];
-        return QuizQuestion{ question: q_text.to_vec(), options: options};
+        return Ok(QuizQuestion{ header: header, question: q_text.to_vec(), options: options});
+    }
+
+    pub fn header(&self) -> QuestionHeader{
+        return self.header
     }
 
     pub fn read(path: &Path) -> QuizQuestion{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
         let doc_type = doc["type"].as_str().unwrap();
         assert_eq!(doc_type, "QuizQuestion");
         let q_obj = doc["question"].as_vec().unwrap();
@@ -224,13 +449,13 @@ impl QuizQuestion{
 
         let options : [BKString; 3] = [a[0].clone(), a[1].clone(), a[2].clone()];
 
-        QuizQuestion{question: q, options: options}
+        QuizQuestion{header: QuestionHeader::from_yaml(doc, QuestionHeader::QUIZ, path), question: q, options: options}
     }
 }
 
 impl Asset for QuizQuestion{
     fn to_bytes(&self)->Vec<u8>{
-        let mut out :Vec<u8> = vec![0x01, 0x01, 0x02, 0x05, 0x00];
+        let mut out :Vec<u8> = self.header.to_bytes().to_vec();
         out.push((self.question.len() + self.options.len()) as u8);
         for text in self.question.iter(){
             out.push(text.cmd);
@@ -249,10 +474,23 @@ impl Asset for QuizQuestion{
         return AssetType::QuizQuestion
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
+    fn text_lines(&self) -> Option<Vec<TextLine>>{
+        Some(self.question.iter().map(|t|{t.text_line("question")}).chain(self.options.iter().map(|t|{t.text_line("option")})).collect())
+    }
+
+    fn question_header(&self) -> Option<QuestionHeader>{
+        Some(self.header)
+    }
+
+    fn to_json(&self) -> Option<String>{
+        Some(format!("{{\n  \"type\": \"QuizQuestion\",\n  \"header\": {},\n  \"question\": {},\n  \"options\": {}\n}}\n", self.header.to_json(), bkstrings_to_json(&self.question), bkstrings_to_json(&self.options)))
+    }
+
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let mut bin_file = deps::create(path).unwrap();
         
         writeln!(bin_file, "type: QuizQuestion").unwrap();
+        writeln!(bin_file, "header: {}", self.header.to_yaml()).unwrap();
         writeln!(bin_file, "question:").unwrap();
         for text in self.question.iter(){
             writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
@@ -265,30 +503,37 @@ impl Asset for QuizQuestion{
 }
 
 pub struct GruntyQuestion{
+    header: QuestionHeader,
     question: Vec<BKString>,
     options: [BKString; 3],
 }
 
 impl GruntyQuestion{
     pub fn from_bytes(in_bytes: &[u8])->GruntyQuestion{
-        let mut texts = Vec::new();
-        let mut str_cnt = in_bytes[5];
-        let mut offset : usize = 6;
-        for _i in 0..str_cnt{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            texts.push(i_string);
-            offset += 2 + str_size as usize;
+        return GruntyQuestion::try_from_bytes(in_bytes).unwrap_or_else(|e| panic!("bad question: {}", e))
+    }
+
+    /// the question's strings, then its 3 options
+    pub fn try_from_bytes(in_bytes: &[u8])->Result<GruntyQuestion, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let header = QuestionHeader::from_bytes(&mut r)?;
+        let str_cnt = r.u8()?;
+        let texts = BKString::read_list(&mut r, str_cnt)?;
+        if texts.len() < 3{
+            return Err(ParseError::Invalid(format!("question has {} strings, it needs at least the 3 options", texts.len())));
         }
         let (q_text, o_text) = texts.split_at(texts.len() - 3); 
 
         let options : [BKString; 3] = [o_text[0].clone(), o_text[1].clone(), o_text[2].clone()];
-        return GruntyQuestion{ question: q_text.to_vec(), options: options};
+        return Ok(GruntyQuestion{ header: header, question: q_text.to_vec(), options: options});
+    }
+
+    pub fn header(&self) -> QuestionHeader{
+        return self.header
     }
 
     pub fn read(path: &Path) -> GruntyQuestion{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
         let doc_type = doc["type"].as_str().unwrap();
         assert_eq!(doc_type, "GruntyQuestion");
         let q_obj = doc["question"].as_vec().unwrap();
@@ -303,13 +548,13 @@ impl GruntyQuestion{
 
         let options : [BKString; 3] = [a[0].clone(), a[1].clone(), a[2].clone()];
 
-        GruntyQuestion{question: q, options: options}
+        GruntyQuestion{header: QuestionHeader::from_yaml(doc, QuestionHeader::GRUNTY, path), question: q, options: options}
     }
 }
 
 impl Asset for GruntyQuestion{
     fn to_bytes(&self)->Vec<u8>{
-        let mut out :Vec<u8> = vec![0x01, 0x03, 0x00, 0x05, 0x00];
+        let mut out :Vec<u8> = self.header.to_bytes().to_vec();
         out.push((self.question.len() + self.options.len()) as u8);
         for text in self.question.iter(){
             out.push(text.cmd);
@@ -328,10 +573,23 @@ impl Asset for GruntyQuestion{
         return AssetType::GruntyQuestion
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
+    fn text_lines(&self) -> Option<Vec<TextLine>>{
+        Some(self.question.iter().map(|t|{t.text_line("question")}).chain(self.options.iter().map(|t|{t.text_line("option")})).collect())
+    }
+
+    fn question_header(&self) -> Option<QuestionHeader>{
+        Some(self.header)
+    }
+
+    fn to_json(&self) -> Option<String>{
+        Some(format!("{{\n  \"type\": \"GruntyQuestion\",\n  \"header\": {},\n  \"question\": {},\n  \"options\": {}\n}}\n", self.header.to_json(), bkstrings_to_json(&self.question), bkstrings_to_json(&self.options)))
+    }
+
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let mut bin_file = deps::create(path).unwrap();
         
         writeln!(bin_file, "type: GruntyQuestion").unwrap();
+        writeln!(bin_file, "header: {}", self.header.to_yaml()).unwrap();
         writeln!(bin_file, "question:").unwrap();
         for text in self.question.iter(){
             writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
@@ -343,10 +601,30 @@ impl Asset for GruntyQuestion{
     }
 }
 
-pub trait Asset {
+pub trait Asset: Send + Sync {
     fn to_bytes(&self)->Vec<u8>;
     fn get_type(&self)->AssetType;
-    fn write(&self, path: &Path);
+    fn write(&self, path: &Path, opts: &ExtractOptions);
+
+    /// standalone JSON document for text assets, None for everything else
+    fn to_json(&self) -> Option<String>{ None }
+
+    /// decoded strings of text assets, in file order, None for everything else
+    fn text_lines(&self) -> Option<Vec<TextLine>>{ None }
+
+    /// the 5 header bytes of quiz and Grunty questions, None for everything else
+    fn question_header(&self) -> Option<QuestionHeader>{ None }
+
+    /// what would crash or soft lock the game, for the types construct checks
+    fn problems(&self) -> Vec<String>{ Vec::new() }
+
+    fn as_level_setup(&self) -> Option<&LevelSetup>{ None }
+
+    /// same bytes as `to_bytes`, for building the bin without keeping a copy
+    fn write_bytes(&self, out: &mut dyn Write) -> std::io::Result<()>{ out.write_all(&self.to_bytes()) }
+    /// the length of `to_bytes` when it's known without serializing, None
+    /// makes the bin serialize the asset once and keep the bytes instead
+    fn known_len(&self) -> Option<usize>{ None }
 }
 
 fn string_to_vecu8(string: &str) -> Vec<u8>{
@@ -379,701 +657,810 @@ fn vecu8_to_string(bytes: &Vec<u8>) -> String{
     return out
 }
 
-struct ContInput{
-    x: i8,
-    y: i8,
-    buttons: u16,
-    frames: u8,
-}
-
-impl ContInput{
-    fn to_bytes(&self)->Vec<u8>{
-        let b = self.buttons.to_be_bytes();
-        return vec![self.x as u8, self.y as u8, b[0], b[1], self.frames, 0x00];
-    }
-
-    fn from_yaml(yaml: &Yaml)->ContInput{
-        let x = yaml["x"].as_i64().unwrap() as i8;
-        let y = yaml["y"].as_i64().unwrap() as i8;
-        let buttons = yaml["buttons"].as_i64().unwrap() as u16;
-        let frames = yaml["frames"].as_i64().unwrap() as u8;
-        return ContInput{x: x, y: y, buttons: buttons, frames: frames}
+fn vecu8_to_json_string(bytes: &Vec<u8>) -> String{
+    let mut out : String = String::new();
+    for b in &bytes[..bytes.len() - 1]{
+        match *b {
+            b'"' => out += "\\\"",
+            b'\\' => out += "\\\\",
+            x if x < 0x20 || !x.is_ascii() => out += format!("\\u{:04X}", x).as_str(),
+            x => out.push(x as char),
+        }
     }
+    return out
 }
 
-pub struct DemoButtonFile{
-    inputs: Vec<ContInput>,
-    frame1_flag: u8,
+struct SpriteChunk {
+    x : isize,
+    y : isize,
+    w : usize,
+    h : usize,
+    pub pixel_data : Vec<u8>,
 }
 
-impl DemoButtonFile{
-    pub fn from_bytes(in_bytes: &[u8])->DemoButtonFile{
-        if in_bytes.len() < 4 { return DemoButtonFile{inputs: Vec::new(), frame1_flag: 0}}
-        let expect_len : usize =  u32::from_be_bytes(in_bytes[..4].try_into().unwrap()) as usize;
-        let f1f = in_bytes[9];
-        let inputs : Vec<ContInput> = in_bytes[4..].chunks_exact(6)
-            .map(|a|{
-                ContInput{
-                    x : a[0] as i8, 
-                    y : a[1] as i8,
-                    buttons : u16::from_be_bytes([a[2], a[3]]),
-                    frames : a[4],
-                }
-            })
-            .collect();
-        assert_eq!(expect_len, inputs.len()*6);
-        DemoButtonFile{inputs: inputs, frame1_flag: f1f}
-    }
+impl SpriteChunk {
+    pub fn read(r : &mut ByteReader, format : &ImgFmt)->Result<SpriteChunk, ParseError>{
+        let x = r.u16()? as i16 as isize;
+        let y = r.u16()? as i16 as isize;
+        let w = r.u16()? as usize;
+        let h = r.u16()? as usize;
+        if w == 0{
+            return Err(ParseError::Invalid("sprite chunk is 0 pixels wide".to_string()));
+        }
+        r.align(8);
+        let data_size : usize = w*h*format.bits_per_texel()/8;
+        let data : Vec<u8> = r.take(data_size)?.to_vec();
 
-    pub fn read(path: &Path) -> DemoButtonFile{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
-        let doc_type = doc["type"].as_str().unwrap();
-        let f1f = doc["flag"].as_i64().unwrap() as u8;
-        assert_eq!(doc_type, "DemoInput");
-        
-        let inputs_yaml = doc["inputs"].as_vec().unwrap();
-        let mut inputs : Vec<ContInput> = inputs_yaml.iter().map(|y|{
-            ContInput::from_yaml(y)
+        Ok(SpriteChunk{
+            x : x, 
+            y : y, 
+            w : w, 
+            h : h,
+            pixel_data : data, 
         })
-        .collect();
-        return DemoButtonFile{inputs:inputs, frame1_flag: f1f}
     }
 }
 
-impl Asset for DemoButtonFile{
-    fn to_bytes(&self)->Vec<u8>{
-        if self.inputs.is_empty() { return Vec::new(); }
+/// image a sprite frame is constructed from
+pub enum FrameSource{
+    Png(PathBuf),
+    Aseprite{path: PathBuf, frame: usize, layer: Option<String>},
+}
 
-        let mut output : Vec<u8> = (6*self.inputs.len() as u32).to_be_bytes().to_vec();
-        let mut input_bytes : Vec<u8> = self.inputs.iter().map(|i|{
-            i.to_bytes()
-        })
-        .flatten()
-        .collect();
-        input_bytes[5] = self.frame1_flag;
-        output.append(&mut input_bytes);
-        return output;
+impl FrameSource{
+    pub fn path(&self)->&Path{
+        match self{
+            FrameSource::Png(path) => path,
+            FrameSource::Aseprite{path, ..} => path,
+        }
     }
 
-    fn get_type(&self)->AssetType{
-        return AssetType::DemoInput;
+    fn read_aseprite(path: &Path, layer: &Option<String>)->(AseFile, Option<usize>){
+        let ase = AseFile::read(path);
+        let layer_i = layer.as_ref().map(|name|{
+            ase.layer_index(name).unwrap_or_else(||{panic!("{:?} has no layer \"{}\"", path, name)})
+        });
+        return (ase, layer_i)
     }
 
-    fn write(&self, path: &Path){
-        let mut demo_file = File::create(path).unwrap();
-        writeln!(demo_file, "type: DemoInput").unwrap();
-        writeln!(demo_file, "flag: 0x{:02X}", self.frame1_flag).unwrap();
-        if(self.inputs.len() == 0){
-            writeln!(demo_file, "inputs: []").unwrap();
-            return;
-        }
-        writeln!(demo_file, "inputs:").unwrap();
-        for input in self.inputs.iter(){
-            writeln!(demo_file, "  - {{x: {:3}, y: {:3}, buttons: 0x{:04X}, frames: {}}}", input.x, input.y, input.buttons, input.frames).unwrap();
+    /// palette indices and rgba32 palette colors, None for non indexed images
+    fn read_indexed(&self)->Option<(usize, usize, Vec<u8>, Vec<u8>)>{
+        match self{
+            FrameSource::Png(path) => texture::read_png_indexed(path),
+            FrameSource::Aseprite{path, frame, layer} => {
+                let (ase, layer_i) = FrameSource::read_aseprite(path, layer);
+                ase.frame_indexed(*frame, layer_i)
+            },
         }
     }
-}
-
-/// MidiSeqFile TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
 
-pub struct MidiSeqFile{
-    bytes: Vec<u8>,
-}
-
-impl MidiSeqFile{
-    pub fn from_bytes(in_bytes: &[u8])->MidiSeqFile{
-        MidiSeqFile{bytes: in_bytes.to_vec()}
+    fn read_rgba32(&self)->(usize, usize, Vec<u8>){
+        match self{
+            FrameSource::Png(path) => texture::read_png_rgba32(path),
+            FrameSource::Aseprite{path, frame, layer} => {
+                let (ase, layer_i) = FrameSource::read_aseprite(path, layer);
+                ase.frame_rgba32(*frame, layer_i)
+            },
+        }
     }
+}
 
-    pub fn read(path: &Path) -> MidiSeqFile{
-        MidiSeqFile{bytes: fs::read(path).unwrap()}
-    }
+pub struct SpriteFrame {
+    x : isize, //anchor offset used for in game positioning
+    y : isize,
+    w : usize,
+    h : usize,
+    pub header: Vec<u8>, //unknown bytes 0xA..0x14 of the frame header
+    pub chunks: Vec<(isize, isize, usize, usize)>,
+    palette : Option<Vec<u8>>,
+    indices : Option<Vec<u8>>,
+    pixel_data : Vec<u8>,
 }
 
-impl Asset for MidiSeqFile{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
-    }
+impl SpriteFrame {
+    /// width, height and rgba32 pixels
+    pub fn rgba(&self) -> (usize, usize, &[u8]){
+        return (self.w, self.h, &self.pixel_data)
+    }
+
+    /// offset of the frame from where the game positions the sprite
+    pub fn anchor(&self) -> (isize, isize){
+        return (self.x, self.y)
+    }
+
+    pub fn read(bin : &[u8], file_offset : usize, format : &ImgFmt)->Result<SpriteFrame, ParseError>{
+        let mut r = ByteReader::at(bin, file_offset);
+        let x = r.u16()? as i16 as isize;
+        let y = r.u16()? as i16 as isize;
+        let w = r.u16()? as usize;
+        let h = r.u16()? as usize;
+        let chunk_cnt = r.u16()?;
+        let header = r.take(0x14 - 0xA)?.to_vec();
+        if w*h > 0x100000{
+            return Err(ParseError::Invalid(format!("sprite frame is {}x{}, too big to be real", w, h)));
+        }
+        let mut pxl_data : Vec<Vec<[u8;4]>> = vec![vec![[0; 4]; w]; h];
+        let mut idx_data : Vec<Vec<u8>> = vec![vec![0; w]; h];
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Midi;
-    }
+        let palette : Vec<u8> = match format {
+            ImgFmt::CI4 => {r.align(8); r.take(0x20)?.to_vec()},
+            ImgFmt::CI8 => {r.align(8); r.take(0x200)?.to_vec()},
+            _ => Vec::new(),
+        };
+        let chunks : Vec<SpriteChunk> = match format {
+            ImgFmt::CI4 | ImgFmt::CI8 | ImgFmt::I4 | ImgFmt::I8 | ImgFmt::IA4 | ImgFmt::IA8 | ImgFmt::RGBA32 | ImgFmt::RGBA16 => {
+                (0..chunk_cnt).map(|_|{SpriteChunk::read(&mut r, format)}).collect::<Result<_, _>>()?
+            },
+            _ => Vec::new(),
+        };
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
-    }
-}
+        let layout = chunks.iter().map(|c|{(c.x, c.y, c.w, c.h)}).collect();
+        for chnk in chunks{
+            let indices : Vec<u8> = match format {
+                ImgFmt::CI4 => chnk.pixel_data.iter().map(|a|{[a >> 4, a & 0xF]}).flatten().collect(),
+                ImgFmt::CI8 => chnk.pixel_data.clone(),
+                _ => Vec::new(),
+            };
 
-/// LevelSetup TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
+            let raw_data = match format {
+                ImgFmt::CI4    => Texture::ci4_to_rgba32(&chnk.pixel_data, &palette),
+                ImgFmt::CI8    => Texture::ci8_to_rgba32(&chnk.pixel_data, &palette),
+                ImgFmt::I4     => Texture::i4_to_rgba32(&chnk.pixel_data),
+                ImgFmt::I8     => Texture::i8_to_rgba32(&chnk.pixel_data),
+                ImgFmt::RGBA16 => Texture::rgba16_to_rgba32(&chnk.pixel_data),
+                ImgFmt::RGBA32 => chnk.pixel_data,
+                ImgFmt::IA4    => Texture::ia4_to_rgba32(&chnk.pixel_data),
+                ImgFmt::IA8    => Texture::ia8_to_rgba32(&chnk.pixel_data),
+                _=> Vec::new(),
+            };
 
-pub struct LevelSetup{
-    bytes: Vec<u8>,
-}
+            //single chunk frames ignore the chunk position
+            let (ox, oy) = if chunk_cnt == 1 {(0, 0)} else {(chnk.x, chnk.y)};
+            let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
+            for (j,row) in row_data.iter().enumerate(){
+                for (i, pxl) in row.chunks_exact(4).enumerate(){
+                    let fx :isize = ox + i as isize;
+                    let fy :isize = oy + j as isize;
+                    if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
+                        pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
+                        if !indices.is_empty(){
+                            idx_data[fy as usize][fx as usize] = indices[j*chnk.w + i];
+                        }
+                    }
+                }
+            }
+        }
 
-impl LevelSetup{
-    pub fn from_bytes(in_bytes: &[u8])->LevelSetup{
-        LevelSetup{bytes: in_bytes.to_vec()}
-    }
+        let (pal, idx) = match format{
+            ImgFmt::CI4 | ImgFmt::CI8 => (Some(palette), Some(idx_data.into_iter().flatten().collect())),
+            _ => (None, None),
+        };
 
-    pub fn read(path: &Path) -> LevelSetup{
-        LevelSetup{bytes: fs::read(path).unwrap()}
+        Ok(SpriteFrame{x: x, y: y, w: w as usize,h: h as usize, header: header, chunks: layout, palette : pal, indices: idx, pixel_data: pxl_data.into_iter().flatten().flatten().collect()})
     }
 }
 
-impl Asset for LevelSetup{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
-    }
+impl SpriteFrame {
+    pub fn from_image(src : &FrameSource, palette_path : Option<PathBuf>, header : Vec<u8>, format : &ImgFmt, opts : &ImportOptions)->SpriteFrame{
+        let max_colors : usize = match format{
+            ImgFmt::CI4 => 0x10,
+            ImgFmt::CI8 => 0x100,
+            _ => usize::MAX,
+        };
+        //RGBA16 texels and palette entries only have 1 bit of alpha
+        let one_bit_alpha = match format{
+            ImgFmt::CI4 | ImgFmt::CI8 | ImgFmt::RGBA16 | ImgFmt::IA4 => true,
+            _ => false,
+        };
+        let clamp_alpha = |src : &Path, rgba32 : Vec<u8>|->Vec<u8>{
+            if !one_bit_alpha{
+                return rgba32
+            }
+            let (out, clamped) = Texture::threshold_alpha(&rgba32, opts.alpha_threshold);
+            if clamped > 0{
+                log::warning(None, &format!("{:?}: {} semi-transparent pixels clamped to 1 bit alpha (threshold {})", src, clamped, opts.alpha_threshold));
+            }
+            out
+        };
 
-    fn get_type(&self)->AssetType{
-        return AssetType::LevelSetup;
-    }
+        //a separate palette file replaces the palette of the png
+        let shared_palette : Option<Vec<u8>> = palette_path.map(|p|{
+            let pal = Texture::rgba32_to_rgba16(&clamp_alpha(&p, texture::read_palette(&p)));
+            assert!(pal.len() <= 2*max_colors, "{:?} has {} colors, but {:?} only holds {}", p, pal.len()/2, format, max_colors);
+            pal
+        });
+
+        //indexed images keep their palette order
+        let path = src.path();
+        if let (ImgFmt::CI4 | ImgFmt::CI8, Some((w, h, indices, colors))) = (format, src.read_indexed()){
+            assert!(indices.iter().all(|i|{(*i as usize) < max_colors}), "{:?} uses palette indices past the {} colors {:?} holds", path, max_colors, format);
+            let mut palette = shared_palette.unwrap_or_else(||{Texture::rgba32_to_rgba16(&clamp_alpha(path, colors))});
+            palette.resize(2*max_colors, 0);
+            let pixel_data = Texture::ci8_to_rgba32(&indices, &palette);
+            return SpriteFrame{x: 0, y: 0, w: w, h: h, header: header, chunks: Vec::new(), palette: Some(palette), indices: Some(indices), pixel_data: pixel_data}
+        }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+        let (w, h, pixel_data) = src.read_rgba32();
+        let mut pixel_data = clamp_alpha(path, pixel_data);
+        if let Some(mut palette) = shared_palette{
+            palette.resize(2*max_colors, 0);
+            let indices = Texture::rgba32_to_ci8_nearest(&pixel_data, &palette);
+            let pixel_data = Texture::ci8_to_rgba32(&indices, &palette);
+            return SpriteFrame{x: 0, y: 0, w: w, h: h, header: header, chunks: Vec::new(), palette: Some(palette), indices: Some(indices), pixel_data: pixel_data}
+        }
+        if *format == ImgFmt::RGBA16{
+            pixel_data = Texture::dither_rgba16(&pixel_data, w, h, opts.dither);
+        }
+        let color_cnt = Texture::color_count(&pixel_data);
+        if color_cnt > max_colors{
+            assert!(!opts.strict_colors, "{:?} uses {} colors, but {:?} only holds {}", path, color_cnt, format, max_colors);
+            log::warning(None, &format!("quantizing {:?} from {} to {} colors", path, color_cnt, max_colors));
+            pixel_data = Texture::quantize(&pixel_data, max_colors);
+        }
+        SpriteFrame{x: 0, y: 0, w: w, h: h, header: header, chunks: Vec::new(), palette: None, indices: None, pixel_data: pixel_data}
+    }
+
+    /// the frame as a png, indexed in the original palette order when it has one
+    fn png(&self) -> Vec<u8>{
+        let mut out : Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.w as u32, self.h as u32);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data = match (&self.palette, &self.indices){
+            (Some(pal), Some(indices)) => {
+                //keep palette order so the frame can be rebuilt exactly
+                let colors = Texture::rgba16_to_rgba32(pal);
+                let plte : Vec<u8> = colors.chunks_exact(4).map(|c|{[c[0], c[1], c[2]]}).flatten().collect();
+                let trns : Vec<u8> = colors.chunks_exact(4).map(|c|{c[3]}).collect();
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_palette(plte);
+                encoder.set_trns(trns);
+                indices
+            }
+            _ => {
+                encoder.set_color(png::ColorType::Rgba);
+                &self.pixel_data
+            }
+        };
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        return out
+    }
+
+    /// pixels of a sub rectangle, zeroed outside of the frame
+    fn region(&self, data : &[u8], px_size : usize, x : isize, y : isize, w : usize, h : usize)->Vec<u8>{
+        let mut out : Vec<u8> = Vec::with_capacity(px_size*w*h);
+        for fy in y..y + h as isize{
+            for fx in x..x + w as isize{
+                if (0 <= fx) && (fx < (self.w as isize)) && (0 <= fy) && (fy < (self.h as isize)){
+                    let i = px_size*(fy as usize * self.w + fx as usize);
+                    out.extend_from_slice(&data[i..i+px_size]);
+                }
+                else{
+                    out.resize(out.len() + px_size, 0);
+                }
+            }
+        }
+        return out
     }
-}
 
-/// Animation TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
+    /// recorded chunk grid, or full width strips that each fit in TMEM
+    fn chunk_layout(&self, format : &ImgFmt)->Vec<(isize, isize, usize, usize)>{
+        if !self.chunks.is_empty(){
+            return self.chunks.clone()
+        }
+        let tmem_size = Texture::tmem_available(format);
+        let row_size = Texture::tmem_footprint(self.w, 1, format);
+        assert!(row_size <= tmem_size, "{}px wide {:?} frame: a single row is {} bytes > {} bytes of TMEM", self.w, format, row_size, tmem_size);
+        let rows = tmem_size/row_size;
+        return (0..self.h).step_by(rows)
+            .map(|y|{(0, y as isize, self.w, std::cmp::min(rows, self.h - y))})
+            .collect()
+    }
 
-pub struct Animation{
-    bytes: Vec<u8>,
-}
+    /// appends the frame, with where each part of it starts in `marks`
+    fn to_bytes(&self, out : &mut Vec<u8>, format : &ImgFmt, i : usize, marks : &mut Vec<(usize, String)>){
+        if *format == ImgFmt::RGBA32{
+            //frames larger than the 320x240 screen can never be displayed
+            assert!(self.w*self.h <= 320*240, "{}x{} RGBA32 frame has more texels than the {}x{} screen can display", self.w, self.h, 320, 240);
+        }
+        let chunks = self.chunk_layout(format);
+
+        marks.push((out.len(), format!("frame {}'s header", i)));
+        out.extend_from_slice(&(self.x as i16).to_be_bytes());
+        out.extend_from_slice(&(self.y as i16).to_be_bytes());
+        out.extend_from_slice(&(self.w as u16).to_be_bytes());
+        out.extend_from_slice(&(self.h as u16).to_be_bytes());
+        out.extend_from_slice(&(chunks.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.header);
+
+        let palette = self.palette_colors(format);
+        if matches!(format, ImgFmt::CI4 | ImgFmt::CI8){
+            marks.push((out.len(), format!("frame {}'s palette", i)));
+        }
+        match format{
+            ImgFmt::CI4 => {
+                align(out, 8);
+                out.append(&mut Texture::palette_to_bytes(&palette, 0x10));
+            }
+            ImgFmt::CI8 => {
+                align(out, 8);
+                out.append(&mut Texture::palette_to_bytes(&palette, 0x100));
+            }
+            _ => {}
+        }
 
-impl Animation{
-    pub fn from_bytes(in_bytes: &[u8])->Animation{
-        Animation{bytes: in_bytes.to_vec()}
-    }
+        let single_chunk = chunks.len() == 1;
+        for (j, (cx, cy, w, h)) in chunks.into_iter().enumerate(){
+            marks.push((out.len(), format!("frame {} chunk {}", i, j)));
+            out.extend_from_slice(&(cx as i16).to_be_bytes());
+            out.extend_from_slice(&(cy as i16).to_be_bytes());
+            out.extend_from_slice(&(w as u16).to_be_bytes());
+            out.extend_from_slice(&(h as u16).to_be_bytes());
+            align(out, 8);
 
-    pub fn read(path: &Path) -> Animation{
-        Animation{bytes: fs::read(path).unwrap()}
-    }
-}
+            //single chunk frames ignore the chunk position
+            let (x, y) = if single_chunk {(0, 0)} else {(cx, cy)};
 
-impl Asset for Animation{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
+            out.append(&mut self.texels(format, &palette, x, y, w, h));
+        }
     }
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Animation;
+    /// CI palette as stored in the sprite, built from the pixels when the frame has none
+    fn palette_colors(&self, format : &ImgFmt)->Vec<u16>{
+        match (format, &self.palette){
+            (ImgFmt::CI4 | ImgFmt::CI8, Some(pal)) => pal.chunks_exact(2).map(|a|{u16::from_be_bytes([a[0], a[1]])}).collect(),
+            (ImgFmt::CI4, None) => Texture::rgba32_to_palette(&self.pixel_data, 0x10),
+            (ImgFmt::CI8, None) => Texture::rgba32_to_palette(&self.pixel_data, 0x100),
+            _ => Vec::new(),
+        }
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+    /// encoded texels of a sub rectangle
+    fn texels(&self, format : &ImgFmt, palette : &[u16], x : isize, y : isize, w : usize, h : usize)->Vec<u8>{
+        let rgba32 = self.region(&self.pixel_data, 4, x, y, w, h);
+        match (format, &self.indices){
+            (ImgFmt::CI4, Some(indices)) => self.region(indices, 1, x, y, w, h).chunks_exact(2).map(|a|{(a[0] << 4) | (a[1] & 0xF)}).collect(),
+            (ImgFmt::CI8, Some(indices)) => self.region(indices, 1, x, y, w, h),
+            _ => match format{
+            ImgFmt::CI4    => Texture::rgba32_to_ci4(&rgba32, palette),
+            ImgFmt::CI8    => Texture::rgba32_to_ci8(&rgba32, palette),
+            ImgFmt::I4     => Texture::rgba32_to_i4(&rgba32),
+            ImgFmt::I8     => Texture::rgba32_to_i8(&rgba32),
+            ImgFmt::IA4    => Texture::rgba32_to_ia4(&rgba32),
+            ImgFmt::IA8    => Texture::rgba32_to_ia8(&rgba32),
+            ImgFmt::RGBA16 => Texture::rgba32_to_rgba16(&rgba32),
+            ImgFmt::RGBA32 => rgba32,
+            ImgFmt::Unknown(_) => panic!("Constructing {:?} sprites is not implemented yet", format),
+            },
+        }
     }
 }
 
-/// Model TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
-
-pub struct Model{
-    bytes: Vec<u8>,
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum SpriteVariant{
+    Frames,
+    RawTexture, //frame count > 0x100, one RGBA16 chunk right after an 8 byte header
 }
 
-impl Model{
-    pub fn from_bytes(in_bytes: &[u8])->Model{
-        Model{bytes: in_bytes.to_vec()}
-    }
-
-    pub fn read(path: &Path) -> Model{
-        Model{bytes: fs::read(path).unwrap()}
-    }
+pub struct Sprite{
+    format: ImgFmt,
+    variant: SpriteVariant,
+    header: Vec<u8>,
+    pub frame: Vec<SpriteFrame>,
+    bytes: Vec<u8>,
 }
 
-impl Asset for Model{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
+impl Sprite{
+    pub fn from_bytes(in_bytes: &[u8])->Sprite{
+        Sprite::try_from_bytes(in_bytes).unwrap_or_else(|e|{panic!("bad sprite: {}", e)})
     }
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Model;
-    }
+    pub fn try_from_bytes(in_bytes: &[u8])->Result<Sprite, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let frame_cnt = r.u16()?;
+        let format = r.u16()?;
+        let frmt = ImgFmt::from_sprite_code(format);
+        match frmt {
+            ImgFmt::Unknown(_) => {return Ok(Sprite{format: frmt, variant: SpriteVariant::Frames, header: Vec::new(), frame: Vec::new(), bytes: in_bytes.to_vec()})},
+            _=> {}
+        }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+        if frame_cnt > 0x100{
+            r.offset = 8;
+            let chunk = SpriteChunk::read(&mut r, &ImgFmt::RGBA16)?;
+            let frame = SpriteFrame{x: 0, y: 0, w:chunk.w, h:chunk.h, header: Vec::new(), chunks: vec![(chunk.x, chunk.y, chunk.w, chunk.h)], palette: None, indices: None, pixel_data: Texture::rgba16_to_rgba32(&chunk.pixel_data)};
+            return Ok(Sprite{format: frmt, variant: SpriteVariant::RawTexture, header: in_bytes[..8].to_vec(), frame: vec![frame], bytes: in_bytes.to_vec()});
+        }
+        let header = r.take(0x10 - 4)?.to_vec();
+        let offsets = (0..frame_cnt).map(|_|{r.u32()}).collect::<Result<Vec<u32>, ParseError>>()?;
+        let frames : Vec<SpriteFrame> = offsets.iter()
+                .map(|offset|{
+                    SpriteFrame::read(in_bytes, 0x10 + *offset as usize + 4*frame_cnt as usize, &frmt)
+                })
+                .collect::<Result<_, _>>()?; 
+        return Ok(Sprite{format: frmt, variant: SpriteVariant::Frames, header: header, frame: frames, bytes: in_bytes.to_vec()});
     }
-}
-
-pub struct Texture {
-    texture_type: ImgFmt,
-    w : usize,
-    h : usize,
 
-    palette : Option<Vec<u8>>,
-    pixel_data : Vec<u8>,
-}
+    pub fn read(path: &Path, opts: &ImportOptions) -> Sprite{
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        let doc_type = doc["type"].as_str().unwrap();
+        assert_eq!(doc_type, "Sprite");
+        let containing_folder = path.parent().unwrap();
+
+        //sprites that can't be rebuilt from frames yet keep their original bytes
+        let lossy = doc["lossy"].as_str();
+        if let Some(bin_path) = doc["bin"].as_str(){
+            log::warning(None, &format!("{:?}: copied from {} as it can't be rebuilt from frames, {}", path, bin_path, lossy.unwrap_or("its format isn't supported")));
+            let bytes = deps::read(containing_folder.join(bin_path)).unwrap();
+            let format = ImgFmt::from_sprite_code(u16::from_be_bytes([bytes[2], bytes[3]]));
+            return Sprite{format: format, variant: SpriteVariant::Frames, header: Vec::new(), frame: Vec::new(), bytes: bytes}
+        }
 
-impl Texture {
-    pub fn new(texture_type: ImgFmt, w : usize, h : usize, bin : &[u8])->Texture{
-        let palette : Option<Vec<u8>> = match texture_type{
-            ImgFmt::CI4 => Some(bin[0.. 0x20].to_vec()),
-            ImgFmt::CI8 => Some(bin[0.. 0x200].to_vec()),
-            _=> None,
+        if let Some(reason) = lossy{
+            log::warning(None, &format!("{:?}: rebuilt from frames that don't give back the original bytes, {}", path, reason));
+        }
+        let format = ImgFmt::from_name(doc["format"].as_str().unwrap());
+        let variant = match doc["variant"].as_str(){
+            Some("RawTexture") => SpriteVariant::RawTexture,
+            Some("Frames") | None => SpriteVariant::Frames,
+            Some(x) => panic!("unknown sprite variant \"{}\"", x),
         };
-        
-        let pixel_data = match texture_type {
-            ImgFmt::CI4 => &bin[0x20..],
-            ImgFmt::CI8 => &bin[0x200..],
-            _ => bin,
+        //raw textures always hold RGBA16 texels, whatever their format field says
+        let texel_format = match variant{
+            SpriteVariant::RawTexture => ImgFmt::RGBA16,
+            SpriteVariant::Frames => format,
         };
-
-        return Texture{
-            texture_type : texture_type, 
-            w : w,
-            h : h,
-            palette : palette,
-            pixel_data : pixel_data.to_vec(),
+        let header = yaml_to_bytes(&doc["header"]);
+        //per sprite overrides of the texture settings
+        let mut opts = opts.clone();
+        if let Some(d) = doc["dither"].as_str(){
+            opts.dither = Dither::from_name(d);
         }
-    }
-
-    pub fn to_rgba32(&self) -> Vec<u8>{
-        match self.texture_type{
-            ImgFmt::CI4 => 
-            {   
-                match &self.palette{
-                    None => panic!("Expected CI4 palette, but none found"),
-                    Some(pal) => Texture::ci4_to_rgba32(&self.pixel_data, &pal)
+        if let Some(t) = doc["alpha_threshold"].as_i64(){
+            opts.alpha_threshold = t as u8;
+        }
+        let frames : Vec<SpriteFrame> = doc["frames"].as_vec().unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, y)|{
+                let palette_path = y["palette"].as_str().map(|p|{containing_folder.join(p)});
+                let mut header = yaml_to_bytes(&y["header"]);
+                let (mut x, mut y_anchor) = (0, 0);
+                if header.len() == 0x14{
+                    //descriptors written before anchors were split out hold the whole frame header
+                    x = i16::from_be_bytes([header[0], header[1]]) as isize;
+                    y_anchor = i16::from_be_bytes([header[2], header[3]]) as isize;
+                    header = header.split_off(0xA);
                 }
-            },
-            ImgFmt::CI8 => 
-            {   
-                match &self.palette{
-                    None => panic!("Expected CI8 palette, but none found"),
-                    Some(pal) => Texture::ci8_to_rgba32(&self.pixel_data, &pal)
+                //aseprite frames default to the matching frame of the file
+                let src = match (y["png"].as_str(), y["aseprite"].as_str()){
+                    (Some(png), None) => FrameSource::Png(containing_folder.join(png)),
+                    (None, Some(ase)) => FrameSource::Aseprite{
+                        path: containing_folder.join(ase),
+                        frame: y["ase_frame"].as_i64().map(|f|{f as usize}).unwrap_or(i),
+                        layer: y["ase_layer"].as_str().map(|l|{l.to_string()}),
+                    },
+                    _ => panic!("{:?} frame {}: needs exactly one of png or aseprite", path, i),
+                };
+                let mut frame = SpriteFrame::from_image(&src, palette_path, header, &texel_format, &opts);
+                frame.x = y["x"].as_i64().map(|v|{v as isize}).unwrap_or(x);
+                frame.y = y["y"].as_i64().map(|v|{v as isize}).unwrap_or(y_anchor);
+                if let Some(chunks) = y["chunks"].as_vec(){
+                    frame.chunks = chunks.iter()
+                        .map(|c|{
+                            (c["x"].as_i64().unwrap() as isize, c["y"].as_i64().unwrap() as isize, c["w"].as_i64().unwrap() as usize, c["h"].as_i64().unwrap() as usize)
+                        })
+                        .collect();
                 }
-            }
-            ImgFmt::RGBA16 => Texture::rgba16_to_rgba32(&self.pixel_data),
-            ImgFmt::RGBA32 => self.pixel_data.clone(),
-            ImgFmt::I4 => Texture::i4_to_rgba32(&self.pixel_data),
-            ImgFmt::I8 => Texture::i8_to_rgba32(&self.pixel_data),
-            ImgFmt::IA4 => Texture::ia4_to_rgba32(&self.pixel_data),
-            ImgFmt::IA8 => Texture::ia8_to_rgba32(&self.pixel_data),
-            _ => {panic!("Image type not implemented yet");},
-
-        }
-    }
-
-    pub fn rgba16_to_rgba32(rgba16 : &[u8])->Vec<u8>{
-        return rgba16.chunks_exact(2)
-            .map(|a|{
-                let val = u16::from_be_bytes([a[0], a[1]]);
-                let r16 = ((val >> 11) & 0x1f) as u8;
-                let g16 = ((val >> 6) & 0x1f) as u8;
-                let b16 = ((val >> 1) & 0x1f) as u8;
-                let a16 = (val & 0x1) as u8;
-
-                let r32 = (r16 << 3) | (r16 >> 3);
-                let g32 = (g16 << 3) | (g16 >> 3);
-                let b32 = (b16 << 3) | (b16 >> 3);
-                let a32 = (((a16 << 7) as i8) >> 7) as u8;
-
-                return [r32, g32, b32, a32]
-            })
-            .flatten()
-            .collect()
-    }
-
-    pub fn ci4_to_rgba32(ci4 : &[u8], palatte: &[u8])->Vec<u8>{
-        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
-            .map(|a|{
-                let val = u16::from_be_bytes([a[0], a[1]]);
-                let r16 = ((val >> 11) & 0x1f) as u8;
-                let g16 = ((val >> 6) & 0x1f) as u8;
-                let b16 = ((val >> 1) & 0x1f) as u8;
-                let a16 = (val & 0x1) as u8;
-
-                let r32 = (r16 << 3) | (r16 >> 3);
-                let g32 = (g16 << 3) | (g16 >> 3);
-                let b32 = (b16 << 3) | (b16 >> 3);
-                let a32 = (((a16 << 7) as i8) >> 7) as u8;
-
-                [r32, g32, b32, a32]
+                frame
             })
             .collect();
-
-        return ci4
-            .into_iter()
-            .map(|a|{[a >> 4, a & 0xF]}) //cvt to ci8
-            .flatten()
-            .map(|indx|{pal[indx as usize]})
-            .flatten()
-            .collect()
-    }
-    pub fn ci8_to_rgba32(ci8 : &[u8], palatte: &[u8])->Vec<u8>{
-        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
-            .map(|a|{
-                let val = u16::from_be_bytes([a[0], a[1]]);
-                let r16 = ((val >> 11) & 0x1f) as u8;
-                let g16 = ((val >> 6) & 0x1f) as u8;
-                let b16 = ((val >> 1) & 0x1f) as u8;
-                let a16 = (val & 0x1) as u8;
-
-                let r32 = (r16 << 3) | (r16 >> 3);
-                let g32 = (g16 << 3) | (g16 >> 3);
-                let b32 = (b16 << 3) | (b16 >> 3);
-                let a32 = (((a16 << 7) as i8) >> 7) as u8;
-
-                [r32, g32, b32, a32]
-            })
-            .collect();
-
-        return ci8
-            .iter()
-            .map(|indx|{pal[*indx as usize]})
-            .flatten()
-            .collect()
+        if variant == SpriteVariant::RawTexture{
+            assert_eq!(frames.len(), 1, "raw texture sprites hold exactly one frame");
+        }
+        for (i, frame) in frames.iter().enumerate(){
+            assert!(frame.w <= 0xFFFF && frame.h <= 0xFFFF, "{:?} frame {}: {}x{} is too large", path, i, frame.w, frame.h);
+            for (j, (_, _, w, h)) in frame.chunk_layout(&texel_format).iter().enumerate(){
+                if let Err(e) = Texture::validate_tmem(*w, *h, &texel_format){
+                    panic!("{:?} frame {}: chunk {} is {}", path, i, j, e);
+                }
+            }
+        }
+        Sprite{format: format, variant: variant, header: header, frame: frames, bytes: Vec::new()}
     }
 
-    pub fn i4_to_rgba32(i_4 : &[u8])->Vec<u8>{
-        return i_4.into_iter()
-            .map(|a|{
-                let val1 = (a & 0xF0) | (a >> 4);
-                let val2 = (a << 4) | (a & 0xF);
-                [val1, val1, val1, 0xFF, val2, val2, val2, 0xFF]
-            })
-            .flatten()
-            .collect()
+    fn can_construct(&self)->bool{
+        return match self.format {
+            ImgFmt::Unknown(_) => false,
+            _ => !self.frame.is_empty(),
+        }
     }
 
-    pub fn i8_to_rgba32(i_8 : &[u8])->Vec<u8>{
-        return i_8.iter()
-            .map(|a|{
-                let val = *a;
-                [val, val, val, 0xFF]
-            })
-            .flatten()
-            .collect()
+    /// the sprite rebuilt from its frames, with where each part starts in `marks`
+    fn frames_to_bytes(&self, marks : &mut Vec<(usize, String)>)->Vec<u8>{
+        marks.push((0, "the sprite header".to_string()));
+        let mut out : Vec<u8> = (self.frame.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(&self.format.sprite_code().to_be_bytes());
+        out.extend_from_slice(&self.header);
+        let table_start = out.len();
+        marks.push((table_start, "the frame offset table".to_string()));
+        out.resize(table_start + 4*self.frame.len(), 0);
+        let data_start = out.len();
+        for (i, frame) in self.frame.iter().enumerate(){
+            align(&mut out, 8);
+            let offset = ((out.len() - data_start) as u32).to_be_bytes();
+            out[table_start + 4*i .. table_start + 4*i + 4].copy_from_slice(&offset);
+            frame.to_bytes(&mut out, &self.format, i, marks);
+        }
+        align(&mut out, 8);
+        marks.push((out.len(), "the end of the sprite".to_string()));
+        return out;
     }
 
-    pub fn ia4_to_rgba32(ia4 : &[u8])->Vec<u8>{
-        return ia4
-            .into_iter()
-            .map(|a|{
-                let i1 = (a & 0xE0) | (a >> 3) | (a >> 6);
-                let a1 = (((a << 3) as i8) >> 7) as u8;
-                let i2 = (a >> 1) & 0x7;
-                let i2 = (i2 << 5) | (i2 << 2) | (i2 >> 1);
-                let a2 = (((a << 7) as i8) >> 7) as u8;
-                [i1, i1, i1, a1, i2, i2, i2, a2]
+    /// why the decoded frames don't rebuild the bytes they came from, None
+    /// when they rebuild them byte for byte
+    pub fn lossy_reason(&self)->Option<String>{
+        if self.bytes.is_empty(){
+            return None //read from a descriptor, nothing to compare with
+        }
+        if self.frame.is_empty(){
+            return Some(match self.format{
+                ImgFmt::Unknown(code) => format!("format 0x{:04X} isn't known", code),
+                _ => "it has no frames".to_string(),
             })
-            .flatten()
-            .collect()
+        }
+        let mut marks : Vec<(usize, String)> = Vec::new();
+        let rebuilt = match self.variant{
+            SpriteVariant::RawTexture => {
+                marks.push((0, "the raw texture header".to_string()));
+                marks.push((8, "the raw texture's texels".to_string()));
+                self.raw_texture_to_bytes()
+            },
+            SpriteVariant::Frames => self.frames_to_bytes(&mut marks),
+        };
+        let at = rebuilt.iter().zip(self.bytes.iter()).position(|(a, b)|{a != b})
+            .or(if rebuilt.len() == self.bytes.len() {None} else {Some(rebuilt.len().min(self.bytes.len()))})?;
+        if at == rebuilt.len(){
+            return Some(format!("the 0x{:X} bytes after its last frame aren't kept", self.bytes.len() - at))
+        }
+        let part = &marks.iter().rev().find(|(start, _)|{*start <= at}).unwrap().1;
+        let why = match part{
+            p if p.ends_with("palette") => ", its colors are reordered or unused entries aren't zero",
+            p if p.contains("chunk") => ", its chunks overlap or hold pixels outside the frame",
+            _ => "",
+        };
+        return Some(format!("{} differs from 0x{:X}{}", part, at, why))
     }
 
-    pub fn ia8_to_rgba32(ia8 : &[u8])->Vec<u8>{
-        return ia8
-            .iter()
-            .map(|a|{
-                let val = (*a & 0xF0) | (*a >> 4);
-                let alpha = (*a << 4) | (*a & 0xF);
-                [val, val, val, alpha]
-            })
-            .flatten()
-            .collect()
+    fn raw_texture_to_bytes(&self)->Vec<u8>{
+        let frame = &self.frame[0];
+        let (x, y) = match frame.chunks.first(){
+            Some((x, y, _, _)) => (*x, *y),
+            None => (0, 0),
+        };
+        let mut out = self.header.clone();
+        out.extend_from_slice(&(x as i16).to_be_bytes());
+        out.extend_from_slice(&(y as i16).to_be_bytes());
+        out.extend_from_slice(&(frame.w as u16).to_be_bytes());
+        out.extend_from_slice(&(frame.h as u16).to_be_bytes());
+        align(&mut out, 8);
+        out.append(&mut Texture::rgba32_to_rgba16(&frame.pixel_data));
+        align(&mut out, 8);
+        return out
     }
 }
 
-struct SpriteChunk {
-    x : isize,
-    y : isize,
-    w : usize,
-    h : usize,
-    pub pixel_data : Vec<u8>,
-}
-
-impl SpriteChunk {
-    pub fn new(bin : &[u8], file_offset : &mut usize, format : &ImgFmt)->SpriteChunk{
-        let chunk_bin = &bin[*file_offset..];
-        let x = i16::from_be_bytes([chunk_bin[0], chunk_bin[1]]) as isize;
-        let y = i16::from_be_bytes([chunk_bin[2], chunk_bin[3]]) as isize;
-        let w = u16::from_be_bytes([chunk_bin[4], chunk_bin[5]]) as usize;
-        let h = u16::from_be_bytes([chunk_bin[6], chunk_bin[7]]) as usize;
-        // println!("\t\t{:02X?}", &chunk_bin[..8]);
-        *file_offset += 8;
-        *file_offset = (*file_offset + (8 - 1)) & !(8 - 1); //align
-        let pxl_size : usize = match format{
-            ImgFmt::I4 | ImgFmt::IA4 | ImgFmt::CI4 => 4,
-            ImgFmt::I8 | ImgFmt::IA8 | ImgFmt::CI8 => 8,
-            ImgFmt::RGBA16 => 16,
-            ImgFmt::RGBA32 => 32,
-            _=> 0,
-        };
-        let data_size : usize = w*h*pxl_size/8;
-
-        let data : Vec<u8> = bin[*file_offset .. *file_offset + data_size].to_vec();
-        *file_offset += data_size;
-
-        SpriteChunk{
-            x : x, 
-            y : y, 
-            w : w, 
-            h : h,
-            pixel_data : data, 
+impl Asset for Sprite{
+    fn to_bytes(&self)->Vec<u8>{
+        if !self.can_construct(){
+            return self.bytes.clone();
         }
+        if self.variant == SpriteVariant::RawTexture{
+            return self.raw_texture_to_bytes();
+        }
+        return self.frames_to_bytes(&mut Vec::new())
     }
-}
-
-pub struct SpriteFrame {
-    w : usize,
-    h : usize,
-    pub header: Vec<u8>,
-    pub chk_hdrs: Vec<Vec<u8>>,
-    palette : Option<Vec<u8>>,
-    pixel_data : Vec<u8>,
-}
-
-impl SpriteFrame {
-    pub fn new(bin : &[u8], file_offset : usize, format : &ImgFmt)->SpriteFrame{
-        let header = bin[file_offset..file_offset+0x14].to_vec();
-        // println!("\t{:02X?}", &header);
-        let frame_bin = &bin[file_offset..];
-        let x = i16::from_be_bytes([frame_bin[0], frame_bin[1]]) as isize;
-        let y = i16::from_be_bytes([frame_bin[2], frame_bin[3]]) as isize;
-        let w = u16::from_be_bytes([frame_bin[4], frame_bin[5]]) as usize;
-        let h = u16::from_be_bytes([frame_bin[6], frame_bin[7]]) as usize;
-        let mut pxl_data : Vec<Vec<[u8;4]>> = vec![vec![[0; 4]; w]; h];
-        
-        let chunk_cnt = u16::from_be_bytes([frame_bin[8], frame_bin[9]]);
-        let mut palette :Vec<u8> = Vec::new();
 
-        let mut offset = file_offset + 0x14;
-        let mut chunks : Vec<SpriteChunk> = Vec::new();
-        let mut chk_hdrs : Vec<Vec<u8>> = Vec::new();
+    fn get_type(&self)->AssetType{
+        return AssetType::Sprite(self.format);
+    }
 
-        match format {
-            ImgFmt::CI4 => {
-                //align with file
-                offset = (offset + (8 - 1)) & !(8 - 1) ; //align to 0x8
-                palette  = bin[offset.. offset + 0x20].to_vec();
-                offset += 0x20;
-                
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }                
-            }
-            ImgFmt::CI8 => {
-                //align with file
-                offset = (offset + (8 - 1)) & !(8 - 1) ; //align to 0x8
-                palette  = bin[offset.. offset + 0x200].to_vec();
-                offset += 0x200;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::I4 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::I8 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::RGBA32 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
+    fn write(&self, path: &Path, opts: &ExtractOptions){
+        //write descriptor yaml and folder containing frame pngs
+        let containing_folder = path.parent().unwrap();
+        let base_name = path.file_name().unwrap().to_str().unwrap().trim_end_matches(".sprite.yaml");
+        let base_path = containing_folder.join(base_name);
+        let fmt_str = format!("{:?}", self.format).to_lowercase();
+        //the descriptor is written in one go once every frame is listed
+        let mut desc_f : Vec<u8> = Vec::new();
+        writeln!(desc_f, "type: Sprite").unwrap();
+        writeln!(desc_f, "format: {:?}", self.format).unwrap();
+        if let Some(reason) = self.lossy_reason(){
+            writeln!(desc_f, "lossy: {:?} # why construct won't give back the same bytes", reason).unwrap();
+        }
+        if !self.can_construct(){
+            let bin_name = format!("{}.sprite.{}.bin", base_name, fmt_str);
+            let mut bin_file = deps::create(containing_folder.join(&bin_name)).unwrap();
+            bin_file.write_all(&self.bytes).unwrap();
+            writeln!(desc_f, "bin: {:?}", bin_name).unwrap();
+        }
+        else{
+            writeln!(desc_f, "variant: {:?}", self.variant).unwrap();
+            if self.format == ImgFmt::RGBA16 || self.variant == SpriteVariant::RawTexture{
+                writeln!(desc_f, "dither: None # None, Ordered, or FloydSteinberg").unwrap();
             }
-            ImgFmt::RGBA16 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
+            let one_bit_alpha = matches!(self.format, ImgFmt::CI4 | ImgFmt::CI8 | ImgFmt::RGBA16);
+            if one_bit_alpha || self.variant == SpriteVariant::RawTexture{
+                writeln!(desc_f, "alpha_threshold: 128").unwrap();
             }
-            _ => {}
+            writeln!(desc_f, "header: {}", bytes_to_yaml(&self.header)).unwrap();
         }
-
-        for chnk in chunks{
-            let raw_data = match format {
-                ImgFmt::CI4    => Texture::ci4_to_rgba32(&chnk.pixel_data, &palette),
-                ImgFmt::CI8    => Texture::ci8_to_rgba32(&chnk.pixel_data, &palette),
-                ImgFmt::I4     => Texture::i4_to_rgba32(&chnk.pixel_data),
-                ImgFmt::I8     => Texture::i4_to_rgba32(&chnk.pixel_data),
-                ImgFmt::RGBA16 => Texture::rgba16_to_rgba32(&chnk.pixel_data),
-                ImgFmt::RGBA32 => chnk.pixel_data,
-                ImgFmt::IA4    => Texture::ia4_to_rgba32(&chnk.pixel_data),
-                ImgFmt::IA8    => Texture::ia4_to_rgba32(&chnk.pixel_data),
-                _=> Vec::new(),
-            };
-
-            if(chunk_cnt) == 1{
-                let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
-
-                for (j,row) in row_data.iter().enumerate(){
-                    for (i, pxl) in row.chunks_exact(4).enumerate(){
-                        let fx :isize = i as isize;
-                        let fy :isize = j as isize;
-                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
-                            pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
-                        }
-                    }
-                }
-            }
-            else{
-                let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
-                for (j,row) in row_data.iter().enumerate(){
-                    for (i, pxl) in row.chunks_exact(4).enumerate(){
-                        let fx :isize = (chnk.x + i as isize) as isize;
-                        let fy :isize = (chnk.y + j as isize) as isize;
-                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
-                            pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
+        if self.frame.is_empty(){
+            deps::write(path, &desc_f).unwrap();
+            return;
+        }
+        writeln!(desc_f, "frames:").unwrap();
+        
+        deps::create_dir_all(&base_path).unwrap();
+        let mut palettes : Vec<(&Vec<u8>, String)> = Vec::new();
+        let mut png_names : Vec<String> = Vec::new();
+        for(i, frame) in self.frame.iter().enumerate(){
+            let png_name = format!("{}/{:02X}.{}.png", base_name, i, fmt_str);
+            let pal_entry = match &frame.palette{
+                None => String::new(),
+                Some(pal) => {
+                    //frames with identical palettes share one palette file
+                    let pal_name = match palettes.iter().find(|(p, _)|{*p == pal}){
+                        Some((_, name)) => name.clone(),
+                        None => {
+                            let name = format!("{}/{:02X}.pal.png", base_name, palettes.len());
+                            texture::write_png_rgba32(&containing_folder.join(&name), pal.len()/2, 1, &Texture::rgba16_to_rgba32(pal));
+                            palettes.push((pal, name.clone()));
+                            name
                         }
-                    }
+                    };
+                    format!("    palette: {:?}\n", pal_name)
                 }
+            };
+            writeln!(desc_f, "  - png: {:?}", png_name).unwrap();
+            write!(desc_f, "{}", pal_entry).unwrap();
+            writeln!(desc_f, "    x: {}", frame.x).unwrap();
+            writeln!(desc_f, "    y: {}", frame.y).unwrap();
+            writeln!(desc_f, "    header: {}", bytes_to_yaml(&frame.header)).unwrap();
+            writeln!(desc_f, "    chunks:").unwrap();
+            for (x, y, w, h) in frame.chunks.iter(){
+                writeln!(desc_f, "      - {{x: {}, y: {}, w: {}, h: {}}}", x, y, w, h).unwrap();
             }
+            png_names.push(png_name);
+        }
+        deps::write(path, &desc_f).unwrap();
+        //encoding is most of the time extraction takes, the files still go out in order
+        let pngs = super::map_threads(&self.frame, opts.threads, |frame|{frame.png()});
+        for (name, png) in png_names.iter().zip(pngs.into_iter()){
+            deps::write(containing_folder.join(name), png).unwrap();
         }
 
-        let pal = match format{
-            ImgFmt::CI4 | ImgFmt::CI8 => Some(palette),
-            _ => None,
-        };
-
-        SpriteFrame{w: w as usize,h: h as usize, header: header, chk_hdrs:chk_hdrs, palette : pal, pixel_data: pxl_data.into_iter().flatten().flatten().collect()}
+        if opts.c_arrays && self.can_construct(){
+            self.write_c_arrays(&containing_folder.join(format!("{}.inc.c", base_name)), base_name);
+        }
+        if let (Some(delay), true) = (opts.apng_delay_ms, self.frame.len() > 1){
+            self.write_apng(&containing_folder.join(format!("{}.apng", base_name)), delay);
+        }
     }
 }
 
-pub struct Sprite{
-    format: ImgFmt,
-    pub frame: Vec<SpriteFrame>,
-    bytes: Vec<u8>,
-}
-
 impl Sprite{
-    pub fn from_bytes(in_bytes: &[u8])->Sprite{
-        let frame_cnt = u16::from_be_bytes([in_bytes[0], in_bytes[1]]);
-        let format = u16::from_be_bytes([in_bytes[2], in_bytes[3]]);
-        let frmt = match format{
-            0x0001 => ImgFmt::CI4,
-            0x0004 => ImgFmt::CI8,
-            0x0020 => ImgFmt::I4,
-            0x0040 => ImgFmt::I8,
-            0x0400 => ImgFmt::RGBA16,
-            0x0800 => ImgFmt::RGBA32,
-            _ => ImgFmt::Unknown(format),
-        };
-        match frmt {
-            ImgFmt::Unknown(_) => {return Sprite{format: frmt, frame: Vec::new(), bytes: in_bytes.to_vec()}},
-            _=> {}
+    /// pack every frame into one png, with a json atlas of frame rects and anchors
+    pub fn write_sheet(&self, png_path: &Path, json_path: &Path){
+        const PADDING : usize = 1;
+        assert!(!self.frame.is_empty(), "sprite has no decodable frames");
+
+        //shelf pack in frame order, rows about as wide as the sheet is tall
+        let area : usize = self.frame.iter().map(|f|{(f.w + PADDING)*(f.h + PADDING)}).sum();
+        let max_w = self.frame.iter().map(|f|{f.w}).max().unwrap();
+        let row_limit = std::cmp::max(max_w, (area as f64).sqrt().ceil() as usize);
+        let mut rects : Vec<(usize, usize)> = Vec::new();
+        let (mut x, mut y, mut row_h, mut sheet_w) = (0, 0, 0, 0);
+        for frame in self.frame.iter(){
+            if x > 0 && x + frame.w > row_limit{
+                x = 0;
+                y += row_h + PADDING;
+                row_h = 0;
+            }
+            rects.push((x, y));
+            sheet_w = std::cmp::max(sheet_w, x + frame.w);
+            row_h = std::cmp::max(row_h, frame.h);
+            x += frame.w + PADDING;
         }
+        let sheet_h = y + row_h;
 
-        if frame_cnt > 0x100{
-            let mut offset = 8 as usize;
-            let chunk = SpriteChunk::new(in_bytes, &mut offset, &ImgFmt::RGBA16);
-            let frame = SpriteFrame{w:chunk.w, h:chunk.h, header: Vec::new(), chk_hdrs: vec![in_bytes[8..16].to_vec()], palette: None, pixel_data: Texture::rgba16_to_rgba32(&chunk.pixel_data)};
-            return Sprite{format: frmt, frame: vec![frame], bytes: in_bytes.to_vec()};
-        }
-        // println!("{:02X?}", &in_bytes[..0x10]);
-        let frames : Vec<SpriteFrame>= in_bytes[0x10..]
-                .chunks_exact(0x4)
-                .take(frame_cnt as usize)
-                .map(|a|{
-                    let offset = u32::from_be_bytes(a.try_into().unwrap());
-                    SpriteFrame::new(in_bytes, 0x10 + offset as usize + 4*frame_cnt as usize, &frmt)
-                })
-                .collect(); 
-        return Sprite{format: frmt, frame: frames, bytes: in_bytes.to_vec()};
-    }
-
-    pub fn read(path: &Path) -> Sprite{
-        Sprite{format: ImgFmt::Unknown(0), frame: Vec::new(), bytes: fs::read(path).unwrap()}
+        let mut canvas = vec![0u8; 4*sheet_w*sheet_h];
+        for (frame, (fx, fy)) in self.frame.iter().zip(rects.iter()){
+            for (j, row) in frame.pixel_data.chunks_exact(4*frame.w).enumerate(){
+                let start = 4*(sheet_w*(fy + j) + fx);
+                canvas[start .. start + 4*frame.w].copy_from_slice(row);
+            }
+        }
+        texture::write_png_rgba32(png_path, sheet_w, sheet_h, &canvas);
+
+        let mut json_f = deps::create(json_path).unwrap();
+        writeln!(json_f, "{{").unwrap();
+        writeln!(json_f, "  \"image\": {:?},", png_path.file_name().unwrap().to_str().unwrap()).unwrap();
+        writeln!(json_f, "  \"format\": \"{:?}\",", self.format).unwrap();
+        writeln!(json_f, "  \"size\": {{\"w\": {}, \"h\": {}}},", sheet_w, sheet_h).unwrap();
+        writeln!(json_f, "  \"frames\": [").unwrap();
+        for (i, (frame, (fx, fy))) in self.frame.iter().zip(rects.iter()).enumerate(){
+            let (ax, ay) = (frame.x, frame.y);
+            let sep = if i + 1 < self.frame.len() {","} else {""};
+            writeln!(json_f, "    {{\"index\": {}, \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}, \"anchor\": {{\"x\": {}, \"y\": {}}}}}{}", i, fx, fy, frame.w, frame.h, ax, ay, sep).unwrap();
+        }
+        writeln!(json_f, "  ]").unwrap();
+        writeln!(json_f, "}}").unwrap();
     }
-}
 
-/// Sprite TODO !!!!!!!!!
-///     - struct members
-///     - read
-///     - to_bytes
-
-impl Asset for Sprite{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
+    /// each frame's texels and palette as u64 arrays the decomp can #include
+    fn write_c_arrays(&self, path: &Path, base_name: &str){
+        let texel_format = match self.variant{
+            SpriteVariant::RawTexture => ImgFmt::RGBA16,
+            SpriteVariant::Frames => self.format,
+        };
+        let prefix = format!("sprite_{}", texture::c_identifier(base_name));
+        let mut c_f = deps::create(path).unwrap();
+        for (i, frame) in self.frame.iter().enumerate(){
+            let palette = frame.palette_colors(&texel_format);
+            if !palette.is_empty(){
+                let entries = if texel_format == ImgFmt::CI4 {0x10} else {0x100};
+                let comment = format!("frame {:02X} palette: RGBA16, {} entries", i, entries);
+                texture::write_c_array(&mut c_f, &format!("{}_{:02X}_pal", prefix, i), &comment, &Texture::palette_to_bytes(&palette, entries));
+            }
+            let texels = frame.texels(&texel_format, &palette, 0, 0, frame.w, frame.h);
+            let comment = format!("frame {:02X}: {:?} {}x{}, {} bytes", i, texel_format, frame.w, frame.h, texels.len());
+            texture::write_c_array(&mut c_f, &format!("{}_{:02X}", prefix, i), &comment, &texels);
+        }
     }
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Sprite(self.format);
+    /// all frames on one canvas the size of the largest frame, anchored top left
+    fn write_apng(&self, path: &Path, delay_ms: u16){
+        let w = self.frame.iter().map(|f|{f.w}).max().unwrap();
+        let h = self.frame.iter().map(|f|{f.h}).max().unwrap();
+        let anim_f = deps::create(path).unwrap();
+        let ref mut bw = BufWriter::new(anim_f);
+        let mut encoder = png::Encoder::new(bw, w as u32, h as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frame.len() as u32, 0).unwrap();
+        encoder.set_frame_delay(delay_ms, 1000).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        for frame in self.frame.iter(){
+            let mut canvas = vec![0u8; 4*w*h];
+            for (j, row) in frame.pixel_data.chunks_exact(4*frame.w).enumerate(){
+                canvas[4*w*j .. 4*w*j + 4*frame.w].copy_from_slice(row);
+            }
+            writer.write_image_data(&canvas).unwrap();
+        }
     }
+}
 
-    fn write(&self, path: &Path){
-        //write bin. TODO remove once one to 1 conversion
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
-
-        //write descriptor yaml and folder containing frame pngs
-        let base_name = Path::new(path.file_stem().unwrap());
-        let fmt_str = base_name.extension().unwrap();
-        let new_base = Path::new(base_name.file_stem().unwrap());
-        let base_name = Path::new(new_base.file_stem().unwrap());
-        let base_path = path.parent().unwrap().join(base_name);
-        let mut desc_path = base_path.clone();
-        desc_path.set_extension("sprite.yaml");
-        let mut desc_f = File::create(desc_path).unwrap();
-        writeln!(desc_f, "type: Sprite").unwrap();
-        writeln!(desc_f, "format: {:?}", self.format).unwrap();
-        writeln!(desc_f, "frames:").unwrap();
-        
-        DirBuilder::new().recursive(true).create(&base_path.clone()).unwrap();
-        for(i, frame) in self.frame.iter().enumerate(){
-            let mut i_path = base_path.join(format!("{:02X}.", i));
-            i_path.set_extension(format!("{}.png",fmt_str.to_str().unwrap()));
-            writeln!(desc_f, "  - {:?}", i_path).unwrap();
-            let texture_f = File::create(i_path).unwrap();
-            let ref mut w = BufWriter::new(texture_f);
+fn align(bytes: &mut Vec<u8>, alignment: usize){
+    bytes.resize((bytes.len() + (alignment - 1)) & !(alignment - 1), 0);
+}
 
-            let mut encoder = png::Encoder::new(w, frame.w as u32, frame.h as u32);
-            encoder.set_color(png::ColorType::Rgba);
-            encoder.set_depth(png::BitDepth::Eight);
-            let mut writer = encoder.write_header().unwrap();
+pub fn bytes_to_yaml(bytes: &[u8]) -> String{
+    let hex : Vec<String> = bytes.iter().map(|b|{format!("0x{:02X}", b)}).collect();
+    return format!("[{}]", hex.join(", "))
+}
 
-            let data = &frame.pixel_data;
-            // let mirrored : Vec<u8> = data.rchunks_exact(4*frame.w).map(|a|{a.to_vec()}).flatten().collect();
+pub fn yaml_to_bytes(yaml: &Yaml) -> Vec<u8>{
+    return yaml.as_vec().unwrap().iter().map(|b|{b.as_i64().unwrap() as u8}).collect()
+}
 
-            writer.write_image_data(&data).unwrap(); // Save
-        }
+/// a yaml number as f32, reading reals from their text so written floats come back exact
+pub fn yaml_f32(y: &Yaml) -> Option<f32>{
+    match y{
+        Yaml::Real(s) => s.parse::<f32>().ok(),
+        Yaml::Integer(i) => Some(*i as f32),
+        _ => None,
     }
 }
+
@@ -0,0 +1,75 @@
+use super::asset::{self, Asset, AssetType, Model, Sprite};
+use super::log;
+use super::names::NameMap;
+use super::sqlite::Table;
+use super::{type_name, AssetTable};
+
+// a bin as SQLite tables, for questions that would otherwise need code:
+//
+//     SELECT uid, size FROM assets WHERE type = 'Dialog' AND compressed AND size > 1024
+//
+// `assets` has a row per non empty entry, `strings` one per decoded string of
+// the dialogs and questions, `sprite_frames` one per sprite frame and `models`
+// one per model. Every table has a `uid` column to join them on.
+
+/// the tables of every entry of `table`
+pub fn tables(table: &AssetTable, names: &NameMap) -> Vec<Table>{
+    let mut assets = Table::new("assets", &[("uid", "INTEGER"), ("name", "TEXT"), ("segment", "INTEGER"), ("flags", "INTEGER"), ("type", "TEXT"), ("compressed", "INTEGER"), ("stored_size", "INTEGER"), ("size", "INTEGER")]);
+    let mut strings = Table::new("strings", &[("uid", "INTEGER"), ("section", "TEXT"), ("idx", "INTEGER"), ("cmd", "INTEGER"), ("text", "TEXT")]);
+    let mut frames = Table::new("sprite_frames", &[("uid", "INTEGER"), ("frame", "INTEGER"), ("format", "TEXT"), ("width", "INTEGER"), ("height", "INTEGER"), ("x", "INTEGER"), ("y", "INTEGER"), ("chunks", "INTEGER")]);
+    let mut models = Table::new("models", &[("uid", "INTEGER"), ("vertices", "INTEGER"), ("triangles", "INTEGER"), ("textures", "INTEGER"), ("gfx_commands", "INTEGER"), ("bones", "INTEGER"), ("collision_triangles", "INTEGER")]);
+
+    for e in table.entries().filter(|e|{e.flags != 4}){
+        let uid = e.uid;
+        //entries that don't decompress are listed with their stored bytes
+        let bytes = match table.bytes(uid).or_else(|_|{table.stored_bytes(uid).map(|b|{Some(b.to_vec())})}){
+            Ok(Some(b)) => b,
+            _ => continue,
+        };
+        //entries that don't decode are still listed, as Binary
+        let data : Box<dyn Asset> = match table.try_asset(uid){
+            Ok(Some(a)) => a,
+            Ok(None) => continue,
+            Err(err) => {
+                log::warning(Some(uid), &format!("{}, listed as Binary", err));
+                Box::new(asset::Binary::from_bytes(&bytes))
+            },
+        };
+        let asset_type = data.get_type();
+        assets.push(vec![uid.into(), names.name(uid).into(), e.segment.into(), (e.flags as i64).into(), type_name(&asset_type).into(), e.compressed.into(), e.size.into(), bytes.len().into()]);
+
+        let mut sections : Vec<&str> = Vec::new();
+        for line in data.text_lines().unwrap_or_default(){
+            let idx = sections.iter().filter(|s|{**s == line.section}).count();
+            sections.push(line.section);
+            strings.push(vec![uid.into(), line.section.into(), idx.into(), (line.cmd as i64).into(), line.text.into()]);
+        }
+        match asset_type{
+            AssetType::Sprite(format) => {
+                let sprite = match Sprite::try_from_bytes(&bytes){
+                    Ok(sprite) => sprite,
+                    Err(_) => continue,
+                };
+                for (i, f) in sprite.frame.iter().enumerate(){
+                    let (w, h, _) = f.rgba();
+                    let (x, y) = f.anchor();
+                    frames.push(vec![uid.into(), i.into(), format!("{:?}", format).into(), w.into(), h.into(), (x as i64).into(), (y as i64).into(), f.chunks.len().into()]);
+                }
+            },
+            AssetType::Model => {
+                let model = match Model::try_from_bytes(&bytes){
+                    Ok(model) if model.header.is_some() => model,
+                    _ => continue,
+                };
+                //sections that can't be read are NULL
+                models.push(vec![
+                    uid.into(), model.vertices().ok().map(|v|{v.len()}).into(), model.triangles().ok().map(|t|{t.len()}).into(),
+                    model.textures().ok().map(|t|{t.len()}).into(), model.gfx().ok().map(|g|{g.len()}).into(),
+                    model.bones().ok().flatten().map(|b|{b.bones.len()}).into(), model.collision().ok().flatten().map(|c|{c.tri_count}).into(),
+                ]);
+            },
+            _ => {},
+        }
+    }
+    return vec![assets, strings, frames, models]
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+// BPS patches from one file to another, so a mod can ship what it changed
+// without any of the game's own data. The encoder is greedy: bytes that are
+// the same at the same offset are read from the source, runs found elsewhere
+// in the source are copied from there, and anything else is written into the
+// patch. Not the smallest patch possible, but every patcher applies it.
+
+const SOURCE_READ : usize = 0;
+const TARGET_READ : usize = 1;
+const SOURCE_COPY : usize = 2;
+
+/// shortest copy from elsewhere in the source worth its offset
+const MIN_COPY : usize = 8;
+
+fn number(out: &mut Vec<u8>, mut n: usize){
+    loop{
+        let x = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0{
+            out.push(x | 0x80);
+            return;
+        }
+        out.push(x);
+        n -= 1;
+    }
+}
+
+fn read_number(patch: &[u8], at: &mut usize) -> Result<usize, String>{
+    let (mut n, mut shift) = (0usize, 1usize);
+    let too_big = ||{"patch has a number too big to be a size".to_string()};
+    loop{
+        let x = *patch.get(*at).ok_or("patch ends in a number")? as usize;
+        *at += 1;
+        n = (x & 0x7F).checked_mul(shift).and_then(|v|{n.checked_add(v)}).ok_or_else(too_big)?;
+        if x & 0x80 != 0{
+            return Ok(n)
+        }
+        shift = shift.checked_mul(0x80).ok_or_else(too_big)?;
+        n = n.checked_add(shift).ok_or_else(too_big)?;
+    }
+}
+
+fn key(bytes: &[u8], at: usize) -> Option<u64>{
+    return bytes.get(at..at + 8).map(|b|{u64::from_le_bytes(b.try_into().unwrap())})
+}
+
+/// a patch that turns `source` into `target`
+pub fn diff(source: &[u8], target: &[u8]) -> Vec<u8>{
+    //every 4th source offset by its next 8 bytes. Any longer run in the source
+    //covers one of them, so it's found a few target bytes in
+    let mut index : HashMap<u64, usize> = HashMap::new();
+    for at in (0..source.len()).step_by(4){
+        if let Some(k) = key(source, at){
+            index.entry(k).or_insert(at);
+        }
+    }
+
+    let mut out : Vec<u8> = b"BPS1".to_vec();
+    number(&mut out, source.len());
+    number(&mut out, target.len());
+    number(&mut out, 0); //no metadata
+    let mut literal : Vec<u8> = Vec::new();
+    let flush = |out: &mut Vec<u8>, literal: &mut Vec<u8>|{
+        if !literal.is_empty(){
+            number(out, ((literal.len() - 1) << 2) | TARGET_READ);
+            out.append(literal);
+        }
+    };
+    let mut source_rel : usize = 0; //where the last source copy ended
+    let mut t = 0;
+    while t < target.len(){
+        let same = (t..target.len()).take_while(|i|{source.get(*i) == Some(&target[*i])}).count();
+        if same >= 4 || (same > 0 && t + same == target.len()){
+            flush(&mut out, &mut literal);
+            number(&mut out, ((same - 1) << 2) | SOURCE_READ);
+            t += same;
+            continue;
+        }
+        let found = key(target, t).and_then(|k|{index.get(&k)}).map(|s|{
+            (*s, (0..).take_while(|i|{t + i < target.len() && s + i < source.len() && source[s + i] == target[t + i]}).count())
+        });
+        match found{
+            Some((s, len)) if len >= MIN_COPY => {
+                flush(&mut out, &mut literal);
+                number(&mut out, ((len - 1) << 2) | SOURCE_COPY);
+                let delta = s as i64 - source_rel as i64;
+                number(&mut out, ((delta.unsigned_abs() as usize) << 1) | (delta < 0) as usize);
+                source_rel = s + len;
+                t += len;
+            },
+            _ => {
+                literal.push(target[t]);
+                t += 1;
+            },
+        }
+    }
+    flush(&mut out, &mut literal);
+    out.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    out.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    let patch_crc = crc32fast::hash(&out);
+    out.extend_from_slice(&patch_crc.to_le_bytes());
+    return out
+}
+
+/// `patch` applied to `source`, checking every crc
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String>{
+    if !patch.starts_with(b"BPS1") || patch.len() < 16{
+        return Err("not a BPS patch".to_string());
+    }
+    let crc_at = |at: usize|{u32::from_le_bytes(patch[at..at + 4].try_into().unwrap())};
+    let end = patch.len() - 12;
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc_at(patch.len() - 4){
+        return Err("the patch is corrupt".to_string());
+    }
+    if crc32fast::hash(source) != crc_at(end){
+        return Err("the patch is for a different source file".to_string());
+    }
+    let mut at = 4;
+    let source_len = read_number(patch, &mut at)?;
+    let target_len = read_number(patch, &mut at)?;
+    let metadata_len = read_number(patch, &mut at)?;
+    at = at.checked_add(metadata_len).filter(|a|{*a <= end}).ok_or("patch ends in its metadata")?;
+    if source_len != source.len(){
+        return Err(format!("the patch is for a 0x{:X} byte source, not 0x{:X}", source_len, source.len()));
+    }
+    //the size is the patch's word, it only grows as far as the patch fills it
+    let mut target : Vec<u8> = Vec::with_capacity(target_len.min(source.len() + patch.len()));
+    let (mut source_rel, mut target_rel) = (0usize, 0usize);
+    let relative = |patch: &[u8], at: &mut usize, rel: usize| -> Result<usize, String>{
+        let n = read_number(patch, at)?;
+        let offset = if n & 1 != 0 {rel.checked_sub(n >> 1)} else {rel.checked_add(n >> 1)};
+        return offset.ok_or("copy from before the start".to_string())
+    };
+    while at < end{
+        let n = read_number(patch, &mut at)?;
+        let len = (n >> 2) + 1;
+        if len > target_len - target.len(){
+            return Err("the patch writes past the end of the target".to_string());
+        }
+        match n & 3{
+            SOURCE_READ => {
+                let t = target.len();
+                target.extend_from_slice(source.get(t..t + len).ok_or("source read past the end")?);
+            },
+            TARGET_READ => {
+                target.extend_from_slice(patch.get(at..at + len).filter(|_|{at + len <= end}).ok_or("patch ends in a literal")?);
+                at += len;
+            },
+            SOURCE_COPY => {
+                source_rel = relative(patch, &mut at, source_rel)?;
+                target.extend_from_slice(source.get(source_rel..source_rel + len).ok_or("source copy past the end")?);
+                source_rel += len;
+            },
+            _ => {
+                //target copies can overlap what they write, byte by byte
+                target_rel = relative(patch, &mut at, target_rel)?;
+                for _ in 0..len{
+                    let b = *target.get(target_rel).ok_or("target copy past what's written")?;
+                    target.push(b);
+                    target_rel += 1;
+                }
+            },
+        }
+    }
+    if target.len() != target_len || crc32fast::hash(&target) != crc_at(end + 4){
+        return Err("the patched file doesn't match the patch's crc".to_string());
+    }
+    return Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applying_a_diff_gives_the_target(){
+        let source : Vec<u8> = (0..0x400u32).map(|i|{(i*7 % 251) as u8}).collect();
+        let mut target = source.clone();
+        target[0x10..0x18].copy_from_slice(b"CHANGED!");
+        target.extend_from_slice(&source[0x100..0x180]);
+        target.drain(0x200..0x220);
+        let patch = diff(&source, &target);
+        assert_eq!(apply(&source, &patch), Ok(target));
+        assert!(apply(&source[1..], &patch).is_err());
+    }
+
+    #[test]
+    fn corrupt_patches_are_errors(){
+        let source = b"the source file, long enough to copy from".to_vec();
+        let patch = diff(&source, b"the target file, long enough to copy from");
+        let mut flipped = patch.clone();
+        flipped[6] ^= 1;
+        assert_eq!(apply(&source, &flipped), Err("the patch is corrupt".to_string()));
+        assert!(apply(&source, &patch[..12]).is_err());
+        assert!(read_number(&[0x00; 16], &mut 0).is_err());
+        assert_eq!(read_number(&[0x00, 0x80], &mut 0), Ok(0x80));
+    }
+}
@@ -0,0 +1,46 @@
+use super::{type_rules, AssetTable};
+
+// `grep-bytes`: a pattern of hex bytes, `??` for any byte, the same as a type
+// rule's `bytes`, looked for in the decompressed data of every entry. Matches
+// can overlap.
+
+pub struct Match{
+    pub uid : usize,
+    pub offset : usize,
+    pub bytes : Vec<u8>,
+}
+
+/// a pattern like "1F 2E ?? 04", which needs at least one byte that isn't ??
+pub fn parse(text: &str) -> Result<Vec<Option<u8>>, String>{
+    let pattern = type_rules::parse_bytes(text)?;
+    if pattern.iter().all(|b|{b.is_none()}){
+        return Err(format!("\"{}\" matches anything, it needs at least one byte", text));
+    }
+    return Ok(pattern)
+}
+
+/// every offset of `bytes` the pattern matches at
+pub fn find(pattern: &[Option<u8>], bytes: &[u8]) -> Vec<usize>{
+    if bytes.len() < pattern.len(){
+        return Vec::new()
+    }
+    return (0..=bytes.len() - pattern.len())
+        .filter(|at|{pattern.iter().zip(bytes[*at..].iter()).all(|(p, b)|{p.map_or(true, |p|{p == *b})})})
+        .collect()
+}
+
+/// the matches in every non-empty entry, by uid then offset
+pub fn search(table: &AssetTable, pattern: &[Option<u8>]) -> Vec<Match>{
+    let mut out : Vec<Match> = Vec::new();
+    for uid in 0..table.len(){
+        let bytes = match table.bytes(uid){
+            Ok(Some(b)) => b,
+            Ok(None) => continue,
+            Err(e) => panic!("{}", e),
+        };
+        for at in find(pattern, &bytes){
+            out.push(Match{uid: uid, offset: at, bytes: bytes[at..at + pattern.len()].to_vec()});
+        }
+    }
+    return out
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use super::error::ParseError;
+
+// decompressed entries kept on disk, so asking about the same bin again skips
+// decompressing. A file is named after the entry's uid and the sha1 of its
+// compressed bytes, so entries that changed miss and identical ones are shared
+// between bins. Files are written whole under a temporary name then renamed,
+// and one that doesn't hold the size the compressed header promises is
+// ignored, so a cache another run is writing to is still safe to read. It's
+// the machine's, not a project's, so it's always on the disk, not in `deps`.
+
+static DIR : RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// $BK_ASSET_TOOL_CACHE, otherwise bk_asset_tool in the user's cache folder
+pub fn default_dir() -> PathBuf{
+    if let Some(dir) = env::var_os("BK_ASSET_TOOL_CACHE"){
+        return PathBuf::from(dir)
+    }
+    let base = env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(||{env::var_os("LOCALAPPDATA").map(PathBuf::from)})
+        .or_else(||{env::var_os("HOME").map(|h|{PathBuf::from(h).join(".cache")})})
+        .unwrap_or_else(env::temp_dir);
+    return base.join("bk_asset_tool")
+}
+
+/// caches decompressed entries in `dir` from now on, or stops with None
+pub fn set(dir: Option<PathBuf>){
+    *DIR.write().unwrap() = dir;
+}
+
+fn file_name(uid: usize, comp_bin: &[u8]) -> String{
+    return format!("{:04X}-{}", uid, sha1_smol::Sha1::from(comp_bin).digest())
+}
+
+/// the decompressed size from a compressed entry's 11 72 header
+pub(crate) fn unzipped_len(comp_bin: &[u8]) -> Option<usize>{
+    if !comp_bin.starts_with(&[0x11, 0x72]){
+        return None
+    }
+    let len = comp_bin.get(2..6)?;
+    return Some(u32::from_be_bytes(len.try_into().unwrap()) as usize)
+}
+
+/// the raw deflate stream after the header, no longer than the header says
+pub(crate) fn inflate(uid: usize, comp_bin: &[u8]) -> Result<Vec<u8>, ParseError>{
+    let fail = |message: String|{ParseError::Decode{uid: uid, message: message}};
+    let len = unzipped_len(comp_bin).ok_or_else(||{fail("doesn't decompress, it has no 11 72 header".to_string())})?;
+    let bytes = miniz_oxide::inflate::decompress_to_vec_with_limit(&comp_bin[6..], len)
+        .map_err(|e|{fail(format!("doesn't decompress, {:?}", e))})?;
+    if bytes.len() != len{
+        return Err(fail(format!("decompresses to 0x{:X} bytes, its header says 0x{:X}", bytes.len(), len)))
+    }
+    return Ok(bytes)
+}
+
+/// `comp_bin` decompressed, from the cache when it's there
+pub fn unzip(uid: usize, comp_bin: &[u8]) -> Result<Vec<u8>, ParseError>{
+    let dir = match DIR.read().unwrap().clone(){
+        Some(dir) => dir,
+        None => return inflate(uid, comp_bin),
+    };
+    let path = dir.join(file_name(uid, comp_bin));
+    if let Ok(bytes) = fs::read(&path){
+        if Some(bytes.len()) == unzipped_len(comp_bin){
+            return Ok(bytes)
+        }
+    }
+    let bytes = inflate(uid, comp_bin)?;
+    //a cache that can't be written to only costs the time saved
+    let tmp = dir.join(format!("{}.{}.tmp", file_name(uid, comp_bin), std::process::id()));
+    let stored = fs::create_dir_all(&dir).and_then(|_|{fs::write(&tmp, &bytes)}).and_then(|_|{fs::rename(&tmp, &path)});
+    if stored.is_err(){
+        let _ = fs::remove_file(&tmp);
+    }
+    return Ok(bytes)
+}
+
+/// whether `name` is one the cache writes, so clearing leaves anything else be
+fn is_cache_file(name: &str) -> bool{
+    let name = name.split('.').next().unwrap();
+    return name.len() == 45 && name.as_bytes()[4] == b'-' && name.chars().filter(|c|{*c != '-'}).all(|c|{c.is_ascii_hexdigit()})
+}
+
+/// removes every cached entry in `dir`, returns how many there were
+pub fn clear(dir: &Path) -> io::Result<usize>{
+    let entries = match fs::read_dir(dir){
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    for e in entries{
+        let path = e?.path();
+        if path.is_file() && path.file_name().and_then(|n|{n.to_str()}).map_or(false, is_cache_file){
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    //only goes when nothing else is in it
+    let _ = fs::remove_dir(dir);
+    return Ok(removed)
+}
@@ -0,0 +1,395 @@
+use std::convert::TryInto;
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::asset::{Asset, AssetType, ExtractOptions};
+use super::deps;
+use super::error::{ByteReader, ParseError};
+
+// demo inputs replayed by the attract mode: a byte count, then one 6 byte
+// entry per input held for `frames` frames. The game hands each entry's last
+// byte, its flag, to the demo player along with the input. Vanilla demos only
+// set it on the first input, so it's known as the frame 1 flag.
+
+/// Mupen64 input movies: a 0x400 byte header, then 4 bytes per controller per
+/// frame. Each sample is the controller's button word, same bit order as the
+/// game's, then the stick x and y
+const M64_HEADER_SIZE : usize = 0x400;
+const M64_SIGNATURE : &[u8; 4] = b"M64\x1A";
+
+/// the flag vanilla demos set on their first input, written by name
+const FRAME1_FLAG : u8 = 0x01;
+const FRAME1_FLAG_NAME : &str = "frame1";
+
+/// controller button bits, highest first
+pub const BUTTON_NAMES : [(u16, &str); 14] = [
+    (0x8000, "A"),
+    (0x4000, "B"),
+    (0x2000, "Z"),
+    (0x1000, "START"),
+    (0x0800, "D_UP"),
+    (0x0400, "D_DOWN"),
+    (0x0200, "D_LEFT"),
+    (0x0100, "D_RIGHT"),
+    (0x0020, "L"),
+    (0x0010, "R"),
+    (0x0008, "C_UP"),
+    (0x0004, "C_DOWN"),
+    (0x0002, "C_LEFT"),
+    (0x0001, "C_RIGHT"),
+];
+
+/// `[A, Z, C_LEFT]`, or the hex if some set bit has no name
+pub fn buttons_to_yaml(buttons: u16)->String{
+    let named : u16 = BUTTON_NAMES.iter().fold(0, |acc, (bit, _)|{acc | bit});
+    if buttons & !named != 0 {
+        return format!("0x{:04X}", buttons)
+    }
+    let names : Vec<&str> = BUTTON_NAMES.iter()
+        .filter(|(bit, _)|{buttons & bit != 0})
+        .map(|(_, name)|{*name})
+        .collect();
+    return format!("[{}]", names.join(", "))
+}
+
+/// a list of names, or a plain number
+fn buttons_from_yaml(yaml: &Yaml)->u16{
+    if let Some(v) = yaml.as_i64(){
+        return v as u16
+    }
+    let names = yaml.as_vec().expect("buttons must be a list of button names or a number");
+    return names.iter().fold(0, |acc, n|{
+        let n = n.as_str().expect("button names must be strings");
+        let bit = BUTTON_NAMES.iter()
+            .find(|(_, name)|{name.eq_ignore_ascii_case(n)})
+            .unwrap_or_else(|| panic!("unknown button \"{}\"", n)).0;
+        acc | bit
+    })
+}
+
+/// `frame1`, or a byte for the flags that have no name. None if it's left out
+fn flag_from_yaml(yaml: &Yaml)->Result<Option<u8>, ParseError>{
+    return match yaml{
+        Yaml::BadValue => Ok(None),
+        Yaml::String(n) if n.eq_ignore_ascii_case(FRAME1_FLAG_NAME) => Ok(Some(FRAME1_FLAG)),
+        Yaml::Integer(v) if (0..=0xFF).contains(v) => Ok(Some(*v as u8)),
+        _ => Err(ParseError::Invalid(format!("flag should be {} or a byte, not {:?}", FRAME1_FLAG_NAME, yaml))),
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct ContInput{
+    pub x: i8,
+    pub y: i8,
+    pub buttons: u16,
+    pub frames: u8,
+    pub flag: u8, //the frame 1 flag on the first input, 0 on the rest
+}
+
+impl ContInput{
+    fn to_bytes(&self)->Vec<u8>{
+        let b = self.buttons.to_be_bytes();
+        return vec![self.x as u8, self.y as u8, b[0], b[1], self.frames, self.flag];
+    }
+
+    fn from_bytes(a: &[u8])->ContInput{
+        return ContInput{x: a[0] as i8, y: a[1] as i8, buttons: u16::from_be_bytes([a[2], a[3]]), frames: a[4], flag: a[5]}
+    }
+
+    /// `flag` can be left out for 0
+    fn from_yaml(yaml: &Yaml)->Result<ContInput, ParseError>{
+        let x = yaml["x"].as_i64().unwrap() as i8;
+        let y = yaml["y"].as_i64().unwrap() as i8;
+        let buttons = buttons_from_yaml(&yaml["buttons"]);
+        let frames = yaml["frames"].as_i64().unwrap() as u8;
+        let flag = flag_from_yaml(&yaml["flag"])?.unwrap_or(0);
+        return Ok(ContInput{x: x, y: y, buttons: buttons, frames: frames, flag: flag})
+    }
+
+    /// same stick and buttons, so the two can be one input
+    fn same_as(&self, other: &ContInput) -> bool{
+        return self.x == other.x && self.y == other.y && self.buttons == other.buttons
+    }
+}
+
+pub struct DemoButtonFile{
+    pub inputs: Vec<ContInput>,
+}
+
+impl DemoButtonFile{
+    pub fn try_from_bytes(in_bytes: &[u8])->Result<DemoButtonFile, ParseError>{
+        if in_bytes.is_empty() { return Ok(DemoButtonFile{inputs: Vec::new()}) }
+        let expect_len = ByteReader::new(in_bytes).u32()? as usize;
+        //trailing bytes wouldn't be written back, so they're an error too
+        if expect_len == 0 || expect_len % 6 != 0 || expect_len != in_bytes.len() - 4{
+            return Err(ParseError::Invalid(format!("demo says it has 0x{:X} bytes of inputs, it has 0x{:X}", expect_len, in_bytes.len() - 4)));
+        }
+        return Ok(DemoButtonFile::from_bytes(in_bytes))
+    }
+
+    pub fn from_bytes(in_bytes: &[u8])->DemoButtonFile{
+        if in_bytes.len() < 4 { return DemoButtonFile{inputs: Vec::new()}}
+        let expect_len : usize =  u32::from_be_bytes(in_bytes[..4].try_into().unwrap()) as usize;
+        let inputs : Vec<ContInput> = in_bytes[4..].chunks_exact(6).map(ContInput::from_bytes).collect();
+        assert_eq!(expect_len, inputs.len()*6);
+        DemoButtonFile{inputs: inputs}
+    }
+
+    pub fn read(path: &Path) -> DemoButtonFile{
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        return DemoButtonFile::from_yaml(doc).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)})
+    }
+
+    fn from_yaml(doc: &Yaml) -> Result<DemoButtonFile, ParseError>{
+        let doc_type = doc["type"].as_str().unwrap();
+        assert_eq!(doc_type, "DemoInput");
+
+        let inputs_yaml = doc["inputs"].as_vec().unwrap();
+        let mut inputs : Vec<ContInput> = inputs_yaml.iter()
+            .map(ContInput::from_yaml)
+            .collect::<Result<Vec<ContInput>, ParseError>>()?;
+        //older files have the frame 1 flag at the top
+        if let (Some(first), Some(flag)) = (inputs.first_mut(), flag_from_yaml(&doc["flag"])?){
+            if !inputs_yaml[0]["flag"].is_badvalue(){
+                return Err(ParseError::Invalid("the frame 1 flag is given at the top and on the first input".to_string()));
+            }
+            first.flag = flag;
+        }
+        return Ok(DemoButtonFile{inputs:inputs})
+    }
+
+    /// the first input's flag
+    pub fn frame1_flag(&self) -> u8{
+        return self.inputs.first().map_or(0, |i|{i.flag})
+    }
+
+    pub fn frame_count(&self) -> usize{
+        return self.inputs.iter().map(|i|{i.frames as usize}).sum()
+    }
+
+    /// inputs that can't come from a real controller or don't play for any time
+    pub fn validate(&self) -> Vec<String>{
+        let mut errors : Vec<String> = Vec::new();
+        let named : u16 = BUTTON_NAMES.iter().fold(0, |acc, (bit, _)|{acc | bit});
+        for (i, input) in self.inputs.iter().enumerate(){
+            if input.frames == 0{
+                errors.push(format!("input {} is held for 0 frames", i));
+            }
+            if input.buttons & 0x0C00 == 0x0C00{
+                errors.push(format!("input {} holds D_UP and D_DOWN", i));
+            }
+            if input.buttons & 0x0300 == 0x0300{
+                errors.push(format!("input {} holds D_LEFT and D_RIGHT", i));
+            }
+            if input.buttons & !named != 0{
+                errors.push(format!("input {} sets unused button bits 0x{:04X}", i, input.buttons & !named));
+            }
+            if i > 0 && input.flag != 0{
+                errors.push(format!("input {} has flag 0x{:02X}, vanilla demos only flag the first input", i, input.flag));
+            }
+        }
+        if !self.inputs.is_empty() && self.frame_count() == 0{
+            errors.push("demo is 0 frames long".to_string());
+        }
+        return errors
+    }
+
+    /// (button, frames held, times pressed) for every button
+    pub fn button_usage(&self) -> Vec<(&'static str, usize, usize)>{
+        return BUTTON_NAMES.iter().map(|(bit, name)|{
+            let held = self.inputs.iter().filter(|i|{i.buttons & bit != 0}).map(|i|{i.frames as usize}).sum();
+            let mut presses = 0;
+            let mut was_held = false;
+            for i in self.inputs.iter().filter(|i|{i.frames != 0}){
+                let is_held = i.buttons & bit != 0;
+                if is_held && !was_held{ presses += 1; }
+                was_held = is_held;
+            }
+            (*name, held, presses)
+        })
+        .collect()
+    }
+
+    /// the inputs played from frame `start` up to `end`, splitting the inputs
+    /// that cross either end
+    pub fn cut(&self, start: usize, end: usize) -> DemoButtonFile{
+        let mut inputs : Vec<ContInput> = Vec::new();
+        let mut frame = 0;
+        for input in self.inputs.iter(){
+            let input_start = frame.max(start);
+            let input_end = (frame + input.frames as usize).min(end);
+            frame += input.frames as usize;
+            if input_start < input_end{
+                let flag = if inputs.is_empty() {self.frame1_flag()} else {input.flag};
+                inputs.push(ContInput{frames: (input_end - input_start) as u8, flag: flag, ..*input});
+            }
+        }
+        return DemoButtonFile{inputs: inputs}
+    }
+
+    /// `other` played after this one, without its frame 1 flag. The inputs
+    /// either side of the join are merged if they're the same and fit in one
+    pub fn concat(&self, other: &DemoButtonFile) -> DemoButtonFile{
+        let mut inputs = self.inputs.clone();
+        let mut rest : Vec<ContInput> = other.inputs.clone();
+        if let Some(f) = rest.first_mut(){
+            f.flag = 0;
+        }
+        if let (Some(l), Some(f)) = (inputs.last_mut(), rest.first()){
+            if l.same_as(f) && l.frames as usize + f.frames as usize <= 0xFF{
+                l.frames += f.frames;
+                rest.remove(0);
+            }
+        }
+        inputs.extend(rest);
+        return DemoButtonFile{inputs: inputs}
+    }
+
+    /// one m64 sample per frame, each input repeated for its `frames`
+    pub fn to_m64(&self) -> Vec<u8>{
+        let samples : Vec<[u8; 4]> = self.inputs.iter()
+            .flat_map(|i|{
+                let b = i.buttons.to_be_bytes();
+                std::iter::repeat([b[0], b[1], i.x as u8, i.y as u8]).take(i.frames as usize)
+            })
+            .collect();
+        let mut out : Vec<u8> = vec![0; M64_HEADER_SIZE];
+        out[0..4].copy_from_slice(M64_SIGNATURE);
+        out[0x04..0x08].copy_from_slice(&3u32.to_le_bytes()); //version
+        out[0x0C..0x10].copy_from_slice(&(samples.len() as u32).to_le_bytes()); //vi count
+        out[0x14] = 60; //fps
+        out[0x15] = 1; //controllers
+        out[0x18..0x1C].copy_from_slice(&(samples.len() as u32).to_le_bytes());
+        out[0x1C..0x1E].copy_from_slice(&2u16.to_le_bytes()); //starts from power on
+        out[0x20..0x24].copy_from_slice(&1u32.to_le_bytes()); //controller 1 present
+        let rom_name = b"BANJO-KAZOOIE";
+        out[0xC4..0xC4 + rom_name.len()].copy_from_slice(rom_name);
+        out.extend(samples.into_iter().flatten());
+        return out
+    }
+
+    /// inverse of `to_m64`, taking controller 1. Runs of the same sample become
+    /// one input of up to 255 frames
+    pub fn from_m64(in_bytes: &[u8], frame1_flag: u8) -> Result<DemoButtonFile, ParseError>{
+        if in_bytes.len() < M64_HEADER_SIZE || &in_bytes[0..4] != M64_SIGNATURE{
+            return Err(ParseError::Invalid("not an m64 file".to_string()));
+        }
+        let sample_cnt = u32::from_le_bytes(in_bytes[0x18..0x1C].try_into().unwrap()) as usize;
+        let controller_cnt = (in_bytes[0x15] as usize).max(1);
+        let stored = (in_bytes.len() - M64_HEADER_SIZE)/(4*controller_cnt);
+        if stored < sample_cnt{
+            return Err(ParseError::Invalid(format!("m64 says it has {} samples, it has {}", sample_cnt, stored)));
+        }
+        let mut inputs : Vec<ContInput> = Vec::new();
+        for s in in_bytes[M64_HEADER_SIZE..].chunks_exact(4*controller_cnt).take(sample_cnt){
+            let input = ContInput{x: s[2] as i8, y: s[3] as i8, buttons: u16::from_be_bytes([s[0], s[1]]), frames: 1, flag: 0};
+            match inputs.last_mut(){
+                Some(l) if l.same_as(&input) && l.frames < 0xFF => l.frames += 1,
+                _ => inputs.push(input),
+            }
+        }
+        if let Some(first) = inputs.first_mut(){
+            first.flag = frame1_flag;
+        }
+        Ok(DemoButtonFile{inputs: inputs})
+    }
+}
+
+impl Asset for DemoButtonFile{
+    fn to_bytes(&self)->Vec<u8>{
+        if self.inputs.is_empty() { return Vec::new(); }
+
+        let mut output : Vec<u8> = (6*self.inputs.len() as u32).to_be_bytes().to_vec();
+        let mut input_bytes : Vec<u8> = self.inputs.iter().map(|i|{
+            i.to_bytes()
+        })
+        .flatten()
+        .collect();
+        output.append(&mut input_bytes);
+        return output;
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::DemoInput;
+    }
+
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let mut demo_file = deps::create(path).unwrap();
+        writeln!(demo_file, "type: DemoInput").unwrap();
+        if self.inputs.len() == 0{
+            writeln!(demo_file, "inputs: []").unwrap();
+            return;
+        }
+        writeln!(demo_file, "inputs:").unwrap();
+        for input in self.inputs.iter(){
+            let flag = match input.flag{
+                0 => String::new(),
+                FRAME1_FLAG => format!(", flag: {}", FRAME1_FLAG_NAME),
+                f => format!(", flag: 0x{:02X}", f),
+            };
+            writeln!(demo_file, "  - {{x: {:3}, y: {:3}, buttons: {}, frames: {}{}}}", input.x, input.y, buttons_to_yaml(input.buttons), input.frames, flag).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //flags past the first input aren't vanilla but have to survive
+    #[test]
+    fn flags_past_the_first_input_round_trip(){
+        let mut bytes = (3*6u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0x10, 0xF0, 0x80, 0x00, 0x05, 0x01]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x02, 0x40]);
+        bytes.extend_from_slice(&[0xFF, 0x7F, 0x20, 0x01, 0xFF, 0x03]);
+        let demo = DemoButtonFile::try_from_bytes(&bytes).unwrap();
+        assert_eq!(demo.inputs.iter().map(|i|{i.flag}).collect::<Vec<u8>>(), vec![0x01, 0x40, 0x03]);
+        assert_eq!(demo.to_bytes(), bytes);
+
+        let path = std::env::temp_dir().join(format!("bk_asset_tool_demo_{}.yaml", std::process::id()));
+        demo.write(&path, &ExtractOptions::new());
+        let yaml = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(yaml.contains("frames: 5, flag: frame1"));
+        assert!(yaml.contains("frames: 2, flag: 0x40"));
+        assert!(yaml.contains("frames: 255, flag: 0x03"));
+    }
+
+    #[test]
+    fn flags_read_by_name_or_number(){
+        let load = |s: &str|{DemoButtonFile::from_yaml(&YamlLoader::load_from_str(s).unwrap()[0])};
+        let named = load("type: DemoInput\ninputs:\n  - {x: 0, y: 0, buttons: [], frames: 1, flag: frame1}\n").unwrap();
+        let number = load("type: DemoInput\ninputs:\n  - {x: 0, y: 0, buttons: [], frames: 1, flag: 0x01}\n").unwrap();
+        let top = load("type: DemoInput\nflag: 1\ninputs:\n  - {x: 0, y: 0, buttons: [], frames: 1}\n").unwrap();
+        assert_eq!(named.frame1_flag(), 1);
+        assert_eq!(number.frame1_flag(), 1);
+        assert_eq!(top.frame1_flag(), 1);
+        assert!(load("type: DemoInput\nflag: 1\ninputs:\n  - {x: 0, y: 0, buttons: [], frames: 1, flag: frame1}\n").is_err());
+        assert!(load("type: DemoInput\ninputs:\n  - {x: 0, y: 0, buttons: [], frames: 1, flag: 0x100}\n").is_err());
+        assert!(load("type: DemoInput\ninputs:\n  - {x: 0, y: 0, buttons: [], frames: 1, flag: first}\n").is_err());
+    }
+
+    #[test]
+    fn bytes_past_the_inputs_are_an_error(){
+        let mut bytes = 6u32.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0x10, 0xF0, 0x80, 0x00, 0x05, 0x01]);
+        assert!(DemoButtonFile::try_from_bytes(&bytes).is_ok());
+        bytes.push(0);
+        assert!(DemoButtonFile::try_from_bytes(&bytes).is_err());
+        assert!(DemoButtonFile::try_from_bytes(&bytes[..3]).is_err());
+    }
+
+    #[test]
+    fn m64_round_trips_and_checks_its_input(){
+        let demo = DemoButtonFile{inputs: vec![
+            ContInput{x: 10, y: -20, buttons: 0x8000, frames: 3, flag: 1},
+            ContInput{x: 0, y: 0, buttons: 0, frames: 2, flag: 0},
+        ]};
+        let m64 = demo.to_m64();
+        assert_eq!(DemoButtonFile::from_m64(&m64, 1).unwrap().inputs, demo.inputs);
+        assert!(DemoButtonFile::from_m64(&m64[..M64_HEADER_SIZE + 4], 1).is_err());
+        assert!(DemoButtonFile::from_m64(&m64[4..], 1).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::Path;
+use yaml_rust::YamlLoader;
+
+use super::asset::AssetType;
+use super::deps;
+use super::level_setup::{CubeList, LevelSetup};
+use super::model::Model;
+use super::names::NameMap;
+use super::{type_name, AssetTable};
+
+// which assets reference which, for seeing what changing one asset affects.
+// Models reference their embedded textures, setups reference the models and
+// sprites of their props, and the models of their actors when given a table of
+// actor id to model uid (the game keeps that table in code, not in the bin).
+// Animations don't say which model they're for, so they have no edges.
+
+/// prop ids are offsets from these uids
+pub const PROP_MODEL_BASE : usize = 0x2D1;
+pub const PROP_SPRITE_BASE : usize = 0x572;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Node{
+    Asset(usize),
+    Texture(usize, usize), //model uid, index into its texture list
+}
+
+impl Node{
+    pub fn id(&self) -> String{
+        match self{
+            Node::Asset(uid) => format!("0x{:04X}", uid),
+            Node::Texture(uid, i) => format!("0x{:04X}/texture{}", uid, i),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge{
+    pub from : Node,
+    pub to : Node,
+    pub kind : &'static str, //texture, prop_model, prop_sprite or actor_model
+}
+
+pub struct DepGraph{
+    pub types : HashMap<usize, String>,
+    pub labels : HashMap<Node, String>,
+    pub edges : BTreeSet<Edge>,
+    pub warnings : Vec<String>,
+}
+
+/// actor id to model uid, from a yaml mapping like `0x1F4: 0x34D`
+pub fn read_actor_models(path: &Path) -> HashMap<u16, usize>{
+    let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open actor model table")).unwrap()[0];
+    return doc.as_hash().unwrap_or_else(||{panic!("{:?}: actor model tables are a yaml mapping of actor id: model uid", path)}).iter()
+        .map(|(k, v)|{
+            let id = k.as_i64().unwrap_or_else(||{panic!("{:?}: actor id {:?} is not a number", path, k)}) as u16;
+            let uid = v.as_i64().unwrap_or_else(||{panic!("{:?}: model of actor 0x{:X} is not a number", path, id)}) as usize;
+            (id, uid)
+        })
+        .collect()
+}
+
+impl DepGraph{
+    pub fn new(in_bytes: &[u8], actor_models: &HashMap<u16, usize>, names: &NameMap) -> DepGraph{
+        let table = AssetTable::new(in_bytes);
+        let mut graph = DepGraph{types: HashMap::new(), labels: HashMap::new(), edges: BTreeSet::new(), warnings: Vec::new()};
+        let types : HashMap<usize, AssetType> = (0..table.len())
+            .filter_map(|uid|{table.try_asset(uid).ok().flatten().map(|a|{(uid, a.get_type())})})
+            .collect();
+        for (uid, t) in types.iter(){
+            graph.types.insert(*uid, type_name(t));
+            let label = match names.name(*uid){
+                Some(name) => format!("{} {}", name, type_name(t)),
+                None => format!("0x{:04X} {}", uid, type_name(t)),
+            };
+            graph.labels.insert(Node::Asset(*uid), label);
+        }
+
+        let mut uids : Vec<&usize> = types.keys().collect();
+        uids.sort();
+        for uid in uids{
+            //types only has entries that decoded, so decompressed
+            let bytes = match table.bytes(*uid){
+                Ok(Some(b)) => b,
+                _ => continue,
+            };
+            match types[uid]{
+                AssetType::Model => {
+                    let model = Model::from_bytes(&bytes);
+                    for (i, t) in model.textures().unwrap_or_default().iter().enumerate(){
+                        let tex = Node::Texture(*uid, i);
+                        graph.labels.insert(tex.clone(), format!("texture {} {}x{} {:?}", i, t.w, t.h, t.format));
+                        graph.edges.insert(Edge{from: Node::Asset(*uid), to: tex, kind: "texture"});
+                    }
+                },
+                AssetType::LevelSetup => {
+                    let setup = LevelSetup::from_bytes(&bytes);
+                    let grid = match setup.cubes.as_ref(){
+                        Some(grid) => grid,
+                        None => {
+                            graph.warnings.push(format!("0x{:04X}: setup has no cubes or doesn't decode, its references are unknown", uid));
+                            continue;
+                        },
+                    };
+                    for list in grid.cubes.iter().flat_map(|c|{c.lists.iter()}){
+                        match list{
+                            CubeList::Props(props) => for p in props.iter(){
+                                let (base, kind, want) = if p.is_model() {(PROP_MODEL_BASE, "prop_model", "Model")} else {(PROP_SPRITE_BASE, "prop_sprite", "Sprite")};
+                                let target = base + p.id() as usize;
+                                graph.add_reference(*uid, target, kind, want, &format!("prop 0x{:X}", p.id()));
+                            },
+                            CubeList::Nodes(nodes) => for n in nodes.iter(){
+                                if let Some(target) = actor_models.get(&n.id()){
+                                    graph.add_reference(*uid, *target, "actor_model", "Model", &format!("actor 0x{:X}", n.id()));
+                                }
+                            },
+                            CubeList::Unknown(..) => (),
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+        return graph
+    }
+
+    /// edge from `uid` to `target`, if `target` is an asset of the type `want`
+    fn add_reference(&mut self, uid: usize, target: usize, kind: &'static str, want: &str, what: &str){
+        match self.types.get(&target){
+            Some(t) if t.starts_with(want) => {
+                self.edges.insert(Edge{from: Node::Asset(uid), to: Node::Asset(target), kind: kind});
+            },
+            t => {
+                let warning = format!("0x{:04X}: {} refers to 0x{:04X}, which is {}", uid, what, target, t.map_or("empty".to_string(), |t|{format!("a {}", t)}));
+                if !self.warnings.contains(&warning){
+                    self.warnings.push(warning);
+                }
+            },
+        }
+    }
+
+    /// every asset that refers to `node`, directly or through other assets
+    pub fn dependents(&self, node: &Node) -> Vec<Node>{
+        let mut found : BTreeSet<Node> = BTreeSet::new();
+        let mut todo = vec![node.clone()];
+        while let Some(n) = todo.pop(){
+            for e in self.edges.iter().filter(|e|{e.to == n}){
+                if found.insert(e.from.clone()){
+                    todo.push(e.from.clone());
+                }
+            }
+        }
+        return found.into_iter().collect()
+    }
+
+    /// nodes with at least one edge, in uid order
+    fn nodes(&self) -> Vec<Node>{
+        let nodes : BTreeSet<Node> = self.edges.iter().flat_map(|e|{[e.from.clone(), e.to.clone()]}).collect();
+        return nodes.into_iter().collect()
+    }
+
+    fn label(&self, node: &Node) -> String{
+        return self.labels.get(node).cloned().unwrap_or_else(||{node.id()})
+    }
+
+    pub fn write_dot(&self, out: &mut dyn Write) -> io::Result<()>{
+        writeln!(out, "digraph assets {{")?;
+        writeln!(out, "  rankdir=LR;")?;
+        for n in self.nodes(){
+            let shape = match n {Node::Asset(_) => "box", Node::Texture(..) => "ellipse"};
+            writeln!(out, "  \"{}\" [label=\"{}\", shape={}];", n.id(), self.label(&n).replace('"', "\\\""), shape)?;
+        }
+        for e in self.edges.iter(){
+            writeln!(out, "  \"{}\" -> \"{}\" [label=\"{}\"];", e.from.id(), e.to.id(), e.kind)?;
+        }
+        writeln!(out, "}}")?;
+        return Ok(())
+    }
+
+    pub fn write_json(&self, out: &mut dyn Write) -> io::Result<()>{
+        let quote = |s: &str|{format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))};
+        writeln!(out, "{{")?;
+        writeln!(out, "  \"nodes\": [")?;
+        let nodes = self.nodes();
+        for (i, n) in nodes.iter().enumerate(){
+            let kind = match n {Node::Asset(uid) => self.types.get(uid).cloned().unwrap_or("empty".to_string()), Node::Texture(..) => "texture".to_string()};
+            writeln!(out, "    {{\"id\": {}, \"type\": {}, \"label\": {}}}{}", quote(&n.id()), quote(&kind), quote(&self.label(n)), if i + 1 < nodes.len() {","} else {""})?;
+        }
+        writeln!(out, "  ],")?;
+        writeln!(out, "  \"edges\": [")?;
+        for (i, e) in self.edges.iter().enumerate(){
+            writeln!(out, "    {{\"from\": {}, \"to\": {}, \"kind\": {}}}{}", quote(&e.from.id()), quote(&e.to.id()), quote(e.kind), if i + 1 < self.edges.len() {","} else {""})?;
+        }
+        writeln!(out, "  ]")?;
+        writeln!(out, "}}")?;
+        return Ok(())
+    }
+}
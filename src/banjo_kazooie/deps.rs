@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+// every file the library reads or writes goes through here: asset files,
+// archives, manifests, migrations and the files options point to. Reads are
+// recorded for `construct --emit-deps`, whichever asset type reads them, and
+// both go to the installed `FileSystem`: the disk, or a `Memory` one for the
+// browser build and tests, where there is no disk to use. The decompression
+// cache is the exception, it belongs to the machine and stays on its disk, and
+// so are SQLite exports, which SQLite writes itself.
+
+/// where asset files live
+pub trait FileSystem: Send + Sync{
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+    /// names of the files directly in `dir`, not folders
+    fn list(&self, dir: &Path) -> io::Result<Vec<String>>;
+    /// paths of every file under `dir`, in folders too, in path order
+    fn walk(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+}
+
+/// the real file system
+pub struct Disk;
+
+impl FileSystem for Disk{
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>{
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()>{
+        fs::write(path, bytes)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>{
+        fs::create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool{
+        path.exists()
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<String>>{
+        let mut out = Vec::new();
+        for e in fs::read_dir(dir)?{
+            let p = e?.path();
+            if p.is_file(){
+                if let Some(n) = p.file_name().and_then(|n|{n.to_str()}){
+                    out.push(n.to_string());
+                }
+            }
+        }
+        return Ok(out)
+    }
+
+    fn walk(&self, dir: &Path) -> io::Result<Vec<PathBuf>>{
+        let mut out : Vec<PathBuf> = Vec::new();
+        let mut entries : Vec<PathBuf> = fs::read_dir(dir)?.map(|e|{e.map(|e|{e.path()})}).collect::<io::Result<Vec<PathBuf>>>()?;
+        entries.sort();
+        for path in entries.into_iter(){
+            match path.is_dir(){
+                true => out.extend(self.walk(&path)?),
+                false => out.push(path),
+            }
+        }
+        return Ok(out)
+    }
+}
+
+/// files kept in memory by path. Folders aren't tracked, any path can be written
+pub struct Memory{
+    files : Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl Memory{
+    pub fn new() -> Memory{
+        Memory{files: Mutex::new(HashMap::new())}
+    }
+
+    pub fn insert<P: AsRef<Path>>(&self, path: P, bytes: Vec<u8>){
+        self.files.lock().unwrap().insert(path.as_ref().to_path_buf(), bytes);
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>>{
+        self.files.lock().unwrap().get(path.as_ref()).cloned()
+    }
+
+    /// every file's path, sorted
+    pub fn paths(&self) -> Vec<PathBuf>{
+        let mut out : Vec<PathBuf> = self.files.lock().unwrap().keys().cloned().collect();
+        out.sort();
+        return out
+    }
+}
+
+impl FileSystem for Memory{
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>{
+        self.get(path).ok_or_else(||{io::Error::new(io::ErrorKind::NotFound, format!("{:?} is not in memory", path))})
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()>{
+        self.insert(path, bytes.to_vec());
+        Ok(())
+    }
+
+    fn create_dir_all(&self, _path: &Path) -> io::Result<()>{
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool{
+        self.files.lock().unwrap().contains_key(path)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<String>>{
+        return Ok(self.files.lock().unwrap().keys()
+            .filter(|p|{p.parent() == Some(dir)})
+            .filter_map(|p|{p.file_name().and_then(|n|{n.to_str()}).map(|n|{n.to_string()})})
+            .collect())
+    }
+
+    fn walk(&self, dir: &Path) -> io::Result<Vec<PathBuf>>{
+        let mut out : Vec<PathBuf> = self.files.lock().unwrap().keys().filter(|p|{p.starts_with(dir)}).cloned().collect();
+        out.sort();
+        return Ok(out)
+    }
+}
+
+static FILE_SYSTEM : RwLock<Option<Arc<dyn FileSystem>>> = RwLock::new(None);
+
+/// the disk, or an empty `Memory` without the `fs` feature
+fn file_system() -> Arc<dyn FileSystem>{
+    if let Some(f) = FILE_SYSTEM.read().unwrap().as_ref(){
+        return f.clone()
+    }
+    let default : Arc<dyn FileSystem> = match cfg!(feature = "fs"){
+        true => Arc::new(Disk),
+        false => Arc::new(Memory::new()),
+    };
+    return FILE_SYSTEM.write().unwrap().get_or_insert(default).clone()
+}
+
+/// reads and writes go to `f` from now on, on every thread
+pub fn set_file_system(f: Arc<dyn FileSystem>){
+    *FILE_SYSTEM.write().unwrap() = Some(f);
+}
+
+thread_local!{
+    static READ : RefCell<Option<Vec<PathBuf>>> = RefCell::new(None);
+}
+
+fn record(path: &Path){
+    READ.with(|r|{
+        if let Some(paths) = r.borrow_mut().as_mut(){
+            if !paths.iter().any(|p|{p == path}){
+                paths.push(path.to_path_buf());
+            }
+        }
+    });
+}
+
+/// starts recording reads on this thread
+pub fn start(){
+    READ.with(|r|{*r.borrow_mut() = Some(Vec::new())});
+}
+
+/// stops recording, and returns what was read in the order it was first read
+pub fn finish() -> Vec<PathBuf>{
+    return READ.with(|r|{r.borrow_mut().take()}).unwrap_or_default()
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>>{
+    record(path.as_ref());
+    return file_system().read(path.as_ref())
+}
+
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String>{
+    return String::from_utf8(read(path)?).map_err(|e|{io::Error::new(io::ErrorKind::InvalidData, e)})
+}
+
+/// the file's bytes to read from, for decoders that take a reader
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Cursor<Vec<u8>>>{
+    return Ok(Cursor::new(read(path)?))
+}
+
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, bytes: C) -> io::Result<()>{
+    return file_system().write(path.as_ref(), bytes.as_ref())
+}
+
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()>{
+    return file_system().create_dir_all(path.as_ref())
+}
+
+pub fn exists<P: AsRef<Path>>(path: P) -> bool{
+    return file_system().exists(path.as_ref())
+}
+
+/// the folder is recorded like a read, what's in it is an input too
+pub fn list<P: AsRef<Path>>(dir: P) -> io::Result<Vec<String>>{
+    record(dir.as_ref());
+    return file_system().list(dir.as_ref())
+}
+
+/// every file under `dir` at any depth. Only the files read are recorded
+pub fn walk<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>>{
+    return file_system().walk(dir.as_ref())
+}
+
+/// adds `bytes` to the end of an existing file
+pub fn append<P: AsRef<Path>>(path: P, bytes: &[u8]) -> io::Result<()>{
+    let f = file_system();
+    let mut old = f.read(path.as_ref())?;
+    old.extend_from_slice(bytes);
+    return f.write(path.as_ref(), &old)
+}
+
+/// copies a file without recording it as read, it's an output's input
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()>{
+    let f = file_system();
+    return f.write(to.as_ref(), &f.read(from.as_ref())?)
+}
+
+/// a file being written, it's written out on `flush` or when dropped
+pub struct FileWriter{
+    path : PathBuf,
+    bytes : Vec<u8>,
+}
+
+/// like `File::create`, through the installed file system
+pub fn create<P: AsRef<Path>>(path: P) -> io::Result<FileWriter>{
+    let out = FileWriter{path: path.as_ref().to_path_buf(), bytes: Vec::new()};
+    write(&out.path, &[])?;
+    return Ok(out)
+}
+
+impl Write for FileWriter{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>{
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()>{
+        write(&self.path, &self.bytes)
+    }
+}
+
+impl Drop for FileWriter{
+    fn drop(&mut self){
+        if let Err(e) = self.flush(){
+            if !std::thread::panicking(){
+                panic!("could not write {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
+
+/// make depfile of `target` depending on `deps`, with an empty rule for each
+/// dep so deleting one doesn't break the build
+pub fn write_depfile(out: &mut dyn Write, target: &Path, deps: &[PathBuf]) -> io::Result<()>{
+    let escape = |p: &Path|{p.to_string_lossy().replace(' ', "\\ ").replace('#', "\\#")};
+    write!(out, "{}:", escape(target))?;
+    for d in deps.iter(){
+        write!(out, " \\\n  {}", escape(d))?;
+    }
+    writeln!(out)?;
+    for d in deps.iter(){
+        writeln!(out, "\n{}:", escape(d))?;
+    }
+    return Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_lists_only_a_folders_own_files(){
+        let m = Memory::new();
+        m.insert("out/model/a.model.yaml", b"a".to_vec());
+        m.insert("out/model/sub/b.bin", b"b".to_vec());
+        m.insert("out/assets.yaml", b"c".to_vec());
+        assert_eq!(m.list(Path::new("out/model")).unwrap(), vec!["a.model.yaml".to_string()]);
+        assert!(m.exists(Path::new("out/assets.yaml")));
+        assert!(!m.exists(Path::new("out/model")));
+        assert_eq!(m.read(Path::new("out/model/sub/b.bin")).unwrap(), b"b");
+        assert!(m.read(Path::new("out/missing.bin")).is_err());
+    }
+
+    #[test]
+    fn memory_walks_into_folders(){
+        let m = Memory::new();
+        m.insert("out/model/sub/b.bin", b"b".to_vec());
+        m.insert("out/assets.yaml", b"c".to_vec());
+        m.insert("other/d.bin", b"d".to_vec());
+        assert_eq!(m.walk(Path::new("out")).unwrap(), vec![PathBuf::from("out/assets.yaml"), PathBuf::from("out/model/sub/b.bin")]);
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use super::asset::Compression;
+
+// structured errors for the parsers that check their input instead of
+// panicking on it, so malformed bytes (fuzzing, corrupted bins) can be reported
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError{
+    /// needed `need` bytes at `offset`, there are `len`
+    OutOfBounds{offset: usize, need: usize, len: usize},
+    /// a table entry's data runs backwards or past the end of the bin
+    BadEntry{uid: usize, start: usize, end: usize},
+    /// the bytes are all there but don't make sense
+    Invalid(String),
+    /// a decoder that doesn't check its input yet panicked on entry `uid`
+    Decode{uid: usize, message: String},
+}
+
+impl fmt::Display for ParseError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ParseError::OutOfBounds{offset, need, len} => write!(f, "needed 0x{:X} bytes at 0x{:X}, but there are only 0x{:X}", need, offset, len),
+            ParseError::BadEntry{uid, start, end} => write!(f, "uid 0x{:04X} runs from 0x{:X} to 0x{:X}, outside the data", uid, start, end),
+            ParseError::Invalid(msg) => write!(f, "{}", msg),
+            ParseError::Decode{uid, message} => write!(f, "uid 0x{:04X} could not be decoded: {}", uid, message),
+        }
+    }
+}
+
+impl ParseError{
+    /// the entry the error is about, if it's about one
+    pub fn uid(&self) -> Option<usize>{
+        return match self{
+            ParseError::BadEntry{uid, ..} | ParseError::Decode{uid, ..} => Some(*uid),
+            _ => None,
+        }
+    }
+
+    /// an error reading bytes that start `base` bytes into something bigger, as offsets into that
+    pub fn shifted(self, base: usize) -> ParseError{
+        return match self{
+            ParseError::OutOfBounds{offset, need, len} => ParseError::OutOfBounds{offset: base + offset, need: need, len: base + len},
+            e => e,
+        }
+    }
+}
+
+/// a compressed entry that doesn't decompress back to the bytes it was made
+/// from, a compressor bug that would otherwise only show up in game. Building a
+/// bin returns it inside an `io::Error` of kind `InvalidData`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressError{
+    pub uid : usize,
+    pub compression : Compression,
+}
+
+impl fmt::Display for CompressError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "uid 0x{:04X} doesn't decompress back to the same bytes with {:?} compression, try another --compression", self.uid, self.compression)
+    }
+}
+
+impl std::error::Error for CompressError{}
+
+/// bounds checked big endian reads
+pub struct ByteReader<'a>{
+    bytes: &'a [u8],
+    pub offset: usize,
+}
+
+impl<'a> ByteReader<'a>{
+    pub fn new(bytes: &'a [u8]) -> ByteReader<'a>{
+        return ByteReader{bytes: bytes, offset: 0}
+    }
+
+    /// reading from `offset` on
+    pub fn at(bytes: &'a [u8], offset: usize) -> ByteReader<'a>{
+        return ByteReader{bytes: bytes, offset: offset}
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], ParseError>{
+        let end = self.offset.checked_add(n).filter(|e|{*e <= self.bytes.len()})
+            .ok_or(ParseError::OutOfBounds{offset: self.offset, need: n, len: self.bytes.len()})?;
+        let out = &self.bytes[self.offset..end];
+        self.offset = end;
+        return Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ParseError>{
+        return Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, ParseError>{
+        let b = self.take(2)?;
+        return Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, ParseError>{
+        let b = self.take(4)?;
+        return Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, ParseError>{
+        return Ok(f32::from_bits(self.u32()?))
+    }
+
+    /// consumes the next byte only if it is `tag`
+    pub fn expect(&mut self, tag: u8) -> bool{
+        match self.bytes.get(self.offset){
+            Some(b) if *b == tag => {self.offset += 1; true},
+            _ => false,
+        }
+    }
+
+    /// the bytes not read yet
+    pub fn rest(&self) -> &'a [u8]{
+        return self.bytes.get(self.offset..).unwrap_or(&[])
+    }
+
+    pub fn align(&mut self, n: usize){
+        self.offset = (self.offset + n - 1) / n * n;
+    }
+}
+
+/// the panic message of a caught panic
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String{
+    return payload.downcast_ref::<&str>().map(|s|{s.to_string()})
+        .or(payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(||{"unknown panic".to_string()})
+}
@@ -0,0 +1,279 @@
+use std::convert::TryInto;
+
+use super::animation::TRANSFORM_NAMES;
+use super::asset::{AssetType, QuestionHeader};
+use super::demo::buttons_to_yaml;
+use super::model::{ModelHeader, ModelTexture, TextureListHeader};
+use super::texture::ImgFmt;
+
+// annotated hex dumps: each parser's view of an asset's bytes as named fields,
+// for `explain`. Annotation stops where a field would run past the end, and
+// bytes no field covers are dumped as they are, they're the unknown parts.
+
+/// `len` bytes at `offset` that the parser reads as `name`
+pub struct Field{
+    pub offset : usize,
+    pub len : usize,
+    pub name : String,
+}
+
+struct Annotator<'a>{
+    bytes : &'a [u8],
+    fields : Vec<Field>,
+}
+
+impl<'a> Annotator<'a>{
+    fn field(&mut self, offset: usize, len: usize, name: String) -> Option<&'a [u8]>{
+        let b = self.bytes.get(offset..offset.checked_add(len)?)?;
+        self.fields.push(Field{offset: offset, len: len, name: name});
+        return Some(b)
+    }
+
+    fn u8(&mut self, offset: usize, name: &str) -> Option<u8>{
+        let b = self.bytes.get(offset)?;
+        self.fields.push(Field{offset: offset, len: 1, name: format!("{}: {}", name, b)});
+        return Some(*b)
+    }
+
+    fn u16(&mut self, offset: usize, name: &str) -> Option<u16>{
+        let v = u16::from_be_bytes(self.bytes.get(offset..offset + 2)?.try_into().unwrap());
+        self.fields.push(Field{offset: offset, len: 2, name: format!("{}: 0x{:X}", name, v)});
+        return Some(v)
+    }
+
+    fn u32(&mut self, offset: usize, name: &str) -> Option<u32>{
+        let v = u32::from_be_bytes(self.bytes.get(offset..offset + 4)?.try_into().unwrap());
+        self.fields.push(Field{offset: offset, len: 4, name: format!("{}: 0x{:X}", name, v)});
+        return Some(v)
+    }
+}
+
+/// the fields of `bytes` decoded as `asset_type`, in offset order. Empty for
+/// types with no annotations yet
+pub fn fields(asset_type: &AssetType, bytes: &[u8]) -> Vec<Field>{
+    let mut a = Annotator{bytes: bytes, fields: Vec::new()};
+    match asset_type{
+        AssetType::Dialog => {dialog(&mut a);},
+        AssetType::QuizQuestion | AssetType::GruntyQuestion => {question(&mut a);},
+        AssetType::DemoInput => {demo(&mut a);},
+        AssetType::Animation => {animation(&mut a);},
+        AssetType::Sprite(fmt) => {sprite(&mut a, fmt);},
+        AssetType::Midi => {sequence(&mut a);},
+        AssetType::Model => {model(&mut a);},
+        AssetType::Binary | AssetType::LevelSetup => {},
+    }
+    a.fields.sort_by_key(|f|{f.offset});
+    return a.fields
+}
+
+/// cmd byte, length byte and text of `cnt` strings from `offset`
+fn strings(a: &mut Annotator, mut offset: usize, cnt: u8, name: &str) -> Option<usize>{
+    for i in 0..cnt{
+        a.u8(offset, &format!("{}[{}] cmd", name, i))?;
+        let len = a.u8(offset + 1, &format!("{}[{}] length", name, i))? as usize;
+        let text = a.bytes.get(offset + 2..offset + 2 + len)?;
+        let shown : String = text.iter().map(|b|{if (0x20..0x7F).contains(b) {(*b as char).to_string()} else {format!("\\x{:02X}", b)}}).collect();
+        a.field(offset + 2, len, format!("{}[{}] \"{}\"", name, i, shown))?;
+        offset += 2 + len;
+    }
+    return Some(offset)
+}
+
+fn dialog(a: &mut Annotator) -> Option<()>{
+    a.field(0, 3, "header".to_string())?;
+    let cnt = a.u8(3, "bottom string count")?;
+    let offset = strings(a, 4, cnt, "bottom")?;
+    let cnt = a.u8(offset, "top string count")?;
+    strings(a, offset + 1, cnt, "top")?;
+    return Some(())
+}
+
+fn question(a: &mut Annotator) -> Option<()>{
+    for i in 0..5{
+        a.u8(i, &format!("header {}", QuestionHeader::key(i)))?;
+    }
+    let cnt = a.u8(5, "string count, the last 3 are the options")?;
+    strings(a, 6, cnt, "text")?;
+    return Some(())
+}
+
+fn demo(a: &mut Annotator) -> Option<()>{
+    let len = a.u32(0, "input bytes")? as usize;
+    for i in 0..len/6{
+        let offset = 4 + 6*i;
+        let b = a.bytes.get(offset..offset + 6)?;
+        a.field(offset, 2, format!("input {} stick {}, {}", i, b[0] as i8, b[1] as i8))?;
+        a.field(offset + 2, 2, format!("input {} buttons {}", i, buttons_to_yaml(u16::from_be_bytes([b[2], b[3]]))))?;
+        a.u8(offset + 4, &format!("input {} frames", i))?;
+        a.u8(offset + 5, &format!("input {} flag", i))?;
+    }
+    return Some(())
+}
+
+fn animation(a: &mut Annotator) -> Option<()>{
+    a.u16(0, "start frame")?;
+    a.u16(2, "end frame")?;
+    let cnt = a.u16(4, "element count")?;
+    a.field(6, 2, "padding".to_string())?;
+    let mut offset = 8;
+    for i in 0..cnt{
+        let id = u16::from_be_bytes(a.bytes.get(offset..offset + 2)?.try_into().unwrap());
+        let transform = TRANSFORM_NAMES.get((id & 0xF) as usize).copied().unwrap_or("unknown transform");
+        a.field(offset, 2, format!("element {}: bone {} {}", i, id >> 4, transform))?;
+        let key_cnt = a.u16(offset + 2, &format!("element {} key count", i))?;
+        offset += 4;
+        for k in 0..key_cnt{
+            let packed = u16::from_be_bytes(a.bytes.get(offset..offset + 2)?.try_into().unwrap());
+            a.field(offset, 2, format!("key {}: frame {}, interpolation {}", k, packed & 0x3FFF, packed >> 14))?;
+            let value = a.bytes.get(offset + 2..offset + 4)?;
+            a.field(offset + 2, 2, format!("key {} value {}", k, i16::from_be_bytes([value[0], value[1]])))?;
+            offset += 4;
+        }
+    }
+    return Some(())
+}
+
+/// chunk header and texels, the texels aligned to 8
+fn sprite_chunk(a: &mut Annotator, offset: usize, format: &ImgFmt, name: &str) -> Option<usize>{
+    let b = a.bytes.get(offset..offset + 8)?;
+    let (w, h) = (u16::from_be_bytes([b[4], b[5]]) as usize, u16::from_be_bytes([b[6], b[7]]) as usize);
+    a.field(offset, 8, format!("{} at {}, {}, {}x{}", name, i16::from_be_bytes([b[0], b[1]]), i16::from_be_bytes([b[2], b[3]]), w, h))?;
+    let start = (offset + 8 + 7) & !7;
+    let len = w*h*format.bits_per_texel()/8;
+    a.field(start, len, format!("{} {:?} texels", name, format))?;
+    return Some(start + len)
+}
+
+fn sprite(a: &mut Annotator, format: &ImgFmt) -> Option<()>{
+    let cnt = a.u16(0, "frame count")? as usize;
+    a.u16(2, "format")?;
+    if let ImgFmt::Unknown(_) = format{
+        return None
+    }
+    if cnt > 0x100{
+        a.field(4, 4, "raw texture header".to_string())?;
+        sprite_chunk(a, 8, &ImgFmt::RGBA16, "texture")?;
+        return Some(())
+    }
+    a.field(4, 0xC, "header".to_string())?;
+    let data_start = 0x10 + 4*cnt;
+    for i in 0..cnt{
+        let offset = data_start + a.u32(0x10 + 4*i, &format!("frame {} offset", i))? as usize;
+        let b = a.bytes.get(offset..offset + 0x14)?;
+        a.field(offset, 4, format!("frame {} anchor {}, {}", i, i16::from_be_bytes([b[0], b[1]]), i16::from_be_bytes([b[2], b[3]])))?;
+        a.field(offset + 4, 4, format!("frame {} size {}x{}", i, u16::from_be_bytes([b[4], b[5]]), u16::from_be_bytes([b[6], b[7]])))?;
+        let chunk_cnt = a.u16(offset + 8, &format!("frame {} chunk count", i))?;
+        a.field(offset + 0xA, 0xA, format!("frame {} header", i))?;
+        let mut pos = offset + 0x14;
+        let palette_len = match format{
+            ImgFmt::CI4 => 0x20,
+            ImgFmt::CI8 => 0x200,
+            _ => 0,
+        };
+        if palette_len != 0{
+            pos = (pos + 7) & !7;
+            a.field(pos, palette_len, format!("frame {} palette", i))?;
+            pos += palette_len;
+        }
+        for c in 0..chunk_cnt{
+            pos = sprite_chunk(a, pos, format, &format!("frame {} chunk {}", i, c))?;
+        }
+    }
+    return Some(())
+}
+
+fn sequence(a: &mut Annotator) -> Option<()>{
+    let mut offsets : Vec<(usize, usize)> = Vec::new(); //offset, track
+    for i in 0..16{
+        let offset = a.u32(4*i, &format!("track {} offset", i))? as usize;
+        if offset != 0{
+            offsets.push((offset, i));
+        }
+    }
+    a.u32(0x40, "division")?;
+    offsets.sort();
+    for (j, (offset, track)) in offsets.iter().enumerate(){
+        let end = offsets.get(j + 1).map_or(a.bytes.len(), |(o, _)|{*o});
+        a.field(*offset, end.saturating_sub(*offset), format!("track {} events", track))?;
+    }
+    return Some(())
+}
+
+fn model(a: &mut Annotator) -> Option<()>{
+    let header = ModelHeader::from_bytes(a.bytes)?;
+    let names : [(usize, usize, &str); 16] = [
+        (0x00, 4, "magic"), (0x04, 4, "geo layout offset"), (0x08, 2, "texture list offset"), (0x0A, 2, "geo type"),
+        (0x0C, 4, "display list offset"), (0x10, 4, "vertex store offset"), (0x14, 4, "unk14"), (0x18, 4, "bone list offset"),
+        (0x1C, 4, "collision offset"), (0x20, 4, "unk20 offset"), (0x24, 4, "effects offset"), (0x28, 4, "unk28"),
+        (0x2C, 4, "animated textures offset"), (0x30, 2, "tri count"), (0x32, 2, "vertex count"), (0x34, 4, "unk34"),
+    ];
+    for (offset, len, name) in names.iter(){
+        if *len == 2 {a.u16(*offset, name)?;} else {a.u32(*offset, name)?;}
+    }
+
+    //sections run up to the next one
+    let mut sections : Vec<(usize, &str)> = [
+        (header.geo_layout, "geo layout"), (header.texture_list, "texture list"), (header.display_list, "display list"),
+        (header.vertex_store, "vertex store"), (header.bone_list, "bone list"), (header.collision, "collision"),
+        (header.unk20, "unk20"), (header.effects, "effects"), (header.animated_textures, "animated textures"),
+    ].iter().filter_map(|(o, n)|{o.map(|o|{(o, *n)})}).collect();
+    sections.sort();
+    for (j, (offset, name)) in sections.iter().enumerate(){
+        let end = sections.get(j + 1).map_or(a.bytes.len(), |(o, _)|{*o});
+        if *name != "texture list"{
+            a.field(*offset, end.saturating_sub(*offset), format!("{} section", name))?;
+            continue;
+        }
+        a.u32(*offset, "texture data size")?;
+        let cnt = a.u16(*offset + 4, "texture count")? as usize;
+        a.field(*offset + 6, 2, "padding".to_string())?;
+        let entries = *offset + TextureListHeader::SIZE;
+        for i in 0..cnt{
+            let e = entries + i*ModelTexture::SIZE;
+            a.u32(e, &format!("texture {} data offset", i))?;
+            let code = u16::from_be_bytes(a.bytes.get(e + 4..e + 6)?.try_into().unwrap());
+            a.field(e + 4, 2, format!("texture {} format {:?}", i, ImgFmt::from_model_code(code)))?;
+            a.field(e + 6, 2, format!("texture {} unknown", i))?;
+            a.u8(e + 8, &format!("texture {} width", i))?;
+            a.u8(e + 9, &format!("texture {} height", i))?;
+            a.field(e + 0xA, 6, format!("texture {} unknown", i))?;
+        }
+        let data = entries + cnt*ModelTexture::SIZE;
+        a.field(data, end.saturating_sub(data), "texture data".to_string())?;
+    }
+    return Some(())
+}
+
+/// hex dump with each field's name after its bytes. Fields longer than `max_len`
+/// show their first row only, bytes no field covers are always shown
+pub fn dump(bytes: &[u8], fields: &[Field], max_len: usize) -> String{
+    let mut out = String::new();
+    let row = |out: &mut String, offset: usize, b: &[u8], name: &str|{
+        let hex : Vec<String> = b.iter().map(|x|{format!("{:02X}", x)}).collect();
+        out.push_str(&format!("{:06X}  {:<47}  {}\n", offset, hex.join(" "), name).trim_end());
+        out.push('\n');
+    };
+    let mut pos = 0;
+    for f in fields.iter(){
+        if f.offset < pos{
+            continue;
+        }
+        //unannotated bytes before the field
+        for start in (pos..f.offset).step_by(0x10){
+            row(&mut out, start, &bytes[start..f.offset.min(start + 0x10)], "");
+        }
+        let end = f.offset + f.len;
+        for (i, start) in (f.offset..end).step_by(0x10).enumerate(){
+            if i == 1 && f.len > max_len{
+                out.push_str(&format!("{:06X}  ... 0x{:X} bytes\n", start, f.len));
+                break;
+            }
+            row(&mut out, start, &bytes[start..end.min(start + 0x10)], if i == 0 {&f.name} else {""});
+        }
+        pos = end;
+    }
+    for start in (pos..bytes.len()).step_by(0x10){
+        row(&mut out, start, &bytes[start..bytes.len().min(start + 0x10)], "");
+    }
+    return out
+}
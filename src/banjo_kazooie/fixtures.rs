@@ -0,0 +1,78 @@
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::YamlLoader;
+
+use super::deps;
+use super::manifest::sha1;
+use super::{asset, type_name, AssetTable};
+
+// golden fixtures: a few decompressed entries of each type from a vanilla bin,
+// with the sha1 of their bytes and of what they encode back to, so the parsers
+// can be regression checked without a ROM. `fixtures.yaml` lists them.
+
+/// writes `per_type` entries of each type into `out_dir`, spread from the
+/// smallest to the largest. Returns how many were written
+pub fn generate(in_bytes: &[u8], out_dir: &Path, per_type: usize) -> usize{
+    let table = AssetTable::new(in_bytes);
+    let mut by_type : Vec<(String, Vec<(usize, usize)>)> = Vec::new(); //type, (size, uid)
+    for (uid, a) in table.assets(){
+        let name = type_name(&a.get_type());
+        let size = a.known_len().unwrap_or_else(||{a.to_bytes().len()});
+        match by_type.iter_mut().find(|(n, _)|{*n == name}){
+            Some((_, uids)) => uids.push((size, uid)),
+            None => by_type.push((name, vec![(size, uid)])),
+        }
+    }
+    by_type.sort_by(|a, b|{a.0.cmp(&b.0)});
+
+    deps::create_dir_all(out_dir).unwrap();
+    let mut yaml = deps::create(out_dir.join("fixtures.yaml")).expect("could not write fixtures.yaml");
+    writeln!(yaml, "tool: bk_asset_tool {}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(yaml, "fixtures:").unwrap();
+    let mut written = 0;
+    for (name, mut uids) in by_type{
+        uids.sort();
+        let n = per_type.min(uids.len());
+        let mut picks : Vec<usize> = (0..n).map(|i|{if n == 1 {0} else {i*(uids.len() - 1)/(n - 1)}}).collect();
+        picks.dedup();
+        for i in picks{
+            let uid = uids[i].1;
+            let bytes = table.bytes(uid).unwrap_or_else(|e|{panic!("{}", e)}).unwrap();
+            let segment = table.entry(uid).unwrap_or_else(|e|{panic!("{}", e)}).segment;
+            let rebuilt = asset::from_seg_indx_and_bytes(segment, uid, &bytes).to_bytes();
+            let file_name = format!("{}_{:04X}.bin", name, uid);
+            deps::write(out_dir.join(&file_name), &bytes).expect("could not write fixture");
+            writeln!(yaml, "  - {{uid: 0x{:04X}, segment: {}, type: {}, file: {}, sha1: {}, rebuilt_sha1: {}}}", uid, segment, name, file_name, sha1(&bytes), sha1(&rebuilt)).unwrap();
+            written += 1;
+        }
+    }
+    return written
+}
+
+/// decodes and re-encodes every fixture in `dir`. One line per problem, empty
+/// if they all match
+pub fn check(dir: &Path) -> Vec<String>{
+    let yaml_path = dir.join("fixtures.yaml");
+    let doc = &YamlLoader::load_from_str(&deps::read_to_string(&yaml_path).expect("could not open fixtures.yaml")).unwrap()[0];
+    let mut errors : Vec<String> = Vec::new();
+    for y in doc["fixtures"].as_vec().unwrap_or_else(||{panic!("{:?} has no fixtures", yaml_path)}){
+        let file_name = y["file"].as_str().unwrap();
+        let bytes = deps::read(dir.join(file_name)).unwrap_or_else(|_|{panic!("could not read fixture {}", file_name)});
+        if sha1(&bytes) != y["sha1"].as_str().unwrap(){
+            errors.push(format!("{}: sha1 is {}, the fixture file has changed", file_name, sha1(&bytes)));
+            continue;
+        }
+        let uid = y["uid"].as_i64().unwrap() as usize;
+        let segment = y["segment"].as_i64().unwrap() as usize;
+        let a = asset::from_seg_indx_and_bytes(segment, uid, &bytes);
+        let name = type_name(&a.get_type());
+        if name != y["type"].as_str().unwrap(){
+            errors.push(format!("{}: decodes as {}, expected {}", file_name, name, y["type"].as_str().unwrap()));
+        }
+        let rebuilt = sha1(&a.to_bytes());
+        if rebuilt != y["rebuilt_sha1"].as_str().unwrap(){
+            errors.push(format!("{}: rebuilds to sha1 {}, expected {}", file_name, rebuilt, y["rebuilt_sha1"].as_str().unwrap()));
+        }
+    }
+    return errors
+}
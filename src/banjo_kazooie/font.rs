@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use yaml_rust::YamlLoader;
+
+use super::asset::{Asset, AssetType, ExtractOptions, ImportOptions, Sprite};
+use super::AssetTable;
+use super::deps;
+
+// the game's fonts are ordinary sprites with one frame per glyph, and the text
+// code advances by each glyph's width from a table of its own, outside the
+// asset bin. A font is exported as a sprite descriptor whose frames are the
+// glyph pngs, so it rebuilds like any other sprite, plus a sheet of every
+// glyph and a widths.yaml. The advances in it come from that table when it's
+// given, one byte per glyph, and `build` turns the edited widths.yaml back into
+// one. Glyphs can be redrawn or appended for bigger charsets.
+
+/// what `candidates` takes as a font, it's only a heuristic: the bin doesn't
+/// mark which sprites are fonts
+#[derive(Clone, Copy)]
+pub struct FontShape{
+    /// at least this many glyphs
+    pub min_glyphs : usize,
+    /// and no glyph bigger than this on either side
+    pub max_glyph_size : usize,
+}
+
+impl FontShape{
+    pub fn new() -> FontShape{
+        FontShape{min_glyphs: 0x20, max_glyph_size: 0x20}
+    }
+}
+
+/// the height most glyphs share
+fn line_height(sprite: &Sprite) -> usize{
+    let mut counts : HashMap<usize, usize> = HashMap::new();
+    for f in sprite.frame.iter(){
+        *counts.entry(f.rgba().1).or_insert(0) += 1;
+    }
+    return counts.into_iter().max_by_key(|(h, n)|{(*n, *h)}).map_or(0, |(h, _)|{h})
+}
+
+/// many small frames, three quarters of them the same height
+pub fn is_font(sprite: &Sprite, shape: FontShape) -> bool{
+    let frames = &sprite.frame;
+    if frames.len() < shape.min_glyphs || frames.iter().map(|f|{f.rgba()}).any(|(w, h, _)|{w > shape.max_glyph_size || h > shape.max_glyph_size || w == 0}){
+        return false
+    }
+    let h = line_height(sprite);
+    return 4*frames.iter().filter(|f|{f.rgba().1 == h}).count() >= 3*frames.len()
+}
+
+/// uids of every sprite in the bin shaped like a font. Check them in their
+/// atlas, any sprite of small, even frames passes
+pub fn candidates(in_bytes: &[u8], shape: FontShape) -> Vec<usize>{
+    let table = AssetTable::new(in_bytes);
+    return (0..table.len())
+        .filter(|uid|{
+            match table.try_asset(*uid).ok().flatten().map(|a|{a.get_type()}){
+                Some(AssetType::Sprite(_)) => table.bytes(*uid).ok().flatten().map_or(false, |b|{is_font(&Sprite::from_bytes(&b), shape)}),
+                _ => false,
+            }
+        })
+        .collect()
+}
+
+/// each glyph's size and anchor, and its advance from the game's width table
+/// when there is one. Without it the advances are left for the user to fill in
+pub fn write_widths(out: &mut dyn Write, sprite: &Sprite, widths: Option<&[u8]>) -> io::Result<()>{
+    writeln!(out, "line_height: {}", line_height(sprite))?;
+    match widths{
+        Some(_) => writeln!(out, "glyphs: # advance is from the width table, w/h/x/y are the glyph's frame")?,
+        None => writeln!(out, "glyphs: # no width table was given, fill in each advance before font-build")?,
+    }
+    for (i, f) in sprite.frame.iter().enumerate(){
+        let ((w, h, _), (x, y)) = (f.rgba(), f.anchor());
+        let advance = widths.and_then(|t|{t.get(i)}).map_or(String::from("~"), |a|{a.to_string()});
+        writeln!(out, "  - {{index: 0x{:02X}, advance: {}, w: {}, h: {}, x: {}, y: {}}}", i, advance, w, h, x, y)?;
+    }
+    return Ok(())
+}
+
+/// the advances of a widths.yaml, one byte per glyph in index order
+pub fn read_widths(path: &Path, glyph_count: usize) -> Vec<u8>{
+    let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open width table")).unwrap()[0];
+    let glyphs = doc["glyphs"].as_vec().unwrap_or_else(||{panic!("{:?}: no glyphs list", path)});
+    assert_eq!(glyphs.len(), glyph_count, "{:?}: has {} glyphs, the font has {}", path, glyphs.len(), glyph_count);
+    return glyphs.iter().enumerate().map(|(i, g)|{
+        assert!(g["index"].is_badvalue() || g["index"].as_i64() == Some(i as i64), "{:?}: glyph {} is listed as index {:?}, keep them in frame order", path, i, g["index"].as_i64());
+        let advance = g["advance"].as_i64().unwrap_or_else(||{panic!("{:?}: glyph 0x{:02X} has no advance", path, i)});
+        assert!((0..=0xFF).contains(&advance), "{:?}: glyph 0x{:02X} advance {} doesn't fit in a byte", path, i, advance);
+        advance as u8
+    }).collect()
+}
+
+/// `<name>.sprite.yaml` with its glyph pngs, `<name>.atlas.png` and `.json`,
+/// and `<name>.widths.yaml` in `out_dir`. `widths` is the game's width table
+pub fn export(sprite: &Sprite, widths: Option<&[u8]>, out_dir: &Path, name: &str){
+    assert!(!sprite.frame.is_empty(), "font has no decodable glyphs");
+    if let Some(t) = widths{
+        assert!(t.len() >= sprite.frame.len(), "the width table has {} entries, the font has {} glyphs", t.len(), sprite.frame.len());
+    }
+    deps::create_dir_all(out_dir).expect("could not create font folder");
+    sprite.write(&out_dir.join(format!("{}.sprite.yaml", name)), &ExtractOptions::new());
+    sprite.write_sheet(&out_dir.join(format!("{}.atlas.png", name)), &out_dir.join(format!("{}.atlas.json", name)));
+    let mut widths_f = deps::create(out_dir.join(format!("{}.widths.yaml", name))).expect("could not create width table");
+    write_widths(&mut widths_f, sprite, widths).unwrap();
+}
+
+/// the font's sprite bytes from an exported descriptor to `out_path`, and the
+/// width table from its widths.yaml to `out_path` with a `.widths.bin`
+/// extension. Glyphs off the line height and advances narrower than their
+/// glyph are warnings
+pub fn build(yaml_path: &Path, widths_path: &Path, out_path: &Path) -> Vec<String>{
+    let sprite = Sprite::read(yaml_path, &ImportOptions::new());
+    let widths = read_widths(widths_path, sprite.frame.len());
+    let h = line_height(&sprite);
+    let mut warnings : Vec<String> = sprite.frame.iter().map(|f|{f.rgba().1}).enumerate()
+        .filter(|(_, glyph_h)|{*glyph_h != h})
+        .map(|(i, glyph_h)|{format!("glyph 0x{:02X} is {} pixels tall, the rest of the font is {}", i, glyph_h, h)})
+        .collect();
+    warnings.extend(sprite.frame.iter().map(|f|{f.rgba().0}).zip(widths.iter()).enumerate()
+        .filter(|(_, (w, a))|{(**a as usize) < *w})
+        .map(|(i, (w, a))|{format!("glyph 0x{:02X} advances {} but is {} pixels wide, it overlaps the next one", i, a, w)}));
+    deps::write(out_path, sprite.to_bytes()).expect("could not write font");
+    deps::write(out_path.with_extension("widths.bin"), &widths).expect("could not write width table");
+    return warnings
+}
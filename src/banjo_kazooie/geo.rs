@@ -0,0 +1,417 @@
+use std::convert::TryInto;
+use std::io::Write;
+use yaml_rust::Yaml;
+
+use super::error::{ByteReader, ParseError};
+
+// model geo layout: a tree of commands, each starting with a u32 command id and
+// a u32 offset to its next sibling (0 for the last one). Children are reached
+// through offsets stored in the parent command, relative to the parent.
+
+pub const GEO_SORT : u32 = 0x01;
+pub const GEO_BONE : u32 = 0x02;
+pub const GEO_LOAD_DL : u32 = 0x03;
+pub const GEO_SKINNING : u32 = 0x05;
+pub const GEO_BRANCH : u32 = 0x06;
+pub const GEO_LOD : u32 = 0x08;
+pub const GEO_SELECTOR : u32 = 0x0C;
+pub const GEO_DRAW_DISTANCE : u32 = 0x0D;
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16{
+    u16::from_be_bytes(bytes[offset..offset+2].try_into().unwrap())
+}
+
+fn checked_u16(bytes: &[u8], offset: usize) -> Result<u16, ParseError>{
+    return ByteReader::at(bytes, offset).u16()
+}
+
+fn checked_u32(bytes: &[u8], offset: usize) -> Result<u32, ParseError>{
+    return ByteReader::at(bytes, offset).u32()
+}
+
+/// (position, byte width) of each child offset field of a command, relative to the command
+fn child_fields(cmd: u32, count: usize) -> Vec<(usize, usize)>{
+    match cmd{
+        GEO_SORT => vec![(0x20, 2), (0x22, 2)],
+        GEO_BONE => vec![(0x0A, 2)],
+        GEO_BRANCH => vec![(0x08, 4)],
+        GEO_LOD => vec![(0x1C, 4)],
+        GEO_SELECTOR => (0..count).map(|i|{(0x0C + 4*i, 4)}).collect(),
+        GEO_DRAW_DISTANCE => vec![(0x16, 2)],
+        _ => Vec::new(),
+    }
+}
+
+/// every child offset slot of a command, 0 for empty slots
+fn child_slots(bytes: &[u8], cmd_offset: usize) -> Result<Vec<usize>, ParseError>{
+    let cmd = checked_u32(bytes, cmd_offset)?;
+    let count = match cmd{
+        GEO_SELECTOR => checked_u16(bytes, cmd_offset + 0x08)? as usize,
+        _ => 0,
+    };
+    return child_fields(cmd, count).into_iter()
+        .map(|(pos, width)|{
+            match width{
+                2 => checked_u16(bytes, cmd_offset + pos).map(|o|{o as usize}),
+                _ => checked_u32(bytes, cmd_offset + pos).map(|o|{o as usize}),
+            }
+        })
+        .collect()
+}
+
+/// offsets of a command's children, relative to the command
+pub fn child_offsets(bytes: &[u8], cmd_offset: usize) -> Result<Vec<usize>, ParseError>{
+    return Ok(child_slots(bytes, cmd_offset)?.into_iter().filter(|o|{*o != 0}).collect())
+}
+
+/// offset of every command reachable from `start`, parents before their children
+pub fn command_offsets(bytes: &[u8], start: usize) -> Result<Vec<usize>, ParseError>{
+    let mut out : Vec<usize> = Vec::new();
+    let mut offset = start;
+    loop{
+        out.push(offset);
+        for child in child_offsets(bytes, offset)?{
+            out.append(&mut command_offsets(bytes, offset + child)?);
+        }
+        match checked_u32(bytes, offset + 4)?{
+            0 => break,
+            next => offset += next as usize,
+        }
+    }
+    return Ok(out)
+}
+
+/// display list indices drawn by a command, as (byte offset, index) pairs
+pub fn dl_indices(bytes: &[u8], cmd_offset: usize) -> Result<Vec<(usize, usize)>, ParseError>{
+    return Ok(match checked_u32(bytes, cmd_offset)?{
+        GEO_LOAD_DL => vec![(cmd_offset + 8, checked_u16(bytes, cmd_offset + 8)? as usize)],
+        GEO_SKINNING => {
+            //zero terminated list, the first entry is always drawn
+            let mut out = vec![(cmd_offset + 8, checked_u16(bytes, cmd_offset + 8)? as usize)];
+            let mut pos = cmd_offset + 10;
+            loop{
+                match checked_u16(bytes, pos)?{
+                    0 => break,
+                    i => out.push((pos, i as usize)),
+                }
+                pos += 2;
+            }
+            out
+        },
+        _ => Vec::new(),
+    })
+}
+
+/// rewrite the display list index of every command in the tree at `start`
+pub fn remap_dl_indices(bytes: &mut [u8], start: usize, map: &dyn Fn(usize)->usize) -> Result<(), ParseError>{
+    for cmd_offset in command_offsets(bytes, start)?{
+        for (pos, index) in dl_indices(bytes, cmd_offset)?{
+            let new_index = map(index) as u16;
+            bytes[pos..pos + 2].copy_from_slice(&new_index.to_be_bytes());
+        }
+    }
+    return Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FieldKind{
+    U8,
+    U16,
+    I16x3,
+    F32,
+    F32x3,
+}
+
+/// named fields of each command, (name, position relative to the command, kind)
+fn named_fields(cmd: u32) -> Vec<(&'static str, usize, FieldKind)>{
+    match cmd{
+        GEO_SORT => vec![("point", 0x08, FieldKind::F32x3), ("direction", 0x14, FieldKind::F32x3)],
+        GEO_BONE => vec![("bone", 0x09, FieldKind::U8)],
+        GEO_LOAD_DL => vec![("dl", 0x08, FieldKind::U16)],
+        GEO_LOD => vec![("max_distance", 0x08, FieldKind::F32), ("min_distance", 0x0C, FieldKind::F32), ("point", 0x10, FieldKind::F32x3)],
+        GEO_SELECTOR => vec![("selector", 0x0A, FieldKind::U16)],
+        GEO_DRAW_DISTANCE => vec![("min", 0x08, FieldKind::I16x3), ("max", 0x0E, FieldKind::I16x3)],
+        _ => Vec::new(),
+    }
+}
+
+fn cmd_name(cmd: u32) -> Option<&'static str>{
+    match cmd{
+        GEO_SORT => Some("Sort"),
+        GEO_BONE => Some("Bone"),
+        GEO_LOAD_DL => Some("LoadDl"),
+        GEO_SKINNING => Some("Skinning"),
+        GEO_BRANCH => Some("Branch"),
+        GEO_LOD => Some("Lod"),
+        GEO_SELECTOR => Some("Selector"),
+        GEO_DRAW_DISTANCE => Some("DrawDistance"),
+        _ => None,
+    }
+}
+
+/// one geo layout command with its children. `body` holds the command's bytes
+/// after the 8 byte header up to its first child, so unknown fields and padding
+/// survive a rebuild.
+#[derive(Debug, Clone)]
+pub struct GeoNode{
+    pub cmd : u32,
+    pub body : Vec<u8>,
+    pub children : Vec<Vec<GeoNode>>, //one chain per child slot, empty for unused slots
+}
+
+impl GeoNode{
+    /// the chain of siblings at `start`. The last sibling runs to `end`.
+    pub fn read_chain(bytes: &[u8], start: usize, end: usize) -> Result<Vec<GeoNode>, ParseError>{
+        let bytes = bytes.get(..end).ok_or(ParseError::OutOfBounds{offset: start, need: end.saturating_sub(start), len: bytes.len()})?;
+        let mut out : Vec<GeoNode> = Vec::new();
+        let mut offset = start;
+        loop{
+            let next = checked_u32(bytes, offset + 4)? as usize;
+            let node_end = match next{
+                0 => end,
+                n => offset + n,
+            };
+            if node_end > end || node_end < offset + 8{
+                return Err(ParseError::Invalid(format!("geo command at 0x{:X} runs past its parent", offset)));
+            }
+            out.push(GeoNode::read(bytes, offset, node_end)?);
+            match next{
+                0 => break,
+                _ => offset = node_end,
+            }
+        }
+        return Ok(out)
+    }
+
+    fn read(bytes: &[u8], offset: usize, end: usize) -> Result<GeoNode, ParseError>{
+        let cmd = checked_u32(bytes, offset)?;
+        let slots = child_slots(bytes, offset)?;
+        let fields_end = child_fields(cmd, slots.len()).iter().map(|(pos, width)|{pos + width}).max().unwrap_or(8);
+        let mut starts : Vec<usize> = slots.iter().filter(|o|{**o != 0}).cloned().collect();
+        starts.sort();
+        if let Some(o) = starts.iter().find(|o|{**o < fields_end || offset + **o >= end}){
+            return Err(ParseError::Invalid(format!("geo command at 0x{:X} has a bad child offset 0x{:X}", offset, o)));
+        }
+        let body_end = starts.first().map(|o|{offset + o}).unwrap_or(end);
+        let children = slots.iter()
+            .map(|o|{
+                match o{
+                    0 => Ok(Vec::new()),
+                    o => {
+                        //a child chain runs until the next child, or the end of the command
+                        let chain_end = starts.iter().find(|s|{*s > o}).map(|s|{offset + s}).unwrap_or(end);
+                        GeoNode::read_chain(bytes, offset + o, chain_end)
+                    },
+                }
+            })
+            .collect::<Result<Vec<Vec<GeoNode>>, ParseError>>()?;
+        return Ok(GeoNode{cmd: cmd, body: bytes[offset + 8..body_end].to_vec(), children: children})
+    }
+
+    pub fn chain_to_bytes(chain: &[GeoNode]) -> Vec<u8>{
+        let mut out : Vec<u8> = Vec::new();
+        for (i, node) in chain.iter().enumerate(){
+            let mut node_bytes = node.to_bytes();
+            if i + 1 < chain.len(){
+                let next = node_bytes.len() as u32;
+                node_bytes[4..8].copy_from_slice(&next.to_be_bytes());
+            }
+            out.append(&mut node_bytes);
+        }
+        return out
+    }
+
+    /// the command with its children, the next sibling offset left at 0
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = self.cmd.to_be_bytes().to_vec();
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&self.body);
+        let fields = child_fields(self.cmd, self.children.len());
+        let fields_end = fields.iter().map(|(pos, width)|{pos + width}).max().unwrap_or(8);
+        assert!(out.len() >= fields_end, "geo command 0x{:X} is too short for its {} children", self.cmd, self.children.len());
+        if self.cmd == GEO_SELECTOR{
+            let count = self.children.len() as u16;
+            out[0x08..0x0A].copy_from_slice(&count.to_be_bytes());
+        }
+        for (chain, (pos, width)) in self.children.iter().zip(fields.iter()){
+            let child_offset = match chain.is_empty(){
+                true => 0,
+                false => out.len(),
+            };
+            match width{
+                2 => {
+                    assert!(child_offset <= 0xFFFF, "geo command 0x{:X} child is too far away for a 16 bit offset", self.cmd);
+                    out[*pos..pos + 2].copy_from_slice(&(child_offset as u16).to_be_bytes());
+                },
+                _ => out[*pos..pos + 4].copy_from_slice(&(child_offset as u32).to_be_bytes()),
+            }
+            out.append(&mut GeoNode::chain_to_bytes(chain));
+        }
+        return out
+    }
+
+    /// display list indices this command draws itself
+    pub fn dls(&self) -> Vec<usize>{
+        match self.cmd{
+            GEO_LOAD_DL => vec![u16_at(&self.body, 0) as usize],
+            GEO_SKINNING => self.skinning_dls().into_iter().map(|i|{i as usize}).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// bone a `Bone` command binds its children to
+    pub fn bone(&self) -> Option<usize>{
+        match self.cmd{
+            GEO_BONE => Some(self.body[1] as usize),
+            _ => None,
+        }
+    }
+
+    /// display lists a skinning command draws, the first one always and the rest up to a 0
+    fn skinning_dls(&self) -> Vec<u16>{
+        let mut out : Vec<u16> = vec![u16_at(&self.body, 0)];
+        for pair in self.body[2..].chunks_exact(2){
+            match u16::from_be_bytes([pair[0], pair[1]]){
+                0 => break,
+                i => out.push(i),
+            }
+        }
+        return out
+    }
+
+    /// writes `chain` as a yaml sequence with its items at `indent` spaces
+    pub fn write_yaml_chain(out: &mut dyn Write, chain: &[GeoNode], indent: usize){
+        let pad = " ".repeat(indent);
+        for node in chain.iter(){
+            match cmd_name(node.cmd){
+                Some(name) => writeln!(out, "{}- cmd: {}", pad, name).unwrap(),
+                None => writeln!(out, "{}- cmd: 0x{:X}", pad, node.cmd).unwrap(),
+            }
+            let cmd_bytes = node.header_and_body();
+            for (name, pos, kind) in named_fields(node.cmd){
+                let f32_at = |o : usize|{f32::from_be_bytes(cmd_bytes[o..o + 4].try_into().unwrap())};
+                let value = match kind{
+                    FieldKind::U8 => format!("{}", cmd_bytes[pos]),
+                    FieldKind::U16 => format!("{}", u16_at(&cmd_bytes, pos)),
+                    FieldKind::I16x3 => format!("[{}, {}, {}]", u16_at(&cmd_bytes, pos) as i16, u16_at(&cmd_bytes, pos + 2) as i16, u16_at(&cmd_bytes, pos + 4) as i16),
+                    FieldKind::F32 => format!("{:?}", f32_at(pos)),
+                    FieldKind::F32x3 => format!("[{:?}, {:?}, {:?}]", f32_at(pos), f32_at(pos + 4), f32_at(pos + 8)),
+                };
+                writeln!(out, "{}  {}: {}", pad, name, value).unwrap();
+            }
+            if node.cmd == GEO_SKINNING{
+                writeln!(out, "{}  dls: {:?}", pad, node.skinning_dls()).unwrap();
+            }
+            let hex : String = node.body.iter().map(|b|{format!("{:02X}", b)}).collect();
+            writeln!(out, "{}  raw: \"{}\"", pad, hex).unwrap();
+            if !node.children.is_empty(){
+                writeln!(out, "{}  children:", pad).unwrap();
+                for chain in node.children.iter(){
+                    match chain.is_empty(){
+                        true => writeln!(out, "{}    - []", pad).unwrap(),
+                        false => {
+                            writeln!(out, "{}    -", pad).unwrap();
+                            GeoNode::write_yaml_chain(out, chain, indent + 6);
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    /// the command's bytes up to its first child, so field positions match the format
+    fn header_and_body(&self) -> Vec<u8>{
+        let mut out = self.cmd.to_be_bytes().to_vec();
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&self.body);
+        return out
+    }
+
+    /// a chain written by `write_yaml_chain`. Named fields win over the bytes in `raw`.
+    pub fn from_yaml_chain(yaml: &Yaml) -> Vec<GeoNode>{
+        yaml.as_vec().expect("geo layout chains are yaml sequences").iter()
+            .map(|y|{GeoNode::from_yaml(y)})
+            .collect()
+    }
+
+    fn from_yaml(yaml: &Yaml) -> GeoNode{
+        let cmd = match &yaml["cmd"]{
+            Yaml::String(name) => [GEO_SORT, GEO_BONE, GEO_LOAD_DL, GEO_SKINNING, GEO_BRANCH, GEO_LOD, GEO_SELECTOR, GEO_DRAW_DISTANCE].iter()
+                .find(|c|{cmd_name(**c) == Some(name.as_str())})
+                .cloned()
+                .unwrap_or_else(||{panic!("unknown geo command \"{}\"", name)}),
+            Yaml::Integer(i) => *i as u32,
+            y => panic!("bad geo command {:?}", y),
+        };
+        let hex = yaml["raw"].as_str().unwrap_or_else(||{panic!("geo command {:?} has no raw bytes", cmd_name(cmd))});
+        assert!(hex.len() % 2 == 0, "geo raw bytes \"{}\" are not whole bytes", hex);
+        let body : Vec<u8> = (0..hex.len()).step_by(2)
+            .map(|i|{u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_else(|_|{panic!("bad hex \"{}\" in geo raw bytes", &hex[i..i + 2])})})
+            .collect();
+        let children : Vec<Vec<GeoNode>> = yaml["children"].as_vec()
+            .map(|v|{v.iter().map(|c|{GeoNode::from_yaml_chain(c)}).collect()})
+            .unwrap_or_default();
+        let mut node = GeoNode{cmd: cmd, body: body, children: children};
+
+        //selectors can gain or lose choices, moving the bytes after their offset table
+        if cmd == GEO_SELECTOR && node.body.len() >= 4{
+            let old_cnt = u16_at(&node.body, 0) as usize;
+            let table_end = std::cmp::min(4 + 4*old_cnt, node.body.len());
+            let tail = node.body.split_off(table_end);
+            node.body.resize(4 + 4*node.children.len(), 0);
+            node.body.extend_from_slice(&tail);
+        }
+
+        let mut cmd_bytes = node.header_and_body();
+        for (name, pos, kind) in named_fields(cmd){
+            let y = &yaml[name];
+            if y.is_badvalue(){
+                continue;
+            }
+            let size = match kind{
+                FieldKind::U8 => 1,
+                FieldKind::U16 => 2,
+                FieldKind::F32 => 4,
+                FieldKind::I16x3 => 6,
+                FieldKind::F32x3 => 12,
+            };
+            assert!(pos + size <= cmd_bytes.len(), "geo command {:?} raw bytes are too short for {}", cmd_name(cmd), name);
+            let int = |y : &Yaml, min : i64, max : i64|->i64{
+                let i = y.as_i64().unwrap_or_else(||{panic!("geo {} has to be an integer", name)});
+                assert!(min <= i && i <= max, "geo {} {} is outside {}..={}", name, i, min, max);
+                i
+            };
+            let float = |y : &Yaml|->f32{
+                match y{
+                    Yaml::Real(s) | Yaml::String(s) => s.parse::<f32>().unwrap_or_else(|_|{panic!("geo {} \"{}\" is not a number", name, s)}),
+                    Yaml::Integer(i) => *i as f32,
+                    _ => panic!("geo {} has to be a number", name),
+                }
+            };
+            let list = |n : usize|->&Vec<Yaml>{
+                let v = y.as_vec().unwrap_or_else(||{panic!("geo {} has to be a list", name)});
+                assert_eq!(v.len(), n, "geo {} needs {} values", name, n);
+                v
+            };
+            let bytes : Vec<u8> = match kind{
+                FieldKind::U8 => vec![int(y, 0, 0xFF) as u8],
+                FieldKind::U16 => (int(y, 0, 0xFFFF) as u16).to_be_bytes().to_vec(),
+                FieldKind::I16x3 => list(3).iter().flat_map(|v|{(int(v, i16::MIN as i64, i16::MAX as i64) as i16).to_be_bytes()}).collect(),
+                FieldKind::F32 => float(y).to_be_bytes().to_vec(),
+                FieldKind::F32x3 => list(3).iter().flat_map(|v|{float(v).to_be_bytes()}).collect(),
+            };
+            cmd_bytes[pos..pos + bytes.len()].copy_from_slice(&bytes);
+        }
+        if cmd == GEO_SKINNING{
+            if let Some(dls) = yaml["dls"].as_vec(){
+                //the list and its terminator have to fit in the command's existing bytes
+                let mut list : Vec<u8> = dls.iter().flat_map(|d|{(d.as_i64().expect("skinning dls are integers") as u16).to_be_bytes()}).collect();
+                list.extend_from_slice(&[0, 0]);
+                assert!(8 + list.len() <= cmd_bytes.len(), "skinning dls {:?} don't fit in the command's {} bytes", dls, cmd_bytes.len());
+                cmd_bytes[8..8 + list.len()].copy_from_slice(&list);
+            }
+        }
+        node.body = cmd_bytes[8..].to_vec();
+        return node
+    }
+}
@@ -0,0 +1,366 @@
+use std::convert::TryInto;
+
+// F3DEX display list commands, as used by model display lists
+
+pub const G_SPNOOP : u8 = 0x00;
+pub const G_VTX : u8 = 0x04;
+pub const G_DL : u8 = 0x06;
+pub const G_TRI2 : u8 = 0xB1;
+pub const G_CLEARGEOMETRYMODE : u8 = 0xB6;
+pub const G_SETGEOMETRYMODE : u8 = 0xB7;
+pub const G_ENDDL : u8 = 0xB8;
+pub const G_SETOTHERMODE_L : u8 = 0xB9;
+pub const G_TEXTURE : u8 = 0xBB;
+pub const G_TRI1 : u8 = 0xBF;
+pub const G_SETTIMG : u8 = 0xFD;
+pub const G_SETTILESIZE : u8 = 0xF2;
+pub const G_LOADBLOCK : u8 = 0xF3;
+pub const G_SETTILE : u8 = 0xF5;
+pub const G_LOADTLUT : u8 = 0xF0;
+pub const G_SETCOMBINE : u8 = 0xFC;
+pub const G_SETOTHERMODE_H : u8 = 0xBA;
+pub const G_RDPLOADSYNC : u8 = 0xE6;
+pub const G_RDPPIPESYNC : u8 = 0xE7;
+pub const G_RDPTILESYNC : u8 = 0xE8;
+pub const G_RDPFULLSYNC : u8 = 0xE9;
+pub const G_SETFOGCOLOR : u8 = 0xF8;
+pub const G_SETBLENDCOLOR : u8 = 0xF9;
+pub const G_SETPRIMCOLOR : u8 = 0xFA;
+pub const G_SETENVCOLOR : u8 = 0xFB;
+
+/// G_CC_MODULATERGBA in both cycles
+pub const CC_MODULATE_RGBA : Gfx = Gfx{w0: 0xFC121824, w1: 0xFF33FFFF};
+/// G_CC_SHADE in both cycles
+pub const CC_SHADE : Gfx = Gfx{w0: 0xFCFFFFFF, w1: 0xFFFE793C};
+
+pub const G_TX_RENDERTILE : usize = 0;
+pub const G_TX_LOADTILE : usize = 7;
+
+/// vertices the F3DEX vertex buffer holds
+pub const VTX_BUFFER_SIZE : usize = 32;
+
+/// one 8 byte display list command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gfx{
+    pub w0 : u32,
+    pub w1 : u32,
+}
+
+impl Gfx{
+    pub const SIZE : usize = 8;
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Gfx{
+        Gfx{
+            w0: u32::from_be_bytes(in_bytes[0..4].try_into().unwrap()),
+            w1: u32::from_be_bytes(in_bytes[4..8].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = self.w0.to_be_bytes().to_vec();
+        out.extend_from_slice(&self.w1.to_be_bytes());
+        return out
+    }
+
+    /// G_VTX loading `n` vertices into slots starting at `v0`
+    pub fn new_vtx(v0: usize, n: usize, segment: u8, offset: usize) -> Gfx{
+        Gfx{
+            w0: ((G_VTX as u32) << 24) | (((2*v0) as u32) << 16) | ((n as u32) << 10) | (16*n as u32 - 1),
+            w1: ((segment as u32) << 24) | offset as u32,
+        }
+    }
+
+    pub fn new_tri1(slots: [usize; 3]) -> Gfx{
+        Gfx{w0: (G_TRI1 as u32) << 24, w1: Gfx::slots_word(slots)}
+    }
+
+    pub fn new_tri2(a: [usize; 3], b: [usize; 3]) -> Gfx{
+        Gfx{w0: ((G_TRI2 as u32) << 24) | Gfx::slots_word(a), w1: Gfx::slots_word(b)}
+    }
+
+    pub fn new_end_dl() -> Gfx{
+        Gfx{w0: (G_ENDDL as u32) << 24, w1: 0}
+    }
+
+    fn new(op: u8, w0: u32, w1: u32) -> Gfx{
+        Gfx{w0: ((op as u32) << 24) | (w0 & 0x00FFFFFF), w1: w1}
+    }
+
+    pub fn new_sync(op: u8) -> Gfx{
+        Gfx::new(op, 0, 0)
+    }
+
+    /// gsSPTexture with both scales at 0xFFFF
+    pub fn new_texture(on: bool) -> Gfx{
+        Gfx::new(G_TEXTURE, (G_TX_RENDERTILE << 8) as u32 | on as u32, 0xFFFFFFFF)
+    }
+
+    pub fn new_set_timg(fmt: u8, siz: u8, width: usize, segment: u8, offset: usize) -> Gfx{
+        Gfx::new(G_SETTIMG, ((fmt as u32) << 21) | ((siz as u32) << 19) | (width as u32 - 1), ((segment as u32) << 24) | offset as u32)
+    }
+
+    /// wrapping tile with masks of log2 of the size
+    pub fn new_set_tile(fmt: u8, siz: u8, line: usize, tmem: usize, tile: usize, palette: usize, mask_s: usize, mask_t: usize) -> Gfx{
+        Gfx::new(G_SETTILE,
+            ((fmt as u32) << 21) | ((siz as u32) << 19) | ((line as u32) << 9) | tmem as u32,
+            ((tile as u32) << 24) | ((palette as u32) << 20) | ((mask_t as u32) << 14) | ((mask_s as u32) << 4))
+    }
+
+    pub fn new_load_block(tile: usize, lrs: usize, dxt: usize) -> Gfx{
+        Gfx::new(G_LOADBLOCK, 0, ((tile as u32) << 24) | ((lrs as u32) << 12) | dxt as u32)
+    }
+
+    /// size in texels, stored as 10.2 fixed point
+    pub fn new_set_tile_size(tile: usize, w: usize, h: usize) -> Gfx{
+        Gfx::new(G_SETTILESIZE, 0, ((tile as u32) << 24) | (((w as u32 - 1) << 2) << 12) | ((h as u32 - 1) << 2))
+    }
+
+    pub fn new_load_tlut(tile: usize, count: usize) -> Gfx{
+        Gfx::new(G_LOADTLUT, 0, ((tile as u32) << 24) | (((count as u32 - 1) & 0x3FF) << 14))
+    }
+
+    pub fn new_set_other_mode_h(shift: usize, len: usize, data: u32) -> Gfx{
+        Gfx::new(G_SETOTHERMODE_H, ((shift as u32) << 8) | len as u32, data)
+    }
+
+    pub fn opcode(&self) -> u8{
+        (self.w0 >> 24) as u8
+    }
+
+    /// segment offset of an address argument
+    pub fn address_offset(&self) -> usize{
+        (self.w1 & 0x00FFFFFF) as usize
+    }
+
+    pub fn segment(&self) -> u8{
+        (self.w1 >> 24) as u8
+    }
+
+    /// G_VTX: (first buffer slot, vertex count)
+    pub fn vtx_range(&self) -> (usize, usize){
+        ((((self.w0 >> 16) & 0xFF) / 2) as usize, ((self.w0 & 0xFFFF) >> 10) as usize)
+    }
+
+    /// G_TRI1: the triangle's buffer slots
+    pub fn tri1(&self) -> [usize; 3]{
+        Gfx::tri_slots(self.w1)
+    }
+
+    /// G_TRI2: both triangles' buffer slots
+    pub fn tri2(&self) -> [[usize; 3]; 2]{
+        [Gfx::tri_slots(self.w0), Gfx::tri_slots(self.w1)]
+    }
+
+    fn tri_slots(w: u32) -> [usize; 3]{
+        [((w >> 16) & 0xFF) as usize / 2, ((w >> 8) & 0xFF) as usize / 2, (w & 0xFF) as usize / 2]
+    }
+
+    fn slots_word(slots: [usize; 3]) -> u32{
+        (((2*slots[0]) as u32) << 16) | (((2*slots[1]) as u32) << 8) | (2*slots[2]) as u32
+    }
+
+    /// one line of text, `raw w0 w1` for commands whose named form wouldn't
+    /// assemble back to the same bytes
+    pub fn to_text(&self) -> String{
+        let (w0, w1) = (self.w0, self.w1);
+        let rgba = |w: u32|{format!("{} {} {} {}", w >> 24, bits(w, 16, 8), bits(w, 8, 8), bits(w, 0, 8))};
+        let text = match self.opcode(){
+            G_SPNOOP => String::from("noop"),
+            G_VTX => {
+                let (v0, n) = self.vtx_range();
+                format!("vtx {} {} 0x{:08X}", v0, n, w1)
+            },
+            G_DL => format!("{} 0x{:08X}", if bits(w0, 16, 8) == 1 {"branch_dl"} else {"dl"}, w1),
+            G_TRI1 => {
+                let t = self.tri1();
+                format!("tri1 {} {} {}", t[0], t[1], t[2])
+            },
+            G_TRI2 => {
+                let [a, b] = self.tri2();
+                format!("tri2 {} {} {} {} {} {}", a[0], a[1], a[2], b[0], b[1], b[2])
+            },
+            G_ENDDL => String::from("enddl"),
+            G_TEXTURE => format!("texture 0x{:04X} 0x{:04X} {} {} {}", w1 >> 16, w1 & 0xFFFF, bits(w0, 11, 3), bits(w0, 8, 3), w0 & 0xFF),
+            G_SETGEOMETRYMODE => format!("setgeometrymode 0x{:08X}", w1),
+            G_CLEARGEOMETRYMODE => format!("cleargeometrymode 0x{:08X}", w1),
+            G_SETOTHERMODE_H => format!("setothermode_h {} {} 0x{:08X}", bits(w0, 8, 8), w0 & 0xFF, w1),
+            G_SETOTHERMODE_L => format!("setothermode_l {} {} 0x{:08X}", bits(w0, 8, 8), w0 & 0xFF, w1),
+            G_RDPLOADSYNC => String::from("loadsync"),
+            G_RDPPIPESYNC => String::from("pipesync"),
+            G_RDPTILESYNC => String::from("tilesync"),
+            G_RDPFULLSYNC => String::from("fullsync"),
+            G_SETTIMG => format!("settimg {} {} {} 0x{:08X}", bits(w0, 21, 3), bits(w0, 19, 2), bits(w0, 0, 12) + 1, w1),
+            G_SETTILE => format!("settile {} {} {} 0x{:03X} {} {} {} {} {} {} {} {}",
+                bits(w0, 21, 3), bits(w0, 19, 2), bits(w0, 9, 9), bits(w0, 0, 9),
+                bits(w1, 24, 3), bits(w1, 20, 4), bits(w1, 18, 2), bits(w1, 14, 4), bits(w1, 10, 4), bits(w1, 8, 2), bits(w1, 4, 4), bits(w1, 0, 4)),
+            G_LOADBLOCK => format!("loadblock {} {} {} {} {}", bits(w1, 24, 3), bits(w0, 12, 12), bits(w0, 0, 12), bits(w1, 12, 12), bits(w1, 0, 12)),
+            G_SETTILESIZE => format!("settilesize {} {} {} {} {}", bits(w1, 24, 3), bits(w0, 12, 12), bits(w0, 0, 12), bits(w1, 12, 12), bits(w1, 0, 12)),
+            G_LOADTLUT => format!("loadtlut {} {}", bits(w1, 24, 3), bits(w1, 14, 10) + 1),
+            G_SETCOMBINE => format!("setcombine 0x{:06X} 0x{:08X}", w0 & 0xFFFFFF, w1),
+            G_SETFOGCOLOR => format!("setfogcolor {}", rgba(w1)),
+            G_SETBLENDCOLOR => format!("setblendcolor {}", rgba(w1)),
+            G_SETPRIMCOLOR => format!("setprimcolor {} {} {}", bits(w0, 8, 8), w0 & 0xFF, rgba(w1)),
+            G_SETENVCOLOR => format!("setenvcolor {}", rgba(w1)),
+            _ => String::new(),
+        };
+        return match Gfx::from_text(&text){
+            Ok(g) if g == *self => text,
+            _ => format!("raw 0x{:08X} 0x{:08X}", w0, w1),
+        }
+    }
+
+    /// the gbi.h macro building this command, with segment addresses named by `address`.
+    /// Commands without a named text form are written as a raw initializer.
+    pub fn to_c(&self, address: &dyn Fn(u32)->String) -> String{
+        let text = self.to_text();
+        let mut words = text.split_whitespace();
+        let name = words.next().unwrap();
+        let args : Vec<String> = words.map(|w|{w.to_string()}).collect();
+        let a = args.join(", ");
+        return match name{
+            "noop" => String::from("gsSPNoOp()"),
+            "vtx" => format!("gsSPVertex({}, {}, {})", address(parse_arg(&args[2]).unwrap()), args[1], args[0]),
+            "dl" => format!("gsSPDisplayList({})", address(parse_arg(&args[0]).unwrap())),
+            "branch_dl" => format!("gsSPBranchList({})", address(parse_arg(&args[0]).unwrap())),
+            "tri1" => format!("gsSP1Triangle({}, 0)", a),
+            "tri2" => format!("gsSP2Triangles({}, {}, {}, 0, {}, {}, {}, 0)", args[0], args[1], args[2], args[3], args[4], args[5]),
+            "enddl" => String::from("gsSPEndDisplayList()"),
+            "texture" => format!("gsSPTexture({})", a),
+            "setgeometrymode" => format!("gsSPSetGeometryMode({})", a),
+            "cleargeometrymode" => format!("gsSPClearGeometryMode({})", a),
+            "setothermode_h" => format!("gsSPSetOtherMode(G_SETOTHERMODE_H, {})", a),
+            "setothermode_l" => format!("gsSPSetOtherMode(G_SETOTHERMODE_L, {})", a),
+            "loadsync" => String::from("gsDPLoadSync()"),
+            "pipesync" => String::from("gsDPPipeSync()"),
+            "tilesync" => String::from("gsDPTileSync()"),
+            "fullsync" => String::from("gsDPFullSync()"),
+            "settimg" => format!("gsDPSetTextureImage({}, {}, {}, {})", args[0], args[1], args[2], address(parse_arg(&args[3]).unwrap())),
+            "settile" => format!("gsDPSetTile({})", a),
+            "loadblock" => format!("gsDPLoadBlock({})", a),
+            "settilesize" => format!("gsDPSetTileSize({})", a),
+            "loadtlut" => format!("gsDPLoadTLUTCmd({}, {})", args[0], parse_arg(&args[1]).unwrap() - 1),
+            "setfogcolor" => format!("gsDPSetFogColor({})", a),
+            "setblendcolor" => format!("gsDPSetBlendColor({})", a),
+            "setprimcolor" => format!("gsDPSetPrimColor({})", a),
+            "setenvcolor" => format!("gsDPSetEnvColor({})", a),
+            _ => format!("{{{{0x{:08X}, 0x{:08X}}}}}", self.w0, self.w1),
+        }
+    }
+
+    /// parse one line written by `to_text`
+    pub fn from_text(line: &str) -> Result<Gfx, String>{
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or("empty command")?;
+        let args = words.map(parse_arg).collect::<Result<Vec<u32>, String>>()?;
+        let arg_cnt = match name{
+            "noop" | "enddl" | "loadsync" | "pipesync" | "tilesync" | "fullsync" => 0,
+            "dl" | "branch_dl" | "setgeometrymode" | "cleargeometrymode" => 1,
+            "raw" | "loadtlut" | "setcombine" => 2,
+            "vtx" | "tri1" | "setothermode_h" | "setothermode_l" => 3,
+            "settimg" | "setfogcolor" | "setblendcolor" | "setenvcolor" => 4,
+            "texture" | "loadblock" | "settilesize" => 5,
+            "tri2" | "setprimcolor" => 6,
+            "settile" => 12,
+            _ => return Err(format!("unknown command \"{}\"", name)),
+        };
+        if args.len() != arg_cnt{
+            return Err(format!("{} takes {} arguments, found {}", name, arg_cnt, args.len()));
+        }
+        let a = |i: usize, n: u32| -> Result<u32, String>{
+            match args[i] < (1u64 << n) as u32{
+                true => Ok(args[i]),
+                false => Err(format!("{} argument {} doesn't fit in {} bits", name, i + 1, n)),
+            }
+        };
+        let rgba = |i: usize| -> Result<u32, String>{Ok((a(i, 8)? << 24) | (a(i + 1, 8)? << 16) | (a(i + 2, 8)? << 8) | a(i + 3, 8)?)};
+        let slot = |i: usize| -> Result<usize, String>{
+            match args[i] < VTX_BUFFER_SIZE as u32{
+                true => Ok(args[i] as usize),
+                false => Err(format!("{} vertex slot {} is outside the {} vertex buffer", name, args[i], VTX_BUFFER_SIZE)),
+            }
+        };
+        return Ok(match name{
+            "raw" => Gfx{w0: args[0], w1: args[1]},
+            "noop" => Gfx::new_sync(G_SPNOOP),
+            "vtx" => {
+                if args[1] == 0 || slot(0)? + args[1] as usize > VTX_BUFFER_SIZE{
+                    return Err(format!("vtx of {} vertices at slot {} doesn't fit the vertex buffer", args[1], args[0]));
+                }
+                Gfx::new_vtx(args[0] as usize, args[1] as usize, (args[2] >> 24) as u8, (args[2] & 0xFFFFFF) as usize)
+            },
+            "dl" => Gfx::new(G_DL, 0, args[0]),
+            "branch_dl" => Gfx::new(G_DL, 1 << 16, args[0]),
+            "tri1" => Gfx::new_tri1([slot(0)?, slot(1)?, slot(2)?]),
+            "tri2" => Gfx::new_tri2([slot(0)?, slot(1)?, slot(2)?], [slot(3)?, slot(4)?, slot(5)?]),
+            "enddl" => Gfx::new_end_dl(),
+            "texture" => Gfx::new(G_TEXTURE, (a(2, 3)? << 11) | (a(3, 3)? << 8) | a(4, 8)?, (a(0, 16)? << 16) | a(1, 16)?),
+            "setgeometrymode" => Gfx::new(G_SETGEOMETRYMODE, 0, args[0]),
+            "cleargeometrymode" => Gfx::new(G_CLEARGEOMETRYMODE, 0, args[0]),
+            "setothermode_h" => Gfx::new(G_SETOTHERMODE_H, (a(0, 8)? << 8) | a(1, 8)?, args[2]),
+            "setothermode_l" => Gfx::new(G_SETOTHERMODE_L, (a(0, 8)? << 8) | a(1, 8)?, args[2]),
+            "loadsync" => Gfx::new_sync(G_RDPLOADSYNC),
+            "pipesync" => Gfx::new_sync(G_RDPPIPESYNC),
+            "tilesync" => Gfx::new_sync(G_RDPTILESYNC),
+            "fullsync" => Gfx::new_sync(G_RDPFULLSYNC),
+            "settimg" => {
+                if args[2] == 0 || args[2] > 0x1000{
+                    return Err(format!("settimg width {} is outside 1-4096", args[2]));
+                }
+                Gfx::new(G_SETTIMG, (a(0, 3)? << 21) | (a(1, 2)? << 19) | (args[2] - 1), args[3])
+            },
+            "settile" => Gfx::new(G_SETTILE,
+                (a(0, 3)? << 21) | (a(1, 2)? << 19) | (a(2, 9)? << 9) | a(3, 9)?,
+                (a(4, 3)? << 24) | (a(5, 4)? << 20) | (a(6, 2)? << 18) | (a(7, 4)? << 14) | (a(8, 4)? << 10) | (a(9, 2)? << 8) | (a(10, 4)? << 4) | a(11, 4)?),
+            "loadblock" => Gfx::new(G_LOADBLOCK, (a(1, 12)? << 12) | a(2, 12)?, (a(0, 3)? << 24) | (a(3, 12)? << 12) | a(4, 12)?),
+            "settilesize" => Gfx::new(G_SETTILESIZE, (a(1, 12)? << 12) | a(2, 12)?, (a(0, 3)? << 24) | (a(3, 12)? << 12) | a(4, 12)?),
+            "loadtlut" => {
+                if args[1] == 0 || args[1] > 0x100{
+                    return Err(format!("loadtlut count {} is outside 1-256", args[1]));
+                }
+                Gfx::new_load_tlut(a(0, 3)? as usize, args[1] as usize)
+            },
+            "setcombine" => Gfx::new(G_SETCOMBINE, a(0, 24)?, args[1]),
+            "setfogcolor" => Gfx::new(G_SETFOGCOLOR, 0, rgba(0)?),
+            "setblendcolor" => Gfx::new(G_SETBLENDCOLOR, 0, rgba(0)?),
+            "setprimcolor" => Gfx::new(G_SETPRIMCOLOR, (a(0, 8)? << 8) | a(1, 8)?, rgba(2)?),
+            "setenvcolor" => Gfx::new(G_SETENVCOLOR, 0, rgba(0)?),
+            _ => unreachable!(),
+        })
+    }
+}
+
+fn bits(w: u32, shift: u32, n: u32) -> u32{
+    (w >> shift) & ((1u64 << n) - 1) as u32
+}
+
+/// decimal or 0x prefixed hex
+fn parse_arg(s: &str) -> Result<u32, String>{
+    match s.strip_prefix("0x").or(s.strip_prefix("0X")){
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse::<u32>(),
+    }.map_err(|_|{format!("bad number \"{}\"", s)})
+}
+
+/// a display list as text, one command per line with a blank line after each
+/// G_ENDDL. Lines are numbered by command index, which is what geo layouts
+/// use to pick a list.
+pub fn disassemble(cmds: &[Gfx]) -> String{
+    let mut out = String::new();
+    for (i, cmd) in cmds.iter().enumerate(){
+        out += &format!("{:<40}# {:04X}\n", cmd.to_text(), i);
+        if cmd.opcode() == G_ENDDL{
+            out += "\n";
+        }
+    }
+    return out
+}
+
+/// the commands of a `disassemble`d display list, `#` starts a comment
+pub fn assemble(text: &str) -> Result<Vec<Gfx>, String>{
+    text.lines()
+        .enumerate()
+        .map(|(i, l)|{(i, l.split('#').next().unwrap().trim())})
+        .filter(|(_, l)|{!l.is_empty()})
+        .map(|(i, l)|{Gfx::from_text(l).map_err(|e|{format!("line {}: {}", i + 1, e)})})
+        .collect()
+}
@@ -0,0 +1,281 @@
+use std::convert::TryInto;
+use std::path::Path;
+
+use super::deps;
+use super::json::Json;
+use super::texture;
+
+// glTF 2.0 reader (.gltf with external or base64 buffers, and .glb), flattened
+// to world space triangles. Anything the model format can't keep is listed in
+// `report` instead of failing the import.
+
+pub struct GltfTriangle{
+    pub vertices : [usize; 3],
+    pub material : Option<usize>, //index into images
+}
+
+pub struct GltfMesh{
+    pub positions : Vec<[f32; 3]>,
+    pub uvs : Vec<[f32; 2]>,
+    pub colors : Vec<[f32; 4]>,
+    pub triangles : Vec<GltfTriangle>,
+    pub images : Vec<(usize, usize, Vec<u8>)>, //rgba32 base color textures
+    pub report : Vec<String>,
+}
+
+type Mat4 = [f32; 16]; //column major
+
+const IDENTITY : Mat4 = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+
+fn mat_mul(a: &Mat4, b: &Mat4) -> Mat4{
+    let mut out = [0.0; 16];
+    for c in 0..4{
+        for r in 0..4{
+            out[4*c + r] = (0..4).map(|k|{a[4*k + r]*b[4*c + k]}).sum();
+        }
+    }
+    return out
+}
+
+fn node_matrix(node: &Json) -> Mat4{
+    let m = node["matrix"].as_f32_vec();
+    if m.len() == 16{
+        return m.try_into().unwrap()
+    }
+    let t = node["translation"].as_f32_vec();
+    let r = node["rotation"].as_f32_vec();
+    let s = node["scale"].as_f32_vec();
+    let (tx, ty, tz) = if t.len() == 3 {(t[0], t[1], t[2])} else {(0.0, 0.0, 0.0)};
+    let (x, y, z, w) = if r.len() == 4 {(r[0], r[1], r[2], r[3])} else {(0.0, 0.0, 0.0, 1.0)};
+    let (sx, sy, sz) = if s.len() == 3 {(s[0], s[1], s[2])} else {(1.0, 1.0, 1.0)};
+    return [
+        (1.0 - 2.0*(y*y + z*z))*sx, (2.0*(x*y + z*w))*sx, (2.0*(x*z - y*w))*sx, 0.0,
+        (2.0*(x*y - z*w))*sy, (1.0 - 2.0*(x*x + z*z))*sy, (2.0*(y*z + x*w))*sy, 0.0,
+        (2.0*(x*z + y*w))*sz, (2.0*(y*z - x*w))*sz, (1.0 - 2.0*(x*x + y*y))*sz, 0.0,
+        tx, ty, tz, 1.0,
+    ]
+}
+
+fn transform(m: &Mat4, p: [f32; 3]) -> [f32; 3]{
+    [0, 1, 2].map(|r|{m[r]*p[0] + m[4 + r]*p[1] + m[8 + r]*p[2] + m[12 + r]})
+}
+
+fn base64_decode(text: &str) -> Vec<u8>{
+    const ALPHABET : &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let values : Vec<u32> = text.bytes()
+        .filter_map(|c|{ALPHABET.iter().position(|a|{*a == c}).map(|v|{v as u32})})
+        .collect();
+    let mut out : Vec<u8> = Vec::new();
+    for group in values.chunks(4){
+        let bits = group.iter().enumerate().fold(0u32, |acc, (i, v)|{acc | (v << (18 - 6*i))});
+        for i in 0..group.len().saturating_sub(1){
+            out.push((bits >> (16 - 8*i)) as u8);
+        }
+    }
+    return out
+}
+
+/// padded base64, for data uris in written gltfs
+pub fn base64_encode(bytes: &[u8]) -> String{
+    const ALPHABET : &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for group in bytes.chunks(3){
+        let bits = group.iter().enumerate().fold(0u32, |acc, (i, b)|{acc | ((*b as u32) << (16 - 8*i))});
+        for i in 0..4{
+            match i <= group.len(){
+                true => out.push(ALPHABET[((bits >> (18 - 6*i)) & 0x3F) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    return out
+}
+
+/// contents of a buffer or image uri, either a data uri or a file next to the gltf
+fn read_uri(uri: &str, folder: &Path) -> Vec<u8>{
+    match uri.strip_prefix("data:"){
+        Some(data) => base64_decode(data.splitn(2, ",").nth(1).expect("data uri without data")),
+        None => deps::read(folder.join(uri)).unwrap_or_else(|_|{panic!("could not read {:?}", folder.join(uri))}),
+    }
+}
+
+struct Gltf{
+    doc : Json,
+    buffers : Vec<Vec<u8>>,
+}
+
+impl Gltf{
+    fn buffer_view(&self, view: usize) -> &[u8]{
+        let v = &self.doc["bufferViews"][view];
+        let start = v["byteOffset"].as_usize().unwrap_or(0);
+        let len = v["byteLength"].as_usize().unwrap();
+        return &self.buffers[v["buffer"].as_usize().unwrap()][start..start + len]
+    }
+
+    /// an accessor's elements as floats, normalized integers mapped to 0.0-1.0
+    fn accessor(&self, index: usize) -> Vec<Vec<f32>>{
+        let a = &self.doc["accessors"][index];
+        let count = a["count"].as_usize().unwrap();
+        let components = match a["type"].as_str().unwrap(){
+            "SCALAR" => 1, "VEC2" => 2, "VEC3" => 3, "VEC4" => 4, "MAT4" => 16,
+            x => panic!("unsupported accessor type {}", x),
+        };
+        let (size, read) : (usize, fn(&[u8]) -> f32) = match a["componentType"].as_i64().unwrap(){
+            5120 => (1, |b|{b[0] as i8 as f32}),
+            5121 => (1, |b|{b[0] as f32}),
+            5122 => (2, |b|{i16::from_le_bytes([b[0], b[1]]) as f32}),
+            5123 => (2, |b|{u16::from_le_bytes([b[0], b[1]]) as f32}),
+            5125 => (4, |b|{u32::from_le_bytes(b[0..4].try_into().unwrap()) as f32}),
+            5126 => (4, |b|{f32::from_le_bytes(b[0..4].try_into().unwrap())}),
+            x => panic!("unsupported accessor component type {}", x),
+        };
+        let scale = match (a["normalized"].as_bool().unwrap_or(false), size){
+            (true, 1) => if a["componentType"].as_i64() == Some(5120) {127.0} else {255.0},
+            (true, 2) => if a["componentType"].as_i64() == Some(5122) {32767.0} else {65535.0},
+            _ => 1.0,
+        };
+        let view_index = match a["bufferView"].as_usize(){
+            Some(v) => v,
+            None => return vec![vec![0.0; components]; count], //sparse only accessors start zeroed
+        };
+        let view = self.buffer_view(view_index);
+        let offset = a["byteOffset"].as_usize().unwrap_or(0);
+        let stride = self.doc["bufferViews"][view_index]["byteStride"].as_usize().unwrap_or(size*components);
+        return (0..count)
+            .map(|i|{
+                (0..components).map(|c|{read(&view[offset + i*stride + c*size..]) / scale}).collect()
+            })
+            .collect()
+    }
+}
+
+impl GltfMesh{
+    pub fn read(path: &Path) -> GltfMesh{
+        let bytes = deps::read(path).expect("could not read gltf");
+        let folder = path.parent().unwrap();
+
+        //glb: json chunk followed by an optional binary chunk
+        let (text, glb_bin) = match &bytes[..4] == b"glTF"{
+            true => {
+                let json_len = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+                let json = String::from_utf8_lossy(&bytes[20..20 + json_len]).to_string();
+                let bin_start = 20 + json_len;
+                let bin = match bytes.len() > bin_start + 8{
+                    true => {
+                        let bin_len = u32::from_le_bytes(bytes[bin_start..bin_start + 4].try_into().unwrap()) as usize;
+                        Some(bytes[bin_start + 8..bin_start + 8 + bin_len].to_vec())
+                    },
+                    false => None,
+                };
+                (json, bin)
+            },
+            false => (String::from_utf8_lossy(&bytes).to_string(), None),
+        };
+        let doc = Json::parse(&text).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+        let buffers : Vec<Vec<u8>> = doc["buffers"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[])
+            .iter()
+            .map(|b|{
+                match b["uri"].as_str(){
+                    Some(uri) => read_uri(uri, folder),
+                    None => glb_bin.clone().expect("buffer without uri outside of a glb"),
+                }
+            })
+            .collect();
+        let gltf = Gltf{doc: doc, buffers: buffers};
+        let doc = &gltf.doc;
+
+        let mut mesh = GltfMesh{positions: Vec::new(), uvs: Vec::new(), colors: Vec::new(), triangles: Vec::new(), images: Vec::new(), report: Vec::new()};
+
+        //materials map to their base color image
+        let mut image_of_texture : Vec<Option<usize>> = Vec::new();
+        for (i, image) in doc["images"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter().enumerate(){
+            let data = match (image["uri"].as_str(), image["bufferView"].as_usize()){
+                (Some(uri), _) => read_uri(uri, folder),
+                (None, Some(view)) => gltf.buffer_view(view).to_vec(),
+                _ => panic!("image {} has no data", i),
+            };
+            assert!(data.starts_with(b"\x89PNG"), "image {} is not a png, only png textures are supported", i);
+            mesh.images.push(texture::decode_png_rgba32(&data));
+        }
+        for t in doc["textures"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+            image_of_texture.push(t["source"].as_usize());
+        }
+        let material_image = |m : &Json|->Option<usize>{
+            let pbr = &m["pbrMetallicRoughness"];
+            pbr["baseColorTexture"]["index"].as_usize().and_then(|t|{*image_of_texture.get(t)?})
+        };
+        for (i, m) in doc["materials"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter().enumerate(){
+            let factor = m["pbrMetallicRoughness"]["baseColorFactor"].as_f32_vec();
+            if !factor.is_empty() && factor.iter().any(|f|{*f != 1.0}){
+                mesh.report.push(format!("material {}: base color factor {:?} ignored", i, factor));
+            }
+            for map in ["normalTexture", "occlusionTexture", "emissiveTexture"]{
+                if !m[map].is_null(){
+                    mesh.report.push(format!("material {}: {} dropped", i, map));
+                }
+            }
+            if !m["pbrMetallicRoughness"]["metallicRoughnessTexture"].is_null(){
+                mesh.report.push(format!("material {}: metallicRoughnessTexture dropped", i));
+            }
+        }
+
+        //walk the default scene so node transforms are applied
+        let scene = doc["scene"].as_usize().unwrap_or(0);
+        let mut stack : Vec<(usize, Mat4)> = doc["scenes"][scene]["nodes"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[])
+            .iter()
+            .filter_map(|n|{n.as_usize()})
+            .map(|n|{(n, IDENTITY)})
+            .collect();
+        let mut dropped : Vec<&str> = Vec::new();
+        while let Some((n, parent)) = stack.pop(){
+            let node = &doc["nodes"][n];
+            let world = mat_mul(&parent, &node_matrix(node));
+            for child in node["children"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+                stack.push((child.as_usize().unwrap(), world));
+            }
+            if !node["skin"].is_null() && !dropped.contains(&"skin"){
+                dropped.push("skin");
+            }
+            let m = match node["mesh"].as_usize(){
+                Some(m) => m,
+                None => continue,
+            };
+            for prim in doc["meshes"][m]["primitives"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+                if prim["mode"].as_i64().unwrap_or(4) != 4{
+                    mesh.report.push(format!("mesh {}: non triangle primitive skipped", m));
+                    continue;
+                }
+                let attrs = &prim["attributes"];
+                let positions = gltf.accessor(attrs["POSITION"].as_usize().expect("primitive without positions"));
+                let uvs = attrs["TEXCOORD_0"].as_usize().map(|a|{gltf.accessor(a)});
+                let colors = attrs["COLOR_0"].as_usize().map(|a|{gltf.accessor(a)});
+                if let Json::Object(members) = attrs{
+                    for (name, _) in members.iter(){
+                        let kept = ["POSITION", "TEXCOORD_0", "COLOR_0"].contains(&name.as_str());
+                        if !kept && !dropped.contains(&name.as_str()){
+                            dropped.push(name);
+                        }
+                    }
+                }
+                let base = mesh.positions.len();
+                for (i, p) in positions.iter().enumerate(){
+                    mesh.positions.push(transform(&world, [p[0], p[1], p[2]]));
+                    mesh.uvs.push(uvs.as_ref().map(|u|{[u[i][0], u[i][1]]}).unwrap_or([0.0, 0.0]));
+                    mesh.colors.push(colors.as_ref().map(|c|{[c[i][0], c[i][1], c[i][2], *c[i].get(3).unwrap_or(&1.0)]}).unwrap_or([1.0; 4]));
+                }
+                let indices : Vec<usize> = match prim["indices"].as_usize(){
+                    Some(a) => gltf.accessor(a).iter().map(|i|{i[0] as usize}).collect(),
+                    None => (0..positions.len()).collect(),
+                };
+                let material = prim["material"].as_usize().and_then(|i|{material_image(&doc["materials"][i])});
+                for tri in indices.chunks_exact(3){
+                    mesh.triangles.push(GltfTriangle{vertices: [base + tri[0], base + tri[1], base + tri[2]], material: material});
+                }
+            }
+        }
+        for attr in dropped{
+            mesh.report.push(format!("{} dropped, models only keep positions, one uv set and vertex colors", attr));
+        }
+        return mesh
+    }
+}
@@ -0,0 +1,192 @@
+use std::ops::Index;
+
+// small json reader, indexed like yaml_rust::Yaml so missing keys read as Null
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+static NULL : Json = Json::Null;
+
+impl Json{
+    pub fn parse(text: &str) -> Result<Json, String>{
+        let chars : Vec<char> = text.chars().collect();
+        let mut pos = 0;
+        let value = parse_value(&chars, &mut pos)?;
+        skip_whitespace(&chars, &mut pos);
+        if pos != chars.len(){
+            return Err(format!("trailing characters at {}", pos));
+        }
+        return Ok(value)
+    }
+
+    pub fn as_f64(&self) -> Option<f64>{
+        match self{ Json::Number(n) => Some(*n), _ => None }
+    }
+
+    pub fn as_i64(&self) -> Option<i64>{
+        self.as_f64().map(|n|{n as i64})
+    }
+
+    pub fn as_usize(&self) -> Option<usize>{
+        self.as_f64().map(|n|{n as usize})
+    }
+
+    pub fn as_bool(&self) -> Option<bool>{
+        match self{ Json::Bool(b) => Some(*b), _ => None }
+    }
+
+    pub fn as_str(&self) -> Option<&str>{
+        match self{ Json::String(s) => Some(s), _ => None }
+    }
+
+    pub fn as_vec(&self) -> Option<&Vec<Json>>{
+        match self{ Json::Array(a) => Some(a), _ => None }
+    }
+
+    pub fn is_null(&self) -> bool{
+        *self == Json::Null
+    }
+
+    /// numbers of an array, empty if it isn't one
+    pub fn as_f32_vec(&self) -> Vec<f32>{
+        self.as_vec().map(|a|{a.iter().filter_map(|n|{n.as_f64()}).map(|n|{n as f32}).collect()}).unwrap_or_default()
+    }
+}
+
+impl<'a> Index<&'a str> for Json{
+    type Output = Json;
+    fn index(&self, key: &str) -> &Json{
+        match self{
+            Json::Object(members) => members.iter().find(|(k, _)|{k == key}).map(|(_, v)|{v}).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+impl Index<usize> for Json{
+    type Output = Json;
+    fn index(&self, i: usize) -> &Json{
+        match self{
+            Json::Array(a) => a.get(i).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+fn skip_whitespace(chars: &[char], pos: &mut usize){
+    while *pos < chars.len() && chars[*pos].is_whitespace(){
+        *pos += 1;
+    }
+}
+
+fn expect_word(chars: &[char], pos: &mut usize, word: &str, value: Json) -> Result<Json, String>{
+    let end = *pos + word.len();
+    if end <= chars.len() && chars[*pos..end].iter().collect::<String>() == word{
+        *pos = end;
+        return Ok(value)
+    }
+    return Err(format!("unexpected character at {}", pos))
+}
+
+fn parse_value(chars: &[char], pos: &mut usize) -> Result<Json, String>{
+    skip_whitespace(chars, pos);
+    match chars.get(*pos){
+        None => Err(String::from("unexpected end of json")),
+        Some('n') => expect_word(chars, pos, "null", Json::Null),
+        Some('t') => expect_word(chars, pos, "true", Json::Bool(true)),
+        Some('f') => expect_word(chars, pos, "false", Json::Bool(false)),
+        Some('"') => parse_string(chars, pos).map(|s|{Json::String(s)}),
+        Some('[') => {
+            *pos += 1;
+            let mut items : Vec<Json> = Vec::new();
+            skip_whitespace(chars, pos);
+            if chars.get(*pos) == Some(&']'){
+                *pos += 1;
+                return Ok(Json::Array(items))
+            }
+            loop{
+                items.push(parse_value(chars, pos)?);
+                skip_whitespace(chars, pos);
+                match chars.get(*pos){
+                    Some(',') => *pos += 1,
+                    Some(']') => {*pos += 1; return Ok(Json::Array(items))},
+                    _ => return Err(format!("expected , or ] at {}", pos)),
+                }
+            }
+        },
+        Some('{') => {
+            *pos += 1;
+            let mut members : Vec<(String, Json)> = Vec::new();
+            skip_whitespace(chars, pos);
+            if chars.get(*pos) == Some(&'}'){
+                *pos += 1;
+                return Ok(Json::Object(members))
+            }
+            loop{
+                skip_whitespace(chars, pos);
+                let key = parse_string(chars, pos)?;
+                skip_whitespace(chars, pos);
+                if chars.get(*pos) != Some(&':'){
+                    return Err(format!("expected : at {}", pos));
+                }
+                *pos += 1;
+                members.push((key, parse_value(chars, pos)?));
+                skip_whitespace(chars, pos);
+                match chars.get(*pos){
+                    Some(',') => *pos += 1,
+                    Some('}') => {*pos += 1; return Ok(Json::Object(members))},
+                    _ => return Err(format!("expected , or }} at {}", pos)),
+                }
+            }
+        },
+        Some(_) => {
+            let start = *pos;
+            while *pos < chars.len() && (chars[*pos].is_ascii_digit() || "+-.eE".contains(chars[*pos])){
+                *pos += 1;
+            }
+            let num : String = chars[start..*pos].iter().collect();
+            num.parse::<f64>().map(|n|{Json::Number(n)}).map_err(|_|{format!("bad number \"{}\" at {}", num, start)})
+        },
+    }
+}
+
+fn parse_string(chars: &[char], pos: &mut usize) -> Result<String, String>{
+    if chars.get(*pos) != Some(&'"'){
+        return Err(format!("expected string at {}", pos));
+    }
+    *pos += 1;
+    let mut out = String::new();
+    loop{
+        match chars.get(*pos){
+            None => return Err(String::from("unterminated string")),
+            Some('"') => {*pos += 1; return Ok(out)},
+            Some('\\') => {
+                *pos += 1;
+                match chars.get(*pos){
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('u') => {
+                        let hex : String = chars.get(*pos + 1..*pos + 5).ok_or("short \\u escape")?.iter().collect();
+                        let code = u32::from_str_radix(&hex, 16).map_err(|_|{format!("bad \\u escape at {}", pos)})?;
+                        out.push(std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        *pos += 4;
+                    },
+                    Some(c) => out.push(*c),
+                    None => return Err(String::from("unterminated string")),
+                }
+                *pos += 1;
+            },
+            Some(c) => {out.push(*c); *pos += 1},
+        }
+    }
+}
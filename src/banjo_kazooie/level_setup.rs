@@ -0,0 +1,1152 @@
+use std::convert::TryInto;
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::asset::{bytes_to_yaml, yaml_f32, yaml_to_bytes, Asset, AssetType, ExtractOptions};
+use super::deps;
+use super::error::{ByteReader, ParseError};
+use super::log;
+use super::names::NameMap;
+use super::texture;
+
+// level setups are a stream of tagged sections: a grid of cubes holding the
+// level's objects, the camera nodes and the lights, ended by a 0 tag.
+// Values inside a section are introduced by their own tag bytes.
+
+const SECTION_END : u8 = 0x00;
+const SECTION_CUBES : u8 = 0x01;
+const SECTION_CAMERAS : u8 = 0x03;
+const SECTION_LIGHTING : u8 = 0x04;
+
+const CUBE_END : u8 = 0x01;
+const CUBE_PROPS : u8 = 0x08; //count, then 0x09 and the props
+const CUBE_NODES : u8 = 0x0A; //count, then 0x0B and the nodes
+
+/// the tag byte the stream has to hold next
+fn require(r: &mut ByteReader, tag: u8, what: &str) -> Result<(), ParseError>{
+    if !r.expect(tag){
+        return Err(ParseError::Invalid(format!("expected tag {} of {} at 0x{:X}", tag, what, r.offset)));
+    }
+    return Ok(())
+}
+
+/// an object placed in the level: actors, warps, triggers and other markers
+#[derive(Debug, Clone)]
+pub struct NodeProp{
+    pub bytes : [u8; NodeProp::SIZE],
+}
+
+impl NodeProp{
+    pub const SIZE : usize = 0x14;
+
+    pub fn position(&self) -> [i16; 3]{
+        [0, 2, 4].map(|o|{i16::from_be_bytes(self.bytes[o..o + 2].try_into().unwrap())})
+    }
+
+    pub fn set_position(&mut self, position: [i16; 3]){
+        for (i, p) in position.iter().enumerate(){
+            self.bytes[2*i..2*i + 2].copy_from_slice(&p.to_be_bytes());
+        }
+    }
+
+    /// what the node spawns or marks, an actor id for actors
+    pub fn id(&self) -> u16{
+        u16::from_be_bytes(self.bytes[8..10].try_into().unwrap())
+    }
+
+    pub fn set_id(&mut self, id: u16){
+        self.bytes[8..10].copy_from_slice(&id.to_be_bytes());
+    }
+}
+
+/// a sprite or static model drawn in the level
+#[derive(Debug, Clone)]
+pub struct SpriteProp{
+    pub bytes : [u8; SpriteProp::SIZE],
+}
+
+impl SpriteProp{
+    pub const SIZE : usize = 0xC;
+
+    /// 12 bit sprite or model id
+    pub fn id(&self) -> u16{
+        u16::from_be_bytes(self.bytes[0..2].try_into().unwrap()) >> 4
+    }
+
+    /// bit 1 of the last byte, set for models and clear for sprites
+    pub fn is_model(&self) -> bool{
+        self.bytes[0xB] & 0x2 != 0
+    }
+
+    pub fn set_id(&mut self, id: u16){
+        let low = self.bytes[1] & 0xF;
+        self.bytes[0..2].copy_from_slice(&((id << 4) | low as u16).to_be_bytes());
+    }
+
+    pub fn position(&self) -> [i16; 3]{
+        [4, 6, 8].map(|o|{i16::from_be_bytes(self.bytes[o..o + 2].try_into().unwrap())})
+    }
+
+    pub fn set_position(&mut self, position: [i16; 3]){
+        for (i, p) in position.iter().enumerate(){
+            self.bytes[4 + 2*i..6 + 2*i].copy_from_slice(&p.to_be_bytes());
+        }
+    }
+}
+
+/// a list inside a cube, kept in file order
+#[derive(Debug, Clone)]
+pub enum CubeList{
+    Nodes(Vec<NodeProp>),
+    Props(Vec<SpriteProp>),
+    Unknown(u8, Vec<u8>), //any other tag, followed by a u8 byte count
+}
+
+#[derive(Debug, Clone)]
+pub struct Cube{
+    pub position : [i32; 3],
+    pub lists : Vec<CubeList>,
+}
+
+/// one tagged camera value, its floats or words
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraValue{
+    Floats(Vec<f32>),
+    Word(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Camera{
+    pub id : u16,
+    pub camera_type : u8,
+    pub fields : Vec<(u8, CameraValue)>,
+}
+
+impl Camera{
+    /// how the value after `tag` is stored for a camera type, None if unknown
+    fn field_layout(camera_type: u8, tag: u8) -> Option<CameraValue>{
+        let floats = |n : usize|{Some(CameraValue::Floats(vec![0.0; n]))};
+        match (camera_type, tag){
+            (1, 1) | (1, 4) | (3, 1) | (3, 4) => floats(3),
+            (1, 2) | (1, 3) | (3, 2) | (3, 3) | (3, 6) => floats(2),
+            (2, 1) | (2, 2) => floats(3),
+            (1, 5) | (3, 5) | (4, 1) => Some(CameraValue::Word(0)),
+            _ => None,
+        }
+    }
+
+    /// yaml name of a field. angles are pitch, yaw, roll in degrees
+    pub fn field_name(camera_type: u8, tag: u8) -> Option<&'static str>{
+        match (camera_type, tag){
+            (1, 1) | (2, 1) | (3, 1) => Some("position"),
+            (1, 2) | (3, 2) => Some("speed"), //horizontal, vertical
+            (1, 3) | (3, 3) => Some("rotation_speed"), //speed, acceleration
+            (1, 4) | (2, 2) | (3, 4) => Some("rotation"),
+            (1, 5) | (3, 5) | (4, 1) => Some("flags"),
+            (3, 6) => Some("distance"), //closest, furthest from the player
+            _ => None,
+        }
+    }
+
+    fn field_tag(camera_type: u8, name: &str) -> Option<u8>{
+        (1..=6).find(|tag|{Camera::field_name(camera_type, *tag) == Some(name)})
+    }
+
+    pub fn field(&self, name: &str) -> Option<&CameraValue>{
+        let tag = Camera::field_tag(self.camera_type, name)?;
+        self.fields.iter().find(|(t, _)|{*t == tag}).map(|(_, v)|{v})
+    }
+
+    pub fn position(&self) -> Option<[f32; 3]>{
+        match self.field("position"){
+            Some(CameraValue::Floats(f)) => f.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Light{
+    pub position : [f32; 3],
+    pub radius : [f32; 2], //full brightness and falloff
+    pub color : [u32; 3],
+}
+
+/// every cube from min to max, x then y then z. min above max is an empty grid
+pub struct CubeGrid{
+    pub min : [i32; 3],
+    pub max : [i32; 3],
+    pub cubes : Vec<Cube>,
+}
+
+impl CubeGrid{
+    /// world units along each side of a cube
+    pub const CUBE_SIZE : i32 = 1000;
+
+    /// grid position of the cube containing a world position
+    pub fn cube_of(position: [i16; 3]) -> [i32; 3]{
+        position.map(|p|{(p as i32).div_euclid(CubeGrid::CUBE_SIZE)})
+    }
+
+    /// index into `cubes` of the cube at a grid position
+    pub fn index(&self, position: [i32; 3]) -> Option<usize>{
+        if (0..3).any(|i|{position[i] < self.min[i] || position[i] > self.max[i]}){
+            return None;
+        }
+        let size = [0, 1, 2].map(|i|{(self.max[i] - self.min[i] + 1) as usize});
+        let rel = [0, 1, 2].map(|i|{(position[i] - self.min[i]) as usize});
+        Some((rel[0]*size[1] + rel[1])*size[2] + rel[2])
+    }
+
+    /// grows the grid to cover `position`, keeping every cube's contents
+    fn include(&mut self, position: [i32; 3]){
+        if self.index(position).is_some(){
+            return;
+        }
+        let empty = self.cubes.is_empty();
+        let min = [0, 1, 2].map(|i|{if empty {position[i]} else {self.min[i].min(position[i])}});
+        let max = [0, 1, 2].map(|i|{if empty {position[i]} else {self.max[i].max(position[i])}});
+        let mut old = std::mem::take(&mut self.cubes).into_iter();
+        let old_grid = CubeGrid{min: self.min, max: self.max, cubes: Vec::new()};
+        self.min = min;
+        self.max = max;
+        self.cubes = LevelSetup::grid_positions(min, max).into_iter()
+            .map(|p|{
+                match old_grid.index(p){
+                    Some(_) => old.next().unwrap(), //old cubes come in the same order
+                    None => Cube{position: p, lists: Vec::new()},
+                }
+            })
+            .collect();
+    }
+
+    /// adds a node to the cube its position falls in, growing the grid if needed
+    pub fn insert_node(&mut self, node: NodeProp){
+        let c = CubeGrid::cube_of(node.position());
+        self.include(c);
+        let i = self.index(c).unwrap();
+        let cube = &mut self.cubes[i];
+        match cube.lists.iter_mut().find_map(|l|{if let CubeList::Nodes(n) = l {Some(n)} else {None}}){
+            Some(nodes) => nodes.push(node),
+            None => cube.lists.push(CubeList::Nodes(vec![node])),
+        }
+    }
+
+    /// adds a prop to the cube its position falls in, growing the grid if needed
+    pub fn insert_prop(&mut self, prop: SpriteProp){
+        let c = CubeGrid::cube_of(prop.position());
+        self.include(c);
+        let i = self.index(c).unwrap();
+        let cube = &mut self.cubes[i];
+        match cube.lists.iter_mut().find_map(|l|{if let CubeList::Props(p) = l {Some(p)} else {None}}){
+            Some(props) => props.push(prop),
+            None => cube.lists.push(CubeList::Props(vec![prop])),
+        }
+    }
+
+    /// (cube, list, entry) of every node or every prop, in file order
+    fn slots(&self, nodes: bool) -> Vec<(usize, usize, usize)>{
+        let mut out : Vec<(usize, usize, usize)> = Vec::new();
+        for (c, cube) in self.cubes.iter().enumerate(){
+            for (l, list) in cube.lists.iter().enumerate(){
+                let len = match (list, nodes){
+                    (CubeList::Nodes(n), true) => n.len(),
+                    (CubeList::Props(p), false) => p.len(),
+                    _ => 0,
+                };
+                out.extend((0..len).map(|i|{(c, l, i)}));
+            }
+        }
+        return out
+    }
+
+    /// objects whose position lies outside the cube that holds them
+    pub fn misplaced(&self) -> Vec<String>{
+        let mut out : Vec<String> = Vec::new();
+        for cube in self.cubes.iter(){
+            for list in cube.lists.iter(){
+                let positions : Vec<(&str, [i16; 3])> = match list{
+                    CubeList::Nodes(n) => n.iter().map(|n|{("node", n.position())}).collect(),
+                    CubeList::Props(p) => p.iter().map(|p|{("prop", p.position())}).collect(),
+                    CubeList::Unknown(..) => Vec::new(),
+                };
+                for (kind, p) in positions{
+                    if CubeGrid::cube_of(p) != cube.position{
+                        out.push(format!("{} at {:?} is in cube {:?} but belongs in {:?}", kind, p, cube.position, CubeGrid::cube_of(p)));
+                    }
+                }
+            }
+        }
+        return out
+    }
+}
+
+/// yaml readers shared by setups and patches, panicking with the file's path
+struct SetupYaml<'a>{
+    path : &'a Path,
+    actor_names : NameMap, //node ids can be names from this table
+}
+
+impl<'a> SetupYaml<'a>{
+    fn actor_names(doc: &Yaml, path: &Path) -> NameMap{
+        match doc["actor_names"].as_str(){
+            Some(p) => NameMap::read(&path.parent().unwrap().join(p)),
+            None => NameMap::new(),
+        }
+    }
+
+    fn int(&self, y: &Yaml, what: &str) -> i64{
+        y.as_i64().unwrap_or_else(||{panic!("{:?}: {} has to be an integer", self.path, what)})
+    }
+
+    fn floats(&self, y: &Yaml, n: usize, what: &str) -> Vec<f32>{
+        let v = y.as_vec().filter(|v|{v.len() == n}).unwrap_or_else(||{panic!("{:?}: {} needs {} numbers", self.path, what, n)});
+        v.iter().map(|f|{yaml_f32(f).unwrap_or_else(||{panic!("{:?}: {} has to be numbers", self.path, what)})}).collect()
+    }
+
+    fn ints3(&self, y: &Yaml, what: &str) -> [i64; 3]{
+        let v = y.as_vec().filter(|v|{v.len() == 3}).unwrap_or_else(||{panic!("{:?}: {} needs 3 integers", self.path, what)});
+        [0, 1, 2].map(|i|{self.int(&v[i], what)})
+    }
+
+    /// object positions are stored as s16
+    fn short3(&self, y: &Yaml, what: &str) -> [i16; 3]{
+        self.ints3(y, what).map(|i|{i16::try_from(i).unwrap_or_else(|_|{panic!("{:?}: {} {} is outside -32768 to 32767", self.path, what, i)})})
+    }
+
+    fn actor_id(&self, y: &Yaml) -> u16{
+        match y.as_str(){
+            Some(name) => self.actor_names.uid(name).unwrap_or_else(||{panic!("{:?}: actor {:?} is not in the actor name table", self.path, name)}) as u16,
+            None => u16::try_from(self.int(y, "node id")).unwrap_or_else(|_|{panic!("{:?}: node id {:?} is outside 0 to 0xFFFF", self.path, y)}),
+        }
+    }
+
+    /// `node` with the fields `n` lists replaced
+    fn node(&self, n: &Yaml, mut node: NodeProp) -> NodeProp{
+        if !n["raw"].is_badvalue(){
+            node.bytes = yaml_to_bytes(&n["raw"]).try_into().unwrap_or_else(|_|{panic!("{:?}: node raw bytes are 0x{:X} long", self.path, NodeProp::SIZE)});
+        }
+        if !n["position"].is_badvalue(){
+            node.set_position(self.short3(&n["position"], "node position"));
+        }
+        if !n["id"].is_badvalue(){
+            node.set_id(self.actor_id(&n["id"]));
+        }
+        node
+    }
+
+    /// `prop` with the fields `p` lists replaced
+    fn prop(&self, p: &Yaml, mut prop: SpriteProp) -> SpriteProp{
+        if !p["raw"].is_badvalue(){
+            prop.bytes = yaml_to_bytes(&p["raw"]).try_into().unwrap_or_else(|_|{panic!("{:?}: prop raw bytes are 0x{:X} long", self.path, SpriteProp::SIZE)});
+        }
+        if !p["position"].is_badvalue(){
+            prop.set_position(self.short3(&p["position"], "prop position"));
+        }
+        if !p["id"].is_badvalue(){
+            let id = self.int(&p["id"], "prop id");
+            assert!((0..=0xFFF).contains(&id), "{:?}: prop id 0x{:X} is outside the 12 bits props have", self.path, id);
+            prop.set_id(id as u16);
+        }
+        prop
+    }
+
+    fn camera(&self, c: &Yaml) -> Camera{
+        let camera_type = self.int(&c["type"], "camera type") as u8;
+        //fields are written in the order they are listed
+        let fields = c.as_hash().unwrap().iter()
+            .filter(|(k, _)|{!matches!(k.as_str(), Some("id") | Some("type"))})
+            .map(|(k, v)|{
+                let name = k.as_str().unwrap_or("");
+                let tag = Camera::field_tag(camera_type, name).unwrap_or_else(||{panic!("{:?}: camera type {} has no field {:?}", self.path, camera_type, name)});
+                let value = match Camera::field_layout(camera_type, tag).unwrap(){
+                    CameraValue::Floats(f) => CameraValue::Floats(self.floats(v, f.len(), name)),
+                    CameraValue::Word(_) => CameraValue::Word(self.int(v, name) as u32),
+                };
+                (tag, value)
+            })
+            .collect();
+        Camera{id: self.int(&c["id"], "camera id") as u16, camera_type: camera_type, fields: fields}
+    }
+}
+
+pub struct LevelSetup{
+    pub cubes : Option<CubeGrid>,
+    pub cameras : Option<Vec<Camera>>,
+    pub lights : Option<Vec<Light>>,
+    order : Vec<u8>, //section tags in file order
+    padding : usize,
+    raw : Option<Vec<u8>>, //set for setups that don't decode back to the same bytes
+    actor_names : NameMap, //the table its yaml named, node ids are checked against it
+}
+
+impl LevelSetup{
+    /// decoded, or kept as bytes when it doesn't decode back to the same bytes
+    pub fn from_bytes(in_bytes: &[u8])->LevelSetup{
+        match LevelSetup::try_from_bytes(in_bytes){
+            Ok(setup) => setup,
+            Err(_) => LevelSetup{raw: Some(in_bytes.to_vec()), ..LevelSetup::empty()},
+        }
+    }
+
+    /// decoded, or why it can't be without losing bytes
+    pub fn try_from_bytes(in_bytes: &[u8])->Result<LevelSetup, ParseError>{
+        let setup = LevelSetup::decode(in_bytes)?;
+        if setup.to_bytes() != in_bytes{
+            return Err(ParseError::Invalid("setup doesn't encode back to the same bytes".to_string()));
+        }
+        return Ok(setup)
+    }
+
+    fn empty() -> LevelSetup{
+        LevelSetup{cubes: None, cameras: None, lights: None, order: Vec::new(), padding: 0, raw: None, actor_names: NameMap::new()}
+    }
+
+    /// true if the setup couldn't be decoded and is kept as bytes
+    pub fn is_raw(&self) -> bool{
+        self.raw.is_some()
+    }
+
+    fn decode(in_bytes: &[u8]) -> Result<LevelSetup, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let mut setup = LevelSetup::empty();
+        loop{
+            let tag = r.u8()?;
+            if setup.order.contains(&tag){
+                //a repeated section can't be kept in order
+                return Err(ParseError::Invalid(format!("section {} appears twice", tag)));
+            }
+            match tag{
+                SECTION_END => break,
+                SECTION_CUBES => {
+                    let mut corners = [0i32; 6];
+                    for c in corners.iter_mut(){
+                        *c = r.u32()? as i32;
+                    }
+                    let mut grid = CubeGrid{min: corners[0..3].try_into().unwrap(), max: corners[3..6].try_into().unwrap(), cubes: Vec::new()};
+                    //every cube takes at least its end tag
+                    let cube_cnt = (0..3).map(|i|{(grid.max[i] as i64 - grid.min[i] as i64 + 1).max(0)}).fold(1i64, |n, d|{n.saturating_mul(d)});
+                    if cube_cnt > r.rest().len() as i64{
+                        return Err(ParseError::Invalid(format!("cube grid {:?} to {:?} has more cubes than the setup has bytes", grid.min, grid.max)));
+                    }
+                    for position in LevelSetup::grid_positions(grid.min, grid.max){
+                        grid.cubes.push(LevelSetup::decode_cube(&mut r, position)?);
+                    }
+                    setup.cubes = Some(grid);
+                },
+                SECTION_CAMERAS => setup.cameras = Some(LevelSetup::decode_cameras(&mut r)?),
+                SECTION_LIGHTING => setup.lights = Some(LevelSetup::decode_lights(&mut r)?),
+                x => return Err(ParseError::Invalid(format!("unknown section {} at 0x{:X}", x, r.offset - 1))),
+            }
+            setup.order.push(tag);
+        }
+        let tail = r.rest();
+        if tail.iter().any(|b|{*b != 0}){
+            return Err(ParseError::Invalid(format!("data after the end of the setup at 0x{:X}", r.offset)));
+        }
+        setup.padding = tail.len();
+        return Ok(setup)
+    }
+
+    /// cube coordinates in file order
+    fn grid_positions(min: [i32; 3], max: [i32; 3]) -> Vec<[i32; 3]>{
+        let mut out : Vec<[i32; 3]> = Vec::new();
+        for x in min[0]..=max[0]{
+            for y in min[1]..=max[1]{
+                for z in min[2]..=max[2]{
+                    out.push([x, y, z]);
+                }
+            }
+        }
+        return out
+    }
+
+    fn decode_cube(r: &mut ByteReader, position: [i32; 3]) -> Result<Cube, ParseError>{
+        let mut cube = Cube{position: position, lists: Vec::new()};
+        while !r.expect(CUBE_END){
+            match r.u8()?{
+                CUBE_NODES => {
+                    let count = r.u8()? as usize;
+                    if count > 0{
+                        require(r, CUBE_NODES + 1, "a node list")?;
+                    }
+                    let nodes = (0..count).map(|_|{r.take(NodeProp::SIZE).map(|b|{NodeProp{bytes: b.try_into().unwrap()}})}).collect::<Result<Vec<_>, ParseError>>()?;
+                    cube.lists.push(CubeList::Nodes(nodes));
+                },
+                CUBE_PROPS => {
+                    let count = r.u8()? as usize;
+                    if count > 0{
+                        require(r, CUBE_PROPS + 1, "a prop list")?;
+                    }
+                    let props = (0..count).map(|_|{r.take(SpriteProp::SIZE).map(|b|{SpriteProp{bytes: b.try_into().unwrap()}})}).collect::<Result<Vec<_>, ParseError>>()?;
+                    cube.lists.push(CubeList::Props(props));
+                },
+                tag => {
+                    let len = r.u8()? as usize;
+                    cube.lists.push(CubeList::Unknown(tag, r.take(len)?.to_vec()));
+                },
+            }
+        }
+        return Ok(cube)
+    }
+
+    fn decode_cameras(r: &mut ByteReader) -> Result<Vec<Camera>, ParseError>{
+        let mut cameras : Vec<Camera> = Vec::new();
+        while !r.expect(0){
+            require(r, 1, "a camera id")?;
+            let id = r.u16()?;
+            require(r, 2, "a camera type")?;
+            let camera_type = r.u8()?;
+            let mut fields : Vec<(u8, CameraValue)> = Vec::new();
+            while !r.expect(0){
+                let tag = r.u8()?;
+                let layout = Camera::field_layout(camera_type, tag)
+                    .ok_or_else(||{ParseError::Invalid(format!("camera {} of type {} has an unknown field {}", id, camera_type, tag))})?;
+                let value = match layout{
+                    CameraValue::Floats(f) => CameraValue::Floats((0..f.len()).map(|_|{r.f32()}).collect::<Result<Vec<f32>, ParseError>>()?),
+                    CameraValue::Word(_) => CameraValue::Word(r.u32()?),
+                };
+                fields.push((tag, value));
+            }
+            cameras.push(Camera{id: id, camera_type: camera_type, fields: fields});
+        }
+        return Ok(cameras)
+    }
+
+    fn decode_lights(r: &mut ByteReader) -> Result<Vec<Light>, ParseError>{
+        require(r, 1, "the light count")?;
+        let count = r.u32()? as usize;
+        require(r, 2, "the lights")?;
+        let mut lights : Vec<Light> = Vec::new();
+        for _ in 0..count{
+            require(r, 3, "a light position")?;
+            let position = [r.f32()?, r.f32()?, r.f32()?];
+            require(r, 4, "a light radius")?;
+            let radius = [r.f32()?, r.f32()?];
+            require(r, 5, "a light color")?;
+            let color = [r.u32()?, r.u32()?, r.u32()?];
+            lights.push(Light{position: position, radius: radius, color: color});
+        }
+        return Ok(lights)
+    }
+
+    /// a `.lvl_setup.yaml`, or a raw `.lvl_setup.bin` from older extractions
+    pub fn read(path: &Path) -> LevelSetup{
+        if path.extension().unwrap() == "bin"{
+            return LevelSetup::from_bytes(&deps::read(path).unwrap())
+        }
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        assert_eq!(doc["type"].as_str(), Some("LevelSetup"), "{:?} is not a level setup", path);
+        if let Some(bin) = doc["bin"].as_str(){
+            return LevelSetup::from_bytes(&deps::read(path.parent().unwrap().join(bin)).unwrap())
+        }
+        let y = SetupYaml{path: path, actor_names: SetupYaml::actor_names(doc, path)};
+
+        let mut setup = LevelSetup::empty();
+        //sections are written in the order the yaml lists them
+        setup.order = doc.as_hash().unwrap().keys()
+            .filter_map(|k|{
+                match k.as_str(){
+                    Some("cubes") => Some(SECTION_CUBES),
+                    Some("cameras") => Some(SECTION_CAMERAS),
+                    Some("lights") => Some(SECTION_LIGHTING),
+                    _ => None,
+                }
+            })
+            .collect();
+        let cubes = &doc["cubes"];
+        if !cubes.is_badvalue(){
+            let min = y.ints3(&cubes["min"], "cube min").map(|i|{i as i32});
+            let max = y.ints3(&cubes["max"], "cube max").map(|i|{i as i32});
+            let mut grid = CubeGrid{min: min, max: max, cubes: LevelSetup::grid_positions(min, max).into_iter()
+                .map(|p|{Cube{position: p, lists: Vec::new()}})
+                .collect()};
+            for c in cubes["list"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+                //entries without a position are sorted into cubes by each object's position
+                if c["position"].is_badvalue(){
+                    for n in c["nodes"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+                        grid.insert_node(y.node(n, NodeProp{bytes: [0; NodeProp::SIZE]}));
+                    }
+                    for p in c["props"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+                        grid.insert_prop(y.prop(p, SpriteProp{bytes: [0; SpriteProp::SIZE]}));
+                    }
+                    continue;
+                }
+                let position = y.ints3(&c["position"], "cube position").map(|i|{i as i32});
+                let i = grid.index(position).unwrap_or_else(||{panic!("{:?}: cube {:?} is outside {:?} to {:?}", path, position, grid.min, grid.max)});
+                //lists are written in file order, so walk the keys in order
+                for (key, list) in c.as_hash().unwrap().iter(){
+                    match key.as_str(){
+                        Some("nodes") => grid.cubes[i].lists.push(CubeList::Nodes(list.as_vec().unwrap().iter().map(|n|{y.node(n, NodeProp{bytes: [0; NodeProp::SIZE]})}).collect())),
+                        Some("props") => grid.cubes[i].lists.push(CubeList::Props(list.as_vec().unwrap().iter().map(|p|{y.prop(p, SpriteProp{bytes: [0; SpriteProp::SIZE]})}).collect())),
+                        Some(tag) if tag.starts_with("tag_") => {
+                            let tag = u8::from_str_radix(&tag[4..], 16).unwrap_or_else(|_|{panic!("{:?}: {} is not a cube record tag", path, tag)});
+                            let bytes = yaml_to_bytes(list);
+                            assert!(bytes.len() <= 0xFF, "{:?}: cube record 0x{:02X} is longer than 255 bytes", path, tag);
+                            grid.cubes[i].lists.push(CubeList::Unknown(tag, bytes));
+                        },
+                        _ => {},
+                    }
+                }
+            }
+            for m in grid.misplaced(){
+                log::warning(None, &format!("{:?}: {}", path, m));
+            }
+            setup.cubes = Some(grid);
+        }
+        if let Some(cameras) = doc["cameras"].as_vec(){
+            setup.cameras = Some(cameras.iter().map(|c|{y.camera(c)}).collect());
+        }
+        if let Some(lights) = doc["lights"].as_vec(){
+            setup.lights = Some(lights.iter()
+                .map(|l|{
+                    let color = y.ints3(&l["color"], "light color");
+                    Light{
+                        position: y.floats(&l["position"], 3, "light position").try_into().unwrap(),
+                        radius: y.floats(&l["radius"], 2, "light radius").try_into().unwrap(),
+                        color: color.map(|c|{c as u32}),
+                    }
+                })
+                .collect());
+        }
+        setup.padding = doc["padding"].as_i64().unwrap_or(0) as usize;
+        setup.actor_names = y.actor_names;
+        return setup
+    }
+
+    /// uid of the level setup a patch applies to
+    pub fn patch_uid(path: &Path) -> usize{
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open patch")).unwrap()[0];
+        assert_eq!(doc["type"].as_str(), Some("LevelSetupPatch"), "{:?} is not a level setup patch", path);
+        doc["uid"].as_i64().unwrap_or_else(||{panic!("{:?}: patch has no uid", path)}) as usize
+    }
+
+    /// applies a `LevelSetupPatch` yaml. Indices refer to the numbering in the
+    /// extracted setup, before any of the patch's own changes
+    pub fn apply_patch(&mut self, path: &Path){
+        assert!(self.raw.is_none(), "{:?}: the setup it patches couldn't be decoded", path);
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open patch")).unwrap()[0];
+        let y = SetupYaml{path: path, actor_names: SetupYaml::actor_names(doc, path)};
+        let list = |key: &str|{doc[key].as_vec().cloned().unwrap_or_default()};
+        let has = |keys: &[&str]|{keys.iter().any(|k|{!doc[*k].is_badvalue()})};
+
+        //sections are only added when the patch touches them
+        if has(&["add_nodes", "add_props", "edit_nodes", "edit_props", "remove_nodes", "remove_props"]){
+            self.patch_cubes(doc, &y);
+        }
+        if has(&["cameras", "remove_cameras"]){
+            //cameras are matched by id
+            let cameras = self.cameras.get_or_insert_with(Vec::new);
+            for id in list("remove_cameras").iter().map(|e|{y.int(e, "camera id") as u16}){
+                assert!(cameras.iter().any(|c|{c.id == id}), "{:?}: there is no camera {}", path, id);
+                cameras.retain(|c|{c.id != id});
+            }
+            for c in list("cameras").iter().map(|c|{y.camera(c)}){
+                match cameras.iter_mut().find(|o|{o.id == c.id}){
+                    Some(o) => *o = c,
+                    None => cameras.push(c),
+                }
+            }
+        }
+        if !y.actor_names.is_empty(){
+            self.actor_names = y.actor_names;
+        }
+    }
+
+    fn patch_cubes(&mut self, doc: &Yaml, y: &SetupYaml){
+        let path = y.path;
+        let list = |key: &str|{doc[key].as_vec().cloned().unwrap_or_default()};
+        let grid = self.cubes.get_or_insert_with(||{CubeGrid{min: [0; 3], max: [-1; 3], cubes: Vec::new()}});
+        let node_slots = grid.slots(true);
+        let prop_slots = grid.slots(false);
+        let slot = |slots: &[(usize, usize, usize)], e: &Yaml, what: &str|{
+            let i = y.int(e, what) as usize;
+            *slots.get(i).unwrap_or_else(||{panic!("{:?}: there is no {} {}", path, what, i)})
+        };
+
+        //an index can only be edited or removed once
+        for (kind, keys) in [("node", ["remove_nodes", "edit_nodes"]), ("prop", ["remove_props", "edit_props"])]{
+            let mut seen : Vec<(i64, &str)> = Vec::new();
+            for key in keys{
+                for e in list(key).iter(){
+                    let i = y.int(if key.starts_with("edit") {&e["index"]} else {e}, kind);
+                    match seen.iter().find(|(j, _)|{*j == i}){
+                        Some((_, other)) if *other == key => panic!("{:?}: {} {} is listed twice in {}", path, kind, i, key),
+                        Some((_, other)) => panic!("{:?}: {} {} is in both {} and {}", path, kind, i, other, key),
+                        None => {},
+                    }
+                    seen.push((i, key));
+                }
+            }
+        }
+
+        //edited objects are taken out and put back in whatever cube they end up in
+        let mut removed_nodes : Vec<(usize, usize, usize)> = list("remove_nodes").iter().map(|e|{slot(&node_slots, e, "node")}).collect();
+        let mut removed_props : Vec<(usize, usize, usize)> = list("remove_props").iter().map(|e|{slot(&prop_slots, e, "prop")}).collect();
+        let mut new_nodes : Vec<NodeProp> = Vec::new();
+        let mut new_props : Vec<SpriteProp> = Vec::new();
+        for e in list("edit_nodes").iter(){
+            let (c, l, i) = slot(&node_slots, &e["index"], "node");
+            if let CubeList::Nodes(n) = &grid.cubes[c].lists[l]{
+                new_nodes.push(y.node(e, n[i].clone()));
+            }
+            removed_nodes.push((c, l, i));
+        }
+        for e in list("edit_props").iter(){
+            let (c, l, i) = slot(&prop_slots, &e["index"], "prop");
+            if let CubeList::Props(p) = &grid.cubes[c].lists[l]{
+                new_props.push(y.prop(e, p[i].clone()));
+            }
+            removed_props.push((c, l, i));
+        }
+        //last first, so earlier entries keep their place
+        removed_nodes.sort();
+        removed_props.sort();
+        for (c, l, i) in removed_nodes.into_iter().chain(removed_props.into_iter()).rev(){
+            match &mut grid.cubes[c].lists[l]{
+                CubeList::Nodes(n) => {n.remove(i);},
+                CubeList::Props(p) => {p.remove(i);},
+                CubeList::Unknown(..) => {},
+            }
+        }
+        new_nodes.extend(list("add_nodes").iter().map(|n|{y.node(n, NodeProp{bytes: [0; NodeProp::SIZE]})}));
+        new_props.extend(list("add_props").iter().map(|p|{y.prop(p, SpriteProp{bytes: [0; SpriteProp::SIZE]})}));
+        for n in new_nodes.into_iter(){
+            grid.insert_node(n);
+        }
+        for p in new_props.into_iter(){
+            grid.insert_prop(p);
+        }
+    }
+
+    /// problems the game would crash or soft lock on: lists too long for their
+    /// count byte, a grid that doesn't match its bounds, cameras sharing an id,
+    /// positions that aren't numbers, and node ids missing from the setup's
+    /// actor table when it has one. Warps are checked by `warps`, since their
+    /// maps are other entries
+    pub fn validate(&self) -> Vec<String>{
+        let actor_names = &self.actor_names;
+        let mut errors : Vec<String> = Vec::new();
+        if let Some(grid) = self.cubes.as_ref(){
+            let positions = LevelSetup::grid_positions(grid.min, grid.max);
+            if positions.len() != grid.cubes.len() || positions.iter().zip(grid.cubes.iter()).any(|(p, c)|{*p != c.position}){
+                errors.push(format!("cube list doesn't match the grid from {:?} to {:?}", grid.min, grid.max));
+            }
+            for cube in grid.cubes.iter(){
+                for list in cube.lists.iter(){
+                    match list{
+                        CubeList::Nodes(nodes) => {
+                            if nodes.len() > 0xFF{
+                                errors.push(format!("cube {:?} has {} nodes, the limit is 255", cube.position, nodes.len()));
+                            }
+                            if !actor_names.is_empty(){
+                                for n in nodes.iter().filter(|n|{actor_names.name(n.id() as usize).is_none()}){
+                                    errors.push(format!("node at {:?} in cube {:?} has unknown id 0x{:04X}", n.position(), cube.position, n.id()));
+                                }
+                            }
+                        },
+                        CubeList::Props(props) => if props.len() > 0xFF{
+                            errors.push(format!("cube {:?} has {} props, the limit is 255", cube.position, props.len()));
+                        },
+                        CubeList::Unknown(tag, bytes) => if bytes.len() > 0xFF{
+                            errors.push(format!("cube {:?} record 0x{:02X} is longer than 255 bytes", cube.position, tag));
+                        },
+                    }
+                }
+            }
+        }
+        if let Some(cameras) = self.cameras.as_ref(){
+            for (i, c) in cameras.iter().enumerate(){
+                if cameras[..i].iter().any(|o|{o.id == c.id}){
+                    errors.push(format!("camera id {} is used more than once", c.id));
+                }
+                for (tag, value) in c.fields.iter(){
+                    if let CameraValue::Floats(f) = value{
+                        if f.iter().any(|v|{!v.is_finite()}){
+                            errors.push(format!("camera {} {} is not a number", c.id, Camera::field_name(c.camera_type, *tag).unwrap()));
+                        }
+                    }
+                }
+            }
+        }
+        for (i, l) in self.lights.iter().flatten().enumerate(){
+            if l.position.iter().chain(l.radius.iter()).any(|v|{!v.is_finite()}){
+                errors.push(format!("light {} position or radius is not a number", i));
+            }
+        }
+        return errors
+    }
+
+    /// (node id, position, map) of every node the actor table says warps to a map
+    pub fn warps(&self) -> Vec<(u16, [i16; 3], usize)>{
+        let mut out : Vec<(u16, [i16; 3], usize)> = Vec::new();
+        for cube in self.cubes.iter().flat_map(|g|{g.cubes.iter()}){
+            for list in cube.lists.iter(){
+                if let CubeList::Nodes(nodes) = list{
+                    out.extend(nodes.iter().filter_map(|n|{self.actor_names.warp(n.id() as usize).map(|m|{(n.id(), n.position(), m)})}));
+                }
+            }
+        }
+        return out
+    }
+}
+
+impl LevelSetup{
+    fn cubes_to_bytes(grid: &CubeGrid, out: &mut Vec<u8>){
+        for c in grid.min.iter().chain(grid.max.iter()){
+            out.extend_from_slice(&c.to_be_bytes());
+        }
+        for cube in grid.cubes.iter(){
+            for list in cube.lists.iter(){
+                match list{
+                    CubeList::Nodes(nodes) => {
+                        assert!(nodes.len() <= 0xFF, "cube {:?} has {} nodes, the limit is 255", cube.position, nodes.len());
+                        out.extend_from_slice(&[CUBE_NODES, nodes.len() as u8]);
+                        if !nodes.is_empty(){
+                            out.push(CUBE_NODES + 1);
+                        }
+                        for n in nodes.iter(){
+                            out.extend_from_slice(&n.bytes);
+                        }
+                    },
+                    CubeList::Props(props) => {
+                        assert!(props.len() <= 0xFF, "cube {:?} has {} props, the limit is 255", cube.position, props.len());
+                        out.extend_from_slice(&[CUBE_PROPS, props.len() as u8]);
+                        if !props.is_empty(){
+                            out.push(CUBE_PROPS + 1);
+                        }
+                        for p in props.iter(){
+                            out.extend_from_slice(&p.bytes);
+                        }
+                    },
+                    CubeList::Unknown(tag, bytes) => {
+                        out.extend_from_slice(&[*tag, bytes.len() as u8]);
+                        out.extend_from_slice(bytes);
+                    },
+                }
+            }
+            out.push(CUBE_END);
+        }
+    }
+
+    fn cameras_to_bytes(cameras: &[Camera], out: &mut Vec<u8>){
+        for c in cameras.iter(){
+            out.push(1);
+            out.extend_from_slice(&c.id.to_be_bytes());
+            out.extend_from_slice(&[2, c.camera_type]);
+            for (tag, value) in c.fields.iter(){
+                out.push(*tag);
+                match value{
+                    CameraValue::Floats(f) => for v in f.iter(){ out.extend_from_slice(&v.to_bits().to_be_bytes()); },
+                    CameraValue::Word(w) => out.extend_from_slice(&w.to_be_bytes()),
+                }
+            }
+            out.push(0);
+        }
+        out.push(0);
+    }
+
+    fn lights_to_bytes(lights: &[Light], out: &mut Vec<u8>){
+        out.push(1);
+        out.extend_from_slice(&(lights.len() as u32).to_be_bytes());
+        out.push(2);
+        for l in lights.iter(){
+            out.push(3);
+            for p in l.position.iter(){
+                out.extend_from_slice(&p.to_bits().to_be_bytes());
+            }
+            out.push(4);
+            for r in l.radius.iter(){
+                out.extend_from_slice(&r.to_bits().to_be_bytes());
+            }
+            out.push(5);
+            for c in l.color.iter(){
+                out.extend_from_slice(&c.to_be_bytes());
+            }
+        }
+    }
+
+    /// sections in file order, ones added since reading go after the rest
+    fn section_order(&self) -> Vec<u8>{
+        let mut order = self.order.clone();
+        for (tag, present) in [(SECTION_CUBES, self.cubes.is_some()), (SECTION_CAMERAS, self.cameras.is_some()), (SECTION_LIGHTING, self.lights.is_some())]{
+            if present && !order.contains(&tag){
+                order.push(tag);
+            }
+        }
+        return order
+    }
+
+    fn write_cubes(grid: &CubeGrid, actor_names: &NameMap, setup_f: &mut dyn Write){
+        writeln!(setup_f, "cubes:").unwrap();
+        writeln!(setup_f, "  min: {:?}", grid.min).unwrap();
+        writeln!(setup_f, "  max: {:?}", grid.max).unwrap();
+        writeln!(setup_f, "  list:{}", if grid.cubes.iter().all(|c|{c.lists.is_empty()}) {" []"} else {""}).unwrap();
+        //objects are numbered in file order for patches to refer to
+        let mut node_i = 0;
+        let mut prop_i = 0;
+        for cube in grid.cubes.iter().filter(|c|{!c.lists.is_empty()}){
+            writeln!(setup_f, "    - position: {:?}", cube.position).unwrap();
+            for list in cube.lists.iter(){
+                match list{
+                    CubeList::Nodes(nodes) => {
+                        writeln!(setup_f, "      nodes:{}", if nodes.is_empty() {" []"} else {""}).unwrap();
+                        for n in nodes.iter(){
+                            let id = match actor_names.name(n.id() as usize){
+                                Some(name) => format!("{:?}", name),
+                                None => format!("0x{:04X}", n.id()),
+                            };
+                            writeln!(setup_f, "        - {{id: {}, position: {:?}, raw: {}}} # {}", id, n.position(), bytes_to_yaml(&n.bytes), node_i).unwrap();
+                            node_i += 1;
+                        }
+                    },
+                    CubeList::Props(props) => {
+                        writeln!(setup_f, "      props:{}", if props.is_empty() {" []"} else {""}).unwrap();
+                        for p in props.iter(){
+                            writeln!(setup_f, "        - {{id: 0x{:03X}, position: {:?}, raw: {}}} # {}", p.id(), p.position(), bytes_to_yaml(&p.bytes), prop_i).unwrap();
+                            prop_i += 1;
+                        }
+                    },
+                    CubeList::Unknown(tag, bytes) => {
+                        writeln!(setup_f, "      tag_{:02X}: {}", tag, bytes_to_yaml(bytes)).unwrap();
+                    },
+                }
+            }
+        }
+    }
+
+    fn write_cameras(cameras: &[Camera], setup_f: &mut dyn Write){
+        writeln!(setup_f, "cameras:{}", if cameras.is_empty() {" []"} else {""}).unwrap();
+        for c in cameras.iter(){
+            let fields : Vec<String> = c.fields.iter()
+                .map(|(tag, value)|{
+                    let name = Camera::field_name(c.camera_type, *tag).unwrap();
+                    match value{
+                        CameraValue::Floats(f) => format!("{}: [{}]", name, f.iter().map(|v|{format!("{:?}", v)}).collect::<Vec<String>>().join(", ")),
+                        CameraValue::Word(w) => format!("{}: 0x{:08X}", name, w),
+                    }
+                })
+                .collect();
+            writeln!(setup_f, "  - id: {}", c.id).unwrap();
+            writeln!(setup_f, "    type: {}", c.camera_type).unwrap();
+            for f in fields.iter(){
+                writeln!(setup_f, "    {}", f).unwrap();
+            }
+        }
+    }
+
+    fn write_lights(lights: &[Light], setup_f: &mut dyn Write){
+        writeln!(setup_f, "lights:{}", if lights.is_empty() {" []"} else {""}).unwrap();
+        for l in lights.iter(){
+            writeln!(setup_f, "  - {{position: [{:?}, {:?}, {:?}], radius: [{:?}, {:?}], color: {:?}}}",
+                l.position[0], l.position[1], l.position[2], l.radius[0], l.radius[1], l.color).unwrap();
+        }
+    }
+}
+
+impl LevelSetup{
+    /// writes every node, prop and camera position as a small octahedron, one
+    /// obj object each, for checking a layout in a 3d editor
+    pub fn write_preview_obj(&self, obj_path: &Path, actor_names: &NameMap){
+        let mut markers : Vec<(String, [f32; 3], f32)> = Vec::new();
+        if let Some(grid) = self.cubes.as_ref(){
+            for cube in grid.cubes.iter(){
+                for list in cube.lists.iter(){
+                    match list{
+                        CubeList::Nodes(nodes) => for n in nodes.iter(){
+                            let name = match actor_names.name(n.id() as usize){
+                                Some(name) => texture::c_identifier(name),
+                                None => format!("{:04X}", n.id()),
+                            };
+                            markers.push((format!("node_{}", name), n.position().map(|p|{p as f32}), 25.0));
+                        },
+                        CubeList::Props(props) => for p in props.iter(){
+                            markers.push((format!("prop_{:03X}", p.id()), p.position().map(|p|{p as f32}), 15.0));
+                        },
+                        CubeList::Unknown(..) => {},
+                    }
+                }
+            }
+        }
+        for c in self.cameras.iter().flatten(){
+            if let Some(position) = c.position(){
+                markers.push((format!("camera_{}", c.id), position, 50.0));
+            }
+        }
+
+        let mut obj_f = deps::create(obj_path).unwrap();
+        for (i, (name, p, size)) in markers.iter().enumerate(){
+            writeln!(obj_f, "o {}_{}", name, i).unwrap();
+            for axis in 0..3{
+                for sign in [1.0, -1.0]{
+                    let mut v = *p;
+                    v[axis] += sign*size;
+                    writeln!(obj_f, "v {} {} {}", v[0], v[1], v[2]).unwrap();
+                }
+            }
+            //vertices are +x -x +y -y +z -z
+            let base = 6*i + 1;
+            for (a, b, c) in [(0, 2, 4), (2, 1, 4), (1, 3, 4), (3, 0, 4), (2, 0, 5), (1, 2, 5), (3, 1, 5), (0, 3, 5)]{
+                writeln!(obj_f, "f {} {} {}", base + a, base + b, base + c).unwrap();
+            }
+        }
+    }
+}
+
+impl Asset for LevelSetup{
+    fn to_bytes(&self)->Vec<u8>{
+        if let Some(raw) = self.raw.as_ref(){
+            return raw.clone();
+        }
+        let mut out : Vec<u8> = Vec::new();
+        for tag in self.section_order(){
+            out.push(tag);
+            match tag{
+                SECTION_CUBES => LevelSetup::cubes_to_bytes(self.cubes.as_ref().unwrap(), &mut out),
+                SECTION_CAMERAS => LevelSetup::cameras_to_bytes(self.cameras.as_ref().unwrap(), &mut out),
+                SECTION_LIGHTING => LevelSetup::lights_to_bytes(self.lights.as_ref().unwrap(), &mut out),
+                _ => unreachable!(),
+            }
+        }
+        out.push(SECTION_END);
+        out.resize(out.len() + self.padding, 0);
+        return out
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::LevelSetup;
+    }
+
+    fn problems(&self) -> Vec<String>{
+        self.validate()
+    }
+
+    fn as_level_setup(&self) -> Option<&LevelSetup>{
+        Some(self)
+    }
+
+    fn write(&self, path: &Path, opts: &ExtractOptions){
+        let mut setup_f = deps::create(path).unwrap();
+        writeln!(setup_f, "type: LevelSetup").unwrap();
+        if let Some(raw) = self.raw.as_ref(){
+            //keep setups that don't decode cleanly as they are
+            let base_name = path.file_name().unwrap().to_str().unwrap().trim_end_matches(".lvl_setup.yaml");
+            let bin_name = format!("{}.lvl_setup.bin", base_name);
+            deps::write(path.parent().unwrap().join(&bin_name), raw).unwrap();
+            writeln!(setup_f, "bin: {:?}", bin_name).unwrap();
+            return;
+        }
+        if self.padding != 0{
+            writeln!(setup_f, "padding: {}", self.padding).unwrap();
+        }
+        //a copy of the table goes next to the setups so the folder rebuilds on its own
+        let actor_names = match opts.actor_names.as_ref(){
+            Some(table) => {
+                deps::copy(table, path.parent().unwrap().join("actor_names.yaml")).expect("could not copy actor name table");
+                writeln!(setup_f, "actor_names: \"actor_names.yaml\"").unwrap();
+                NameMap::read(table)
+            },
+            None => NameMap::new(),
+        };
+        for tag in self.section_order(){
+            match tag{
+                SECTION_CUBES => LevelSetup::write_cubes(self.cubes.as_ref().unwrap(), &actor_names, &mut setup_f),
+                SECTION_CAMERAS => LevelSetup::write_cameras(self.cameras.as_ref().unwrap(), &mut setup_f),
+                SECTION_LIGHTING => LevelSetup::write_lights(self.lights.as_ref().unwrap(), &mut setup_f),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn be(words: &[u32]) -> Vec<u8>{
+        words.iter().flat_map(|w|{w.to_be_bytes()}).collect()
+    }
+
+    //sections out of the usual order, every kind of cube list, and padding
+    #[test]
+    fn setup_round_trips(){
+        let mut bytes : Vec<u8> = vec![SECTION_LIGHTING, 1];
+        bytes.extend(be(&[1]));
+        bytes.push(2);
+        bytes.push(3);
+        bytes.extend(be(&[1.0f32.to_bits(), (-2.5f32).to_bits(), 300.0f32.to_bits()]));
+        bytes.push(4);
+        bytes.extend(be(&[100.0f32.to_bits(), 400.0f32.to_bits()]));
+        bytes.push(5);
+        bytes.extend(be(&[0xFF, 0x80, 0x00]));
+
+        bytes.push(SECTION_CUBES);
+        bytes.extend(be(&[(-1i32) as u32, 0, 0, 0, 0, 0]));
+        bytes.extend_from_slice(&[CUBE_NODES, 1, CUBE_NODES + 1]);
+        bytes.extend((0..NodeProp::SIZE as u8).collect::<Vec<u8>>());
+        bytes.extend_from_slice(&[CUBE_PROPS, 2, CUBE_PROPS + 1]);
+        bytes.extend((0..2*SpriteProp::SIZE as u8).collect::<Vec<u8>>());
+        bytes.extend_from_slice(&[0x06, 3, 0xAA, 0xBB, 0xCC, CUBE_END]);
+        bytes.extend_from_slice(&[CUBE_NODES, 0, CUBE_END]);
+
+        bytes.push(SECTION_CAMERAS);
+        bytes.extend_from_slice(&[1, 0x00, 0x07, 2, 3, 6]);
+        bytes.extend(be(&[250.0f32.to_bits(), 900.0f32.to_bits()]));
+        bytes.push(5);
+        bytes.extend(be(&[0x0000_0002]));
+        bytes.extend_from_slice(&[0, 1, 0x00, 0x08, 2, 4, 1]);
+        bytes.extend(be(&[1]));
+        bytes.extend_from_slice(&[0, 0]);
+
+        bytes.push(SECTION_END);
+        bytes.extend_from_slice(&[0; 5]);
+
+        let setup = LevelSetup::try_from_bytes(&bytes).unwrap();
+        assert!(!setup.is_raw());
+        let grid = setup.cubes.as_ref().unwrap();
+        assert_eq!(grid.cubes.len(), 2);
+        assert_eq!(grid.cubes[0].position, [-1, 0, 0]);
+        assert_eq!(grid.cubes[0].lists.len(), 3);
+        assert!(matches!(&grid.cubes[0].lists[2], CubeList::Unknown(0x06, b) if b == &vec![0xAA, 0xBB, 0xCC]));
+        let cameras = setup.cameras.as_ref().unwrap();
+        assert_eq!(cameras.iter().map(|c|{(c.id, c.camera_type, c.fields.len())}).collect::<Vec<_>>(), vec![(7, 3, 2), (8, 4, 1)]);
+        assert_eq!(setup.lights.as_ref().unwrap()[0].color, [0xFF, 0x80, 0x00]);
+        assert_eq!(setup.to_bytes(), bytes);
+    }
+
+    //maps with an empty grid, no cameras and no lights
+    #[test]
+    fn empty_sections_round_trip(){
+        let mut bytes : Vec<u8> = vec![SECTION_CUBES];
+        bytes.extend(be(&[0, 0, 0, (-1i32) as u32, (-1i32) as u32, (-1i32) as u32]));
+        bytes.extend_from_slice(&[SECTION_CAMERAS, 0, SECTION_LIGHTING, 1, 0, 0, 0, 0, 2, SECTION_END]);
+        let setup = LevelSetup::try_from_bytes(&bytes).unwrap();
+        assert!(setup.cubes.as_ref().unwrap().cubes.is_empty());
+        assert!(setup.cameras.as_ref().unwrap().is_empty());
+        assert!(setup.lights.as_ref().unwrap().is_empty());
+        assert_eq!(setup.to_bytes(), bytes);
+    }
+
+    //every problem is listed, not just the first
+    #[test]
+    fn validate_lists_every_problem(){
+        let mut setup = LevelSetup::empty();
+        let camera = |id: u16, x: f32|{Camera{id: id, camera_type: 2, fields: vec![(1, CameraValue::Floats(vec![x, 0.0, 0.0]))]}};
+        setup.cameras = Some(vec![camera(3, 0.0), camera(3, f32::NAN)]);
+        setup.lights = Some(vec![Light{position: [0.0, f32::INFINITY, 0.0], radius: [1.0, 2.0], color: [0; 3]}]);
+        setup.order = vec![SECTION_CAMERAS, SECTION_LIGHTING];
+        let problems = setup.validate();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(setup.warps().is_empty());
+    }
+
+    #[test]
+    fn setups_that_dont_decode_are_kept_as_bytes(){
+        let bytes = vec![SECTION_CAMERAS, 1, 0x00, 0x01, 2, 9, 7, 0, 0, SECTION_END];
+        assert!(LevelSetup::try_from_bytes(&bytes).is_err());
+        let setup = LevelSetup::from_bytes(&bytes);
+        assert!(setup.is_raw());
+        assert_eq!(setup.to_bytes(), bytes);
+    }
+}
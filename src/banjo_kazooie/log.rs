@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// what extract and construct report while they run. In text mode warnings go
+// to stderr and the rest is printed the way it always was; with
+// `--log-format json` every line on stdout is one json object instead, so CI
+// and wrapper scripts can read failures without scraping text:
+//
+//     {"level": "warning", "uid": 26, "message": "..."}
+//     {"level": "info", "event": "summary", "entries": 3000, ...}
+//     {"level": "error", "message": "..."}
+
+static JSON : AtomicBool = AtomicBool::new(false);
+static WARNINGS : AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_json(json: bool){
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool{
+    return JSON.load(Ordering::Relaxed)
+}
+
+/// how many warnings there have been so far
+pub fn warnings() -> usize{
+    return WARNINGS.load(Ordering::Relaxed)
+}
+
+/// `s` as a json string
+pub fn string(s: &str) -> String{
+    let mut out = String::from("\"");
+    for c in s.chars(){
+        match c{
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    return out + "\""
+}
+
+/// one json line from a level and (key, json value) fields
+fn line(level: &str, fields: &[(&str, String)]) -> String{
+    let fields : Vec<String> = fields.iter().map(|(k, v)|{format!("{}: {}", string(k), v)}).collect();
+    return format!("{{\"level\": {}, {}}}", string(level), fields.join(", "))
+}
+
+/// a warning, about entry `uid` if there is one
+pub fn warning(uid: Option<usize>, message: &str){
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    if !is_json(){
+        eprintln!("warning: {}", message);
+        return;
+    }
+    let mut fields : Vec<(&str, String)> = Vec::new();
+    if let Some(uid) = uid{
+        fields.push(("uid", uid.to_string()));
+    }
+    fields.push(("message", string(message)));
+    println!("{}", line("warning", &fields));
+}
+
+/// what stopped the run
+pub fn error(message: &str){
+    match is_json(){
+        true => println!("{}", line("error", &[("message", string(message))])),
+        false => eprintln!("error: {}", message),
+    }
+}
+
+/// `text` in text mode, or the event with its fields as json
+pub fn info(text: &str, event: &str, fields: &[(&str, String)]){
+    match is_json(){
+        true => {
+            let mut all = vec![("event", string(event))];
+            all.extend(fields.iter().cloned());
+            println!("{}", line("info", &all));
+        },
+        false => println!("{}", text),
+    }
+}
+
+/// an event only json output has, like a step of the run starting or ending
+pub fn progress(event: &str, fields: &[(&str, String)]){
+    if is_json(){
+        info("", event, fields);
+    }
+}
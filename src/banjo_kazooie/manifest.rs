@@ -0,0 +1,67 @@
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::YamlLoader;
+
+use super::{deps, AssetTable};
+
+// build manifest, a record of a constructed bin for CI to check a rebuild
+// against: the tool version, the bin's size and sha1, and each entry's
+// decompressed size and sha1.
+
+pub fn sha1(bytes: &[u8]) -> String{
+    return sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+pub fn write(path: &Path, in_bytes: &[u8]){
+    let table = AssetTable::new(in_bytes);
+    let mut out = deps::create(path).expect("could not write manifest");
+    writeln!(out, "tool: bk_asset_tool {}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(out, "size: 0x{:X}", in_bytes.len()).unwrap();
+    writeln!(out, "sha1: {}", sha1(in_bytes)).unwrap();
+    writeln!(out, "assets:").unwrap();
+    for e in table.entries(){
+        if let Some(bytes) = table.bytes(e.uid).unwrap_or_else(|e|{panic!("{}", e)}){
+            writeln!(out, "  - {{uid: 0x{:04X}, size: 0x{:X}, sha1: {}}}", e.uid, bytes.len(), sha1(&bytes)).unwrap();
+        }
+    }
+}
+
+/// differences between the bin and the manifest, empty if it matches
+pub fn verify(path: &Path, in_bytes: &[u8]) -> Vec<String>{
+    let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open manifest")).unwrap()[0];
+    let mut errors : Vec<String> = Vec::new();
+    let expect_size = doc["size"].as_i64().expect("manifest has no size") as usize;
+    if expect_size != in_bytes.len(){
+        errors.push(format!("bin is 0x{:X} bytes, expected 0x{:X}", in_bytes.len(), expect_size));
+    }
+    let expect_sha1 = doc["sha1"].as_str().expect("manifest has no sha1");
+    if expect_sha1 != sha1(in_bytes){
+        errors.push(format!("bin sha1 is {}, expected {}", sha1(in_bytes), expect_sha1));
+    }
+
+    let table = AssetTable::new(in_bytes);
+    let listed : Vec<usize> = doc["assets"].as_vec().expect("manifest has no assets").iter()
+        .map(|y|{
+            let uid = y["uid"].as_i64().unwrap() as usize;
+            let bytes = if uid < table.len() {table.bytes(uid)} else {Ok(None)};
+            match bytes{
+                Ok(None) => errors.push(format!("0x{:04X}: missing", uid)),
+                Err(e) => errors.push(format!("0x{:04X}: {}", uid, e)),
+                Ok(Some(b)) => {
+                    let expect_len = y["size"].as_i64().unwrap() as usize;
+                    if b.len() != expect_len{
+                        errors.push(format!("0x{:04X}: 0x{:X} bytes, expected 0x{:X}", uid, b.len(), expect_len));
+                    }
+                    else if y["sha1"].as_str().unwrap() != sha1(&b){
+                        errors.push(format!("0x{:04X}: sha1 is {}, expected {}", uid, sha1(&b), y["sha1"].as_str().unwrap()));
+                    }
+                },
+            }
+            uid
+        })
+        .collect();
+    for e in table.entries().filter(|e|{e.flags != 4 && !listed.contains(&e.uid)}){
+        errors.push(format!("0x{:04X}: not in the manifest", e.uid));
+    }
+    return errors
+}
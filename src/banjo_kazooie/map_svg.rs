@@ -0,0 +1,121 @@
+use std::io::{self, Write};
+
+use super::level_setup::{CubeList, LevelSetup};
+use super::names::NameMap;
+
+// a setup's objects seen from above as an svg, for documenting a level. x runs
+// right and z down, the way the game's map screens face. Nodes are dots colored
+// by what they spawn, props are grey squares and cameras are triangles, with a
+// legend of every kind and how many of it there are.
+
+/// pixels across the plot, the height follows the map's shape
+const WIDTH : f32 = 1024.0;
+const MARGIN : f32 = 24.0;
+const LEGEND_WIDTH : f32 = 260.0;
+const ROW : f32 = 18.0;
+
+enum Shape{
+    Dot,
+    Square,
+    Triangle,
+}
+
+struct Marker{
+    x : f32,
+    z : f32,
+    kind : usize, //index into the legend
+    title : String,
+}
+
+/// a color of its own for each id, spread around the hue circle
+fn color(id: u16) -> String{
+    let hue = (id as u32).wrapping_mul(137) % 360;
+    return format!("hsl({}, 70%, 45%)", hue)
+}
+
+fn escape(s: &str) -> String{
+    return s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn shape(out: &mut dyn Write, shape: &Shape, x: f32, y: f32, fill: &str, title: &str) -> io::Result<()>{
+    let title = if title.is_empty() {String::new()} else {format!("<title>{}</title>", escape(title))};
+    return match shape{
+        Shape::Dot => writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\">{}</circle>", x, y, fill, title),
+        Shape::Square => writeln!(out, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"6\" height=\"6\" fill=\"{}\">{}</rect>", x - 3.0, y - 3.0, fill, title),
+        Shape::Triangle => writeln!(out, "<polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{}\" stroke=\"black\">{}</polygon>", x, y - 6.0, x - 5.0, y + 4.0, x + 5.0, y + 4.0, fill, title),
+    }
+}
+
+pub fn write(out: &mut dyn Write, setup: &LevelSetup, actor_names: &NameMap) -> io::Result<()>{
+    //(name, shape, color, count) of each kind
+    let mut legend : Vec<(String, Shape, String, usize)> = Vec::new();
+    let mut node_kinds : Vec<u16> = Vec::new();
+    let mut markers : Vec<Marker> = Vec::new();
+    let mut add = |legend: &mut Vec<(String, Shape, String, usize)>, kind: usize, p: [f32; 3], title: String|{
+        legend[kind].3 += 1;
+        markers.push(Marker{x: p[0], z: p[2], kind: kind, title: title});
+    };
+
+    legend.push(("props".to_string(), Shape::Square, "#888888".to_string(), 0));
+    legend.push(("cameras".to_string(), Shape::Triangle, "#ffffff".to_string(), 0));
+    for cube in setup.cubes.iter().flat_map(|g|{g.cubes.iter()}){
+        for list in cube.lists.iter(){
+            match list{
+                CubeList::Nodes(nodes) => for n in nodes.iter(){
+                    let name = actor_names.name(n.id() as usize).map_or(format!("0x{:04X}", n.id()), |name|{format!("{} (0x{:04X})", name, n.id())});
+                    let kind = match node_kinds.iter().position(|k|{*k == n.id()}){
+                        Some(k) => k + 2,
+                        None => {
+                            node_kinds.push(n.id());
+                            legend.push((name.clone(), Shape::Dot, color(n.id()), 0));
+                            legend.len() - 1
+                        },
+                    };
+                    add(&mut legend, kind, n.position().map(|p|{p as f32}), name);
+                },
+                CubeList::Props(props) => for p in props.iter(){
+                    let title = format!("{} 0x{:03X}", if p.is_model() {"model"} else {"sprite"}, p.id());
+                    add(&mut legend, 0, p.position().map(|p|{p as f32}), title);
+                },
+                CubeList::Unknown(..) => {},
+            }
+        }
+    }
+    for c in setup.cameras.iter().flatten(){
+        if let Some(position) = c.position(){
+            add(&mut legend, 1, position, format!("camera {}", c.id));
+        }
+    }
+    //nodes by how many there are, then the props and cameras
+    let mut order : Vec<usize> = (2..legend.len()).collect();
+    order.sort_by_key(|k|{std::cmp::Reverse(legend[*k].3)});
+    order.extend([0, 1]);
+
+    let (min_x, max_x) = markers.iter().fold((f32::MAX, f32::MIN), |(a, b), m|{(a.min(m.x), b.max(m.x))});
+    let (min_z, max_z) = markers.iter().fold((f32::MAX, f32::MIN), |(a, b), m|{(a.min(m.z), b.max(m.z))});
+    let span = if markers.is_empty() {1.0} else {(max_x - min_x).max(max_z - min_z).max(1.0)};
+    let scale = (WIDTH - 2.0*MARGIN)/span;
+    let plot_h = if markers.is_empty() {0.0} else {(max_z - min_z)*scale} + 2.0*MARGIN;
+    let height = plot_h.max(2.0*MARGIN + ROW*order.len() as f32);
+    let to_px = |m: &Marker|{(MARGIN + (m.x - min_x)*scale, MARGIN + (m.z - min_z)*scale)};
+
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" font-family=\"sans-serif\" font-size=\"12\">", WIDTH + LEGEND_WIDTH, height)?;
+    writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"#f4f1e8\"/>")?;
+    writeln!(out, "<rect x=\"{:.0}\" y=\"{:.0}\" width=\"{:.0}\" height=\"{:.0}\" fill=\"none\" stroke=\"#ccc\"/>", MARGIN/2.0, MARGIN/2.0, WIDTH - MARGIN, plot_h - MARGIN)?;
+    //props under nodes under cameras
+    for kind_order in [0usize, 2, 1]{
+        for m in markers.iter().filter(|m|{m.kind.min(2) == kind_order}){
+            let (x, y) = to_px(m);
+            let (_, s, fill, _) = &legend[m.kind];
+            shape(out, s, x, y, fill, &format!("{} at {:.0}, {:.0}", m.title, m.x, m.z))?;
+        }
+    }
+    for (row, k) in order.iter().enumerate(){
+        let (name, s, fill, count) = &legend[*k];
+        let y = MARGIN + ROW*row as f32;
+        shape(out, s, WIDTH + 10.0, y, fill, "")?;
+        writeln!(out, "<text x=\"{:.0}\" y=\"{:.0}\">{} x{}</text>", WIDTH + 22.0, y + 4.0, escape(name), count)?;
+    }
+    writeln!(out, "</svg>")?;
+    return Ok(())
+}
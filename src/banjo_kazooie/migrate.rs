@@ -0,0 +1,140 @@
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::asset::{self, Asset, ExtractOptions};
+use super::deps;
+use super::level_setup::LevelSetup;
+
+// upgrades an extracted folder to the current assets.yaml schema in place. The
+// readers still take older layouts, this rewrites them into what extract
+// writes now so they can be edited the same way. Each step takes the folder
+// from one version to the next.
+//
+// 1: no schema_version. Sprite frames keep their whole 0x14 byte header, and
+//    level setups that decode may still be raw .lvl_setup.bin files
+// 2: frame anchors are x and y, setups that decode are .lvl_setup.yaml
+
+pub const SCHEMA_VERSION : u32 = 2;
+
+/// version of a parsed assets.yaml, 1 if it doesn't say
+pub fn schema_version(doc: &Yaml) -> u32{
+    return doc["schema_version"].as_i64().map_or(1, |v|{v as u32})
+}
+
+type Step = fn(&Path, &Yaml, &mut String, &mut Vec<String>);
+
+/// step from the version at its index + 1 to the next
+const STEPS : [Step; 1] = [v1_to_v2];
+
+/// upgrades `yaml_path` and the files it lists, returning what changed. Folders
+/// from a newer version of the tool are an error
+pub fn migrate(yaml_path: &Path) -> Result<Vec<String>, String>{
+    let mut text = deps::read_to_string(yaml_path).map_err(|e|{format!("{:?}: {}", yaml_path, e)})?;
+    let doc = YamlLoader::load_from_str(&text).map_err(|e|{format!("{:?}: {}", yaml_path, e)})?.remove(0);
+    let version = schema_version(&doc);
+    if version > SCHEMA_VERSION{
+        return Err(format!("{:?} is schema version {}, this bk_asset_tool only knows up to {}", yaml_path, version, SCHEMA_VERSION));
+    }
+    let mut report : Vec<String> = Vec::new();
+    if version == SCHEMA_VERSION{
+        return Ok(report);
+    }
+    let folder = yaml_path.parent().unwrap();
+    for step in STEPS[version as usize - 1..].iter(){
+        step(folder, &doc, &mut text, &mut report);
+    }
+
+    //the version goes first, replacing any older one
+    let lines : Vec<&str> = text.lines().filter(|l|{!l.starts_with("schema_version:")}).collect();
+    let text = format!("schema_version: {}\n{}\n", SCHEMA_VERSION, lines.join("\n"));
+    deps::write(yaml_path, text).map_err(|e|{format!("{:?}: {}", yaml_path, e)})?;
+    report.push(format!("{:?}: schema version {} -> {}", yaml_path, version, SCHEMA_VERSION));
+    return Ok(report)
+}
+
+fn v1_to_v2(folder: &Path, doc: &Yaml, assets_yaml: &mut String, report: &mut Vec<String>){
+    for y in doc["files"].as_vec().unwrap().iter(){
+        let uid = y["uid"].as_i64().unwrap();
+        let relative_path = y["relative_path"].as_str().unwrap();
+        let path = folder.join(relative_path);
+        match y["type"].as_str().unwrap(){
+            t if t.starts_with("Sprite_") => {
+                let text = deps::read_to_string(&path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+                match split_frame_anchors(&text){
+                    Ok(Some((new_text, count))) => {
+                        deps::write(&path, new_text).unwrap();
+                        report.push(format!("0x{:04X}: {}, {} frame anchors split out of their headers", uid, relative_path, count));
+                    },
+                    Ok(None) => (),
+                    Err(e) => report.push(format!("0x{:04X}: {}, {}, left as it is", uid, relative_path, e)),
+                }
+            },
+            "LevelSetup" if relative_path.ends_with(".bin") => {
+                let setup = LevelSetup::read(&path);
+                if setup.is_raw(){
+                    continue; //still doesn't decode, so it stays raw
+                }
+                let new_relative = format!("{}.yaml", relative_path.trim_end_matches(".bin"));
+                setup.write(&folder.join(&new_relative), &ExtractOptions::new());
+                let quoted = format!("{:?}", relative_path);
+                *assets_yaml = match assets_yaml.contains(&quoted){
+                    true => assets_yaml.replace(&quoted, &format!("{:?}", new_relative)),
+                    false => assets_yaml.replace(relative_path, &new_relative),
+                };
+                report.push(format!("0x{:04X}: {} decoded to {}, the bin is no longer used", uid, relative_path, new_relative));
+            },
+            _ => (),
+        }
+    }
+}
+
+/// sprite descriptor text with every 0x14 byte frame header written as x, y
+/// and the rest of the header, and how many frames changed. None if none did
+fn split_frame_anchors(text: &str) -> Result<Option<(String, usize)>, String>{
+    let doc = &YamlLoader::load_from_str(text).map_err(|e|{e.to_string()})?[0];
+    let frames = match doc["frames"].as_vec(){
+        Some(frames) => frames,
+        None => return Ok(None),
+    };
+    let whole : Vec<bool> = frames.iter()
+        .map(|f|{f["header"].as_vec().map_or(false, |h|{h.len() == 0x14}) && f["x"].is_badvalue() && f["y"].is_badvalue()})
+        .collect();
+    if !whole.contains(&true){
+        return Ok(None)
+    }
+
+    //frames start at the list items after `frames:`, their header lines follow
+    let mut out : Vec<String> = Vec::new();
+    let mut frame : Option<usize> = None;
+    let mut item_indent : Option<usize> = None;
+    let mut in_frames = false;
+    let mut count = 0;
+    for line in text.lines(){
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if indent == 0 && !trimmed.is_empty() && !trimmed.starts_with('-') && !trimmed.starts_with('#'){
+            in_frames = line.starts_with("frames:");
+        }
+        else if in_frames && trimmed.starts_with("- ") && item_indent.map_or(true, |i|{i == indent}){
+            item_indent = Some(indent);
+            frame = Some(frame.map_or(0, |f|{f + 1}));
+        }
+        let col = line.find("header:");
+        match (in_frames, frame, col){
+            (true, Some(i), Some(col)) if whole.get(i) == Some(&true) => {
+                let header = asset::yaml_to_bytes(&frames[i]["header"]);
+                let x = i16::from_be_bytes([header[0], header[1]]);
+                let y = i16::from_be_bytes([header[2], header[3]]);
+                out.push(format!("{}header: {}", &line[..col], asset::bytes_to_yaml(&header[0xA..])));
+                out.push(format!("{}x: {}", " ".repeat(col), x));
+                out.push(format!("{}y: {}", " ".repeat(col), y));
+                count += 1;
+            },
+            _ => out.push(line.to_string()),
+        }
+    }
+    if count != whole.iter().filter(|w|{**w}).count(){
+        return Err("frames are laid out differently than extract writes them".to_string())
+    }
+    return Ok(Some((out.join("\n") + "\n", count)))
+}
@@ -1,12 +1,59 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
-use std::fs::{self, DirBuilder};
 use std::io::{Write, Read};
+use std::panic;
 use std::path::Path;
 use yaml_rust::{YamlLoader,Yaml};
 
 use rarezip::bk;
 
+use error::{ByteReader, ParseError};
+
+pub mod address_map;
+pub mod annotations;
+pub mod archive;
+pub mod animation;
+pub mod aseprite;
 pub mod asset;
+#[cfg(feature = "sqlite")]
+pub mod asset_db;
+pub mod bps;
+pub mod byte_search;
+pub mod cache;
+pub mod demo;
+pub mod dep_graph;
+pub mod deps;
+pub mod error;
+pub mod explain;
+#[cfg(feature = "fs")]
+pub mod fixtures;
+pub mod font;
+pub mod geo;
+pub mod gfx;
+pub mod gltf;
+pub mod json;
+pub mod level_setup;
+pub mod log;
+#[cfg(feature = "fs")]
+pub mod manifest;
+pub mod map_svg;
+pub mod migrate;
+pub mod model;
+pub mod names;
+pub mod obj;
+pub mod quiz;
+pub mod roundtrip;
+pub mod scene;
+pub mod segment_map;
+pub mod sequence;
+pub mod splat;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod text_diff;
+pub mod texture;
+pub mod type_rules;
+pub mod version_diff;
+pub mod words;
 
 #[derive(Clone, Copy)]
 struct AssetMeta{
@@ -23,6 +70,27 @@ impl AssetMeta {
         return AssetMeta{offset: offset as usize, c_flag: c_flag != 0, t_flag: t_flag}
     }
 
+    /// segment number + 1 of each entry, a new segment starts where bit 1 of
+    /// the type flag changes, or where the segment map says. Empty entries are 0
+    pub fn segments(metas: &[AssetMeta]) -> Vec<usize>{
+        if let Some(segments) = segment_map::segments(&metas.iter().map(|m|{m.t_flag}).collect::<Vec<u16>>()){
+            return segments
+        }
+        let mut segment : usize = 0;
+        let mut prev_t : u16 = 0x3;
+        return metas.iter().map(|m|{
+            if m.t_flag == 4{
+                return 0
+            }
+            if m.t_flag != 2 && (prev_t & 2) != (m.t_flag & 2){
+                segment += 1;
+                prev_t = m.t_flag;
+            }
+            return segment
+        })
+        .collect()
+    }
+
     pub fn to_bytes(&self) -> Vec<u8>{
         let mut out : Vec<u8> = (self.offset as u32).to_be_bytes().to_vec();
         out.push(0x00);
@@ -37,213 +105,682 @@ struct AssetEntry{
     pub uid  : usize,
     pub seg : usize,
     pub meta : AssetMeta,
-    pub data : Option<Box<dyn asset::Asset>>
+    pub data : Option<Box<dyn asset::Asset>>,
+    pub compression : Option<asset::Compression>, //overrides the folder's
+    pub type_override : Option<String>, //decoded as this instead of the detected type
+    pub stored : Option<(bool, Vec<u8>)>, //compressed and bytes of the entry in a base bin
+    pub verbatim : bool, //data is the entry as it's stored, written without compressing it
+    pub notes : annotations::Notes, //kept from an earlier extraction
 }
 
 impl AssetEntry{
     pub fn new(uid:usize)->AssetEntry{
-        AssetEntry{uid: uid, seg: 0, meta: AssetMeta{offset:0, c_flag:false, t_flag:4}, data: None}
+        AssetEntry{uid: uid, seg: 0, meta: AssetMeta{offset:0, c_flag:false, t_flag:4}, data: None, compression: None, type_override: None, stored: None, verbatim: false, notes: annotations::Notes::default()}
     }
 
-    pub fn from_yaml(yaml:&Yaml)->AssetEntry{
+    /// the base bin's bytes, if they're still what this entry holds. An entry
+    /// that isn't in assets.yaml is always the base's. `raw` is its data's
+    /// bytes: the size in a compressed entry's header rules most edits out
+    /// without decompressing it, the rest are compared by sha1
+    fn kept_bytes(&self, raw: Option<&[u8]>) -> Option<&[u8]>{
+        let (compressed, bytes) = self.stored.as_ref()?;
+        if *compressed != self.meta.c_flag{
+            return None
+        }
+        let raw = match (&self.data, raw){
+            (None, _) => return Some(bytes),
+            (Some(_), Some(raw)) => raw,
+            (Some(_), None) => return None,
+        };
+        let base_len = if *compressed {cache::unzipped_len(bytes)} else {Some(bytes.len())};
+        if base_len != Some(raw.len()){
+            return None
+        }
+        let base_digest = match compressed{
+            true => sha1_smol::Sha1::from(cache::unzip(self.uid, bytes).ok()?).digest().bytes(),
+            false => sha1_smol::Sha1::from(bytes).digest().bytes(),
+        };
+        return (base_digest == sha1_smol::Sha1::from(raw).digest().bytes()).then(||{bytes.as_slice()})
+    }
+
+    /// `compressed` and `flags` can be left out to copy them from `prev`
+    pub fn from_yaml(yaml:&Yaml, prev: Option<&AssetMeta>)->AssetEntry{
         assert!(yaml["uid"].as_i64().is_some(),"could not read uid as interger");
         let uid = yaml["uid"].as_i64().unwrap() as usize;
-        let c_type : bool = yaml["compressed"].as_bool().unwrap();
-        let t_type : u16 = yaml["flags"].as_i64().unwrap() as u16;
+        let c_type : bool = yaml["compressed"].as_bool().or(prev.map(|p|{p.c_flag})).unwrap_or_else(|| panic!("uid 0x{:04X} has no compressed", uid));
+        let t_type : u16 = yaml["flags"].as_i64().map(|f|{f as u16}).or(prev.map(|p|{p.t_flag})).unwrap_or_else(|| panic!("uid 0x{:04X} has no flags", uid));
         let meta = AssetMeta{offset: 0, c_flag: c_type , t_flag: t_type };
-        AssetEntry{meta: meta, ..AssetEntry::new(uid)}
+        //0 when left out, filled in from the flags on read
+        let seg = yaml["segment"].as_i64().unwrap_or(0) as usize;
+        let compression = yaml["compression"].as_str().map(|c|{asset::Compression::from_str(c)});
+        let type_override = yaml["type_override"].as_str().map(|t|{asset::check_type_name(t).to_string()});
+        let verbatim = yaml["verbatim"].as_bool().unwrap_or(false);
+        AssetEntry{seg: seg, meta: meta, compression: compression, type_override: type_override, verbatim: verbatim, ..AssetEntry::new(uid)}
+    }
+}
+
+/// one asset table entry, read without decompressing it
+pub struct TableEntry{
+    pub uid : usize,
+    pub segment : usize,
+    pub compressed : bool,
+    pub flags : u16,
+    pub offset : usize, //from the start of the data, after the table
+    pub size : usize, //bytes in the bin, compressed if it's compressed
+}
+
+/// the asset table of a bin. Nothing is decompressed or decoded until an entry
+/// is asked for, for quick queries on the whole bin
+pub struct AssetTable<'a>{
+    data_bytes : &'a [u8],
+    metas : Vec<AssetMeta>, //includes the end of table entry
+    segments : Vec<usize>,
+}
+
+impl<'a> AssetTable<'a>{
+    pub fn new(in_bytes: &'a [u8]) -> AssetTable<'a>{
+        return AssetTable::try_new(in_bytes).unwrap_or_else(|e| panic!("bad asset table: {}", e))
+    }
+
+    /// checks the table fits and every entry's data is inside the bin
+    pub fn try_new(in_bytes: &'a [u8]) -> Result<AssetTable<'a>, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let asset_slot_cnt = r.u32()? as usize;
+        r.take(4)?;
+        let table_bytes = r.take(asset_slot_cnt.checked_mul(8).ok_or(ParseError::Invalid("asset table is too long".to_string()))?)?;
+        let data_bytes = &in_bytes[r.offset..];
+        if asset_slot_cnt == 0{
+            return Err(ParseError::Invalid("asset table has no end entry".to_string()));
+        }
+        let metas : Vec<AssetMeta> = table_bytes.chunks_exact(8).map(|chunk| {AssetMeta::from_bytes(chunk)}).collect();
+        //empty entries too, their size is still the distance to the next one
+        for (uid, w) in metas.windows(2).enumerate(){
+            if w[0].offset > w[1].offset || w[1].offset > data_bytes.len(){
+                return Err(ParseError::BadEntry{uid: uid, start: w[0].offset, end: w[1].offset});
+            }
+        }
+        let segments = AssetMeta::segments(&metas);
+        return Ok(AssetTable{data_bytes: data_bytes, metas: metas, segments: segments})
+    }
+
+    /// number of entries, not counting the end of the table
+    pub fn len(&self) -> usize{
+        return self.metas.len().saturating_sub(1)
+    }
+
+    /// where the data starts in the bin, the size of the table
+    pub fn data_start(&self) -> usize{
+        return 8 + 8*self.metas.len()
+    }
+
+    /// the end of the last entry's data, from the start of the data
+    pub fn data_end(&self) -> usize{
+        return self.metas.last().map_or(0, |m|{m.offset})
+    }
+
+    pub fn entry(&self, uid: usize) -> Result<TableEntry, ParseError>{
+        assert!(uid < self.len(), "uid 0x{:X} is outside of the asset table", uid);
+        let this = &self.metas[uid];
+        let end = self.metas[uid + 1].offset;
+        return Ok(TableEntry{
+            uid: uid,
+            segment: self.segments[uid],
+            compressed: this.c_flag,
+            flags: this.t_flag,
+            offset: this.offset,
+            size: end.checked_sub(this.offset).ok_or(ParseError::BadEntry{uid: uid, start: this.offset, end: end})?,
+        })
+    }
+
+    /// every entry, `try_new` already checked none of them runs backwards
+    pub fn entries(&self) -> impl Iterator<Item = TableEntry> + '_{
+        return (0..self.len()).filter_map(move |uid|{self.entry(uid).ok()})
+    }
+
+    /// decompressed bytes, None for empty entries
+    pub fn bytes(&self, uid: usize) -> Result<Option<Vec<u8>>, ParseError>{
+        assert!(uid < self.len(), "uid 0x{:X} is outside of the asset table", uid);
+        let this = &self.metas[uid];
+        if this.t_flag == 4 {
+            return Ok(None);
+        }
+        let comp_bin = self.stored_bytes(uid)?;
+        return Ok(Some(match this.c_flag {
+            true  => cache::unzip(uid, comp_bin)?,
+            false => comp_bin.to_vec(),
+        }))
+    }
+
+    /// the entry's bytes as they are in the bin, compressed if it's compressed
+    pub fn stored_bytes(&self, uid: usize) -> Result<&'a [u8], ParseError>{
+        assert!(uid < self.len(), "uid 0x{:X} is outside of the asset table", uid);
+        let (start, end) = (self.metas[uid].offset, self.metas[uid + 1].offset);
+        return self.data_bytes.get(start..end).ok_or(ParseError::BadEntry{uid: uid, start: start, end: end})
+    }
+
+    /// decoded the same way as a full extract
+    pub fn asset(&self, uid: usize) -> Option<Box<dyn asset::Asset>>{
+        return self.bytes(uid).unwrap_or_else(|e|{panic!("{}", e)}).map(|b|{asset::from_seg_indx_and_bytes(self.segments[uid], uid, &b)})
+    }
+
+    /// one line per entry that differs in `new`, by uid
+    pub fn changes(&self, new: &AssetTable) -> Vec<String>{
+        let mut out : Vec<String> = Vec::new();
+        for uid in 0..self.len().max(new.len()){
+            let old_e = if uid < self.len() && self.metas[uid].t_flag != 4 {self.entry(uid).ok()} else {None};
+            let new_e = if uid < new.len() && new.metas[uid].t_flag != 4 {new.entry(uid).ok()} else {None};
+            let (o, n) = match (old_e, new_e){
+                (None, None) => continue,
+                (None, Some(n)) => {out.push(format!("0x{:04X}: new, 0x{:X} bytes", uid, n.size)); continue},
+                (Some(_), None) => {out.push(format!("0x{:04X}: removed", uid)); continue},
+                (Some(o), Some(n)) => (o, n),
+            };
+            let mut diffs : Vec<String> = Vec::new();
+            if o.flags != n.flags{
+                diffs.push(format!("flags 0x{:04X} -> 0x{:04X}", o.flags, n.flags));
+            }
+            if o.compressed != n.compressed{
+                diffs.push(format!("compressed {} -> {}", o.compressed, n.compressed));
+            }
+            if o.size != n.size{
+                diffs.push(format!("size 0x{:X} -> 0x{:X}", o.size, n.size));
+            }
+            let same = matches!((self.stored_bytes(uid), new.stored_bytes(uid)), (Ok(a), Ok(b)) if a == b) || matches!((self.bytes(uid), new.bytes(uid)), (Ok(a), Ok(b)) if a == b);
+            if !same{
+                diffs.push("contents changed".to_string());
+            }
+            if !diffs.is_empty(){
+                out.push(format!("0x{:04X}: {}", uid, diffs.join(", ")));
+            }
+        }
+        return out
+    }
+
+    /// `asset`, with bytes its type can't read as an error for that uid
+    pub fn try_asset(&self, uid: usize) -> Result<Option<Box<dyn asset::Asset>>, ParseError>{
+        return self.bytes(uid)?.map(|b|{asset::try_from_seg_indx_and_bytes(self.segments[uid], &b)}).transpose()
+            .map_err(|e|{ParseError::Decode{uid: uid, message: e.to_string()}})
+    }
+
+    /// `try_asset`, decoded as the named type instead of the detected one
+    pub fn try_asset_as(&self, uid: usize, type_name: &str) -> Result<Option<Box<dyn asset::Asset>>, ParseError>{
+        return self.bytes(uid)?.map(|b|{asset::try_from_type_name_and_bytes(type_name, &b)}).transpose()
+            .map_err(|e|{ParseError::Decode{uid: uid, message: e.to_string()}})
+    }
+
+    /// every non-empty entry, decoded one at a time as the iterator advances
+    pub fn assets(&self) -> impl Iterator<Item = (usize, Box<dyn asset::Asset>)> + '_{
+        return (0..self.len()).filter_map(move |uid|{self.asset(uid).map(|a|{(uid, a)})})
+    }
+}
+
+/// how construct lays out the data, each entry's bytes and the whole bin are
+/// padded with `fill` to a multiple of their alignment
+#[derive(Clone, Copy)]
+pub struct Alignment{
+    pub asset : usize,
+    pub bin : usize,
+    pub fill : u8,
+}
+
+impl Alignment{
+    pub fn new() -> Alignment{
+        return Alignment{asset: 1, bin: 0x10, fill: 0}
+    }
+
+    /// the largest power of 2, up to 0x10, all of `sizes` are a multiple of
+    fn detect(sizes: &[usize]) -> usize{
+        let mut align = 0x10;
+        while align > 1 && sizes.iter().any(|s|{s % align != 0}){
+            align /= 2;
+        }
+        return align
+    }
+
+    /// what the packer of `in_bytes` did, so construct can do the same
+    fn of(table: &AssetTable, in_bytes: &[u8]) -> Alignment{
+        let data_offsets : Vec<usize> = table.metas.iter().map(|m|{m.offset}).collect();
+        let data_end = table.metas.last().unwrap().offset;
+        return Alignment{
+            asset: Alignment::detect(&data_offsets),
+            bin: Alignment::detect(&[in_bytes.len()]),
+            fill: if table.data_bytes.len() > data_end {*table.data_bytes.last().unwrap()} else {0},
+        }
+    }
+
+    /// fill bytes after `len` bytes to reach a multiple of `align`
+    fn padding(&self, len: usize, align: usize) -> usize{
+        return (align - len % align) % align
     }
 }
 
 pub struct AssetFolder{
-    assets : Vec<AssetEntry>
+    assets : Vec<AssetEntry>,
+    rom_size : Option<usize>, //space the bin has in the rom
+    alignment : Alignment,
+    compression : asset::Compression,
+    threads : usize, //compressing construct's entries
 }
 
 impl AssetFolder{
     pub fn new() -> AssetFolder{
-        return AssetFolder{assets: Vec::new()}
+        return AssetFolder{assets: Vec::new(), rom_size: None, alignment: Alignment::new(), compression: asset::Compression::Match, threads: 1}
     }
 
     pub fn from_bytes(in_bytes: &[u8]) -> AssetFolder{
-        let asset_slot_cnt : usize = u32::from_be_bytes(in_bytes[..4].try_into().unwrap()) as usize;
-        let (table_bytes, data_bytes) = in_bytes[8..].split_at(8*asset_slot_cnt);
-
-        let meta_info : Vec<AssetMeta> = table_bytes.chunks_exact(8).map(|chunk| {AssetMeta::from_bytes(chunk)}).collect();
-        let mut segment : usize = 0; //segment number + 1
-        let mut prev_t : u16 = 0x3; //used for segment_detection
-        let asset_list : Vec<AssetEntry> = meta_info.windows(2).enumerate().map(|(i, window)|{
-            let this = &window[0];
-            let next = &window[1];
-
-            if this.t_flag == 4{ //empty entry
-                return AssetEntry{uid : i, seg : 0, meta : this.clone(), data : None};
-            }
-            else if (this.t_flag != 2)
-                    && (prev_t & 2) != (this.t_flag & 2)
-            {
-                segment += 1;
-                prev_t = this.t_flag;
+        return AssetFolder::try_from_bytes(in_bytes).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// a bad table or any entry failing to decode is an error instead of a panic
+    pub fn try_from_bytes(in_bytes: &[u8]) -> Result<AssetFolder, ParseError>{
+        return AssetFolder::from_bytes_with(in_bytes, &[], false).map(|(af, _)|{af})
+    }
+
+    /// like `try_from_bytes`, but an entry that fails to decode is kept as a
+    /// Binary of its decompressed bytes, or of its stored bytes, written back as
+    /// they are, if it doesn't decompress. Their errors come back with the folder
+    pub fn from_bytes_permissive(in_bytes: &[u8]) -> Result<(AssetFolder, Vec<ParseError>), ParseError>{
+        return AssetFolder::from_bytes_with(in_bytes, &[], true)
+    }
+
+    /// `try_from_bytes` or, if `permissive`, `from_bytes_permissive`, with the
+    /// uids in `overrides` decoded as the type named with them
+    pub fn from_bytes_with(in_bytes: &[u8], overrides: &[(usize, String)], permissive: bool) -> Result<(AssetFolder, Vec<ParseError>), ParseError>{
+        let table = AssetTable::try_new(in_bytes)?;
+        let mut errors : Vec<ParseError> = Vec::new();
+        let asset_list : Vec<AssetEntry> = (0..table.len()).map(|i|{
+            let meta = table.metas[i];
+            let type_override = overrides.iter().find(|(uid, _)|{*uid == i}).map(|(_, t)|{t.clone()});
+            let decoded = match type_override.as_ref(){
+                Some(t) => table.try_asset_as(i, t),
+                None => table.try_asset(i),
+            };
+            let mut verbatim = false;
+            let data = match decoded{
+                Ok(data) => data,
+                Err(ParseError::Decode{uid, message}) if permissive => {
+                    let bytes = match table.bytes(i){
+                        Ok(b) => {
+                            errors.push(ParseError::Decode{uid: uid, message: message});
+                            b.unwrap()
+                        },
+                        Err(_) => {
+                            errors.push(ParseError::Decode{uid: uid, message: format!("{}, so its stored bytes are kept as they are", message)});
+                            verbatim = true;
+                            table.stored_bytes(i)?.to_vec()
+                        },
+                    };
+                    Some(Box::new(asset::Binary::from_bytes(&bytes)) as Box<dyn asset::Asset>)
+                },
+                Err(e) => return Err(e),
+            };
+            Ok(AssetEntry{uid : i, seg : table.segments[i], meta : meta, data : data, compression : None, type_override : type_override, stored : None, verbatim : verbatim, notes : annotations::Notes::default()})
+        }).collect::<Result<Vec<AssetEntry>, ParseError>>()?;
+
+        return Ok((AssetFolder{assets: asset_list, rom_size: Some(in_bytes.len()), alignment: Alignment::of(&table, in_bytes), compression: asset::Compression::Match, threads: 1}, errors));
+    }
+
+    /// every entry of `in_bytes` as it's stored, for `read` to replace only the
+    /// entries its assets.yaml lists. Entries that end up with the same bytes
+    /// keep the base's compressed bytes instead of being compressed again
+    pub fn from_base(in_bytes: &[u8]) -> AssetFolder{
+        let table = AssetTable::new(in_bytes);
+        let assets : Vec<AssetEntry> = (0..table.len())
+            .map(|i|{
+                let meta = table.metas[i];
+                let stored = if meta.t_flag == 4 {None} else {Some((meta.c_flag, table.stored_bytes(i).unwrap_or_else(|e|{panic!("{}", e)}).to_vec()))};
+                AssetEntry{seg: table.segments[i], meta: meta, stored: stored, ..AssetEntry::new(i)}
+            })
+            .collect();
+        return AssetFolder{assets: assets, rom_size: Some(in_bytes.len()), alignment: Alignment::of(&table, in_bytes), compression: asset::Compression::Match, threads: 1}
+    }
+
+    /// the notes and tags of an earlier extraction's `assets.yaml`, and of its
+    /// files, to be written again with the entries they were on
+    pub fn keep_notes(&mut self, yaml_path: &Path, names: &names::NameMap){
+        for (uid, notes) in annotations::read(yaml_path, names).into_iter(){
+            match self.assets.get_mut(uid).filter(|a|{a.data.is_some()}){
+                Some(a) => a.notes = notes,
+                None => log::warning(Some(uid), &format!("uid 0x{:04X} is empty now, its notes and tags in {:?} are dropped", uid, yaml_path)),
             }
+        }
+    }
+
+    /// decompressed bytes of one table entry without parsing the rest of the table
+    pub fn asset_bytes(in_bytes: &[u8], uid: usize) -> Option<Vec<u8>>{
+        return AssetTable::new(in_bytes).bytes(uid).unwrap_or_else(|e|{panic!("{}", e)})
+    }
+
+    /// number of entries, not counting the end of the table
+    pub fn len(&self) -> usize{
+        return self.assets.len()
+    }
+
+    /// decoded asset, None for empty entries
+    pub fn asset(&self, uid: usize) -> Option<&dyn asset::Asset>{
+        return self.assets.get(uid).and_then(|a|{a.data.as_deref()})
+    }
+
+    /// (compressed, flags, segment) of an entry
+    pub fn table_info(&self, uid: usize) -> Option<(bool, u16, usize)>{
+        return self.assets.get(uid).map(|a|{(a.meta.c_flag, a.meta.t_flag, a.seg)})
+    }
 
-            //decompress
-            let comp_bin = &data_bytes[this.offset.. next.offset];
-            let decomp_bin = match this.c_flag {
-                true  => bk::unzip(comp_bin),
-                false => comp_bin.to_vec(),
+    /// swaps a non-empty entry's contents for raw decompressed bytes, keeping its flags
+    pub fn replace_bytes(&mut self, uid: usize, bytes: &[u8]){
+        let entry = self.assets.get_mut(uid).filter(|a|{a.data.is_some() || a.stored.is_some()}).unwrap_or_else(|| panic!("uid 0x{:04X} is not a non-empty entry", uid));
+        entry.data = Some(Box::new(asset::Binary::from_bytes(bytes)));
+    }
+
+    /// uids of every level setup in the table
+    pub fn level_setup_uids(&self) -> Vec<usize>{
+        self.assets.iter()
+            .filter(|a|{matches!(a.data.as_ref().map(|d|{d.get_type()}), Some(asset::AssetType::LevelSetup))})
+            .map(|a|{a.uid})
+            .collect()
+    }
+
+    /// every problem of every model and level setup read from files, and warps
+    /// to maps whose setup isn't in the table. Entries only in a base bin
+    /// aren't checked
+    pub fn validate(&self) -> Vec<String>{
+        let mut out : Vec<String> = Vec::new();
+        let has_setup = |uid: usize|{
+            self.assets.get(uid).map_or(false, |a|{
+                match a.data.as_ref(){
+                    Some(d) => matches!(d.get_type(), asset::AssetType::LevelSetup),
+                    None => a.stored.is_some() && asset::segment_holds(a.seg, &asset::AssetType::LevelSetup),
+                }
+            })
+        };
+        for a in self.assets.iter(){
+            let data = match a.data.as_ref(){
+                Some(d) => d,
+                None => continue,
             };
-            let this_asset = asset::from_seg_indx_and_bytes(segment, i, &decomp_bin);
-            let out = AssetEntry{uid : i, seg :segment, meta : this.clone(), data : Some(this_asset)};
-            return out
-        }).collect();
+            out.extend(data.problems().into_iter().map(|p|{format!("uid 0x{:04X}: {}", a.uid, p)}));
+            for (id, position, map) in data.as_level_setup().map(|s|{s.warps()}).unwrap_or_default(){
+                if !has_setup(scene::MAP_SETUP_BASE + map){
+                    out.push(format!("uid 0x{:04X}: node 0x{:04X} at {:?} warps to map 0x{:02X}, which has no level setup (uid 0x{:04X})", a.uid, id, position, map, scene::MAP_SETUP_BASE + map));
+                }
+            }
+        }
+        return out
+    }
 
+    /// groups of uids whose decompressed bytes are the same, smallest uid first.
+    /// They can't share one blob in the table, each entry's size is the
+    /// distance to the next entry's offset, so a shared offset would make the
+    /// entry before it empty. That's why there's no dedup construct mode
+    pub fn duplicates(&self) -> Vec<Vec<usize>>{
+        //by the sha1 of the bytes, groups stay in the order of their first uid
+        let mut groups : Vec<Vec<usize>> = Vec::new();
+        let mut by_digest : HashMap<[u8; 20], usize> = HashMap::new();
+        for a in self.assets.iter(){
+            let digest = match &a.data{
+                Some(d) => sha1_smol::Sha1::from(d.to_bytes()).digest().bytes(),
+                None => continue,
+            };
+            match by_digest.get(&digest){
+                Some(i) => groups[*i].push(a.uid),
+                None => {
+                    by_digest.insert(digest, groups.len());
+                    groups.push(vec![a.uid]);
+                },
+            }
+        }
+        return groups.into_iter().filter(|uids|{uids.len() > 1}).collect()
+    }
 
-        return AssetFolder{assets: asset_list};
+    /// size of the bin it was extracted from, or the `rom_size` it was read with
+    pub fn rom_size(&self) -> Option<usize>{
+        return self.rom_size
     }
 
     pub fn to_bytes(&mut self) -> Vec<u8>{
-        if self.assets.last().unwrap().data.is_some(){
+        let mut out : Vec<u8> = Vec::new();
+        self.write_to(&mut out).unwrap_or_else(|e|{panic!("{}", e)});
+        return out;
+    }
+
+    /// writes the bin straight to `out`. Entries are held in memory until their
+    /// offsets are known, except the ones that know their length without being
+    /// serialized, which are written from the asset. Returns the number of bytes
+    /// written
+    pub fn write_to<W: Write>(&mut self, out: &mut W) -> std::io::Result<usize>{
+        let terminated = self.assets.last().map_or(false, |a|{a.data.is_none() && a.stored.is_none()});
+        if !terminated{
             self.assets.push(AssetEntry::new(self.assets.len())); //used to make table length correct
         }
 
-        //get compressed version if compressed
-        let comp_bins: Vec<Vec<u8>> = self.assets.iter().map(|a|{
-            return match &a.data {
-                None => Vec::new(),
-                Some(ass) => {
-                    match &a.meta.c_flag{
-                        true => bk::zip(&ass.to_bytes()),
-                        false => ass.to_bytes(),
-                    }
-                },
+        //bytes kept from a base bin are written as they are
+        let alignment = self.alignment;
+        let compression = self.compression;
+        //each entry with data is serialized once, for comparing with the base,
+        //compressing, or because its length isn't known without it. Only the
+        //ones that know their length are left to stream out at the end
+        let mut raws : Vec<Option<Vec<u8>>> = self.assets.iter().map(|a|{
+            match &a.data{
+                Some(d) if a.verbatim || a.meta.c_flag || a.stored.is_some() || d.known_len().is_none() => Some(d.to_bytes()),
+                _ => None,
+            }
+        }).collect();
+        let kept : Vec<Option<Vec<u8>>> = self.assets.iter().zip(raws.iter_mut()).map(|(a, raw)|{
+            let k = match (&a.data, a.verbatim){
+                (Some(_), true) => raw.take(),
+                _ => a.kept_bytes(raw.as_deref()).map(|b|{b.to_vec()}),
+            };
+            if k.is_some(){
+                *raw = None;
+            }
+            return k
+        }).collect();
+        let to_zip : Vec<Option<(usize, Vec<u8>, asset::Compression)>> = self.assets.iter().zip(raws.iter_mut()).map(|(a, raw)|{
+            return match (&a.data, a.meta.c_flag) {
+                (Some(_), true) => raw.take().map(|raw|{(a.uid, raw, a.compression.unwrap_or(compression))}),
+                _ => None,
             }
         })
         .collect();
+        let zipped = zip_all(&to_zip, self.threads).map_err(|e|{std::io::Error::new(std::io::ErrorKind::InvalidData, e)})?;
+        let bins : Vec<Option<Vec<u8>>> = zipped.into_iter().zip(kept.into_iter()).zip(raws.into_iter())
+            .map(|((c, k), raw)|{k.or(c).or(raw)})
+            .collect();
 
         //update asset offsets
-        let data_offsets: Vec<usize> = comp_bins.iter().map(|v| v.len()).collect();
-        self.assets.iter_mut().zip(data_offsets.iter()).fold(0, |o, (a, s)|{
+        let sizes: Vec<usize> = self.assets.iter().zip(bins.iter()).map(|(a, b)|{
+            let len = match (b, &a.data) {
+                (Some(bin), _) => bin.len(),
+                (None, Some(ass)) => ass.known_len().unwrap(),
+                (None, None) => 0,
+            };
+            return len + alignment.padding(len, alignment.asset)
+        })
+        .collect();
+        self.assets.iter_mut().zip(sizes.iter()).fold(0, |o, (a, s)|{
             a.meta.offset = o;
             return o + *s;
         });
 
         //convert everything to bytes
-        let mut out : Vec<u8> = ((self.assets.len()) as u32).to_be_bytes().to_vec();
-        out.append(&mut vec![0xff, 0xff, 0xff, 0xff]);
-
-        let mut meta_bytes : Vec<u8> = self.assets.iter()
-            .map(|a|{return a.meta.to_bytes()})
-            .flatten()
-            .collect();
-
-        let mut data_bytes: Vec<u8> = comp_bins.into_iter().flatten().collect();
-
-        out.append(&mut meta_bytes);
-        out.append(&mut data_bytes);
-        self.assets.pop();
-        return out;
+        out.write_all(&(self.assets.len() as u32).to_be_bytes())?;
+        out.write_all(&[0xff, 0xff, 0xff, 0xff])?;
+        for a in self.assets.iter(){
+            out.write_all(&a.meta.to_bytes())?;
+        }
+        for ((a, b), size) in self.assets.iter().zip(bins.iter()).zip(sizes.iter()){
+            let len = match (b, &a.data) {
+                (Some(bin), _) => {out.write_all(bin)?; bin.len()},
+                (None, Some(ass)) => {ass.write_bytes(out)?; ass.known_len().unwrap()},
+                (None, None) => 0,
+            };
+            out.write_all(&vec![alignment.fill; size - len])?;
+        }
+        let len = 8 + 8*self.assets.len() + sizes.iter().sum::<usize>();
+        let bin_padding = alignment.padding(len, alignment.bin);
+        out.write_all(&vec![alignment.fill; bin_padding])?;
+        if !terminated{
+            self.assets.pop();
+        }
+        return Ok(len + bin_padding);
     }
 
-    pub fn write(&self, out_dir_path: &Path){
+    pub fn write(&self, out_dir_path: &Path, opts: &asset::ExtractOptions){
         let asset_yaml_path = out_dir_path.join("assets.yaml");
 
         //write assets.yaml
-        let mut asset_yaml = fs::File::create(&asset_yaml_path).expect("could not write file");
+        let mut asset_yaml = deps::create(&asset_yaml_path).expect("could not write file");
         
 
         //assets.to_file
+        writeln!(asset_yaml, "schema_version: {}", migrate::SCHEMA_VERSION).unwrap();
         writeln!(asset_yaml, "tbl_len: 0x{:X}", self.assets.len() + 1).unwrap();
-        writeln!(asset_yaml, "files:").unwrap();
-        for elem in self.assets.iter()
-            .filter(|a| match a.data {None => false, _ => true})
-        {
-            
-            let data = match &elem.data {
-                Some(x) => x,
-                None => panic!("None data element reached"),
-            };
-            let mut tmp_str: String;
-            let data_type_str = match data.get_type(){
-                asset::AssetType::Animation => "Animation",
-                asset::AssetType::Binary => "Binary",
-                asset::AssetType::DemoInput => "DemoInput",
-                asset::AssetType::Dialog => "Dialog",
-                asset::AssetType::GruntyQuestion => "GruntyQuestion",
-                asset::AssetType::Midi => "Midi",
-                asset::AssetType::Model => "Model",
-                asset::AssetType::LevelSetup => "LevelSetup",
-                asset::AssetType::QuizQuestion => "QuizQuestion",
-                asset::AssetType::Sprite(fmt) => {let f = format!("{:?}",fmt).to_uppercase(); tmp_str = String::from("Sprite_") + &f; &tmp_str},
-                _ => "Binary",
-            };
-            let mut tmp_str2: String;
-            let file_ext = match data.get_type(){
-                asset::AssetType::Binary => ".bin",
-                asset::AssetType::Dialog => ".dialog",
-                asset::AssetType::GruntyQuestion => ".grunty_q",
-                asset::AssetType::QuizQuestion => ".quiz_q",
-                asset::AssetType::DemoInput => ".demo",
-                asset::AssetType::Midi => ".midi.bin",
-                asset::AssetType::Model => ".model.bin",
-                asset::AssetType::LevelSetup => ".lvl_setup.bin",
-                asset::AssetType::Animation => ".anim.bin",
-                asset::AssetType::Sprite(fmt) => {tmp_str2 = format!(".sprite.{:?}.bin",fmt).to_lowercase(); &tmp_str2.as_str()},
-                _ => ".bin"
-            };
-            let containing_folder = match data.get_type(){
-                asset::AssetType::Binary => "bin",
-                asset::AssetType::Dialog => "dialog",
-                asset::AssetType::GruntyQuestion => "grunty_q",
-                asset::AssetType::QuizQuestion => "quiz_q",
-                asset::AssetType::DemoInput => "demo",
-                asset::AssetType::Midi => "midi",
-                asset::AssetType::Model => "model",
-                asset::AssetType::LevelSetup => "lvl_setup",
-                asset::AssetType::Animation => "anim",
-                asset::AssetType::Sprite(fmt) => "sprite",
-                _ => "bin"
-            };
+        if let Some(size) = self.rom_size{
+            writeln!(asset_yaml, "rom_size: 0x{:X}", size).unwrap();
+        }
+        writeln!(asset_yaml, "alignment: {{asset: 0x{:X}, bin: 0x{:X}, fill: 0x{:02X}}}", self.alignment.asset, self.alignment.bin, self.alignment.fill).unwrap();
+        //entry lines by the type folder they're written to
+        let mut lines : Vec<(String, String)> = Vec::new();
+        //the assets are written on the threads, a sprite's frames aren't split
+        //over them again
+        let entries : Vec<&AssetEntry> = self.assets.iter().filter(|a|{a.data.is_some()}).collect();
+        let inner_opts = asset::ExtractOptions{threads: 1, ..opts.clone()};
+        let written = map_threads(&entries, opts.threads, |elem|{
+            write_asset(elem.data.as_deref().unwrap(), elem.uid, out_dir_path, if opts.threads > 1 {&inner_opts} else {opts})
+        });
+        for (elem, (data_type_str, relative_path)) in entries.into_iter().zip(written.into_iter()){
+            let type_override = elem.type_override.as_ref().map_or(String::new(), |t|{format!(", type_override: {}", t)})
+                + if elem.verbatim {", verbatim: true"} else {""};
+            let name = opts.names.name(elem.uid).map_or(String::new(), |n|{format!(", name: {:?}", n)});
+            let folder = relative_path.split('/').next().unwrap().to_string();
+            lines.push((folder, format!("  - {{uid: 0x{:04X}{}, segment: {}, type: {:6}{}, compressed: {:5}, flags: 0x{:04X}, relative_path: {:?}{}}}", elem.uid, name, elem.seg, data_type_str, type_override, elem.meta.c_flag, elem.meta.t_flag, relative_path, elem.notes.entry_keys())));
+            if !elem.notes.file_lines.is_empty(){
+                if !annotations::holds_notes(&relative_path){
+                    log::warning(Some(elem.uid), &format!("uid 0x{:04X}: {} can't hold notes, the ones from its old file are dropped", elem.uid, relative_path));
+                }
+                else{
+                    deps::append(out_dir_path.join(&relative_path), format!("{}\n", elem.notes.file_lines.join("\n")).as_bytes()).unwrap();
+                }
+            }
+        }
 
-            let elem_folder = out_dir_path.join(containing_folder);
-            DirBuilder::new().recursive(true).create(&elem_folder).unwrap();
-            assert!(fs::metadata(&elem_folder).unwrap().is_dir());
-            
-            let elem_path = elem_folder.join(format!("{:04X}{}", elem.uid, file_ext));
-            let relative_path = elem_path.strip_prefix(out_dir_path).unwrap().to_str().unwrap();
-            writeln!(asset_yaml, "  - {{uid: 0x{:04X}, type: {:6}, compressed: {:5}, flags: 0x{:04X}, relative_path: {:?}}}", elem.uid, data_type_str, elem.meta.c_flag, elem.meta.t_flag, relative_path).unwrap();
-        
-            data.write(&elem_path);
+        if opts.split_index{
+            let mut folders : Vec<&str> = lines.iter().map(|(f, _)|{f.as_str()}).collect();
+            folders.sort();
+            folders.dedup();
+            writeln!(asset_yaml, "include:").unwrap();
+            for folder in folders.iter(){
+                writeln!(asset_yaml, "  - {}.yaml", folder).unwrap();
+                let mut index = deps::create(out_dir_path.join(format!("{}.yaml", folder))).expect("could not write file");
+                writeln!(index, "files:").unwrap();
+                for (_, line) in lines.iter().filter(|(f, _)|{f == folder}){
+                    writeln!(index, "{}", line).unwrap();
+                }
+            }
+        }
+        else{
+            writeln!(asset_yaml, "files:").unwrap();
+            for (_, line) in lines.iter(){
+                writeln!(asset_yaml, "{}", line).unwrap();
+            }
+        }
+
+        //runs of empty entries with the same flags
+        let mut runs : Vec<(usize, usize, AssetMeta)> = Vec::new();
+        for elem in self.assets.iter().filter(|a|{a.data.is_none()}){
+            match runs.last_mut(){
+                Some((uid, count, meta)) if *uid + *count == elem.uid && meta.c_flag == elem.meta.c_flag && meta.t_flag == elem.meta.t_flag => *count += 1,
+                _ => runs.push((elem.uid, 1, elem.meta)),
+            }
+        }
+        if runs.is_empty(){
+            writeln!(asset_yaml, "empty: []").unwrap();
+        }
+        else{
+            writeln!(asset_yaml, "empty:").unwrap();
+            for (uid, count, meta) in runs.iter(){
+                writeln!(asset_yaml, "  - {{uid: 0x{:04X}, count: {}, compressed: {:5}, flags: 0x{:04X}}}", uid, count, meta.c_flag, meta.t_flag).unwrap();
+            }
         }
 
 
     }
 
-    pub fn read(&mut self, yaml_path: &Path){
+    pub fn read(&mut self, yaml_path: &Path, opts: &asset::ImportOptions){
         assert_eq!(yaml_path.extension().unwrap(), "yaml");
         let containing_folder = yaml_path.parent().unwrap();
         let base_name = yaml_path.file_stem().unwrap();
         
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(yaml_path).expect("could not open yaml")).unwrap()[0];
+        let doc = &load_index(yaml_path, &opts.names);
+        let version = migrate::schema_version(doc);
+        assert!(version <= migrate::SCHEMA_VERSION, "{:?} is schema version {}, this bk_asset_tool only knows up to {}", yaml_path, version, migrate::SCHEMA_VERSION);
+        if version < migrate::SCHEMA_VERSION{
+            log::warning(None, &format!("{:?} is schema version {}, `bk_asset_tool migrate` upgrades it to {}", yaml_path, version, migrate::SCHEMA_VERSION));
+        }
 
-        let asset_meta : Vec<AssetEntry> = doc["files"].as_vec().unwrap()
+        //in uid order, so new entries without flags continue the entry before them,
+        //or take the flags of the same entry in a base bin or extraction
+        let mut files : Vec<&Yaml> = doc["files"].as_vec().unwrap().iter().collect();
+        files.sort_by_key(|y|{y["uid"].as_i64()});
+        let mut asset_meta : Vec<AssetEntry> = Vec::new();
+        for y in files.into_iter(){
+            let base = y["uid"].as_i64().and_then(|uid|{self.assets.get(uid as usize)}).filter(|a|{a.stored.is_some() || a.data.is_some()}).map(|a|{&a.meta});
+            let entry = AssetEntry::from_yaml(y, base.or(asset_meta.last().map(|a|{&a.meta})));
+            asset_meta.push(entry);
+        }
+        //empty entries, older assets.yaml don't list them and get the default flags
+        let empty_meta : Vec<AssetEntry> = doc["empty"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[])
             .iter()
-            .map(|y|{ AssetEntry::from_yaml(y)})
+            .flat_map(|y|{
+                let first = AssetEntry::from_yaml(y, None);
+                let count = y["count"].as_i64().unwrap_or(1) as usize;
+                (first.uid..first.uid + count).map(move |uid|{AssetEntry{meta: first.meta, ..AssetEntry::new(uid)}})
+            })
             .collect();
-        let expect_len = doc["tbl_len"].as_i64().unwrap() as usize;
-        let max_id :usize = asset_meta.iter().fold(0, |max, a|{
+        //a base bin's table is kept unless assets.yaml says otherwise
+        let expect_len = doc["tbl_len"].as_i64().map(|l|{l as usize}).or(if self.assets.is_empty() {None} else {Some(self.assets.len())}).expect("assets.yaml has no tbl_len");
+        let max_id :usize = asset_meta.iter().chain(empty_meta.iter()).fold(0, |max, a|{
             return if max > a.uid {max} else {a.uid}
         });
 
+        //uids past the end grow the table, the old end becomes an empty entry
         let expect_len = if expect_len < max_id + 1 {max_id + 1} else {expect_len};
+        self.rom_size = doc["rom_size"].as_i64().map(|s|{s as usize}).or(self.rom_size);
+        //the import options win over assets.yaml
+        let align_yaml = &doc["alignment"];
+        self.alignment = Alignment{
+            asset: opts.asset_align.or(align_yaml["asset"].as_i64().map(|a|{a as usize})).unwrap_or(self.alignment.asset),
+            bin: opts.bin_align.or(align_yaml["bin"].as_i64().map(|a|{a as usize})).unwrap_or(self.alignment.bin),
+            fill: opts.fill.or(align_yaml["fill"].as_i64().map(|f|{f as u8})).unwrap_or(self.alignment.fill),
+        };
+        assert!(self.alignment.asset > 0 && self.alignment.bin > 0, "alignment must be at least 1");
+        self.compression = opts.compression;
+        self.threads = opts.threads;
 
         if self.assets.len() < expect_len {
-            let mut i = 0;
+            let mut i = self.assets.len();
             self.assets.resize_with(expect_len, ||{ let j = i; i += 1; return AssetEntry::new(j)})
         }
 
-        for a in asset_meta.into_iter(){
+        for a in empty_meta.into_iter(){
             let i = a.uid.clone();
             self.assets[i] = a;
         }
+        for mut a in asset_meta.into_iter(){
+            let i = a.uid.clone();
+            a.stored = self.assets[i].stored.take();
+            self.assets[i] = a;
+        }
+
+        //the game and extract only know segments from where the flags change
+        let metas : Vec<AssetMeta> = self.assets.iter().map(|a|{a.meta}).collect();
+        for (a, seg) in self.assets.iter_mut().zip(AssetMeta::segments(&metas).into_iter()){
+            if a.meta.t_flag == 4{
+                continue;
+            }
+            assert!(a.seg == 0 || a.seg == seg, "uid 0x{:04X} is listed in segment {}, but its flags put it in segment {}", a.uid, a.seg, seg);
+            a.seg = seg;
+        }
 
         for y in doc["files"].as_vec().unwrap().iter(){
             let uid :usize = y["uid"].as_i64().unwrap() as usize;
@@ -254,14 +791,343 @@ impl AssetFolder{
                 "GruntyQuestion"    => Some(Box::new(asset::GruntyQuestion::read(&containing_folder.join(relative_path)))),
                 "QuizQuestion"      => Some(Box::new(asset::QuizQuestion::read(&containing_folder.join(relative_path)))),
                 "DemoInput"         => Some(Box::new(asset::DemoButtonFile::read(&containing_folder.join(relative_path)))),
-                // "Midi"              => Some(Box::new(asset::MidiSeqFile::read(&containing_folder.join(relative_path)))),
-                // "Model"             => Some(Box::new(asset::Model::read(&containing_folder.join(relative_path)))),
-                // "LevelSetup"        => Some(Box::new(asset::LevelSetup::read(&containing_folder.join(relative_path)))),
-                // "Animation"         => Some(Box::new(asset::Animation::read(&containing_folder.join(relative_path)))),
-                // x if x.starts_with("Sprite_") => Some(Box::new(asset::Sprite::read(&containing_folder.join(relative_path)))),
+                "Midi"              => Some(Box::new(asset::MidiSeqFile::read(&containing_folder.join(relative_path)))),
+                "Model"             => Some(Box::new(asset::Model::read(&containing_folder.join(relative_path), opts))),
+                "LevelSetup"        => {
+                    let mut setup = asset::LevelSetup::read(&containing_folder.join(relative_path));
+                    for patch in opts.patches.iter().filter(|p|{level_setup::LevelSetup::patch_uid(p) == uid}){
+                        setup.apply_patch(patch);
+                    }
+                    Some(Box::new(setup))
+                },
+                "Animation"         => Some(Box::new(asset::Animation::read(&containing_folder.join(relative_path)))),
+                x if x.starts_with("Sprite") => Some(Box::new(asset::Sprite::read(&containing_folder.join(relative_path), opts))),
                 _ => Some(Box::new(asset::Binary::read(&containing_folder.join(relative_path)))),
             };
+            let seg = self.assets[uid].seg;
+            //an overridden type can be anywhere, but it has to be what was extracted
+            match self.assets[uid].type_override.as_ref(){
+                Some(t) => assert!(data.as_ref().map_or(true, |d|{asset::is_type_name(&d.get_type(), t)}), "uid 0x{:04X}: type_override is {}, but it was extracted as {}. Extract it again", uid, t, y["type"].as_str().unwrap()),
+                None => assert!(data.as_ref().map_or(true, |d|{asset::segment_holds(seg, &d.get_type())}), "uid 0x{:04X}: segment {} can't hold a {}", uid, seg, y["type"].as_str().unwrap()),
+            }
             self.assets[uid].data = data;
         }
+        for patch in opts.patches.iter(){
+            let uid = level_setup::LevelSetup::patch_uid(patch);
+            //a setup only in the base bin is decoded from it to be patched
+            if let Some(a) = self.assets.get_mut(uid).filter(|a|{a.data.is_none()}){
+                if let Some((compressed, bytes)) = a.stored.as_ref(){
+                    let decoded = match compressed{
+                        true => cache::unzip(uid, bytes),
+                        false => Ok(bytes.clone()),
+                    }.and_then(|b|{asset::LevelSetup::try_from_bytes(&b)});
+                    let mut setup = decoded.unwrap_or_else(|e|{panic!("{:?}: uid 0x{:04X} in the base bin can't be patched: {}", patch, uid, e)});
+                    setup.apply_patch(patch);
+                    a.data = Some(Box::new(setup));
+                }
+            }
+            let is_setup = self.assets.get(uid).and_then(|a|{a.data.as_ref()}).map(|d|{matches!(d.get_type(), asset::AssetType::LevelSetup)});
+            assert!(is_setup == Some(true), "{:?}: uid 0x{:04X} is not a level setup", patch, uid);
+        }
+    }
+}
+
+/// gives entries that only have a `name` the uid the name map has for it
+fn resolve_names(doc: &mut Yaml, names: &names::NameMap, yaml_path: &Path){
+    let files = match doc{
+        Yaml::Hash(h) => match h.get_mut(&Yaml::String("files".to_string())){
+            Some(Yaml::Array(files)) => files,
+            _ => return,
+        },
+        _ => return,
+    };
+    let mut listed : HashSet<i64> = files.iter().filter_map(|y|{y["uid"].as_i64()}).collect();
+    for f in files.iter_mut(){
+        if let Yaml::Hash(h) = f{
+            let uid_key = Yaml::String("uid".to_string());
+            if h.contains_key(&uid_key){
+                continue;
+            }
+            let name = h.get(&Yaml::String("name".to_string())).and_then(|n|{n.as_str()}).map(|n|{n.to_string()});
+            if let Some(name) = name{
+                let uid = names.uid(&name).unwrap_or_else(||{panic!("{:?}: no uid is named \"{}\", pass the name map with --names", yaml_path, name)});
+                assert!(listed.insert(uid as i64), "{:?}: \"{}\" is uid 0x{:04X}, which is listed already", yaml_path, name, uid);
+                h.insert(uid_key, Yaml::Integer(uid as i64));
+            }
+        }
+    }
+}
+
+/// an assets.yaml with the `files` and `empty` entries of the yaml files its
+/// `include` lists added to its own. The included files are next to it, and
+/// their relative paths are from its folder too. Entries listed by `name` get
+/// their uid from `names` before patterns are expanded, so a pattern skips them
+pub fn load_index(yaml_path: &Path, names: &names::NameMap) -> Yaml{
+    let load = |path: &Path|{
+        YamlLoader::load_from_str(&deps::read_to_string(path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)})).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)}).remove(0)
+    };
+    let mut doc = load(yaml_path);
+    let includes : Vec<String> = doc["include"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter()
+        .map(|i|{i.as_str().unwrap_or_else(||{panic!("{:?}: include {:?} is not a path", yaml_path, i)}).to_string()})
+        .collect();
+    let folder = yaml_path.parent().unwrap();
+    if includes.is_empty(){
+        resolve_names(&mut doc, names, yaml_path);
+        expand_paths(&mut doc, folder);
+        return doc
+    }
+    let mut lists : Vec<(&str, Vec<Yaml>)> = ["files", "empty"].iter().map(|k|{(*k, doc[*k].as_vec().cloned().unwrap_or_default())}).collect();
+    //which file listed each uid, so two listing the same one is caught here
+    let mut listed : HashMap<i64, String> = HashMap::new();
+    for y in lists[0].1.iter(){
+        if let Some(uid) = y["uid"].as_i64(){
+            listed.insert(uid, "assets.yaml".to_string());
+        }
+    }
+    for include in includes.iter(){
+        let part = load(&folder.join(include));
+        assert!(part["include"].is_badvalue(), "{:?}: included yaml can't include more", folder.join(include));
+        for (key, list) in lists.iter_mut(){
+            for y in part[*key].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter(){
+                if let (Some(uid), true) = (y["uid"].as_i64(), *key == "files"){
+                    if let Some(other) = listed.insert(uid, include.clone()){
+                        panic!("uid 0x{:04X} is listed in both {} and {}", uid, other, include);
+                    }
+                }
+                list.push(y.clone());
+            }
+        }
+    }
+    if let Yaml::Hash(h) = &mut doc{
+        for (key, list) in lists.into_iter(){
+            h.insert(Yaml::String(key.to_string()), Yaml::Array(list));
+        }
+    }
+    resolve_names(&mut doc, names, yaml_path);
+    expand_paths(&mut doc, folder);
+    return doc
+}
+
+/// the type extract writes with each extension, json ones included
+fn type_from_path(path: &str) -> Option<&'static str>{
+    let path = path.strip_suffix(".json").unwrap_or(path);
+    //the raw copies the yaml types keep next to themselves aren't assets
+    if path.ends_with(".bin") && [".sprite.", ".model.", ".anim.", ".lvl_setup.", ".midi."].iter().any(|k|{path.contains(k)}){
+        return None
+    }
+    let types = [
+        (".seq.yaml", "Midi"), (".model.yaml", "Model"), (".lvl_setup.yaml", "LevelSetup"), (".anim.yaml", "Animation"), (".sprite.yaml", "Sprite"),
+        (".dialog", "Dialog"), (".grunty_q", "GruntyQuestion"), (".quiz_q", "QuizQuestion"), (".demo", "DemoInput"), (".bin", "Binary"),
+    ];
+    return types.iter().find(|(ext, _)|{path.ends_with(ext)}).map(|(_, t)|{*t})
+}
+
+/// whether `name` matches `pattern`, where * is any run of characters and ? any one
+fn glob_match(pattern: &[char], name: &[char]) -> bool{
+    return match pattern.split_first(){
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i|{glob_match(rest, &name[i..])}),
+        Some((p, rest)) => name.split_first().map_or(false, |(n, name)|{(*p == '?' || p == n) && glob_match(rest, name)}),
+    }
+}
+
+/// `files` entries with a relative_path like "dialog/*.dialog", or a folder
+/// ending in /, become one entry per file they match. The uid is the hex at the
+/// start of the file name, as extract names them, the type comes from the
+/// extension unless it's given, and the entry's other keys go to every file.
+/// Uids listed on their own keep their own entry
+fn expand_paths(doc: &mut Yaml, folder: &Path){
+    let files = match doc{
+        Yaml::Hash(h) => match h.get_mut(&Yaml::String("files".to_string())){
+            Some(Yaml::Array(files)) => files,
+            _ => return,
+        },
+        _ => return,
+    };
+    let is_pattern = |y: &Yaml|{y["relative_path"].as_str().map_or(false, |p|{p.contains('*') || p.contains('?') || p.ends_with('/')})};
+    if !files.iter().any(is_pattern){
+        return
+    }
+    let (patterns, mut listed) : (Vec<Yaml>, Vec<Yaml>) = files.drain(..).partition(is_pattern);
+    let mut taken : HashMap<i64, String> = listed.iter().filter_map(|y|{y["uid"].as_i64()}).map(|uid|{(uid, String::new())}).collect();
+    for y in patterns.iter(){
+        let pattern = y["relative_path"].as_str().unwrap();
+        assert!(y["uid"].is_badvalue(), "{}: a pattern's uids come from its file names, it can't have a uid", pattern);
+        let (dir, name) = pattern.rsplit_once('/').unwrap_or(("", pattern));
+        assert!(!dir.contains('*') && !dir.contains('?'), "{}: only file names can have * and ?, not folders", pattern);
+        let name : Vec<char> = if name.is_empty() {vec!['*']} else {name.chars().collect()};
+        let mut paths : Vec<String> = deps::list(folder.join(dir)).unwrap_or_else(|e|{panic!("{}: {}", pattern, e)}).into_iter()
+            .filter(|n|{glob_match(&name, &n.chars().collect::<Vec<char>>())})
+            .map(|n|{if dir.is_empty() {n} else {format!("{}/{}", dir, n)}})
+            .collect();
+        paths.sort();
+        for path in paths.into_iter(){
+            //a folder has pngs, textures and such next to the assets, only the assets are taken
+            let asset_type = match (y["type"].as_str(), type_from_path(&path)){
+                (Some(t), _) => asset::check_type_name(t),
+                (None, Some(t)) => t,
+                (None, None) if pattern.ends_with('/') => continue,
+                (None, None) => panic!("{}: {} has no type, give the pattern one", pattern, path),
+            };
+            let file_name = path.rsplit('/').next().unwrap();
+            let hex = file_name.split('.').next().unwrap();
+            let uid = i64::from_str_radix(hex, 16).unwrap_or_else(|_|{panic!("{}: {} doesn't start with its uid in hex", pattern, path)});
+            match taken.get(&uid){
+                Some(other) if other.is_empty() => continue,
+                Some(other) => panic!("uid 0x{:04X} is both {} and {}", uid, other, path),
+                None => {taken.insert(uid, path.clone());},
+            }
+            let mut entry = y.as_hash().unwrap().clone();
+            entry.insert(Yaml::String("uid".to_string()), Yaml::Integer(uid));
+            entry.insert(Yaml::String("type".to_string()), Yaml::String(asset_type.to_string()));
+            entry.insert(Yaml::String("relative_path".to_string()), Yaml::String(path));
+            listed.push(Yaml::Hash(entry));
+        }
+    }
+    *files = listed;
+}
+
+/// the `type_override`s of an assets.yaml, so extracting again into the same
+/// folder keeps them
+pub fn type_overrides(yaml_path: &Path, names: &names::NameMap) -> Vec<(usize, String)>{
+    let doc = &load_index(yaml_path, names);
+    return doc["files"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]).iter()
+        .filter_map(|y|{
+            let t = y["type_override"].as_str()?;
+            Some((y["uid"].as_i64().unwrap() as usize, asset::check_type_name(t).to_string()))
+        })
+        .collect()
+}
+
+/// `f` of each item on `threads` threads, in order. Each thread takes the next
+/// item when it's done with one, so a run of slow items is still shared
+pub(crate) fn map_threads<T: Sync, R: Send>(items: &[T], threads: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R>{
+    if threads <= 1 || items.len() < 2{
+        return items.iter().map(f).collect()
+    }
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let (f, next) = (&f, &next);
+    let mut done : Vec<(usize, R)> = std::thread::scope(|scope|{
+        let handles : Vec<_> = (0..threads.min(items.len()))
+            .map(|_|{scope.spawn(move ||{
+                let mut out : Vec<(usize, R)> = Vec::new();
+                loop{
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    match items.get(i){
+                        Some(item) => out.push((i, f(item))),
+                        None => return out,
+                    }
+                }
+            })})
+            .collect();
+        handles.into_iter()
+            .flat_map(|h|{h.join().unwrap_or_else(|p|{panic::resume_unwind(p)})})
+            .collect()
+    });
+    done.sort_by_key(|(i, _)|{*i});
+    return done.into_iter().map(|(_, r)|{r}).collect()
+}
+
+/// compressed bytes of each entry, split over `threads` threads. Each is
+/// decompressed again to check it, the first that doesn't come back is the error
+fn zip_all(entries: &[Option<(usize, Vec<u8>, asset::Compression)>], threads: usize) -> Result<Vec<Option<Vec<u8>>>, error::CompressError>{
+    return map_threads(entries, threads, |e|{
+        e.as_ref().map(|(uid, bytes, compression)|{
+            let bin = zip(bytes, *compression);
+            match cache::inflate(*uid, &bin).map_or(false, |b|{b == *bytes}){
+                true => Ok(bin),
+                false => Err(error::CompressError{uid: *uid, compression: *compression}),
+            }
+        }).transpose()
+    }).into_iter().collect()
+}
+
+/// what the original compressor pads its output to a multiple of, with zeros
+const ZIP_ALIGN : usize = 0x10;
+
+/// `Match` is the original compressor. The others wrap a miniz deflate stream in
+/// the same header and padding, which inflates the same in game but won't match
+/// the original
+fn zip(bytes: &[u8], compression: asset::Compression) -> Vec<u8>{
+    let level = match compression{
+        asset::Compression::Match => return bk::zip(bytes),
+        asset::Compression::Fast => 1,
+        asset::Compression::Max => 10,
+    };
+    let mut out : Vec<u8> = vec![0x11, 0x72];
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.append(&mut miniz_oxide::deflate::compress_to_vec(bytes, level));
+    out.resize((out.len() + ZIP_ALIGN - 1)/ZIP_ALIGN*ZIP_ALIGN, 0);
+    return out
+}
+
+/// the type as assets.yaml names it, sprites with their format
+pub fn type_name(asset_type: &asset::AssetType) -> String{
+    return match asset_type{
+        asset::AssetType::Animation => "Animation".to_string(),
+        asset::AssetType::Binary => "Binary".to_string(),
+        asset::AssetType::DemoInput => "DemoInput".to_string(),
+        asset::AssetType::Dialog => "Dialog".to_string(),
+        asset::AssetType::GruntyQuestion => "GruntyQuestion".to_string(),
+        asset::AssetType::Midi => "Midi".to_string(),
+        asset::AssetType::Model => "Model".to_string(),
+        asset::AssetType::LevelSetup => "LevelSetup".to_string(),
+        asset::AssetType::QuizQuestion => "QuizQuestion".to_string(),
+        asset::AssetType::Sprite(fmt) => String::from("Sprite_") + &format!("{:?}",fmt).to_uppercase(),
+    }
+}
+
+/// the folder extract writes the type to
+pub fn type_folder(asset_type: &asset::AssetType) -> &'static str{
+    return match asset_type{
+        asset::AssetType::Binary => "bin",
+        asset::AssetType::Dialog => "dialog",
+        asset::AssetType::GruntyQuestion => "grunty_q",
+        asset::AssetType::QuizQuestion => "quiz_q",
+        asset::AssetType::DemoInput => "demo",
+        asset::AssetType::Midi => "midi",
+        asset::AssetType::Model => "model",
+        asset::AssetType::LevelSetup => "lvl_setup",
+        asset::AssetType::Animation => "anim",
+        asset::AssetType::Sprite(_) => "sprite",
+    }
+}
+
+/// writes one decoded asset under `out_dir_path` the same way extract does, and
+/// returns its assets.yaml type and relative path
+pub fn write_asset(data: &dyn asset::Asset, uid: usize, out_dir_path: &Path, opts: &asset::ExtractOptions) -> (String, String){
+    let data_type_str = type_name(&data.get_type());
+    let file_ext = match data.get_type(){
+        asset::AssetType::Binary => ".bin",
+        asset::AssetType::Dialog => ".dialog",
+        asset::AssetType::GruntyQuestion => ".grunty_q",
+        asset::AssetType::QuizQuestion => ".quiz_q",
+        asset::AssetType::DemoInput => ".demo",
+        asset::AssetType::Midi => ".seq.yaml",
+        asset::AssetType::Model => ".model.yaml",
+        asset::AssetType::LevelSetup => ".lvl_setup.yaml",
+        asset::AssetType::Animation => ".anim.yaml",
+        asset::AssetType::Sprite(_) => ".sprite.yaml",
+        _ => ".bin"
+    };
+    let containing_folder = type_folder(&data.get_type());
+
+    let elem_folder = out_dir_path.join(containing_folder);
+    deps::create_dir_all(&elem_folder).unwrap();
+
+    let json = match opts.text_format {
+        asset::TextFormat::Json => data.to_json(),
+        asset::TextFormat::Yaml => None,
+    };
+    let json_ext = if json.is_some() {".json"} else {""};
+
+    let name = opts.names.name(uid).map_or(String::new(), |n|{format!(".{}", texture::c_identifier(n))});
+    let elem_path = elem_folder.join(format!("{:04X}{}{}{}", uid, name, file_ext, json_ext));
+    let relative_path = elem_path.strip_prefix(out_dir_path).unwrap().to_str().unwrap();
+
+    match json {
+        Some(doc) => deps::write(&elem_path, doc).expect("could not write file"),
+        None => data.write(&elem_path, opts),
     }
+    return (data_type_str, relative_path.to_string())
 }
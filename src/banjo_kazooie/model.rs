@@ -0,0 +1,1707 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::asset::{yaml_f32, Asset, AssetType, ExtractOptions, ImportOptions};
+use super::deps;
+use super::error::{ByteReader, ParseError};
+use super::log;
+use super::geo;
+use super::gfx::{self, Gfx};
+use super::gltf::GltfMesh;
+use super::obj::ObjMesh;
+use super::texture::{self, ImgFmt, Texture};
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16{
+    u16::from_be_bytes(bytes[offset..offset+2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32{
+    u32::from_be_bytes(bytes[offset..offset+4].try_into().unwrap())
+}
+
+/// offset of an optional section, 0 means the model doesn't have one
+fn section(offset: u32) -> Option<usize>{
+    match offset{
+        0 => None,
+        x => Some(x as usize),
+    }
+}
+
+/// the 0x38 byte header every model starts with
+#[derive(Debug, Clone)]
+pub struct ModelHeader{
+    pub geo_layout : Option<usize>,
+    pub texture_list : Option<usize>,
+    pub geo_type : u16,
+    pub display_list : Option<usize>,
+    pub vertex_store : Option<usize>,
+    pub unk14 : u32,
+    pub bone_list : Option<usize>,
+    pub collision : Option<usize>,
+    pub unk20 : Option<usize>,
+    pub effects : Option<usize>,
+    pub unk28 : u32,
+    pub animated_textures : Option<usize>,
+    pub tri_count : u16,
+    pub vertex_count : u16,
+    pub unk34 : f32,
+}
+
+impl ModelHeader{
+    pub const SIZE : usize = 0x38;
+    pub const MAGIC : u32 = 0x0000000B;
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Option<ModelHeader>{
+        if in_bytes.len() < ModelHeader::SIZE || u32_at(in_bytes, 0) != ModelHeader::MAGIC{
+            return None;
+        }
+        Some(ModelHeader{
+            geo_layout: section(u32_at(in_bytes, 0x04)),
+            texture_list: section(u16_at(in_bytes, 0x08) as u32),
+            geo_type: u16_at(in_bytes, 0x0A),
+            display_list: section(u32_at(in_bytes, 0x0C)),
+            vertex_store: section(u32_at(in_bytes, 0x10)),
+            unk14: u32_at(in_bytes, 0x14),
+            bone_list: section(u32_at(in_bytes, 0x18)),
+            collision: section(u32_at(in_bytes, 0x1C)),
+            unk20: section(u32_at(in_bytes, 0x20)),
+            effects: section(u32_at(in_bytes, 0x24)),
+            unk28: u32_at(in_bytes, 0x28),
+            animated_textures: section(u32_at(in_bytes, 0x2C)),
+            tri_count: u16_at(in_bytes, 0x30),
+            vertex_count: u16_at(in_bytes, 0x32),
+            unk34: f32::from_bits(u32_at(in_bytes, 0x34)),
+        })
+    }
+
+    /// every section offset, for moving sections around
+    fn sections_mut(&mut self) -> Vec<&mut Option<usize>>{
+        vec![&mut self.geo_layout, &mut self.texture_list, &mut self.display_list, &mut self.vertex_store, &mut self.bone_list,
+            &mut self.collision, &mut self.unk20, &mut self.effects, &mut self.animated_textures]
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let offset = |o : Option<usize>|{o.unwrap_or(0) as u32};
+        let mut out = ModelHeader::MAGIC.to_be_bytes().to_vec();
+        out.extend_from_slice(&offset(self.geo_layout).to_be_bytes());
+        out.extend_from_slice(&(offset(self.texture_list) as u16).to_be_bytes());
+        out.extend_from_slice(&self.geo_type.to_be_bytes());
+        out.extend_from_slice(&offset(self.display_list).to_be_bytes());
+        out.extend_from_slice(&offset(self.vertex_store).to_be_bytes());
+        out.extend_from_slice(&self.unk14.to_be_bytes());
+        out.extend_from_slice(&offset(self.bone_list).to_be_bytes());
+        out.extend_from_slice(&offset(self.collision).to_be_bytes());
+        out.extend_from_slice(&offset(self.unk20).to_be_bytes());
+        out.extend_from_slice(&offset(self.effects).to_be_bytes());
+        out.extend_from_slice(&self.unk28.to_be_bytes());
+        out.extend_from_slice(&offset(self.animated_textures).to_be_bytes());
+        out.extend_from_slice(&self.tri_count.to_be_bytes());
+        out.extend_from_slice(&self.vertex_count.to_be_bytes());
+        out.extend_from_slice(&self.unk34.to_bits().to_be_bytes());
+        return out
+    }
+}
+
+/// header of the texture segment, the texture data follows the entry table
+#[derive(Debug, Clone)]
+pub struct TextureListHeader{
+    pub data_size : usize,
+    pub count : usize,
+}
+
+impl TextureListHeader{
+    pub const SIZE : usize = 0x8;
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Result<TextureListHeader, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let data_size = r.u32()? as usize;
+        return Ok(TextureListHeader{data_size: data_size, count: r.u16()? as usize})
+    }
+}
+
+/// one entry of the texture list, offset is from the start of the model
+#[derive(Debug, Clone)]
+pub struct ModelTexture{
+    pub offset : usize,
+    pub format : ImgFmt,
+    pub w : usize,
+    pub h : usize,
+}
+
+impl ModelTexture{
+    pub const SIZE : usize = 0x10;
+
+    /// palette and texels of the top level, mipmaps that follow aren't included
+    pub fn byte_size(&self) -> usize{
+        let palette_size = match self.format{
+            ImgFmt::CI4 => 0x20,
+            ImgFmt::CI8 => 0x200,
+            _ => 0,
+        };
+        return palette_size + self.w*self.h*self.format.bits_per_texel()/8
+    }
+}
+
+/// one 0x10 byte Vtx of the vertex store
+#[derive(Debug, Clone)]
+pub struct Vertex{
+    pub position : [i16; 3],
+    pub flag : u16,
+    pub st : [i16; 2], //s10.5 texel coordinates
+    pub color : [u8; 4],
+}
+
+impl Vertex{
+    pub const SIZE : usize = 0x10;
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Result<Vertex, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let position = [r.u16()? as i16, r.u16()? as i16, r.u16()? as i16];
+        let flag = r.u16()?;
+        let st = [r.u16()? as i16, r.u16()? as i16];
+        return Ok(Vertex{position: position, flag: flag, st: st, color: r.take(4)?.try_into().unwrap()})
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out : Vec<u8> = Vec::new();
+        for p in self.position.iter(){
+            out.extend_from_slice(&p.to_be_bytes());
+        }
+        out.extend_from_slice(&self.flag.to_be_bytes());
+        out.extend_from_slice(&self.st[0].to_be_bytes());
+        out.extend_from_slice(&self.st[1].to_be_bytes());
+        out.extend_from_slice(&self.color);
+        return out
+    }
+}
+
+/// a triangle drawn by the display list, with the texture loaded when it was drawn
+#[derive(Debug, Clone)]
+pub struct Triangle{
+    pub vertices : [usize; 3], //indices into the vertex store
+    pub texture : Option<usize>, //index into the texture list
+    pub tex_scale : [f32; 2],
+    pub dl : usize, //which G_ENDDL terminated list it is drawn by
+}
+
+/// header of the display list segment, followed by `count` 8 byte Gfx commands
+#[derive(Debug, Clone)]
+pub struct DisplayListHeader{
+    pub count : usize,
+}
+
+impl DisplayListHeader{
+    pub const SIZE : usize = 0x8;
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Result<DisplayListHeader, ParseError>{
+        return Ok(DisplayListHeader{count: ByteReader::new(in_bytes).u32()? as usize})
+    }
+}
+
+/// header of the vertex store, followed by `count` 0x10 byte Vtx
+#[derive(Debug, Clone)]
+pub struct VertexStoreHeader{
+    pub min : [i16; 3],
+    pub max : [i16; 3],
+    pub center : [i16; 3],
+    pub local_radius : i16,
+    pub count : usize,
+    pub global_radius : i16,
+}
+
+impl VertexStoreHeader{
+    pub const SIZE : usize = 0x18;
+
+    /// bounds and radii that enclose `vertices`
+    pub fn from_vertices(vertices: &[Vertex]) -> VertexStoreHeader{
+        let mut min = [i16::MAX; 3];
+        let mut max = [i16::MIN; 3];
+        for v in vertices.iter(){
+            for i in 0..3{
+                min[i] = std::cmp::min(min[i], v.position[i]);
+                max[i] = std::cmp::max(max[i], v.position[i]);
+            }
+        }
+        if vertices.is_empty(){
+            min = [0; 3];
+            max = [0; 3];
+        }
+        let center = [0, 1, 2].map(|i|{((min[i] as i32 + max[i] as i32)/2) as i16});
+        let radius = |c : [i16; 3]|{
+            vertices.iter()
+                .map(|v|{
+                    let d : Vec<f32> = (0..3).map(|i|{(v.position[i] as i32 - c[i] as i32) as f32}).collect();
+                    (d[0]*d[0] + d[1]*d[1] + d[2]*d[2]).sqrt().ceil() as i16
+                })
+                .max()
+                .unwrap_or(0)
+        };
+        VertexStoreHeader{min: min, max: max, center: center, local_radius: radius(center), count: vertices.len(), global_radius: radius([0; 3])}
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out : Vec<u8> = Vec::new();
+        for v in self.min.iter().chain(self.max.iter()).chain(self.center.iter()){
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        out.extend_from_slice(&self.local_radius.to_be_bytes());
+        out.extend_from_slice(&(self.count as u16).to_be_bytes());
+        out.extend_from_slice(&self.global_radius.to_be_bytes());
+        return out
+    }
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Result<VertexStoreHeader, ParseError>{
+        let bytes = ByteReader::new(in_bytes).take(VertexStoreHeader::SIZE)?;
+        let s16 = |offset : usize|{u16_at(bytes, offset) as i16};
+        return Ok(VertexStoreHeader{
+            min: [s16(0x0), s16(0x2), s16(0x4)],
+            max: [s16(0x6), s16(0x8), s16(0xA)],
+            center: [s16(0xC), s16(0xE), s16(0x10)],
+            local_radius: s16(0x12),
+            count: u16_at(bytes, 0x14) as usize,
+            global_radius: s16(0x16),
+        })
+    }
+}
+
+/// header of the collision segment, followed by the geo and triangle tables
+#[derive(Debug, Clone)]
+pub struct CollisionHeader{
+    pub min : [i16; 3],
+    pub max : [i16; 3],
+    pub stride : [i16; 2],
+    pub geo_count : usize,
+    pub tri_count : usize,
+}
+
+impl CollisionHeader{
+    pub const SIZE : usize = 0x18;
+
+    pub fn from_bytes(in_bytes: &[u8]) -> Result<CollisionHeader, ParseError>{
+        let bytes = ByteReader::new(in_bytes).take(CollisionHeader::SIZE)?;
+        let s16 = |offset : usize|{u16_at(bytes, offset) as i16};
+        return Ok(CollisionHeader{
+            min: [s16(0x0), s16(0x2), s16(0x4)],
+            max: [s16(0x6), s16(0x8), s16(0xA)],
+            stride: [s16(0xC), s16(0xE)],
+            geo_count: u16_at(bytes, 0x10) as usize,
+            tri_count: u16_at(bytes, 0x12) as usize,
+        })
+    }
+}
+
+/// one joint of the bone list, in model space
+#[derive(Debug, Clone)]
+pub struct Bone{
+    pub position : [f32; 3],
+    pub id : u16,
+    pub parent : i16, //-1 for root bones
+}
+
+/// the bone list section, what animations move
+#[derive(Debug, Clone)]
+pub struct BoneList{
+    pub scale : f32,
+    pub bones : Vec<Bone>,
+}
+
+impl BoneList{
+    pub fn from_bytes(in_bytes: &[u8]) -> Result<BoneList, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let scale = r.f32()?;
+        let count = r.u16()? as usize;
+        r.take(2)?;
+        let bones = (0..count).map(|_|{
+            let position = [r.f32()?, r.f32()?, r.f32()?];
+            let id = r.u16()?;
+            Ok(Bone{position: position, id: id, parent: r.u16()? as i16})
+        }).collect::<Result<Vec<Bone>, ParseError>>()?;
+        return Ok(BoneList{scale: scale, bones: bones})
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = self.scale.to_bits().to_be_bytes().to_vec();
+        out.extend_from_slice(&(self.bones.len() as u16).to_be_bytes());
+        out.extend_from_slice(&[0; 2]);
+        for b in self.bones.iter(){
+            for p in b.position.iter(){
+                out.extend_from_slice(&p.to_bits().to_be_bytes());
+            }
+            out.extend_from_slice(&b.id.to_be_bytes());
+            out.extend_from_slice(&b.parent.to_be_bytes());
+        }
+        return out
+    }
+
+    /// a `bones:` mapping written by the model descriptor, vertex ranges are ignored
+    fn from_yaml(yaml: &Yaml) -> BoneList{
+        BoneList{
+            scale: yaml_f32(&yaml["scale"]).expect("bones need a scale"),
+            bones: yaml["list"].as_vec().expect("bones need a list").iter()
+                .map(|b|{
+                    let p = b["position"].as_vec().filter(|p|{p.len() == 3}).expect("bone positions are [x, y, z]");
+                    Bone{
+                        position: [0, 1, 2].map(|i|{yaml_f32(&p[i]).expect("bone positions are numbers")}),
+                        id: b["id"].as_i64().expect("bones need an id") as u16,
+                        parent: b["parent"].as_i64().expect("bones need a parent, -1 for none") as i16,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// a group of vertices that code moves or recolors, e.g. waving water, picked by id
+#[derive(Debug, Clone)]
+pub struct EffectMesh{
+    pub id : u16,
+    pub vertices : Vec<u16>,
+}
+
+impl EffectMesh{
+    /// the effects section, a u16 count followed by each mesh's id, vertex count and vertex indices
+    pub fn list_from_bytes(in_bytes: &[u8]) -> Result<Vec<EffectMesh>, ParseError>{
+        let mut r = ByteReader::new(in_bytes);
+        let mesh_cnt = r.u16()?;
+        (0..mesh_cnt).map(|_|{
+            let id = r.u16()?;
+            let count = r.u16()? as usize;
+            let vertices = (0..count).map(|_|{r.u16()}).collect::<Result<Vec<u16>, ParseError>>()?;
+            Ok(EffectMesh{id: id, vertices: vertices})
+        }).collect()
+    }
+
+    pub fn list_to_bytes(meshes: &[EffectMesh]) -> Vec<u8>{
+        let mut out = (meshes.len() as u16).to_be_bytes().to_vec();
+        for m in meshes.iter(){
+            out.extend_from_slice(&m.id.to_be_bytes());
+            out.extend_from_slice(&(m.vertices.len() as u16).to_be_bytes());
+            for v in m.vertices.iter(){
+                out.extend_from_slice(&v.to_be_bytes());
+            }
+        }
+        return out
+    }
+}
+
+/// texture frames the game steps through in segment memory, for animated water and lava
+#[derive(Debug, Clone)]
+pub struct AnimatedTexture{
+    pub frame_size : u16, //bytes per frame
+    pub frame_count : u16,
+    pub fps : f32,
+}
+
+impl AnimatedTexture{
+    pub const SIZE : usize = 0x8;
+    pub const LIST_COUNT : usize = 4;
+
+    pub fn list_from_bytes(in_bytes: &[u8]) -> Result<Vec<AnimatedTexture>, ParseError>{
+        let list = ByteReader::new(in_bytes).take(AnimatedTexture::SIZE*AnimatedTexture::LIST_COUNT)?;
+        return Ok(list.chunks_exact(AnimatedTexture::SIZE)
+            .map(|t|{AnimatedTexture{frame_size: u16_at(t, 0), frame_count: u16_at(t, 2), fps: f32::from_bits(u32_at(t, 4))}})
+            .collect())
+    }
+
+    pub fn list_to_bytes(textures: &[AnimatedTexture]) -> Vec<u8>{
+        assert_eq!(textures.len(), AnimatedTexture::LIST_COUNT, "models have {} animated texture slots", AnimatedTexture::LIST_COUNT);
+        let mut out : Vec<u8> = Vec::new();
+        for t in textures.iter(){
+            out.extend_from_slice(&t.frame_size.to_be_bytes());
+            out.extend_from_slice(&t.frame_count.to_be_bytes());
+            out.extend_from_slice(&t.fps.to_bits().to_be_bytes());
+        }
+        return out
+    }
+}
+
+/// draw triangles in G_VTX loads that fit the vertex buffer, each load's vertices
+/// are appended to `vertices` so every load is one contiguous range
+fn batch_triangles<K : PartialEq + Copy>(tris: &[[K; 3]], make_vertex: &dyn Fn(K)->Vertex, vertices: &mut Vec<Vertex>, cmds: &mut Vec<Gfx>){
+    let mut batch : Vec<K> = Vec::new();
+    let mut slots : Vec<[usize; 3]> = Vec::new();
+    let flush = |batch : &mut Vec<K>, slots : &mut Vec<[usize; 3]>, vertices : &mut Vec<Vertex>, cmds : &mut Vec<Gfx>|{
+        if slots.is_empty(){
+            return;
+        }
+        cmds.push(Gfx::new_vtx(0, batch.len(), 0x01, vertices.len()*Vertex::SIZE));
+        vertices.extend(batch.iter().map(|k|{make_vertex(*k)}));
+        for pair in slots.chunks(2){
+            cmds.push(match pair{
+                [a, b] => Gfx::new_tri2(*a, *b),
+                _ => Gfx::new_tri1(pair[0]),
+            });
+        }
+        batch.clear();
+        slots.clear();
+    };
+    for tri in tris.iter(){
+        let missing = tri.iter().filter(|c|{!batch.contains(c)}).count();
+        if batch.len() + missing > gfx::VTX_BUFFER_SIZE{
+            flush(&mut batch, &mut slots, vertices, cmds);
+        }
+        slots.push(tri.map(|c|{
+            match batch.iter().position(|b|{*b == c}){
+                Some(slot) => slot,
+                None => {batch.push(c); batch.len() - 1},
+            }
+        }));
+    }
+    flush(&mut batch, &mut slots, vertices, cmds);
+}
+
+/// `r g b a` lines written next to an exported obj
+fn read_vcol(path: &Path) -> Vec<[u8; 4]>{
+    return deps::read_to_string(path).expect("could not read vertex colors")
+        .lines()
+        .filter(|l|{!l.trim().is_empty() && !l.starts_with('#')})
+        .map(|l|{
+            let c : Vec<u8> = l.split_whitespace().map(|w|{w.parse::<u8>().expect("vertex colors are 0-255")}).collect();
+            [c[0], c[1], c[2], *c.get(3).unwrap_or(&0xFF)]
+        })
+        .collect()
+}
+
+/// N64 format a texture fits in TMEM as, downscaling it if it has to
+fn fit_texture(i: usize, w: usize, h: usize, rgba32: &[u8], report: &mut Vec<String>) -> Texture{
+    let color_cnt = Texture::color_count(rgba32);
+    let format = match color_cnt{
+        c if c <= 0x10 => ImgFmt::CI4,
+        c if c <= 0x100 => ImgFmt::CI8,
+        _ => ImgFmt::RGBA16,
+    };
+    //power of two sizes so the texture can wrap, and the entry stores sizes as a byte
+    let pow2 = |x : usize|{std::cmp::min(1usize << (usize::BITS - 1 - x.max(1).leading_zeros()), 0x80)};
+    let (mut new_w, mut new_h) = (pow2(w), pow2(h));
+    while Texture::validate_tmem(new_w, new_h, &format).is_err(){
+        match new_w >= new_h{
+            true => new_w /= 2,
+            false => new_h /= 2,
+        }
+    }
+    if (new_w, new_h) != (w, h){
+        report.push(format!("texture {}: {}x{} resized to {}x{} {:?}", i, w, h, new_w, new_h, format));
+    }
+    if format == ImgFmt::RGBA16{
+        report.push(format!("texture {}: {} colors reduced to 5 bits per channel", i, color_cnt));
+    }
+    return Texture::from_rgba32(format, new_w, new_h, &texture::resize_rgba32(rgba32, w, h, new_w, new_h))
+}
+
+/// commands loading a texture (and its palette) into TMEM and setting up the render tile
+fn texture_load_cmds(tex: &Texture, data_offset: usize) -> Vec<Gfx>{
+    const FMT_RGBA : u8 = 0;
+    const FMT_CI : u8 = 2;
+    const TEXTLUT_SHIFT : usize = 14;
+    const TT_RGBA16 : u32 = 0x8000;
+    let (w, h) = (tex.width(), tex.height());
+    let (fmt, siz, palette_cnt) = match tex.format(){
+        ImgFmt::CI4 => (FMT_CI, 0, 0x10),
+        ImgFmt::CI8 => (FMT_CI, 1, 0x100),
+        ImgFmt::RGBA16 => (FMT_RGBA, 2, 0),
+        x => panic!("{:?} textures can't be loaded by built models", x),
+    };
+    let mut cmds : Vec<Gfx> = Vec::new();
+    if palette_cnt > 0{
+        cmds.push(Gfx::new_set_timg(FMT_RGBA, 2, 1, 0x02, data_offset));
+        cmds.push(Gfx::new_sync(gfx::G_RDPTILESYNC));
+        cmds.push(Gfx::new_set_tile(0, 0, 0, 0x100, gfx::G_TX_LOADTILE, 0, 0, 0));
+        cmds.push(Gfx::new_sync(gfx::G_RDPLOADSYNC));
+        cmds.push(Gfx::new_load_tlut(gfx::G_TX_LOADTILE, palette_cnt));
+        cmds.push(Gfx::new_sync(gfx::G_RDPPIPESYNC));
+        cmds.push(Gfx::new_set_other_mode_h(TEXTLUT_SHIFT, 2, TT_RGBA16));
+    }
+    else{
+        cmds.push(Gfx::new_set_other_mode_h(TEXTLUT_SHIFT, 2, 0));
+    }
+    //texels are loaded as 16 bit words, like gsDPLoadTextureBlock
+    let texel_offset = data_offset + 2*palette_cnt;
+    let bits = tex.format().bits_per_texel();
+    let words_per_line = std::cmp::max(1, w*bits/64);
+    let dxt = (2048 + words_per_line - 1)/words_per_line;
+    let lrs = (w*h*bits/16 + if bits < 16 {1} else {0}) - 1;
+    let log2 = |x : usize|{(usize::BITS - 1 - x.leading_zeros()) as usize};
+    cmds.push(Gfx::new_set_timg(fmt, 2, 1, 0x02, texel_offset));
+    cmds.push(Gfx::new_set_tile(fmt, 2, 0, 0, gfx::G_TX_LOADTILE, 0, 0, 0));
+    cmds.push(Gfx::new_sync(gfx::G_RDPLOADSYNC));
+    cmds.push(Gfx::new_load_block(gfx::G_TX_LOADTILE, lrs, dxt));
+    cmds.push(Gfx::new_sync(gfx::G_RDPPIPESYNC));
+    cmds.push(Gfx::new_set_tile(fmt, siz, (w*bits/8 + 7)/8, 0, gfx::G_TX_RENDERTILE, 0, log2(w), log2(h)));
+    cmds.push(Gfx::new_set_tile_size(gfx::G_TX_RENDERTILE, w, h));
+    cmds.push(gfx::CC_MODULATE_RGBA);
+    cmds.push(Gfx::new_texture(true));
+    return cmds
+}
+
+pub struct Model{
+    bytes: Vec<u8>,
+    pub header: Option<ModelHeader>, //None for models without the 0x0B header
+}
+
+impl Model{
+    pub fn from_bytes(in_bytes: &[u8])->Model{
+        Model{bytes: in_bytes.to_vec(), header: ModelHeader::from_bytes(in_bytes)}
+    }
+
+    /// `from_bytes`, after checking every section the header points at can be read.
+    /// The triangle walk isn't part of it, `validate` reports on that
+    pub fn try_from_bytes(in_bytes: &[u8]) -> Result<Model, ParseError>{
+        let model = Model::from_bytes(in_bytes);
+        for t in model.textures()?{
+            model.texture(&t)?;
+        }
+        model.texture_data()?;
+        model.gfx()?;
+        model.vertices()?;
+        model.bones()?;
+        model.effects()?;
+        model.animated_textures()?;
+        model.collision()?;
+        model.geo_layout()?;
+        return Ok(model)
+    }
+
+    /// a model drawn by one display list, built from a glTF scene scaled by `scale`.
+    /// Everything that had to be downgraded is listed in the returned report.
+    /// A scene with more triangles or vertices than the header can count is an error
+    pub fn from_gltf(mesh: &GltfMesh, scale: f32) -> Result<(Model, Vec<String>), ParseError>{
+        if mesh.triangles.len() > u16::MAX as usize{
+            return Err(ParseError::Invalid(format!("scene has {} triangles, a model holds at most {}", mesh.triangles.len(), u16::MAX)));
+        }
+        let mut report = mesh.report.clone();
+        let textures : Vec<Texture> = mesh.images.iter()
+            .enumerate()
+            .map(|(i, (w, h, rgba32))|{fit_texture(i, *w, *h, rgba32, &mut report)})
+            .collect();
+
+        //texture segment, each texture's palette then texels
+        let mut tex_data : Vec<u8> = Vec::new();
+        let mut tex_offsets : Vec<usize> = Vec::new();
+        for t in textures.iter(){
+            tex_offsets.push(tex_data.len());
+            tex_data.append(&mut t.to_bytes());
+            tex_data.resize((tex_data.len() + 7) & !7, 0);
+        }
+
+        let clamp = |f : f32, clamped : &mut usize|->i16{
+            let r = f.round();
+            if r < i16::MIN as f32 || r > i16::MAX as f32{
+                *clamped += 1;
+            }
+            r.max(i16::MIN as f32).min(i16::MAX as f32) as i16
+        };
+        let mut clamped_positions = 0;
+        let mut clamped_uvs = 0;
+        let mut cmds : Vec<Gfx> = Vec::new();
+        let mut vertices : Vec<Vertex> = Vec::new();
+        let mut materials : Vec<Option<usize>> = mesh.triangles.iter().map(|t|{t.material}).collect();
+        materials.sort();
+        materials.dedup();
+        for material in materials{
+            let (w, h) = match material{
+                Some(i) => {
+                    cmds.append(&mut texture_load_cmds(&textures[i], tex_offsets[i]));
+                    (textures[i].width() as f32, textures[i].height() as f32)
+                },
+                None => {
+                    cmds.push(gfx::CC_SHADE);
+                    cmds.push(Gfx::new_texture(false));
+                    (0.0, 0.0)
+                },
+            };
+            let tris : Vec<[usize; 3]> = mesh.triangles.iter().filter(|t|{t.material == material}).map(|t|{t.vertices}).collect();
+            let make_vertex = |v : usize|{
+                let (p, uv, c) = (mesh.positions[v], mesh.uvs[v], mesh.colors[v]);
+                let (mut cp, mut cuv) = (0, 0);
+                let vtx = Vertex{
+                    position: [clamp(p[0]*scale, &mut cp), clamp(p[1]*scale, &mut cp), clamp(p[2]*scale, &mut cp)],
+                    flag: 0,
+                    st: [clamp(uv[0]*w*32.0, &mut cuv), clamp(uv[1]*h*32.0, &mut cuv)],
+                    color: c.map(|x|{(x.max(0.0).min(1.0)*255.0).round() as u8}),
+                };
+                (vtx, cp, cuv)
+            };
+            let mut batch_vertices : Vec<Vertex> = Vec::new();
+            batch_triangles(&tris, &|v|{make_vertex(v).0}, &mut batch_vertices, &mut cmds);
+            for v in tris.iter().flatten(){
+                let (_, cp, cuv) = make_vertex(*v);
+                clamped_positions += cp;
+                clamped_uvs += cuv;
+            }
+            //batch_triangles addresses vertices from the start of the store
+            let base = vertices.len();
+            if base > 0{
+                for cmd in cmds.iter_mut().rev().take_while(|c|{c.opcode() != gfx::G_TEXTURE && c.opcode() != gfx::G_SETCOMBINE}){
+                    if cmd.opcode() == gfx::G_VTX{
+                        cmd.w1 += (base*Vertex::SIZE) as u32;
+                    }
+                }
+            }
+            vertices.append(&mut batch_vertices);
+        }
+        cmds.push(Gfx::new_end_dl());
+        if vertices.len() > u16::MAX as usize{
+            return Err(ParseError::Invalid(format!("scene needs {} vertices, a model holds at most {}", vertices.len(), u16::MAX)));
+        }
+        if clamped_positions > 0{
+            report.push(format!("{} vertex coordinates clamped to 16 bits, try a smaller scale", clamped_positions));
+        }
+        if clamped_uvs > 0{
+            report.push(format!("{} texture coordinates clamped to the s10.5 range", clamped_uvs));
+        }
+
+        //header, texture list, display list, vertex store, geo layout
+        let mut bytes = vec![0u8; ModelHeader::SIZE];
+        let texture_list = bytes.len();
+        bytes.extend_from_slice(&(tex_data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&(textures.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0; 2]);
+        for (t, offset) in textures.iter().zip(tex_offsets.iter()){
+            bytes.extend_from_slice(&(*offset as u32).to_be_bytes());
+            bytes.extend_from_slice(&t.format().model_code().to_be_bytes());
+            bytes.extend_from_slice(&[0; 2]);
+            bytes.push(t.width() as u8);
+            bytes.push(t.height() as u8);
+            bytes.extend_from_slice(&[0; 6]);
+        }
+        bytes.append(&mut tex_data);
+        bytes.resize((bytes.len() + 7) & !7, 0);
+        let display_list = bytes.len();
+        bytes.extend_from_slice(&(cmds.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        for cmd in cmds.iter(){
+            bytes.append(&mut cmd.to_bytes());
+        }
+        let vertex_store = bytes.len();
+        bytes.append(&mut VertexStoreHeader::from_vertices(&vertices).to_bytes());
+        for v in vertices.iter(){
+            bytes.append(&mut v.to_bytes());
+        }
+        bytes.resize((bytes.len() + 7) & !7, 0);
+        let geo_layout = bytes.len();
+        bytes.extend_from_slice(&geo::GEO_LOAD_DL.to_be_bytes());
+        bytes.extend_from_slice(&[0; 12]); //last command, drawing list 0
+
+        let header = ModelHeader{
+            geo_layout: Some(geo_layout),
+            texture_list: Some(texture_list),
+            geo_type: 0,
+            display_list: Some(display_list),
+            vertex_store: Some(vertex_store),
+            unk14: 0,
+            bone_list: None,
+            collision: None,
+            unk20: None,
+            effects: None,
+            unk28: 0,
+            animated_textures: None,
+            tri_count: mesh.triangles.len() as u16,
+            vertex_count: vertices.len() as u16,
+            unk34: 0.0,
+        };
+        bytes[..ModelHeader::SIZE].copy_from_slice(&header.to_bytes());
+        return Ok((Model::from_bytes(&bytes), report))
+    }
+
+    /// a raw `.model.bin`, a `.model.yaml` whose texture pngs replace the textures of its bin,
+    /// or a `.model.yaml` built from a glTF scene
+    pub fn read(path: &Path, opts: &ImportOptions) -> Model{
+        if path.extension().unwrap() == "bin"{
+            return Model::from_bytes(&deps::read(path).unwrap())
+        }
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        assert_eq!(doc["type"].as_str().unwrap(), "Model");
+        let containing_folder = path.parent().unwrap();
+        if let Some(gltf_path) = doc["gltf"].as_str(){
+            let scale = doc["scale"].as_f64().or(doc["scale"].as_i64().map(|s|{s as f64})).unwrap_or(1.0) as f32;
+            let (model, report) = Model::from_gltf(&GltfMesh::read(&containing_folder.join(gltf_path)), scale).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+            for line in report{
+                log::warning(None, &format!("{:?}: {}", path, line));
+            }
+            return model
+        }
+        let mut model = Model::from_bytes(&deps::read(containing_folder.join(doc["bin"].as_str().unwrap())).unwrap());
+
+        for y in doc["textures"].as_vec().map(|v|{v.as_slice()}).unwrap_or(&[]){
+            let i = y["index"].as_i64().unwrap() as usize;
+            model.replace_texture(i, &containing_folder.join(y["png"].as_str().unwrap()), opts).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+        }
+
+        //edited vertex positions, uvs and colors, applied before any rebuilt geometry
+        if let Some(csv_path) = doc["vertices"].as_str().map(|p|{containing_folder.join(p)}){
+            model.import_vertex_csv(&csv_path);
+        }
+
+        if !doc["bones"].is_badvalue(){
+            let offset = model.header.as_ref().and_then(|h|{h.bone_list}).unwrap_or_else(||{panic!("{:?}: model has no bone list", path)});
+            model.replace_section(offset, BoneList::from_yaml(&doc["bones"]).to_bytes());
+        }
+
+        if !doc["effects"].is_badvalue(){
+            let offset = model.header.as_ref().and_then(|h|{h.effects}).unwrap_or_else(||{panic!("{:?}: model has no effects", path)});
+            let vertex_cnt = model.vertices().unwrap_or_else(|e|{panic!("{:?}: {}", path, e)}).len();
+            let meshes : Vec<EffectMesh> = doc["effects"].as_vec().expect("effects are a list").iter()
+                .map(|m|{
+                    EffectMesh{
+                        id: m["id"].as_i64().expect("effects need an id") as u16,
+                        vertices: m["vertices"].as_vec().expect("effects need a vertex list").iter()
+                            .map(|v|{
+                                let v = v.as_i64().expect("effect vertices are indices") as usize;
+                                assert!(v < vertex_cnt, "{:?}: effect vertex {} is past the model's {} vertices", path, v, vertex_cnt);
+                                v as u16
+                            })
+                            .collect(),
+                    }
+                })
+                .collect();
+            model.replace_section(offset, EffectMesh::list_to_bytes(&meshes));
+        }
+        if !doc["animated_textures"].is_badvalue(){
+            let offset = model.header.as_ref().and_then(|h|{h.animated_textures}).unwrap_or_else(||{panic!("{:?}: model has no animated textures", path)});
+            let textures : Vec<AnimatedTexture> = doc["animated_textures"].as_vec().expect("animated textures are a list").iter()
+                .map(|t|{
+                    AnimatedTexture{
+                        frame_size: t["frame_size"].as_i64().expect("animated textures need a frame_size") as u16,
+                        frame_count: t["frame_count"].as_i64().expect("animated textures need a frame_count") as u16,
+                        fps: yaml_f32(&t["fps"]).expect("animated textures need an fps"),
+                    }
+                })
+                .collect();
+            let bytes = AnimatedTexture::list_to_bytes(&textures);
+            let end = offset + bytes.len();
+            let len = model.bytes.len();
+            model.bytes.get_mut(offset..end)
+                .unwrap_or_else(||{panic!("{:?}: {} animated textures run to 0x{:X}, past the model's 0x{:X} bytes", path, textures.len(), end, len)})
+                .copy_from_slice(&bytes);
+        }
+
+        //edited geo layout tree, before an obj import remaps its display list indices
+        if !doc["geo_layout"].is_badvalue(){
+            model.import_geo_layout(&geo::GeoNode::from_yaml_chain(&doc["geo_layout"]));
+        }
+
+        //geometry from an edited obj, with the vertex colors of its .vcol if there is one
+        assert!(doc["obj"].is_badvalue() || doc["gfx"].is_badvalue(), "{:?}: obj and gfx both replace the display lists, use one", path);
+        if let Some(obj_path) = doc["obj"].as_str().map(|p|{containing_folder.join(p)}){
+            let vcol_path = obj_path.with_extension("vcol");
+            let colors = match deps::exists(&vcol_path){
+                true => Some(read_vcol(&vcol_path)),
+                false => None,
+            };
+            model.import_obj(&ObjMesh::read(&obj_path), colors);
+        }
+
+        //hand edited display lists
+        if let Some(gfx_path) = doc["gfx"].as_str().map(|p|{containing_folder.join(p)}){
+            let text = deps::read_to_string(&gfx_path).expect("could not read gfx");
+            let cmds = gfx::assemble(&text).unwrap_or_else(|e|{panic!("{:?}: {}", gfx_path, e)});
+            model.import_gfx(&cmds);
+        }
+        return model
+    }
+
+    /// problems the game would crash or draw garbage on: vertex loads past the buffer
+    /// or the vertex store, segment addresses outside their section, textures that don't
+    /// fit their data or TMEM, and collision triangles past their tables or vertices
+    pub fn validate(&self) -> Vec<String>{
+        let mut errors : Vec<String> = Vec::new();
+        //a section that can't be read at all ends the checks
+        if let Err(e) = self.find_problems(&mut errors){
+            errors.push(e.to_string());
+        }
+        return errors
+    }
+
+    fn find_problems(&self, errors: &mut Vec<String>) -> Result<(), ParseError>{
+        let header = match self.header.as_ref(){
+            Some(h) => h,
+            None => return Ok(()),
+        };
+        let vertex_cnt = self.vertex_store()?.map(|s|{s.count}).unwrap_or(0);
+        let tex_data = self.texture_data()?;
+
+        for (i, t) in self.textures()?.iter().enumerate(){
+            if let ImgFmt::Unknown(code) = t.format{
+                errors.push(format!("texture {}: unknown format code 0x{:X}", i, code));
+                continue;
+            }
+            if let Err(e) = Texture::validate_tmem(t.w, t.h, &t.format){
+                errors.push(format!("texture {}: {}, split it or pick a smaller format", i, e));
+            }
+            if let Some(data) = tex_data.as_ref(){
+                if t.offset + t.byte_size() > data.end{
+                    errors.push(format!("texture {}: {:?} {}x{} runs 0x{:X} bytes past the texture data", i, t.format, t.w, t.h, t.offset + t.byte_size() - data.end));
+                }
+            }
+        }
+
+        if let (Some(offset), Some(dl)) = (header.display_list, self.display_list()?){
+            let available = (self.section_end(offset).saturating_sub(offset + DisplayListHeader::SIZE))/Gfx::SIZE;
+            if dl.count > available{
+                errors.push(format!("display list header says {} commands, but its section only holds {}", dl.count, available));
+            }
+        }
+        let cmds = self.gfx()?;
+        if cmds.last().map(|c|{c.opcode() != gfx::G_ENDDL}).unwrap_or(false){
+            errors.push(String::from("the last display list doesn't end with G_ENDDL"));
+        }
+        for (i, cmd) in cmds.iter().enumerate(){
+            match cmd.opcode(){
+                gfx::G_VTX => {
+                    let (v0, n) = cmd.vtx_range();
+                    if n == 0 || v0 + n > gfx::VTX_BUFFER_SIZE{
+                        errors.push(format!("gfx {:04X}: loads {} vertices into slot {}, past the {} vertex buffer", i, n, v0, gfx::VTX_BUFFER_SIZE));
+                    }
+                    let first = cmd.address_offset()/Vertex::SIZE;
+                    if cmd.segment() == 0x01 && first + n > vertex_cnt{
+                        errors.push(format!("gfx {:04X}: loads vertices {} to {}, but the store has {}", i, first, first + n, vertex_cnt));
+                    }
+                },
+                gfx::G_TRI1 | gfx::G_TRI2 => {
+                    let slots : Vec<usize> = match cmd.opcode(){
+                        gfx::G_TRI1 => cmd.tri1().to_vec(),
+                        _ => cmd.tri2().concat(),
+                    };
+                    if let Some(s) = slots.iter().find(|s|{**s >= gfx::VTX_BUFFER_SIZE}){
+                        errors.push(format!("gfx {:04X}: triangle uses vertex slot {}, past the {} vertex buffer", i, s, gfx::VTX_BUFFER_SIZE));
+                    }
+                },
+                gfx::G_SETTIMG if cmd.segment() == 0x02 => {
+                    let in_data = tex_data.as_ref().map(|d|{d.start + cmd.address_offset() < d.end}).unwrap_or(false);
+                    if !in_data{
+                        errors.push(format!("gfx {:04X}: texture address 0x{:08X} is outside the texture data", i, cmd.w1));
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if let (Some(offset), Some(col)) = (header.collision, self.collision()?){
+            let geo_start = offset + CollisionHeader::SIZE;
+            let tri_start = geo_start + 4*col.geo_count;
+            let end = tri_start + 0xC*col.tri_count;
+            if end > self.section_end(offset){
+                errors.push(format!("collision has {} geo cells and {} triangles, more than its section holds", col.geo_count, col.tri_count));
+                return Ok(());
+            }
+            for g in 0..col.geo_count{
+                let (start, count) = (u16_at(&self.bytes, geo_start + 4*g) as usize, u16_at(&self.bytes, geo_start + 4*g + 2) as usize);
+                if start + count > col.tri_count{
+                    errors.push(format!("collision cell {}: triangles {} to {} are past the {} triangles", g, start, start + count, col.tri_count));
+                }
+            }
+            for t in 0..col.tri_count{
+                let tri = tri_start + 0xC*t;
+                if let Some(v) = (0..3).map(|k|{u16_at(&self.bytes, tri + 2*k) as usize}).find(|v|{*v >= vertex_cnt}){
+                    errors.push(format!("collision triangle {}: vertex {} is past the store's {} vertices", t, v, vertex_cnt));
+                }
+            }
+        }
+        return Ok(())
+    }
+
+    /// encode a png in place of a texture, it has to keep the texture's size and format
+    fn texture_from_png(path: &Path, entry: &ModelTexture, opts: &ImportOptions) -> Vec<u8>{
+        let max_colors = match entry.format{
+            ImgFmt::CI4 => 0x10,
+            ImgFmt::CI8 => 0x100,
+            _ => usize::MAX,
+        };
+        //indexed pngs keep their palette order
+        if let (ImgFmt::CI4 | ImgFmt::CI8, Some((w, h, indices, colors))) = (entry.format, texture::read_png_indexed(path)){
+            assert!((w, h) == (entry.w, entry.h), "{:?} is {}x{}, the texture it replaces is {}x{}", path, w, h, entry.w, entry.h);
+            assert!(indices.iter().all(|i|{(*i as usize) < max_colors}), "{:?} uses palette indices past the {} colors {:?} holds", path, max_colors, entry.format);
+            let (colors, _) = Texture::threshold_alpha(&colors, opts.alpha_threshold);
+            let mut out = Texture::rgba32_to_rgba16(&colors);
+            out.resize(2*max_colors, 0);
+            match entry.format{
+                ImgFmt::CI4 => out.extend(indices.chunks_exact(2).map(|a|{(a[0] << 4) | (a[1] & 0xF)})),
+                _ => out.extend_from_slice(&indices),
+            }
+            return out
+        }
+
+        let (w, h, mut rgba32) = texture::read_png_rgba32(path);
+        assert!((w, h) == (entry.w, entry.h), "{:?} is {}x{}, the texture it replaces is {}x{}", path, w, h, entry.w, entry.h);
+        let color_cnt = Texture::color_count(&rgba32);
+        if color_cnt > max_colors{
+            assert!(!opts.strict_colors, "{:?} uses {} colors, but {:?} only holds {}", path, color_cnt, entry.format, max_colors);
+            log::warning(None, &format!("quantizing {:?} from {} to {} colors", path, color_cnt, max_colors));
+            rgba32 = Texture::quantize(&rgba32, max_colors);
+        }
+        return Texture::from_rgba32(entry.format, w, h, &rgba32).to_bytes()
+    }
+
+    /// texture `i` of the texture list encoded from a png of the same size, in
+    /// place. Nothing else moves, so the vertices and display list stay as they are.
+    /// The display list loads each texture with its size, so a png of another size
+    /// is refused before it's decoded
+    pub fn replace_texture(&mut self, i: usize, png_path: &Path, opts: &ImportOptions) -> Result<ModelTexture, String>{
+        let entries = self.textures().map_err(|e|{format!("model textures can't be read: {}", e)})?;
+        let entry = entries.get(i).ok_or_else(||{format!("model has no texture {}, it has {}", i, entries.len())})?;
+        let (w, h) = texture::png_size(png_path);
+        if (w, h) != (entry.w, entry.h){
+            return Err(format!("{:?} is {}x{}, texture {} it replaces is {}x{}, and textures can only be replaced by ones of the same size", png_path, w, h, i, entry.w, entry.h))
+        }
+        self.texture(entry).map_err(|e|{format!("texture {} can't be read: {}", i, e)})?;
+        let bytes = Model::texture_from_png(png_path, entry, opts);
+        assert!(bytes.len() == entry.byte_size(), "{:?} encodes to 0x{:X} bytes, the texture it replaces is 0x{:X}", png_path, bytes.len(), entry.byte_size());
+        self.bytes[entry.offset..entry.offset + bytes.len()].copy_from_slice(&bytes);
+        return Ok(entry.clone())
+    }
+
+    /// the section at `offset` read with `read`, errors give offsets into the model
+    fn read_section<T>(&self, offset: Option<usize>, read: fn(&[u8]) -> Result<T, ParseError>) -> Result<Option<T>, ParseError>{
+        let offset = match offset{
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let bytes = self.bytes.get(offset..)
+            .ok_or_else(||{ParseError::Invalid(format!("section at 0x{:X} starts past the end of the model, 0x{:X}", offset, self.bytes.len()))})?;
+        return read(bytes).map(Some).map_err(|e|{e.shifted(offset)})
+    }
+
+    pub fn texture_list(&self) -> Result<Option<TextureListHeader>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.texture_list}), TextureListHeader::from_bytes)
+    }
+
+    /// start of the texture data, segment 2 addresses are relative to it
+    fn texture_data_start(&self) -> Result<Option<usize>, ParseError>{
+        let (offset, list) = match (self.header.as_ref().and_then(|h|{h.texture_list}), self.texture_list()?){
+            (Some(offset), Some(list)) => (offset, list),
+            _ => return Ok(None),
+        };
+        return Ok(Some(offset + TextureListHeader::SIZE + list.count*ModelTexture::SIZE))
+    }
+
+    /// the texture data as a range of the model
+    fn texture_data(&self) -> Result<Option<std::ops::Range<usize>>, ParseError>{
+        let (start, list) = match (self.texture_data_start()?, self.texture_list()?){
+            (Some(start), Some(list)) => (start, list),
+            _ => return Ok(None),
+        };
+        ByteReader::at(&self.bytes, start).take(list.data_size)?;
+        return Ok(Some(start..start + list.data_size))
+    }
+
+    /// every texture of the texture list, in list order
+    pub fn textures(&self) -> Result<Vec<ModelTexture>, ParseError>{
+        let (list_offset, list, data_start) = match (self.header.as_ref().and_then(|h|{h.texture_list}), self.texture_list()?, self.texture_data_start()?){
+            (Some(offset), Some(list), Some(data_start)) => (offset, list, data_start),
+            _ => return Ok(Vec::new()),
+        };
+        let mut r = ByteReader::at(&self.bytes, list_offset + TextureListHeader::SIZE);
+        return (0..list.count)
+            .map(|_|{
+                let entry = r.take(ModelTexture::SIZE)?;
+                Ok(ModelTexture{
+                    offset: data_start + u32_at(entry, 0) as usize,
+                    format: ImgFmt::from_model_code(u16_at(entry, 4)),
+                    w: entry[8] as usize,
+                    h: entry[9] as usize,
+                })
+            })
+            .collect()
+    }
+
+    pub fn texture(&self, entry: &ModelTexture) -> Result<Texture, ParseError>{
+        let data = ByteReader::at(&self.bytes, entry.offset).take(entry.byte_size())?;
+        return Ok(Texture::new(entry.format, entry.w, entry.h, data))
+    }
+
+    pub fn display_list(&self) -> Result<Option<DisplayListHeader>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.display_list}), DisplayListHeader::from_bytes)
+    }
+
+    /// every command of the display list segment
+    pub fn gfx(&self) -> Result<Vec<Gfx>, ParseError>{
+        let (offset, list) = match (self.header.as_ref().and_then(|h|{h.display_list}), self.display_list()?){
+            (Some(offset), Some(list)) => (offset, list),
+            _ => return Ok(Vec::new()),
+        };
+        let cmds = ByteReader::at(&self.bytes, offset + DisplayListHeader::SIZE).take(list.count*Gfx::SIZE)?;
+        return Ok(cmds.chunks_exact(Gfx::SIZE)
+            .map(|g|{Gfx::from_bytes(g)})
+            .collect())
+    }
+
+    /// every vertex of the vertex store, segment 1 addresses are relative to the first
+    pub fn vertices(&self) -> Result<Vec<Vertex>, ParseError>{
+        let (offset, store) = match (self.header.as_ref().and_then(|h|{h.vertex_store}), self.vertex_store()?){
+            (Some(offset), Some(store)) => (offset, store),
+            _ => return Ok(Vec::new()),
+        };
+        let mut r = ByteReader::at(&self.bytes, offset + VertexStoreHeader::SIZE);
+        return (0..store.count)
+            .map(|_|{Vertex::from_bytes(r.take(Vertex::SIZE)?)})
+            .collect()
+    }
+
+    /// one row per vertex of the vertex store. Lit display lists read r g b as a signed normal.
+    pub fn write_vertex_csv(&self, path: &Path) -> Result<(), ParseError>{
+        let vertices = self.vertices()?;
+        let mut csv_f = deps::create(path).unwrap();
+        writeln!(csv_f, "index,x,y,z,flag,s,t,r,g,b,a").unwrap();
+        for (i, v) in vertices.iter().enumerate(){
+            writeln!(csv_f, "{},{},{},{},{},{},{},{},{},{},{}", i,
+                v.position[0], v.position[1], v.position[2], v.flag, v.st[0], v.st[1],
+                v.color[0], v.color[1], v.color[2], v.color[3]
+            ).unwrap();
+        }
+        return Ok(())
+    }
+
+    /// overwrite the vertex store from a `write_vertex_csv` file. The vertex count can't
+    /// change, and the store's bounds are only recomputed if a position moved.
+    pub fn import_vertex_csv(&mut self, path: &Path){
+        let text = deps::read_to_string(path).expect("could not read vertex csv");
+        let old = self.vertices().unwrap_or_else(|e|{panic!("{:?}: model vertices can't be read: {}", path, e)});
+        let mut new : Vec<Vertex> = old.clone();
+        for (line_i, line) in text.lines().enumerate().skip(1).filter(|(_, l)|{!l.trim().is_empty()}){
+            let fields : Vec<i64> = line.split(',')
+                .map(|f|{f.trim().parse::<i64>().unwrap_or_else(|_|{panic!("{:?} line {}: bad number \"{}\"", path, line_i + 1, f)})})
+                .collect();
+            assert_eq!(fields.len(), 11, "{:?} line {}: expected index,x,y,z,flag,s,t,r,g,b,a", path, line_i + 1);
+            let i = fields[0] as usize;
+            assert!(i < old.len(), "{:?} line {}: vertex {} is past the {} in the model, the vertex count can't change", path, line_i + 1, i, old.len());
+            let in_range = |f : i64, min : i64, max : i64|->i64{
+                assert!(min <= f && f <= max, "{:?} line {}: {} is outside {}..={}", path, line_i + 1, f, min, max);
+                f
+            };
+            let s16 = |f : i64|{in_range(f, i16::MIN as i64, i16::MAX as i64) as i16};
+            let u8 = |f : i64|{in_range(f, 0, 0xFF) as u8};
+            new[i] = Vertex{
+                position: [s16(fields[1]), s16(fields[2]), s16(fields[3])],
+                flag: in_range(fields[4], 0, 0xFFFF) as u16,
+                st: [s16(fields[5]), s16(fields[6])],
+                color: [u8(fields[7]), u8(fields[8]), u8(fields[9]), u8(fields[10])],
+            };
+        }
+        let offset = self.header.as_ref().and_then(|h|{h.vertex_store}).expect("model has no vertex store");
+        let start = offset + VertexStoreHeader::SIZE;
+        for (i, v) in new.iter().enumerate(){
+            self.bytes[start + i*Vertex::SIZE..start + (i + 1)*Vertex::SIZE].copy_from_slice(&v.to_bytes());
+        }
+        if old.iter().zip(new.iter()).any(|(a, b)|{a.position != b.position}){
+            let store = VertexStoreHeader::from_vertices(&new).to_bytes();
+            self.bytes[offset..offset + VertexStoreHeader::SIZE].copy_from_slice(&store);
+        }
+    }
+
+    /// triangles of every display list, walked in order so the vertex buffer and texture state carry over.
+    /// Loads past the vertex buffer and triangles on slots no vertex of the store was loaded into are errors.
+    pub fn triangles(&self) -> Result<Vec<Triangle>, ParseError>{
+        let textures = self.textures()?;
+        let data_start = self.texture_data_start()?.unwrap_or(0);
+        let vertex_cnt = self.vertex_store()?.map(|s|{s.count}).unwrap_or(0);
+        let mut buffer : [Option<usize>; gfx::VTX_BUFFER_SIZE] = [None; gfx::VTX_BUFFER_SIZE];
+        let mut texture : Option<usize> = None;
+        let mut tex_scale = [1.0, 1.0];
+        let mut dl = 0;
+        let mut out : Vec<Triangle> = Vec::new();
+        for (i, cmd) in self.gfx()?.into_iter().enumerate(){
+            let lookup = |slots : [usize; 3]|->Result<[usize; 3], ParseError>{
+                let mut vertices = [0; 3];
+                for (v, s) in vertices.iter_mut().zip(slots.iter()){
+                    *v = buffer.get(*s).copied().flatten()
+                        .ok_or_else(||{ParseError::Invalid(format!("gfx {:04X}: triangle uses vertex slot {}, which holds no vertex of the store", i, s))})?;
+                }
+                Ok(vertices)
+            };
+            match cmd.opcode(){
+                gfx::G_VTX => {
+                    let (v0, n) = cmd.vtx_range();
+                    if v0 + n > gfx::VTX_BUFFER_SIZE{
+                        return Err(ParseError::Invalid(format!("gfx {:04X}: loads {} vertices into slot {}, past the {} vertex buffer", i, n, v0, gfx::VTX_BUFFER_SIZE)));
+                    }
+                    let base = cmd.address_offset()/Vertex::SIZE;
+                    let from_store = cmd.segment() == 0x01 && base + n <= vertex_cnt;
+                    for (k, slot) in buffer[v0..v0 + n].iter_mut().enumerate(){
+                        *slot = match from_store{
+                            true => Some(base + k),
+                            false => None,
+                        };
+                    }
+                },
+                gfx::G_SETTIMG => {
+                    //palette and texel loads both land inside the texture they belong to
+                    let addr = data_start + cmd.address_offset();
+                    texture = textures.iter()
+                        .enumerate()
+                        .filter(|(_, t)|{t.offset <= addr})
+                        .max_by_key(|(_, t)|{t.offset})
+                        .map(|(i, _)|{i});
+                },
+                gfx::G_TEXTURE => {
+                    tex_scale = [(cmd.w1 >> 16) as f32 / 65536.0, (cmd.w1 & 0xFFFF) as f32 / 65536.0];
+                },
+                gfx::G_TRI1 => {
+                    out.push(Triangle{vertices: lookup(cmd.tri1())?, texture: texture, tex_scale: tex_scale, dl: dl});
+                },
+                gfx::G_TRI2 => {
+                    for slots in cmd.tri2(){
+                        out.push(Triangle{vertices: lookup(slots)?, texture: texture, tex_scale: tex_scale, dl: dl});
+                    }
+                },
+                gfx::G_ENDDL => dl += 1,
+                _ => {},
+            }
+        }
+        return Ok(out)
+    }
+
+    /// writes each texture as a png in `parent/folder_name`, returning png names relative to `parent`
+    fn write_texture_pngs(&self, parent: &Path, folder_name: &str) -> Result<Vec<(usize, ModelTexture, String)>, ParseError>{
+        let textures : Vec<(usize, ModelTexture, Texture)> = self.textures()?.into_iter()
+            .enumerate()
+            .filter(|(_, t)|{!matches!(t.format, ImgFmt::Unknown(_))})
+            .map(|(i, t)|{Ok((i, t.clone(), self.texture(&t)?))})
+            .collect::<Result<_, ParseError>>()?;
+        if textures.is_empty(){
+            return Ok(Vec::new());
+        }
+        deps::create_dir_all(parent.join(folder_name)).unwrap();
+        return Ok(textures.into_iter()
+            .map(|(i, entry, tex)|{
+                let fmt_str = format!("{:?}", entry.format).to_lowercase();
+                let png_name = format!("{}/{:02X}.{}.png", folder_name, i, fmt_str);
+                texture::write_png_rgba32(&parent.join(&png_name), entry.w, entry.h, &tex.to_rgba32());
+                (i, entry, png_name)
+            })
+            .collect())
+    }
+
+    /// geometry as an obj with one material per texture, plus the .mtl and a
+    /// `.vcol` of per vertex colors in the same order as the obj's vertices
+    pub fn write_obj(&self, obj_path: &Path) -> Result<(), ParseError>{
+        let containing_folder = obj_path.parent().unwrap();
+        let stem = obj_path.file_stem().unwrap().to_str().unwrap();
+        let vertices = self.vertices()?;
+        let mut triangles = self.triangles()?;
+        let textures = self.write_texture_pngs(containing_folder, &format!("{}.textures", stem))?;
+
+        let mtl_name = format!("{}.mtl", stem);
+        let mut mtl_f = deps::create(containing_folder.join(&mtl_name)).unwrap();
+        writeln!(mtl_f, "newmtl untextured").unwrap();
+        writeln!(mtl_f, "Kd 1.0 1.0 1.0\n").unwrap();
+        for (i, _, png_name) in textures.iter(){
+            writeln!(mtl_f, "newmtl tex_{:02X}", i).unwrap();
+            writeln!(mtl_f, "Kd 1.0 1.0 1.0").unwrap();
+            writeln!(mtl_f, "map_Kd {}\n", png_name).unwrap();
+        }
+
+        let mut vcol_f = deps::create(containing_folder.join(format!("{}.vcol", stem))).unwrap();
+        writeln!(vcol_f, "# r g b a of each v in {}.obj", stem).unwrap();
+        let mut obj_f = deps::create(obj_path).unwrap();
+        writeln!(obj_f, "mtllib {}", mtl_name).unwrap();
+        for v in vertices.iter(){
+            writeln!(obj_f, "v {} {} {}", v.position[0], v.position[1], v.position[2]).unwrap();
+            writeln!(vcol_f, "{} {} {} {}", v.color[0], v.color[1], v.color[2], v.color[3]).unwrap();
+        }
+
+        //one texture coordinate per corner, st depends on the texture drawn with
+        triangles.sort_by_key(|t|{(t.dl, t.texture.map(|i|{i as isize}).unwrap_or(-1))});
+        let mut vt_cnt = 0;
+        let mut dl : Option<usize> = None;
+        let mut material : Option<Option<usize>> = None;
+        for tri in triangles.iter(){
+            let size = tri.texture.and_then(|i|{textures.iter().find(|(j, _, _)|{*j == i})}).map(|(_, t, _)|{(t.w as f32, t.h as f32)});
+            //objects are the display lists the geo layout draws
+            if dl != Some(tri.dl){
+                writeln!(obj_f, "o dl_{:02X}", tri.dl).unwrap();
+                dl = Some(tri.dl);
+                material = None;
+            }
+            if material != Some(tri.texture){
+                match (tri.texture, size){
+                    (Some(i), Some(_)) => writeln!(obj_f, "g tex_{:02X}\nusemtl tex_{:02X}", i, i).unwrap(),
+                    _ => writeln!(obj_f, "g untextured\nusemtl untextured").unwrap(),
+                }
+                material = Some(tri.texture);
+            }
+            let (w, h) = size.unwrap_or((32.0, 32.0));
+            for v in tri.vertices.iter(){
+                let st = vertices[*v].st;
+                let u = st[0] as f32 * tri.tex_scale[0] / 32.0 / w;
+                let t = st[1] as f32 * tri.tex_scale[1] / 32.0 / h;
+                writeln!(obj_f, "vt {} {}", u, 1.0 - t).unwrap();
+            }
+            let corners : Vec<String> = tri.vertices.iter()
+                .enumerate()
+                .map(|(k, v)|{format!("{}/{}", v + 1, vt_cnt + k + 1)})
+                .collect();
+            writeln!(obj_f, "f {}", corners.join(" ")).unwrap();
+            vt_cnt += 3;
+        }
+        return Ok(())
+    }
+
+    /// start index of each G_ENDDL terminated list. Commands after the last
+    /// G_ENDDL are a list of their own
+    fn dl_starts(&self) -> Result<Vec<usize>, ParseError>{
+        let cmds = self.gfx()?;
+        let mut starts = if cmds.is_empty() {Vec::new()} else {vec![0]};
+        for (i, cmd) in cmds.iter().enumerate(){
+            if cmd.opcode() == gfx::G_ENDDL && i + 1 < cmds.len(){
+                starts.push(i + 1);
+            }
+        }
+        return Ok(starts)
+    }
+
+    /// the texture data, vertices and display lists as C for the decomp, named `<prefix>_tex`,
+    /// `<prefix>_vtx` and `<prefix>_dl_NN`. Segment addresses become pointers into those arrays.
+    pub fn write_c(&self, out: &mut dyn Write, prefix: &str) -> Result<(), ParseError>{
+        let tex_data = self.texture_data()?.map(|range|{&self.bytes[range]});
+        let texture_cnt = self.textures()?.len();
+        let vertices = self.vertices()?;
+        let cmds = self.gfx()?;
+        let starts = self.dl_starts()?;
+        writeln!(out, "#include <ultra64.h>\n").unwrap();
+        let tex_name = format!("{}_tex", prefix);
+        let vtx_name = format!("{}_vtx", prefix);
+        if let Some(data) = tex_data{
+            let comment = format!("{} textures, palettes before texels", texture_cnt);
+            texture::write_c_array(out, &tex_name, &comment, data);
+        }
+
+        if !vertices.is_empty(){
+            writeln!(out, "Vtx {}[] = {{", vtx_name).unwrap();
+            for v in vertices.iter(){
+                writeln!(out, "    {{{{{{{}, {}, {}}}, {}, {{{}, {}}}, {{{}, {}, {}, {}}}}}}},",
+                    v.position[0], v.position[1], v.position[2], v.flag, v.st[0], v.st[1],
+                    v.color[0], v.color[1], v.color[2], v.color[3]).unwrap();
+            }
+            writeln!(out, "}};\n").unwrap();
+        }
+
+        //lists stay in order so geo layout indices still count commands from the first one
+        let address = |w : u32|->String{
+            let offset = (w & 0x00FFFFFF) as usize;
+            match w >> 24{
+                0x01 if offset % Vertex::SIZE == 0 && offset/Vertex::SIZE < vertices.len() => format!("&{}[{}]", vtx_name, offset/Vertex::SIZE),
+                0x02 if tex_data.map(|d|{offset < d.len()}).unwrap_or(false) => format!("(u8*){} + 0x{:X}", tex_name, offset),
+                _ => format!("0x{:08X}", w),
+            }
+        };
+        for (n, start) in starts.iter().enumerate(){
+            let end = starts.get(n + 1).cloned().unwrap_or(cmds.len());
+            writeln!(out, "// commands 0x{:04X} to 0x{:04X}", start, end - 1).unwrap();
+            writeln!(out, "Gfx {}_dl_{:02X}[] = {{", prefix, n).unwrap();
+            for cmd in cmds[*start..end].iter(){
+                writeln!(out, "    {},", cmd.to_c(&address)).unwrap();
+            }
+            writeln!(out, "}};\n").unwrap();
+        }
+        return Ok(())
+    }
+
+    /// the state commands between geometry in each list, with the texture loaded after them.
+    /// The last entry of each list is what follows its final triangle.
+    fn material_setups(&self) -> Result<Vec<Vec<(Option<usize>, Vec<Gfx>)>>, ParseError>{
+        let textures = self.textures()?;
+        let data_start = self.texture_data_start()?.unwrap_or(0);
+        let mut out : Vec<Vec<(Option<usize>, Vec<Gfx>)>> = vec![Vec::new()];
+        let mut block : Vec<Gfx> = Vec::new();
+        let mut texture : Option<usize> = None;
+        for cmd in self.gfx()?{
+            match cmd.opcode(){
+                gfx::G_VTX | gfx::G_TRI1 | gfx::G_TRI2 => {
+                    if !block.is_empty(){
+                        out.last_mut().unwrap().push((texture, block.clone()));
+                        block.clear();
+                    }
+                },
+                gfx::G_ENDDL => {
+                    out.last_mut().unwrap().push((texture, block.clone()));
+                    block.clear();
+                    out.push(Vec::new());
+                },
+                op => {
+                    if op == gfx::G_SETTIMG{
+                        let addr = data_start + cmd.address_offset();
+                        texture = textures.iter()
+                            .enumerate()
+                            .filter(|(_, t)|{t.offset <= addr})
+                            .max_by_key(|(_, t)|{t.offset})
+                            .map(|(i, _)|{i});
+                    }
+                    block.push(cmd);
+                },
+            }
+        }
+        out.pop();
+        return Ok(out)
+    }
+
+    /// replace the geometry with an obj's, reusing the materials the display lists already set up.
+    /// Objects `dl_NN` pick the list a face is drawn by, materials `tex_NN` its texture.
+    /// The original vertices stay at the start of the store so collision keeps referencing them.
+    pub fn import_obj(&mut self, obj: &ObjMesh, colors: Option<Vec<[u8; 4]>>){
+        assert!(self.header.is_some(), "only models with a header can import geometry");
+        fn unreadable(e: ParseError) -> !{
+            panic!("the model's geometry can't be read: {}", e)
+        }
+        let textures = self.textures().unwrap_or_else(|e|{unreadable(e)});
+        let old_triangles = self.triangles().unwrap_or_else(|e|{unreadable(e)});
+        let setups = self.material_setups().unwrap_or_else(|e|{unreadable(e)});
+        let old_starts = self.dl_starts().unwrap_or_else(|e|{unreadable(e)});
+        assert!(!old_starts.is_empty(), "model has no display lists to rebuild");
+        let tex_scale = |t : Option<usize>|{
+            old_triangles.iter().find(|tri|{tri.texture == t}).map(|tri|{tri.tex_scale}).unwrap_or([1.0, 1.0])
+        };
+
+        let parse_name = |name : &Option<String>, prefix : &str|->Option<usize>{
+            name.as_ref()
+                .and_then(|n|{n.strip_prefix(prefix)})
+                .map(|n|{usize::from_str_radix(n, 16).unwrap_or_else(|_|{panic!("bad obj name \"{}{}\"", prefix, n)})})
+        };
+
+        assert!(obj.faces.iter().all(|f|{parse_name(&f.object, "dl_").unwrap_or(0) < old_starts.len()}),
+            "obj objects have to be one of the model's {} display lists, dl_00 to dl_{:02X}", old_starts.len(), old_starts.len() - 1);
+
+        let mut vertices = self.vertices().unwrap_or_else(|e|{unreadable(e)});
+        let mut cmds : Vec<Gfx> = Vec::new();
+        let mut new_starts : HashMap<usize, usize> = HashMap::new();
+        let mut tri_cnt = 0;
+        for (dl, old_start) in old_starts.iter().enumerate(){
+            new_starts.insert(*old_start, cmds.len());
+
+            //faces of this list, grouped by material in order of first use
+            let faces : Vec<_> = obj.faces.iter().filter(|f|{parse_name(&f.object, "dl_").unwrap_or(0) == dl}).collect();
+            let mut materials : Vec<Option<usize>> = Vec::new();
+            for f in faces.iter(){
+                let m = parse_name(&f.material, "tex_");
+                if !materials.contains(&m){
+                    materials.push(m);
+                }
+            }
+            for material in materials{
+                if let Some(t) = material{
+                    assert!(t < textures.len(), "obj uses tex_{:02X}, but the model only has {} textures", t, textures.len());
+                }
+                let setup = setups[dl].iter()
+                    .chain(setups.iter().flatten())
+                    .find(|(t, _)|{*t == material})
+                    .map(|(_, cmds)|{cmds.clone()});
+                match (setup, material){
+                    (Some(mut setup), _) => cmds.append(&mut setup),
+                    (None, None) => {},
+                    (None, Some(t)) => panic!("no display list loads texture {:02X}, obj import can only reuse existing materials", t),
+                }
+                let (w, h) = match material{
+                    Some(t) => (textures[t].w as f32, textures[t].h as f32),
+                    None => (0.0, 0.0),
+                };
+                let scale = tex_scale(material);
+
+                let clamp = |f : f32|{f.round().max(i16::MIN as f32).min(i16::MAX as f32) as i16};
+                let make_vertex = |(v, vt) : (usize, Option<usize>)|{
+                    let p = obj.positions[v];
+                    let uv = vt.map(|i|{obj.uvs[i]}).unwrap_or([0.0, 0.0]);
+                    Vertex{
+                        position: [clamp(p[0]), clamp(p[1]), clamp(p[2])],
+                        flag: 0,
+                        st: [clamp(uv[0]*w*32.0/scale[0]), clamp((1.0 - uv[1])*h*32.0/scale[1])],
+                        color: colors.as_ref().and_then(|c|{c.get(v).copied()}).unwrap_or([0xFF; 4]),
+                    }
+                };
+                let tris : Vec<[(usize, Option<usize>); 3]> = faces.iter()
+                    .filter(|f|{parse_name(&f.material, "tex_") == material})
+                    .map(|f|{f.corners})
+                    .collect();
+                tri_cnt += tris.len();
+                batch_triangles(&tris, &make_vertex, &mut vertices, &mut cmds);
+            }
+            //state restored after the last triangle
+            if let Some((_, tail)) = setups[dl].last(){
+                cmds.extend_from_slice(tail);
+            }
+            cmds.push(Gfx::new_end_dl());
+        }
+
+        let mut dl_bytes = (cmds.len() as u32).to_be_bytes().to_vec();
+        dl_bytes.extend_from_slice(&[0; 4]);
+        for cmd in cmds.iter(){
+            dl_bytes.append(&mut cmd.to_bytes());
+        }
+        let mut vtx_bytes = VertexStoreHeader::from_vertices(&vertices).to_bytes();
+        for v in vertices.iter(){
+            vtx_bytes.append(&mut v.to_bytes());
+        }
+        let header = self.header.as_ref().unwrap();
+        let (dl_offset, vtx_offset) = (header.display_list.unwrap(), header.vertex_store.expect("model has no vertex store"));
+        self.replace_section(dl_offset, dl_bytes);
+        let vtx_offset = self.header.as_ref().unwrap().vertex_store.unwrap_or(vtx_offset);
+        self.replace_section(vtx_offset, vtx_bytes);
+
+        let mut header = self.header.clone().unwrap();
+        header.tri_count = tri_cnt as u16;
+        header.vertex_count = vertices.len() as u16;
+        self.bytes[..ModelHeader::SIZE].copy_from_slice(&header.to_bytes());
+        if let Some(geo_offset) = header.geo_layout{
+            geo::remap_dl_indices(&mut self.bytes, geo_offset, &|i|{
+                *new_starts.get(&i).unwrap_or_else(||{panic!("geo layout draws display list index {} that doesn't start a list", i)})
+            }).unwrap_or_else(|e|{unreadable(e)});
+        }
+        self.header = Some(header);
+    }
+
+    /// replace the display list section, keeping the vertices and geo layout
+    pub fn import_gfx(&mut self, cmds: &[Gfx]){
+        let header = self.header.clone().expect("only models with a header have display lists");
+        let dl_offset = header.display_list.expect("model has no display list");
+        if let Some(geo_offset) = header.geo_layout{
+            let drawn = geo::command_offsets(&self.bytes, geo_offset)
+                .and_then(|offsets|{offsets.into_iter().map(|o|{geo::dl_indices(&self.bytes, o)}).collect::<Result<Vec<_>, ParseError>>()})
+                .unwrap_or_else(|e|{panic!("the model's geo layout can't be read: {}", e)});
+            for (_, i) in drawn.into_iter().flatten(){
+                assert!(i < cmds.len(), "geo layout draws display list index {}, but there are only {} commands", i, cmds.len());
+            }
+        }
+        let mut dl_bytes = (cmds.len() as u32).to_be_bytes().to_vec();
+        dl_bytes.extend_from_slice(&[0; 4]);
+        for cmd in cmds.iter(){
+            dl_bytes.append(&mut cmd.to_bytes());
+        }
+        self.replace_section(dl_offset, dl_bytes);
+    }
+
+    pub fn bones(&self) -> Result<Option<BoneList>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.bone_list}), BoneList::from_bytes)
+    }
+
+    /// the bone each vertex is drawn with, from the `Bone` commands of the geo layout
+    /// around the display lists that load it. None for vertices no bound list loads.
+    pub fn vertex_bones(&self) -> Result<Vec<Option<usize>>, ParseError>{
+        let mut dl_bones : Vec<(usize, usize)> = Vec::new();
+        fn walk(chain: &[geo::GeoNode], bone: Option<usize>, out: &mut Vec<(usize, usize)>){
+            for node in chain.iter(){
+                let bone = node.bone().or(bone);
+                if let Some(b) = bone{
+                    out.extend(node.dls().into_iter().map(|dl|{(dl, b)}));
+                }
+                for child in node.children.iter(){
+                    walk(child, bone, out);
+                }
+            }
+        }
+        walk(&self.geo_layout()?.unwrap_or_default(), None, &mut dl_bones);
+
+        let cmds = self.gfx()?;
+        let mut out : Vec<Option<usize>> = vec![None; self.vertices()?.len()];
+        for (dl, bone) in dl_bones{
+            for cmd in cmds.iter().skip(dl).take_while(|c|{c.opcode() != gfx::G_ENDDL}){
+                if cmd.opcode() == gfx::G_VTX{
+                    let (_, n) = cmd.vtx_range();
+                    let base = cmd.address_offset()/Vertex::SIZE;
+                    for v in out.iter_mut().skip(base).take(n){
+                        *v = Some(bone);
+                    }
+                }
+            }
+        }
+        return Ok(out)
+    }
+
+    pub fn effects(&self) -> Result<Option<Vec<EffectMesh>>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.effects}), EffectMesh::list_from_bytes)
+    }
+
+    pub fn animated_textures(&self) -> Result<Option<Vec<AnimatedTexture>>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.animated_textures}), AnimatedTexture::list_from_bytes)
+    }
+
+    /// the geo layout as a tree of commands
+    pub fn geo_layout(&self) -> Result<Option<Vec<geo::GeoNode>>, ParseError>{
+        return self.header.as_ref().and_then(|h|{h.geo_layout})
+            .map(|offset|{geo::GeoNode::read_chain(&self.bytes, offset, self.section_end(offset))})
+            .transpose()
+    }
+
+    pub fn import_geo_layout(&mut self, chain: &[geo::GeoNode]){
+        let offset = self.header.as_ref().and_then(|h|{h.geo_layout}).expect("model has no geo layout");
+        self.replace_section(offset, geo::GeoNode::chain_to_bytes(chain));
+    }
+
+    /// where the section at `offset` ends, at the next section or the end of the model
+    fn section_end(&self, offset: usize) -> usize{
+        let mut header = self.header.clone().unwrap();
+        return header.sections_mut().into_iter()
+            .filter_map(|o|{*o})
+            .filter(|o|{*o > offset})
+            .min()
+            .unwrap_or(self.bytes.len())
+            .min(self.bytes.len())
+    }
+
+    /// swap the bytes of the section at `offset`, moving every later section
+    fn replace_section(&mut self, offset: usize, mut new_bytes: Vec<u8>){
+        let mut header = self.header.clone().unwrap();
+        let end = self.section_end(offset);
+        if self.bytes[offset..end].starts_with(&new_bytes){
+            return; //unchanged, keep the original padding
+        }
+        new_bytes.resize((new_bytes.len() + 7) & !7, 0);
+        let delta = new_bytes.len() as isize - (end - offset) as isize;
+        let tail = self.bytes.split_off(end);
+        self.bytes.truncate(offset);
+        self.bytes.append(&mut new_bytes);
+        self.bytes.extend_from_slice(&tail);
+        for section in header.sections_mut(){
+            if let Some(o) = section{
+                if *o > offset{
+                    *o = (*o as isize + delta) as usize;
+                }
+            }
+        }
+        self.bytes[..ModelHeader::SIZE].copy_from_slice(&header.to_bytes());
+        self.header = Some(header);
+    }
+
+    pub fn vertex_store(&self) -> Result<Option<VertexStoreHeader>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.vertex_store}), VertexStoreHeader::from_bytes)
+    }
+
+    pub fn collision(&self) -> Result<Option<CollisionHeader>, ParseError>{
+        return self.read_section(self.header.as_ref().and_then(|h|{h.collision}), CollisionHeader::from_bytes)
+    }
+
+    /// the descriptor keys after `bin`, with the files they point to
+    fn write_sections(&self, desc_f: &mut dyn Write, containing_folder: &Path, base_name: &str, opts: &ExtractOptions) -> Result<(), ParseError>{
+        if self.vertex_store()?.is_some(){
+            let csv_name = format!("{}.vtx.csv", base_name);
+            self.write_vertex_csv(&containing_folder.join(&csv_name))?;
+            writeln!(desc_f, "vertices: {:?}", csv_name).unwrap();
+        }
+        if let Some(bone_list) = self.bones()?{
+            //vertex ranges each bone moves, for reference only
+            let vertex_bones = self.vertex_bones()?;
+            writeln!(desc_f, "bones:").unwrap();
+            writeln!(desc_f, "  scale: {:?}", bone_list.scale).unwrap();
+            writeln!(desc_f, "  list:").unwrap();
+            for (i, b) in bone_list.bones.iter().enumerate(){
+                let mut ranges : Vec<[usize; 2]> = Vec::new();
+                for (v, _) in vertex_bones.iter().enumerate().filter(|(_, vb)|{**vb == Some(i)}){
+                    match ranges.last_mut(){
+                        Some(r) if r[1] == v => r[1] = v + 1,
+                        _ => ranges.push([v, v + 1]),
+                    }
+                }
+                writeln!(desc_f, "    - {{id: {}, parent: {}, position: [{:?}, {:?}, {:?}], vertices: {:?}}}",
+                    b.id, b.parent, b.position[0], b.position[1], b.position[2], ranges).unwrap();
+            }
+        }
+        if let Some(meshes) = self.effects()?{
+            writeln!(desc_f, "effects:").unwrap();
+            for m in meshes.iter(){
+                writeln!(desc_f, "  - {{id: {}, vertices: {:?}}}", m.id, m.vertices).unwrap();
+            }
+        }
+        if let Some(textures) = self.animated_textures()?{
+            writeln!(desc_f, "animated_textures:").unwrap();
+            for t in textures.iter(){
+                writeln!(desc_f, "  - {{frame_size: 0x{:X}, frame_count: {}, fps: {:?}}}", t.frame_size, t.frame_count, t.fps).unwrap();
+            }
+        }
+        if let Some(chain) = self.geo_layout()?{
+            writeln!(desc_f, "geo_layout:").unwrap();
+            geo::GeoNode::write_yaml_chain(desc_f, &chain, 2);
+        }
+        let textures = self.write_texture_pngs(containing_folder, &format!("{}.textures", base_name))?;
+        if textures.is_empty(){
+            return Ok(());
+        }
+        writeln!(desc_f, "textures:").unwrap();
+        for (i, entry, png_name) in textures.iter(){
+            writeln!(desc_f, "  - {{index: {}, png: {:?}, format: {:?}, w: {}, h: {}}}", i, png_name, entry.format, entry.w, entry.h).unwrap();
+        }
+
+        if opts.c_arrays{
+            let prefix = format!("model_{}", texture::c_identifier(base_name));
+            let mut c_f = deps::create(containing_folder.join(format!("{}.inc.c", base_name))).unwrap();
+            for (i, entry, _) in textures.iter(){
+                let bytes = self.texture(entry)?.to_bytes();
+                let comment = format!("texture {:02X}: {:?} {}x{}, {} bytes", i, entry.format, entry.w, entry.h, bytes.len());
+                texture::write_c_array(&mut c_f, &format!("{}_tex_{:02X}", prefix, i), &comment, &bytes);
+            }
+        }
+        return Ok(())
+    }
+}
+
+impl Asset for Model{
+    fn to_bytes(&self)->Vec<u8>{
+        return self.bytes.clone();
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::Model;
+    }
+
+    fn problems(&self) -> Vec<String>{
+        self.validate()
+    }
+
+    fn write(&self, path: &Path, opts: &ExtractOptions){
+        //write descriptor yaml, the original bin and a folder of texture pngs
+        let containing_folder = path.parent().unwrap();
+        let base_name = path.file_name().unwrap().to_str().unwrap().trim_end_matches(".model.yaml");
+        let bin_name = format!("{}.model.bin", base_name);
+        let mut bin_file = deps::create(containing_folder.join(&bin_name)).unwrap();
+        bin_file.write_all(&self.bytes).unwrap();
+
+        let mut desc_f = deps::create(path).unwrap();
+        writeln!(desc_f, "type: Model").unwrap();
+        writeln!(desc_f, "bin: {:?}", bin_name).unwrap();
+        //every other key only overrides the bin, so a model with a section that can't be read still rebuilds
+        if let Err(e) = self.write_sections(&mut desc_f, containing_folder, base_name, opts){
+            log::warning(None, &format!("{:?}: described up to a section that can't be read, {}", path, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::banjo_kazooie::gltf::GltfTriangle;
+
+    fn triangles(cnt: usize) -> GltfMesh{
+        return GltfMesh{
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            uvs: vec![[0.0, 0.0]; 3],
+            colors: vec![[1.0, 1.0, 1.0, 1.0]; 3],
+            triangles: (0..cnt).map(|_|{GltfTriangle{vertices: [0, 1, 2], material: None}}).collect(),
+            images: Vec::new(),
+            report: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn gltf_triangle_count_is_checked(){
+        let (model, report) = Model::from_gltf(&triangles(1), 1.0).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(model.header.as_ref().unwrap().tri_count, 1);
+        assert!(Model::from_gltf(&triangles(u16::MAX as usize + 1), 1.0).is_err());
+    }
+
+    #[test]
+    fn c_keeps_commands_after_the_last_end_dl(){
+        let (mut model, _) = Model::from_gltf(&triangles(1), 1.0).unwrap();
+        let mut cmds = model.gfx().unwrap();
+        let end_cnt = cmds.iter().filter(|c|{c.opcode() == gfx::G_ENDDL}).count();
+        assert_eq!(model.dl_starts().unwrap().len(), end_cnt);
+
+        cmds.push(Gfx::new_sync(gfx::G_RDPPIPESYNC));
+        model.import_gfx(&cmds);
+        assert_eq!(model.dl_starts().unwrap().len(), end_cnt + 1);
+        let mut c : Vec<u8> = Vec::new();
+        model.write_c(&mut c, "test").unwrap();
+        let c = String::from_utf8(c).unwrap();
+        assert!(c.contains(&format!("Gfx test_dl_{:02X}[]", end_cnt)));
+        assert_eq!(c.matches("Gfx test_dl_").count(), end_cnt + 1);
+    }
+}
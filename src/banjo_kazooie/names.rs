@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::path::Path;
+use yaml_rust::YamlLoader;
+
+use super::deps;
+use super::texture;
+
+// uid to name table, so exported symbols and files can use readable names
+// instead of bare uids. Read from a yaml mapping like `0x07AF: banjo`. Actor
+// tables can also give an entry as `0x0131: {name: warp_to_lair, warp: 0x69}`
+// for nodes that warp to another map, so setups can be checked for warps to
+// maps that aren't there.
+
+#[derive(Clone)]
+pub struct NameMap{
+    names : HashMap<usize, String>,
+    warps : HashMap<usize, usize>, //actor id, map id it warps to
+}
+
+impl NameMap{
+    pub fn new() -> NameMap{
+        NameMap{names: HashMap::new(), warps: HashMap::new()}
+    }
+
+    pub fn read(path: &Path) -> NameMap{
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open name map")).unwrap()[0];
+        let mut names : HashMap<usize, String> = HashMap::new();
+        let mut warps : HashMap<usize, usize> = HashMap::new();
+        for (k, v) in doc.as_hash().unwrap_or_else(||{panic!("{:?}: name maps are a yaml mapping of uid: name", path)}).iter(){
+            let uid = k.as_i64().unwrap_or_else(||{panic!("{:?}: uid {:?} is not a number", path, k)}) as usize;
+            let name = v.as_str().or(v["name"].as_str()).unwrap_or_else(||{panic!("{:?}: name of 0x{:04X} is not a string", path, uid)});
+            if let Some(map) = v["warp"].as_i64(){
+                warps.insert(uid, map as usize);
+            }
+            names.insert(uid, name.to_string());
+        }
+        NameMap{names: names, warps: warps}
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.names.is_empty()
+    }
+
+    /// the map an actor table entry warps to
+    pub fn warp(&self, uid: usize) -> Option<usize>{
+        self.warps.get(&uid).copied()
+    }
+
+    pub fn name(&self, uid: usize) -> Option<&str>{
+        self.names.get(&uid).map(|n|{n.as_str()})
+    }
+
+    /// reverse lookup, for files that refer to assets by name
+    pub fn uid(&self, name: &str) -> Option<usize>{
+        self.names.iter().find(|(_, n)|{n.as_str() == name}).map(|(uid, _)|{*uid})
+    }
+
+    /// C identifier for an asset, `<kind>_<name>` or `<kind>_<uid>` if it has no name
+    pub fn symbol(&self, uid: usize, kind: &str) -> String{
+        match self.name(uid){
+            Some(name) => format!("{}_{}", kind, texture::c_identifier(name)),
+            None => format!("{}_{:04X}", kind, uid),
+        }
+    }
+}
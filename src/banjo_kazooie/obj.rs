@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use super::deps;
+
+// minimal wavefront obj reader, positions, texture coordinates, objects and materials
+
+pub struct ObjFace{
+    pub object : Option<String>,
+    pub material : Option<String>,
+    pub corners : [(usize, Option<usize>); 3], //0 based position and texture coordinate indices
+}
+
+pub struct ObjMesh{
+    pub positions : Vec<[f32; 3]>,
+    pub uvs : Vec<[f32; 2]>,
+    pub faces : Vec<ObjFace>,
+}
+
+/// 1 based or negative (relative to the end) obj index to a 0 based one
+fn obj_index(s: &str, len: usize, path: &Path) -> usize{
+    let i = s.parse::<isize>().unwrap_or_else(|_|{panic!("{:?}: bad index \"{}\"", path, s)});
+    return match i{
+        i if i > 0 => i as usize - 1,
+        i if i < 0 => (len as isize + i) as usize,
+        _ => panic!("{:?}: obj indices start at 1", path),
+    }
+}
+
+impl ObjMesh{
+    pub fn read(path: &Path) -> ObjMesh{
+        let text = deps::read_to_string(path).expect("could not read obj");
+        let mut mesh = ObjMesh{positions: Vec::new(), uvs: Vec::new(), faces: Vec::new()};
+        let mut object : Option<String> = None;
+        let mut material : Option<String> = None;
+        for line in text.lines(){
+            let mut words = line.split_whitespace();
+            let floats = |words: std::str::SplitWhitespace| -> Vec<f32>{
+                words.map(|w|{w.parse::<f32>().unwrap_or_else(|_|{panic!("{:?}: bad number \"{}\"", path, w)})}).collect()
+            };
+            match words.next(){
+                Some("v") => {
+                    let p = floats(words);
+                    mesh.positions.push([p[0], p[1], p[2]]);
+                },
+                Some("vt") => {
+                    let t = floats(words);
+                    mesh.uvs.push([t[0], *t.get(1).unwrap_or(&0.0)]);
+                },
+                Some("o") => object = words.next().map(|w|{w.to_string()}),
+                Some("usemtl") => material = words.next().map(|w|{w.to_string()}),
+                Some("f") => {
+                    let corners : Vec<(usize, Option<usize>)> = words
+                        .map(|w|{
+                            let mut parts = w.split('/');
+                            let v = obj_index(parts.next().unwrap(), mesh.positions.len(), path);
+                            let vt = parts.next().filter(|s|{!s.is_empty()}).map(|s|{obj_index(s, mesh.uvs.len(), path)});
+                            (v, vt)
+                        })
+                        .collect();
+                    assert!(corners.len() >= 3, "{:?}: face with less than 3 corners", path);
+                    //fan triangulate polygons
+                    for i in 1..corners.len() - 1{
+                        mesh.faces.push(ObjFace{
+                            object: object.clone(),
+                            material: material.clone(),
+                            corners: [corners[0], corners[i], corners[i + 1]],
+                        });
+                    }
+                },
+                _ => {},
+            }
+        }
+        return mesh
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+
+use super::asset::{Asset, AssetType, QuestionHeader};
+use super::names::NameMap;
+
+// every quiz and Grunty question in one markdown document, for reviewing the
+// text of a translation or a hack, with the option the header's `answer`
+// points at marked.
+
+pub struct Question{
+    pub uid : usize,
+    pub asset_type : &'static str, //QuizQuestion or GruntyQuestion
+    pub header : QuestionHeader,
+    pub question : Vec<String>,
+    pub options : Vec<String>,
+}
+
+impl Question{
+    /// None for anything that isn't a question
+    pub fn from_asset(uid: usize, asset: &dyn Asset) -> Option<Question>{
+        let asset_type = match asset.get_type(){
+            AssetType::QuizQuestion => "QuizQuestion",
+            AssetType::GruntyQuestion => "GruntyQuestion",
+            _ => return None,
+        };
+        let header = asset.question_header()?;
+        let lines = asset.text_lines().unwrap();
+        let texts = |section: &str|{lines.iter().filter(|l|{l.section == section}).map(|l|{l.text.clone()}).collect::<Vec<String>>()};
+        return Some(Question{uid: uid, asset_type: asset_type, header: header, question: texts("question"), options: texts("option")})
+    }
+
+    /// text that can't be right, and an answer that isn't one of the options
+    pub fn problems(&self) -> Vec<String>{
+        let mut out : Vec<String> = Vec::new();
+        let at = |e: String|{format!("0x{:04X}: {}", self.uid, e)};
+        if self.header.answer as usize >= self.options.len(){
+            out.push(at(format!("answer is option {}, it only has {}", self.header.answer as usize + 1, self.options.len())));
+        }
+        if self.question.iter().all(|q|{q.trim().is_empty()}){
+            out.push(at("has no question text".to_string()));
+        }
+        for (i, o) in self.options.iter().enumerate(){
+            if o.trim().is_empty(){
+                out.push(at(format!("option {} is empty", i + 1)));
+            }
+            if let Some(j) = self.options[..i].iter().position(|p|{p.trim() == o.trim() && !o.trim().is_empty()}){
+                out.push(at(format!("options {} and {} are the same", j + 1, i + 1)));
+            }
+        }
+        return out
+    }
+}
+
+/// every question as a heading, its text quoted, and its options numbered.
+/// Problems are listed under the question too
+pub fn write_report(out: &mut dyn Write, questions: &[Question], names: &NameMap) -> io::Result<()>{
+    let count = |t: &str|{questions.iter().filter(|q|{q.asset_type == t}).count()};
+    writeln!(out, "# Questions")?;
+    writeln!(out)?;
+    writeln!(out, "{} quiz questions, {} Grunty questions", count("QuizQuestion"), count("GruntyQuestion"))?;
+    for q in questions.iter(){
+        writeln!(out)?;
+        match names.name(q.uid){
+            Some(name) => writeln!(out, "## 0x{:04X} {} ({})", q.uid, q.asset_type, name)?,
+            None => writeln!(out, "## 0x{:04X} {}", q.uid, q.asset_type)?,
+        }
+        writeln!(out)?;
+        for line in q.question.iter(){
+            writeln!(out, "> {}", line)?;
+        }
+        writeln!(out)?;
+        for (i, o) in q.options.iter().enumerate(){
+            let answer = if i == q.header.answer as usize {" (answer)"} else {""};
+            writeln!(out, "{}. {}{}", i + 1, o, answer)?;
+        }
+        let problems = q.problems();
+        if !problems.is_empty(){
+            writeln!(out)?;
+            for p in problems.iter(){
+                writeln!(out, "- error: {}", p)?;
+            }
+        }
+    }
+    return Ok(())
+}
@@ -0,0 +1,519 @@
+use super::asset::{Animation, Asset, Binary, DemoButtonFile, Dialog, GruntyQuestion, LevelSetup, MidiSeqFile, Model, QuizQuestion, Sprite};
+use super::error::ParseError;
+use super::geo;
+use super::gfx::{Gfx, VTX_BUFFER_SIZE};
+use super::model::{Bone, BoneList, DisplayListHeader, ModelHeader, Vertex, VertexStoreHeader};
+use super::sequence::{SeqEvent, SeqMessage, SeqTrack, Sequence, TRACK_CNT};
+
+// round trip checks for the parsers: seeded generators of well formed assets,
+// and a check that decoding an asset's bytes and encoding it again gives the
+// same bytes. A new parser gets an `Arbitrary` impl here and a line in
+// `check_all`, which the `roundtrip-check` command runs.
+
+/// xorshift64*, so a failing case can be rerun from its seed
+pub struct Gen{
+    state: u64,
+}
+
+impl Gen{
+    pub fn new(seed: u64) -> Gen{
+        return Gen{state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1}
+    }
+
+    pub fn u32(&mut self) -> u32{
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+    }
+
+    pub fn u8(&mut self) -> u8{
+        return self.u32() as u8
+    }
+
+    pub fn u16(&mut self) -> u16{
+        return self.u32() as u16
+    }
+
+    /// 0 up to but not including `n`
+    pub fn below(&mut self, n: usize) -> usize{
+        return self.u32() as usize % n
+    }
+
+    pub fn bool(&mut self) -> bool{
+        return self.u32() & 1 == 1
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Vec<u8>{
+        return (0..len).map(|_|{self.u8()}).collect()
+    }
+}
+
+/// assets whose well formed bytes can be generated at random
+pub trait Arbitrary: Asset + Sized{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Result<Self, ParseError>;
+}
+
+/// whether `bytes` decode, and encode back to the same bytes. The error says
+/// where they first differ
+pub fn round_trip<T: Arbitrary>(bytes: &[u8]) -> Result<(), String>{
+    let again = T::decode(bytes).map_err(|e|{format!("doesn't decode: {}", e)})?.to_bytes();
+    if again != bytes{
+        let at = bytes.iter().zip(again.iter()).position(|(a, b)|{a != b}).unwrap_or(bytes.len().min(again.len()));
+        return Err(format!("0x{:X} bytes in, 0x{:X} bytes out, first difference at 0x{:X}", bytes.len(), again.len(), at))
+    }
+    return Ok(())
+}
+
+/// `cases` generated assets, case `i` from seed `seed + i`. The error names the
+/// first failing seed
+pub fn check<T: Arbitrary>(seed: u64, cases: usize) -> Result<(), String>{
+    for i in 0..cases as u64{
+        let case_seed = seed.wrapping_add(i);
+        round_trip::<T>(&T::arbitrary(&mut Gen::new(case_seed))).map_err(|e|{format!("seed {}: {}", case_seed, e)})?;
+    }
+    return Ok(())
+}
+
+/// `check` for every asset type with a generator
+pub fn check_all(seed: u64, cases: usize) -> Vec<(&'static str, Result<(), String>)>{
+    return vec![
+        ("Animation", check::<Animation>(seed, cases)),
+        ("Binary", check::<Binary>(seed, cases)),
+        ("DemoInput", check::<DemoButtonFile>(seed, cases)),
+        ("Dialog", check::<Dialog>(seed, cases)),
+        ("GruntyQuestion", check::<GruntyQuestion>(seed, cases)),
+        ("LevelSetup", check::<LevelSetup>(seed, cases)),
+        ("Midi", check::<MidiSeqFile>(seed, cases)),
+        ("Model", check::<Model>(seed, cases)),
+        ("QuizQuestion", check::<QuizQuestion>(seed, cases)),
+        ("Sprite", check::<Sprite>(seed, cases)),
+    ]
+}
+
+/// cmd byte, length byte, text
+fn push_strings(out: &mut Vec<u8>, g: &mut Gen, cnt: usize){
+    for _ in 0..cnt{
+        out.push(g.u8());
+        let len = g.below(0x40);
+        out.push(len as u8);
+        out.append(&mut g.bytes(len));
+    }
+}
+
+impl Arbitrary for Binary{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let len = g.below(0x100);
+        return g.bytes(len)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Binary, ParseError>{
+        return Ok(Binary::from_bytes(bytes))
+    }
+}
+
+impl Arbitrary for MidiSeqFile{
+    /// tracks of channel events, tempos and loops, each ended by an end of track
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let data = |g: &mut Gen|{g.below(0x80) as u8};
+        let mut tracks : Vec<Option<SeqTrack>> = Vec::new();
+        for _ in 0..TRACK_CNT{
+            if g.below(4) != 0{
+                tracks.push(None);
+                continue;
+            }
+            let mut events : Vec<SeqEvent> = Vec::new();
+            let mut loop_start : Option<usize> = None;
+            for _ in 0..g.below(0x20){
+                let channel = g.below(0x10) as u8;
+                let message = match g.below(10){
+                    0 => SeqMessage::Note{channel: channel, note: data(g), velocity: data(g), duration: g.below(0x4000) as u32},
+                    1 => SeqMessage::NoteOff{channel: channel, note: data(g), velocity: data(g)},
+                    2 => SeqMessage::KeyPressure{channel: channel, note: data(g), pressure: data(g)},
+                    3 => SeqMessage::Controller{channel: channel, controller: data(g), value: data(g)},
+                    4 => SeqMessage::Program{channel: channel, program: data(g)},
+                    5 => SeqMessage::ChannelPressure{channel: channel, pressure: data(g)},
+                    6 => SeqMessage::PitchBend{channel: channel, value: g.below(0x4000) as u16},
+                    7 => SeqMessage::Tempo(g.below(0x100_0000) as u32),
+                    8 if loop_start.is_none() => {
+                        loop_start = Some(events.len());
+                        SeqMessage::LoopStart{number: g.u8(), extra: g.u8()}
+                    },
+                    _ => match loop_start.take(){
+                        Some(start) => SeqMessage::LoopEnd{count: g.u8(), current: g.u8(), start: Some(start), offset: 0},
+                        None => SeqMessage::Program{channel: channel, program: data(g)},
+                    },
+                };
+                events.push(SeqEvent{delta: g.below(0x200) as u32, running: g.bool(), message: message});
+            }
+            events.push(SeqEvent{delta: g.below(0x200) as u32, running: false, message: SeqMessage::EndOfTrack});
+            tracks.push(Some(SeqTrack{events: events}));
+        }
+        return Sequence{division: g.below(0x400) as u32 + 1, tracks: tracks}.to_bytes()
+    }
+
+    /// the sequence decoded and encoded again, since the file itself keeps its bytes
+    fn decode(bytes: &[u8]) -> Result<MidiSeqFile, ParseError>{
+        let seq = Sequence::from_bytes(bytes).ok_or_else(||{ParseError::Invalid("not a sequence".to_string())})?;
+        return Ok(MidiSeqFile::from_bytes(&seq.to_bytes()))
+    }
+}
+
+impl Arbitrary for Dialog{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let mut out : Vec<u8> = vec![0x01, 0x03, 0x00];
+        for _ in 0..2{
+            let cnt = g.below(5);
+            out.push(cnt as u8);
+            push_strings(&mut out, g, cnt);
+        }
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Dialog, ParseError>{
+        return Dialog::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for QuizQuestion{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        //only the kind decides the type, the rest of the header is kept as it is
+        let mut out : Vec<u8> = vec![0x01, 0x01, g.below(0x100) as u8, g.below(0x100) as u8, g.below(0x100) as u8];
+        let cnt = 3 + g.below(4);
+        out.push(cnt as u8);
+        push_strings(&mut out, g, cnt);
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<QuizQuestion, ParseError>{
+        return QuizQuestion::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for GruntyQuestion{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let mut out : Vec<u8> = vec![0x01, 0x03, 0x00, 0x05, 0x00];
+        let cnt = 3 + g.below(4);
+        out.push(cnt as u8);
+        push_strings(&mut out, g, cnt);
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<GruntyQuestion, ParseError>{
+        return GruntyQuestion::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for DemoButtonFile{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let cnt = 1 + g.below(0x40);
+        let mut out = ((6*cnt) as u32).to_be_bytes().to_vec();
+        //flags on any input, the yaml has to keep every byte
+        for _ in 0..cnt{
+            out.extend_from_slice(&[g.u8(), g.u8(), g.u8(), g.u8(), 1 + g.below(0xFF) as u8, g.u8()]);
+        }
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<DemoButtonFile, ParseError>{
+        return DemoButtonFile::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for Animation{
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let mut out = g.u16().to_be_bytes().to_vec();
+        out.extend_from_slice(&g.u16().to_be_bytes());
+        let element_cnt = g.below(8);
+        out.extend_from_slice(&(element_cnt as u16).to_be_bytes());
+        out.extend_from_slice(&[0; 2]);
+        for _ in 0..element_cnt{
+            out.extend_from_slice(&((g.below(0x1000) << 4) as u16 | g.below(9) as u16).to_be_bytes());
+            let key_cnt = g.below(6);
+            out.extend_from_slice(&(key_cnt as u16).to_be_bytes());
+            for _ in 0..key_cnt{
+                out.extend_from_slice(&g.u16().to_be_bytes());
+                out.extend_from_slice(&g.u16().to_be_bytes());
+            }
+        }
+        let padding = g.below(8);
+        out.resize(out.len() + padding, 0);
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Animation, ParseError>{
+        return Animation::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for Sprite{
+    /// CI8, IA4, IA8, RGBA16 or RGBA32 frames of one chunk each, the formats
+    /// whose texels survive decoding to RGBA32 unchanged
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let (code, bits) = [(0x0004u16, 8), (0x0080, 4), (0x0100, 8), (0x0400, 16), (0x0800, 32)][g.below(5)];
+        let frame_cnt = 1 + g.below(3);
+        let mut out = (frame_cnt as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(&code.to_be_bytes());
+        out.append(&mut g.bytes(0xC));
+        let table_start = out.len();
+        out.resize(table_start + 4*frame_cnt, 0);
+        let data_start = out.len();
+        for i in 0..frame_cnt{
+            out.resize((out.len() + 7) & !7, 0);
+            let offset = ((out.len() - data_start) as u32).to_be_bytes();
+            out[table_start + 4*i .. table_start + 4*i + 4].copy_from_slice(&offset);
+            let (w, h) = (8*(1 + g.below(4)), 1 + g.below(8));
+            out.extend_from_slice(&g.u16().to_be_bytes());
+            out.extend_from_slice(&g.u16().to_be_bytes());
+            out.extend_from_slice(&(w as u16).to_be_bytes());
+            out.extend_from_slice(&(h as u16).to_be_bytes());
+            out.extend_from_slice(&1u16.to_be_bytes());
+            out.append(&mut g.bytes(0xA));
+            if code == 0x0004{
+                out.resize((out.len() + 7) & !7, 0);
+                out.append(&mut g.bytes(0x200));
+            }
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            out.extend_from_slice(&(w as u16).to_be_bytes());
+            out.extend_from_slice(&(h as u16).to_be_bytes());
+            out.resize((out.len() + 7) & !7, 0);
+            out.append(&mut g.bytes(w*h*bits/8));
+        }
+        out.resize((out.len() + 7) & !7, 0);
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Sprite, ParseError>{
+        return Sprite::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for LevelSetup{
+    /// cubes of nodes, props and unknown lists, cameras and lights, in any order
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let mut sections : Vec<u8> = vec![1, 3, 4];
+        for i in (1..sections.len()).rev(){
+            sections.swap(i, g.below(i + 1));
+        }
+        sections.truncate(g.below(4));
+        let mut out : Vec<u8> = Vec::new();
+        for tag in sections{
+            out.push(tag);
+            match tag{
+                1 => {
+                    let min = [0; 3].map(|_: i32|{g.below(5) as i32 - 2});
+                    let max = min.map(|m|{m + g.below(3) as i32});
+                    for c in min.iter().chain(max.iter()){
+                        out.extend_from_slice(&c.to_be_bytes());
+                    }
+                    let cube_cnt : i32 = (0..3).map(|i|{max[i] - min[i] + 1}).product();
+                    for _ in 0..cube_cnt{
+                        for _ in 0..g.below(3){
+                            match g.below(3){
+                                0 => {
+                                    let cnt = g.below(3);
+                                    out.extend_from_slice(&[0x0A, cnt as u8]);
+                                    if cnt > 0{
+                                        out.push(0x0B);
+                                    }
+                                    out.append(&mut g.bytes(0x14*cnt));
+                                },
+                                1 => {
+                                    let cnt = g.below(3);
+                                    out.extend_from_slice(&[0x08, cnt as u8]);
+                                    if cnt > 0{
+                                        out.push(0x09);
+                                    }
+                                    out.append(&mut g.bytes(0xC*cnt));
+                                },
+                                _ => {
+                                    let len = g.below(8);
+                                    out.extend_from_slice(&[[0x02, 0x03, 0x06, 0x0C][g.below(4)], len as u8]);
+                                    out.append(&mut g.bytes(len));
+                                },
+                            }
+                        }
+                        out.push(0x01);
+                    }
+                },
+                3 => {
+                    for _ in 0..g.below(4){
+                        out.push(1);
+                        out.extend_from_slice(&g.u16().to_be_bytes());
+                        //(field tag, floats, or 0 for a word) of each camera type
+                        let (camera_type, fields) : (u8, &[(u8, usize)]) = match g.below(4){
+                            0 => (1, &[(1, 3), (2, 2), (3, 2), (4, 3), (5, 0)]),
+                            1 => (2, &[(1, 3), (2, 3)]),
+                            2 => (3, &[(1, 3), (2, 2), (3, 2), (4, 3), (5, 0), (6, 2)]),
+                            _ => (4, &[(1, 0)]),
+                        };
+                        out.extend_from_slice(&[2, camera_type]);
+                        for (tag, floats) in fields.iter(){
+                            if !g.bool(){
+                                continue;
+                            }
+                            out.push(*tag);
+                            out.append(&mut g.bytes(4*(*floats).max(1)));
+                        }
+                        out.push(0);
+                    }
+                    out.push(0);
+                },
+                _ => {
+                    let cnt = g.below(4);
+                    out.push(1);
+                    out.extend_from_slice(&(cnt as u32).to_be_bytes());
+                    out.push(2);
+                    for _ in 0..cnt{
+                        out.push(3);
+                        out.append(&mut g.bytes(0xC));
+                        out.push(4);
+                        out.append(&mut g.bytes(0x8));
+                        out.push(5);
+                        out.append(&mut g.bytes(0xC));
+                    }
+                },
+            }
+        }
+        out.push(0);
+        let padding = g.below(8);
+        out.resize(out.len() + padding, 0);
+        return out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<LevelSetup, ParseError>{
+        return LevelSetup::try_from_bytes(bytes)
+    }
+}
+
+impl Arbitrary for Model{
+    /// a header, a display list drawing triangles from vertex loads, the
+    /// vertex store, maybe a bone list, and a geo layout drawing the list
+    fn arbitrary(g: &mut Gen) -> Vec<u8>{
+        let vertex_cnt = 3 + g.below(0x30);
+        let vertices : Vec<Vertex> = (0..vertex_cnt).map(|_|{
+            Vertex{position: [g.u16() as i16, g.u16() as i16, g.u16() as i16], flag: g.u16(), st: [g.u16() as i16, g.u16() as i16], color: [g.u8(), g.u8(), g.u8(), g.u8()]}
+        }).collect();
+        let mut cmds : Vec<Gfx> = Vec::new();
+        let mut tri_cnt = 0;
+        for _ in 0..1 + g.below(4){
+            let n = 3 + g.below(VTX_BUFFER_SIZE - 2).min(vertex_cnt - 3);
+            let v0 = g.below(VTX_BUFFER_SIZE - n + 1);
+            let first = g.below(vertex_cnt - n + 1);
+            cmds.push(Gfx::new_vtx(v0, n, 0x01, first*Vertex::SIZE));
+            for _ in 0..1 + g.below(4){
+                let mut slot = ||{v0 + g.below(n)};
+                cmds.push(Gfx::new_tri1([slot(), slot(), slot()]));
+                tri_cnt += 1;
+            }
+        }
+        cmds.push(Gfx::new_end_dl());
+
+        let mut out = vec![0u8; ModelHeader::SIZE];
+        let display_list = out.len();
+        out.extend_from_slice(&(cmds.len() as u32).to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        for cmd in cmds.iter(){
+            out.append(&mut cmd.to_bytes());
+        }
+        let vertex_store = out.len();
+        out.append(&mut VertexStoreHeader::from_vertices(&vertices).to_bytes());
+        for v in vertices.iter(){
+            out.append(&mut v.to_bytes());
+        }
+        let bone_list = match g.bool(){
+            true => {
+                let offset = out.len();
+                let bones = (0..g.below(6)).map(|i|{
+                    let mut f = ||{f32::from_bits(g.u32())};
+                    Bone{position: [f(), f(), f()], id: g.u16(), parent: g.below(i + 1) as i16 - 1}
+                }).collect();
+                out.append(&mut BoneList{scale: f32::from_bits(g.u32()), bones: bones}.to_bytes());
+                Some(offset)
+            },
+            false => None,
+        };
+        out.resize((out.len() + 7) & !7, 0);
+        let geo_layout = out.len();
+        out.extend_from_slice(&geo::GEO_LOAD_DL.to_be_bytes());
+        out.extend_from_slice(&[0; 12]);
+
+        let header = ModelHeader{
+            geo_layout: Some(geo_layout),
+            texture_list: None,
+            geo_type: g.u16(),
+            display_list: Some(display_list),
+            vertex_store: Some(vertex_store),
+            unk14: g.u32(),
+            bone_list: bone_list,
+            collision: None,
+            unk20: None,
+            effects: None,
+            unk28: g.u32(),
+            animated_textures: None,
+            tri_count: tri_cnt as u16,
+            vertex_count: vertex_cnt as u16,
+            unk34: f32::from_bits(g.u32()),
+        };
+        out[..ModelHeader::SIZE].copy_from_slice(&header.to_bytes());
+        return out
+    }
+
+    /// every section with an encoder written back from what was decoded of it,
+    /// since the model itself keeps its bytes
+    fn decode(bytes: &[u8]) -> Result<Model, ParseError>{
+        let model = Model::try_from_bytes(bytes)?;
+        model.triangles()?;
+        let header = model.header.clone().ok_or_else(||{ParseError::Invalid("model has no header".to_string())})?;
+        let mut out = bytes.to_vec();
+        let mut put = |offset: usize, section: Vec<u8>|{out[offset..offset + section.len()].copy_from_slice(&section)};
+        put(0, header.to_bytes());
+        if let (Some(offset), Some(store)) = (header.vertex_store, model.vertex_store()?){
+            put(offset, store.to_bytes());
+            put(offset + VertexStoreHeader::SIZE, model.vertices()?.iter().flat_map(|v|{v.to_bytes()}).collect());
+        }
+        if let Some(offset) = header.display_list{
+            put(offset + DisplayListHeader::SIZE, model.gfx()?.iter().flat_map(|c|{c.to_bytes()}).collect());
+        }
+        if let (Some(offset), Some(bones)) = (header.bone_list, model.bones()?){
+            put(offset, bones.to_bytes());
+        }
+        return Ok(Model::from_bytes(&out))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn animation(){ check::<Animation>(0, 200).unwrap() }
+
+    #[test]
+    fn binary(){ check::<Binary>(0, 200).unwrap() }
+
+    #[test]
+    fn demo_input(){ check::<DemoButtonFile>(0, 200).unwrap() }
+
+    #[test]
+    fn dialog(){ check::<Dialog>(0, 200).unwrap() }
+
+    #[test]
+    fn grunty_question(){ check::<GruntyQuestion>(0, 200).unwrap() }
+
+    #[test]
+    fn level_setup(){ check::<LevelSetup>(0, 200).unwrap() }
+
+    #[test]
+    fn midi(){ check::<MidiSeqFile>(0, 200).unwrap() }
+
+    #[test]
+    fn model(){ check::<Model>(0, 200).unwrap() }
+
+    #[test]
+    fn quiz_question(){ check::<QuizQuestion>(0, 200).unwrap() }
+
+    #[test]
+    fn sprite(){ check::<Sprite>(0, 200).unwrap() }
+}
@@ -0,0 +1,234 @@
+use std::io::{self, Write};
+
+use super::error::ParseError;
+use super::gltf;
+use super::level_setup::{CameraValue, CubeList, LevelSetup};
+use super::model::Model;
+use super::names::NameMap;
+use super::texture::{self, ImgFmt};
+
+// a map's models and its setup's objects as one glTF scene, for reviewing a
+// level in any 3d viewer. Everything goes in a single .gltf with the buffer and
+// textures as data uris. Models are one mesh each, a primitive per texture,
+// with the vertex colors. Nodes, props, cameras and lights are small marker
+// octahedrons named like the obj preview, under one group node each.
+
+/// a map's setup is this plus the map id
+pub const MAP_SETUP_BASE : usize = 0x71C;
+
+struct Builder{
+    bin : Vec<u8>,
+    buffer_views : Vec<String>,
+    accessors : Vec<String>,
+    images : Vec<String>,
+    materials : Vec<String>,
+    meshes : Vec<String>,
+    nodes : Vec<String>,
+}
+
+impl Builder{
+    fn view(&mut self, bytes: &[u8]) -> usize{
+        while self.bin.len() % 4 != 0{
+            self.bin.push(0);
+        }
+        self.buffer_views.push(format!("{{\"buffer\": 0, \"byteOffset\": {}, \"byteLength\": {}}}", self.bin.len(), bytes.len()));
+        self.bin.extend_from_slice(bytes);
+        return self.buffer_views.len() - 1
+    }
+
+    /// float accessor of VEC2 or VEC3 values, positions also need their bounds
+    fn floats(&mut self, values: &[f32], n: usize, bounds: bool) -> usize{
+        let bytes : Vec<u8> = values.iter().flat_map(|v|{v.to_le_bytes()}).collect();
+        let view = self.view(&bytes);
+        let min_max = match bounds{
+            true => {
+                let axis = |i : usize|{values.iter().skip(i).step_by(n).copied()};
+                let min : Vec<String> = (0..n).map(|i|{format!("{}", axis(i).fold(f32::INFINITY, f32::min))}).collect();
+                let max : Vec<String> = (0..n).map(|i|{format!("{}", axis(i).fold(f32::NEG_INFINITY, f32::max))}).collect();
+                format!(", \"min\": [{}], \"max\": [{}]", min.join(", "), max.join(", "))
+            },
+            false => String::new(),
+        };
+        self.accessors.push(format!("{{\"bufferView\": {}, \"componentType\": 5126, \"count\": {}, \"type\": \"VEC{}\"{}}}", view, values.len()/n, n, min_max));
+        return self.accessors.len() - 1
+    }
+
+    /// normalized u8 VEC4 colors
+    fn colors(&mut self, colors: &[[u8; 4]]) -> usize{
+        let view = self.view(&colors.concat());
+        self.accessors.push(format!("{{\"bufferView\": {}, \"componentType\": 5121, \"normalized\": true, \"count\": {}, \"type\": \"VEC4\"}}", view, colors.len()));
+        return self.accessors.len() - 1
+    }
+
+    fn node(&mut self, json: String) -> usize{
+        self.nodes.push(json);
+        return self.nodes.len() - 1
+    }
+
+    /// mesh of the model's triangles, unindexed since each corner's uv depends
+    /// on the texture its triangle is drawn with
+    fn model(&mut self, model: &Model) -> Result<usize, ParseError>{
+        let vertices = model.vertices()?;
+        let entries = model.textures()?;
+        //material index of each drawable texture
+        let mut materials : Vec<Option<usize>> = Vec::new();
+        for entry in entries.iter(){
+            if matches!(entry.format, ImgFmt::Unknown(_)){
+                materials.push(None);
+                continue;
+            }
+            let png = texture::png_rgba32(entry.w, entry.h, &model.texture(entry)?.to_rgba32());
+            self.images.push(format!("{{\"uri\": \"data:image/png;base64,{}\"}}", gltf::base64_encode(&png)));
+            self.materials.push(format!("{{\"pbrMetallicRoughness\": {{\"baseColorTexture\": {{\"index\": {}}}, \"metallicFactor\": 0}}, \"alphaMode\": \"MASK\", \"doubleSided\": true}}", self.images.len() - 1));
+            materials.push(Some(self.materials.len() - 1));
+        }
+
+        let mut triangles = model.triangles()?;
+        triangles.sort_by_key(|t|{t.texture.map(|i|{i as isize}).unwrap_or(-1)});
+        let mut primitives : Vec<String> = Vec::new();
+        let mut start = 0;
+        while start < triangles.len(){
+            //runs of one texture, they're sorted by it
+            let end = start + triangles[start..].iter().take_while(|t|{t.texture == triangles[start].texture}).count();
+            let group = &triangles[start..end];
+            start = end;
+            let texture = group[0].texture.filter(|i|{materials.get(*i).copied().flatten().is_some()});
+            let (mut positions, mut uvs, mut colors) = (Vec::new(), Vec::new(), Vec::new());
+            for tri in group.iter(){
+                for v in tri.vertices.iter().map(|i|{&vertices[*i]}){
+                    positions.extend(v.position.map(|p|{p as f32}));
+                    colors.push(v.color);
+                    if let Some(i) = texture{
+                        let (w, h) = (entries[i].w as f32, entries[i].h as f32);
+                        uvs.push(v.st[0] as f32 * tri.tex_scale[0] / 32.0 / w);
+                        uvs.push(v.st[1] as f32 * tri.tex_scale[1] / 32.0 / h);
+                    }
+                }
+            }
+            let position = self.floats(&positions, 3, true);
+            let color = self.colors(&colors);
+            let primitive = match texture{
+                Some(i) => {
+                    let uv = self.floats(&uvs, 2, false);
+                    format!("{{\"attributes\": {{\"POSITION\": {}, \"COLOR_0\": {}, \"TEXCOORD_0\": {}}}, \"material\": {}}}", position, color, uv, materials[i].unwrap())
+                },
+                None => format!("{{\"attributes\": {{\"POSITION\": {}, \"COLOR_0\": {}}}}}", position, color),
+            };
+            primitives.push(primitive);
+        }
+        self.meshes.push(format!("{{\"primitives\": [{}]}}", primitives.join(", ")));
+        return Ok(self.meshes.len() - 1)
+    }
+
+    /// unit octahedron, scaled per marker
+    fn marker_mesh(&mut self) -> usize{
+        let mut positions : Vec<f32> = Vec::new();
+        let corners = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]];
+        for (a, b, c) in [(0, 2, 4), (2, 1, 4), (1, 3, 4), (3, 0, 4), (2, 0, 5), (1, 2, 5), (3, 1, 5), (0, 3, 5)]{
+            for i in [a, b, c]{
+                positions.extend_from_slice(&corners[i]);
+            }
+        }
+        let position = self.floats(&positions, 3, true);
+        self.meshes.push(format!("{{\"primitives\": [{{\"attributes\": {{\"POSITION\": {}}}}}]}}", position));
+        return self.meshes.len() - 1
+    }
+}
+
+fn json_str(s: &str) -> String{
+    return format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn list(items: &[usize]) -> String{
+    return items.iter().map(|i|{i.to_string()}).collect::<Vec<String>>().join(", ")
+}
+
+/// the map models and the setup's markers as a .gltf. `models` are (uid, model)
+pub fn write(out: &mut dyn Write, models: &[(usize, Model)], setup: Option<&LevelSetup>, actor_names: &NameMap) -> io::Result<()>{
+    let mut b = Builder{bin: Vec::new(), buffer_views: Vec::new(), accessors: Vec::new(), images: Vec::new(), materials: Vec::new(), meshes: Vec::new(), nodes: Vec::new()};
+    let mut roots : Vec<usize> = Vec::new();
+
+    let model_nodes : Vec<usize> = models.iter()
+        .map(|(uid, model)|{
+            let mesh = b.model(model).unwrap_or_else(|e|{panic!("model 0x{:04X}: {}", uid, e)});
+            b.node(format!("{{\"name\": \"model_{:04X}\", \"mesh\": {}}}", uid, mesh))
+        })
+        .collect();
+    if !model_nodes.is_empty(){
+        roots.push(b.node(format!("{{\"name\": \"map\", \"children\": [{}]}}", list(&model_nodes))));
+    }
+
+    if let Some(setup) = setup{
+        let marker = b.marker_mesh();
+        //(group, name, position, size, extras)
+        let mut markers : Vec<(&str, String, [f32; 3], f32, String)> = Vec::new();
+        for list in setup.cubes.iter().flat_map(|g|{g.cubes.iter()}).flat_map(|c|{c.lists.iter()}){
+            match list{
+                CubeList::Nodes(nodes) => for n in nodes.iter(){
+                    let name = match actor_names.name(n.id() as usize){
+                        Some(name) => texture::c_identifier(name),
+                        None => format!("{:04X}", n.id()),
+                    };
+                    markers.push(("nodes", format!("node_{}", name), n.position().map(|p|{p as f32}), 25.0, format!("{{\"id\": {}}}", n.id())));
+                },
+                CubeList::Props(props) => for p in props.iter(){
+                    let kind = if p.is_model() {"model"} else {"sprite"};
+                    markers.push(("props", format!("prop_{:03X}", p.id()), p.position().map(|p|{p as f32}), 15.0, format!("{{\"id\": {}, \"kind\": \"{}\"}}", p.id(), kind)));
+                },
+                CubeList::Unknown(..) => (),
+            }
+        }
+        for c in setup.cameras.iter().flatten(){
+            if let Some(position) = c.position(){
+                let rotation = match c.field("rotation"){
+                    Some(CameraValue::Floats(r)) => format!(", \"rotation\": [{}]", r.iter().map(|v|{v.to_string()}).collect::<Vec<String>>().join(", ")),
+                    _ => String::new(),
+                };
+                markers.push(("cameras", format!("camera_{}", c.id), position, 50.0, format!("{{\"type\": {}{}}}", c.camera_type, rotation)));
+            }
+        }
+        for (i, l) in setup.lights.iter().flatten().enumerate(){
+            markers.push(("lights", format!("light_{}", i), l.position, 20.0, format!("{{\"radius\": [{}, {}]}}", l.radius[0], l.radius[1])));
+        }
+
+        let mut groups : Vec<usize> = Vec::new();
+        for group in ["nodes", "props", "cameras", "lights"]{
+            let children : Vec<usize> = markers.iter()
+                .enumerate()
+                .filter(|(_, m)|{m.0 == group})
+                .map(|(i, (_, name, p, size, extras))|{
+                    b.node(format!("{{\"name\": {}, \"mesh\": {}, \"translation\": [{}, {}, {}], \"scale\": [{s}, {s}, {s}], \"extras\": {}}}",
+                        json_str(&format!("{}_{}", name, i)), marker, p[0], p[1], p[2], extras, s = size))
+                })
+                .collect();
+            if !children.is_empty(){
+                groups.push(b.node(format!("{{\"name\": \"{}\", \"children\": [{}]}}", group, list(&children))));
+            }
+        }
+        if !groups.is_empty(){
+            roots.push(b.node(format!("{{\"name\": \"setup\", \"children\": [{}]}}", list(&groups))));
+        }
+    }
+
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"asset\": {{\"version\": \"2.0\", \"generator\": \"bk_asset_tool {}\"}},", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "  \"scene\": 0,")?;
+    writeln!(out, "  \"scenes\": [{{\"nodes\": [{}]}}],", list(&roots))?;
+    for (key, items) in [("nodes", &b.nodes), ("meshes", &b.meshes), ("materials", &b.materials), ("images", &b.images), ("accessors", &b.accessors), ("bufferViews", &b.buffer_views)]{
+        if items.is_empty(){
+            continue;
+        }
+        writeln!(out, "  \"{}\": [", key)?;
+        for (i, item) in items.iter().enumerate(){
+            writeln!(out, "    {}{}", item, if i + 1 < items.len() {","} else {""})?;
+        }
+        writeln!(out, "  ],")?;
+    }
+    if !b.images.is_empty(){
+        let textures : Vec<String> = (0..b.images.len()).map(|i|{format!("{{\"source\": {}}}", i)}).collect();
+        writeln!(out, "  \"textures\": [{}],", textures.join(", "))?;
+    }
+    writeln!(out, "  \"buffers\": [{{\"byteLength\": {}, \"uri\": \"data:application/octet-stream;base64,{}\"}}]", b.bin.len(), gltf::base64_encode(&b.bin))?;
+    writeln!(out, "}}")?;
+    return Ok(())
+}
@@ -0,0 +1,68 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::RwLock;
+use yaml_rust::YamlLoader;
+
+use super::{deps, AssetTable, TableEntry};
+
+// segments pinned to uids, for bins where telling them apart from the type
+// flags goes wrong. A segment map lists the first uid of each segment, in
+// order, the same way `detect-segments` prints them:
+//
+//     segments:
+//       - 0x0000
+//       - 0x0371
+//
+// While one is set every entry gets the segment of the last start at or before
+// its uid, and the flags aren't looked at. A segment with no entries has the
+// same start as the one after it.
+
+static STARTS : RwLock<Option<Vec<usize>>> = RwLock::new(None);
+
+/// the segment starts of a segment map
+pub fn read(path: &Path) -> Vec<usize>{
+    let text = deps::read_to_string(path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+    let doc = &YamlLoader::load_from_str(&text).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)})[0];
+    let starts : Vec<usize> = doc["segments"].as_vec().unwrap_or_else(||{panic!("{:?}: no segments list", path)}).iter()
+        .map(|s|{s.as_i64().unwrap_or_else(||{panic!("{:?}: segment start {:?} is not a uid", path, s)}) as usize})
+        .collect();
+    assert!(starts.windows(2).all(|w|{w[0] <= w[1]}), "{:?}: segment starts can't go down", path);
+    return starts
+}
+
+/// uses `starts` instead of the flags from now on
+pub fn set(starts: Vec<usize>){
+    *STARTS.write().unwrap() = Some(starts);
+}
+
+/// the segment of each entry from the map, if one is set, from their type
+/// flags. Empty entries are 0
+pub fn segments(flags: &[u16]) -> Option<Vec<usize>>{
+    let starts = STARTS.read().unwrap();
+    let starts = starts.as_ref()?;
+    return Some(flags.iter().enumerate()
+        .map(|(uid, f)|{
+            if *f == 4{
+                return 0
+            }
+            starts.iter().rposition(|s|{*s <= uid}).unwrap_or(0)
+        })
+        .collect())
+}
+
+/// the segments of the bin as a segment map, with where each one ends, how
+/// many entries it has and the flags of its first entry. A segment without
+/// entries starts where the next one does
+pub fn write(out: &mut dyn Write, table: &AssetTable) -> io::Result<()>{
+    let entries : Vec<TableEntry> = table.entries().filter(|e|{e.flags != 4}).collect();
+    let last = entries.iter().map(|e|{e.segment}).max().unwrap_or(0);
+    writeln!(out, "segments:")?;
+    for seg in 0..=last{
+        let first = entries.iter().find(|e|{e.segment >= seg}).unwrap();
+        let members : Vec<&TableEntry> = entries.iter().filter(|e|{e.segment == seg}).collect();
+        let end = entries.iter().find(|e|{e.segment > seg}).map_or(table.len(), |e|{e.uid});
+        let flags = members.first().map_or(String::new(), |e|{format!(", flags 0x{:04X}", e.flags)});
+        writeln!(out, "  - 0x{:04X} # segment {}, up to 0x{:04X}, {} entries{}", first.uid, seg, end, members.len(), flags)?;
+    }
+    return Ok(())
+}
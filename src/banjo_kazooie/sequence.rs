@@ -0,0 +1,758 @@
+use std::convert::TryInto;
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::asset::{Asset, AssetType, ExtractOptions};
+use super::deps;
+
+// music sequences are libultra compressed midi: 16 track offsets and the
+// division, then each track's events. Notes carry their duration instead of a
+// note off, meta events have no length byte, and repeated runs of bytes are
+// replaced by 0xFE back references into earlier track data.
+
+const BLOCK_CODE : u8 = 0xFE; //FE FE is a literal FE, else FE hi lo len
+const META : u8 = 0xFF;
+const META_LOOP_END : u8 = 0x2D;
+const META_LOOP_START : u8 = 0x2E;
+const META_END_OF_TRACK : u8 = 0x2F;
+const META_TEMPO : u8 = 0x51;
+const DEFAULT_TEMPO : u32 = 500000; //120 bpm, until a tempo event says otherwise
+
+pub const TRACK_CNT : usize = 16;
+
+/// reads a track the way the game does, following back references
+struct TrackReader<'a>{
+    bytes : &'a [u8],
+    pos : usize,
+    backup : Option<(usize, usize)>, //position and bytes left of a back reference
+}
+
+impl<'a> TrackReader<'a>{
+    fn u8(&mut self) -> Option<u8>{
+        if let Some((pos, len)) = self.backup{
+            let b = *self.bytes.get(pos)?;
+            self.backup = if len > 1 {Some((pos + 1, len - 1))} else {None};
+            return Some(b);
+        }
+        let b = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        if b != BLOCK_CODE{
+            return Some(b);
+        }
+        let next = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        if next == BLOCK_CODE{
+            return Some(BLOCK_CODE);
+        }
+        //the distance counts back from the FE byte
+        let distance = u16::from_be_bytes([next, *self.bytes.get(self.pos)?]) as usize;
+        let len = *self.bytes.get(self.pos + 1)? as usize;
+        self.pos += 2;
+        let start = (self.pos - 4).checked_sub(distance)?;
+        if len == 0{
+            return None;
+        }
+        self.backup = Some((start, len));
+        self.u8()
+    }
+
+    /// loop ends are read and rewritten in place by the game, outside of back references
+    fn raw_u8(&mut self) -> Option<u8>{
+        if self.backup.is_some(){
+            return None;
+        }
+        let b = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    /// file position, None inside a back reference
+    fn position(&self) -> Option<usize>{
+        match self.backup{
+            Some(_) => None,
+            None => Some(self.pos),
+        }
+    }
+
+    fn var_len(&mut self) -> Option<u32>{
+        let mut value : u32 = 0;
+        for _ in 0..4{
+            let b = self.u8()?;
+            value = (value << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0{
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+pub fn write_var_len(out: &mut Vec<u8>, value: u32){
+    let mut groups : Vec<u8> = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest != 0{
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeqMessage{
+    Note{channel: u8, note: u8, velocity: u8, duration: u32},
+    NoteOff{channel: u8, note: u8, velocity: u8},
+    KeyPressure{channel: u8, note: u8, pressure: u8},
+    Controller{channel: u8, controller: u8, value: u8},
+    Program{channel: u8, program: u8},
+    ChannelPressure{channel: u8, pressure: u8},
+    PitchBend{channel: u8, value: u16}, //14 bits, 0x2000 is centered
+    Tempo(u32), //microseconds per quarter note
+    LoopStart{number: u8, extra: u8},
+    /// jumps back `offset` bytes, to event `start` of the track when that is known
+    LoopEnd{count: u8, current: u8, start: Option<usize>, offset: u32},
+    EndOfTrack,
+}
+
+impl SeqMessage{
+    fn channel(&self) -> Option<u8>{
+        match self{
+            SeqMessage::Note{channel, ..} | SeqMessage::NoteOff{channel, ..}
+            | SeqMessage::KeyPressure{channel, ..} | SeqMessage::Controller{channel, ..}
+            | SeqMessage::Program{channel, ..} | SeqMessage::ChannelPressure{channel, ..}
+            | SeqMessage::PitchBend{channel, ..} => Some(*channel),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeqEvent{
+    pub delta : u32, //ticks since the previous event
+    pub running : bool, //status byte left out, reusing the previous one
+    pub message : SeqMessage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeqTrack{
+    pub events : Vec<SeqEvent>,
+}
+
+impl SeqTrack{
+    /// removes an event, handing its delta to the next one
+    fn remove_event(track: &mut SeqTrack, i: usize) -> SeqEvent{
+        let e = track.events.remove(i);
+        if let Some(next) = track.events.get_mut(i){
+            next.delta += e.delta;
+        }
+        e
+    }
+
+    /// first event at or after `tick`
+    fn insert_index(track: &SeqTrack, tick: u32) -> usize{
+        track.ticks().iter().position(|t|{*t >= tick}).unwrap_or(track.events.len())
+    }
+
+    /// inserts an event at `i`, which has to be at or after `tick`
+    fn insert_event(track: &mut SeqTrack, i: usize, tick: u32, mut e: SeqEvent){
+        let before = if i == 0 {0} else {track.ticks()[i - 1]};
+        e.delta = tick.saturating_sub(before);
+        if let Some(next) = track.events.get_mut(i){
+            next.delta -= e.delta.min(next.delta);
+        }
+        track.events.insert(i, e);
+    }
+
+    /// where event `k` ends up after `set_loop` moves its markers: the end at
+    /// `end_i` and the start marker before `start_i`, if there was one, come
+    /// out, then a start marker goes in at `start_at` and the end at `end_at`
+    fn shifted(k: usize, start_i: usize, end_i: usize, had_marker: bool, start_at: usize, end_at: usize) -> usize{
+        let mut k = k;
+        if k > end_i {k -= 1;}
+        if had_marker && k >= start_i {k -= 1;}
+        if k >= start_at {k += 1;}
+        if k >= end_at {k += 1;}
+        k
+    }
+
+    /// absolute tick of every event
+    pub fn ticks(&self) -> Vec<u32>{
+        self.events.iter()
+            .scan(0u32, |t, e|{*t += e.delta; Some(*t)})
+            .collect()
+    }
+}
+
+/// a loop of one track, in ticks from the start of the track
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeqLoop{
+    pub track : usize,
+    pub start : u32,
+    pub end : u32,
+    pub count : u8, //0xFF loops forever
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence{
+    pub division : u32, //ticks per quarter note
+    pub tracks : Vec<Option<SeqTrack>>, //always TRACK_CNT long
+}
+
+impl Sequence{
+    pub fn from_bytes(in_bytes: &[u8]) -> Option<Sequence>{
+        let word = |i: usize|{in_bytes.get(4*i..4*i + 4).map(|w|{u32::from_be_bytes(w.try_into().unwrap())})};
+        let division = word(TRACK_CNT)?;
+        let mut tracks : Vec<Option<SeqTrack>> = Vec::new();
+        for i in 0..TRACK_CNT{
+            let offset = word(i)? as usize;
+            tracks.push(match offset{
+                0 => None,
+                _ => Some(Sequence::decode_track(in_bytes, offset)?),
+            });
+        }
+        Some(Sequence{division: division, tracks: tracks})
+    }
+
+    fn decode_track(in_bytes: &[u8], offset: usize) -> Option<SeqTrack>{
+        let mut r = TrackReader{bytes: in_bytes, pos: offset, backup: None};
+        let mut events : Vec<SeqEvent> = Vec::new();
+        let mut starts : Vec<Option<usize>> = Vec::new();
+        let mut last_status : Option<u8> = None;
+        loop{
+            starts.push(r.position());
+            let delta = r.var_len()?;
+            let first = r.u8()?;
+            if first == META{
+                let message = match r.u8()?{
+                    META_TEMPO => SeqMessage::Tempo(u32::from_be_bytes([0, r.u8()?, r.u8()?, r.u8()?])),
+                    META_END_OF_TRACK => SeqMessage::EndOfTrack,
+                    META_LOOP_START => SeqMessage::LoopStart{number: r.u8()?, extra: r.u8()?},
+                    META_LOOP_END => {
+                        let (count, current) = (r.raw_u8()?, r.raw_u8()?);
+                        let offset = u32::from_be_bytes([r.raw_u8()?, r.raw_u8()?, r.raw_u8()?, r.raw_u8()?]);
+                        let target = r.pos.checked_sub(offset as usize);
+                        let start = starts.iter().position(|p|{p.is_some() && *p == target});
+                        SeqMessage::LoopEnd{count: count, current: current, start: start, offset: offset}
+                    },
+                    _ => return None,
+                };
+                let end = message == SeqMessage::EndOfTrack;
+                events.push(SeqEvent{delta: delta, running: false, message: message});
+                if end{
+                    break;
+                }
+                continue;
+            }
+            let (status, running, data1) = match first & 0x80{
+                0 => (last_status?, true, first),
+                _ => (first, false, r.u8()?),
+            };
+            last_status = Some(status);
+            let channel = status & 0xF;
+            let message = match status & 0xF0{
+                0x80 => SeqMessage::NoteOff{channel: channel, note: data1, velocity: r.u8()?},
+                0x90 => SeqMessage::Note{channel: channel, note: data1, velocity: r.u8()?, duration: r.var_len()?},
+                0xA0 => SeqMessage::KeyPressure{channel: channel, note: data1, pressure: r.u8()?},
+                0xB0 => SeqMessage::Controller{channel: channel, controller: data1, value: r.u8()?},
+                0xC0 => SeqMessage::Program{channel: channel, program: data1},
+                0xD0 => SeqMessage::ChannelPressure{channel: channel, pressure: data1},
+                0xE0 => SeqMessage::PitchBend{channel: channel, value: (data1 as u16 & 0x7F) | ((r.u8()? as u16 & 0x7F) << 7)},
+                _ => return None,
+            };
+            events.push(SeqEvent{delta: delta, running: running, message: message});
+        }
+        Some(SeqTrack{events: events})
+    }
+
+    /// standard midi file, format 1 with one midi track per sequence track.
+    /// Notes get a note off after their duration and loop markers become
+    /// `loop_start N` and `loop_end` marker events; loops play once
+    pub fn to_midi(&self) -> Vec<u8>{
+        let tracks : Vec<&SeqTrack> = self.tracks.iter().flatten().collect();
+        let mut out : Vec<u8> = b"MThd".to_vec();
+        out.extend_from_slice(&6u32.to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        out.extend_from_slice(&(self.division as u16).to_be_bytes());
+        for track in tracks{
+            //(tick, note offs first, file order, bytes)
+            let mut midi_events : Vec<(u32, u8, usize, Vec<u8>)> = Vec::new();
+            let mut end = 0;
+            for (i, (tick, e)) in track.ticks().into_iter().zip(track.events.iter()).enumerate(){
+                let status = |s: u8|{s | e.message.channel().unwrap_or(0)};
+                let bytes = match &e.message{
+                    SeqMessage::Note{note, velocity, duration, ..} => {
+                        midi_events.push((tick + duration, 0, i, vec![status(0x80), *note, 0]));
+                        end = end.max(tick + duration);
+                        vec![status(0x90), *note, *velocity]
+                    },
+                    SeqMessage::NoteOff{note, velocity, ..} => vec![status(0x80), *note, *velocity],
+                    SeqMessage::KeyPressure{note, pressure, ..} => vec![status(0xA0), *note, *pressure],
+                    SeqMessage::Controller{controller, value, ..} => vec![status(0xB0), *controller, *value],
+                    SeqMessage::Program{program, ..} => vec![status(0xC0), *program],
+                    SeqMessage::ChannelPressure{pressure, ..} => vec![status(0xD0), *pressure],
+                    SeqMessage::PitchBend{value, ..} => vec![status(0xE0), (value & 0x7F) as u8, (value >> 7) as u8 & 0x7F],
+                    SeqMessage::Tempo(t) => vec![META, META_TEMPO, 3, (t >> 16) as u8, (t >> 8) as u8, *t as u8],
+                    SeqMessage::LoopStart{number, ..} => {
+                        let text = format!("loop_start {}", number);
+                        [vec![META, 0x06, text.len() as u8], text.into_bytes()].concat()
+                    },
+                    SeqMessage::LoopEnd{..} => [vec![META, 0x06, 8], b"loop_end".to_vec()].concat(),
+                    SeqMessage::EndOfTrack => {
+                        end = end.max(tick);
+                        continue;
+                    },
+                };
+                end = end.max(tick);
+                midi_events.push((tick, 1, i, bytes));
+            }
+            midi_events.sort_by_key(|(tick, order, i, _)|{(*tick, *order, *i)});
+
+            let mut data : Vec<u8> = Vec::new();
+            let mut last = 0;
+            for (tick, _, _, bytes) in midi_events.iter(){
+                write_var_len(&mut data, tick - last);
+                data.extend_from_slice(bytes);
+                last = *tick;
+            }
+            write_var_len(&mut data, end - last);
+            data.extend_from_slice(&[META, META_END_OF_TRACK, 0]);
+            out.extend_from_slice(b"MTrk");
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            out.extend(data);
+        }
+        return out
+    }
+
+    /// uncompressed, with FE bytes escaped. Loop ends that know their start
+    /// get their offset recomputed
+    pub fn to_bytes(&self) -> Vec<u8>{
+        assert_eq!(self.tracks.len(), TRACK_CNT, "sequences have {} tracks", TRACK_CNT);
+        let mut out : Vec<u8> = vec![0; 4*TRACK_CNT];
+        out.extend_from_slice(&self.division.to_be_bytes());
+        for (i, track) in self.tracks.iter().enumerate(){
+            let track = match track{
+                Some(t) => t,
+                None => continue,
+            };
+            let offset = out.len() as u32;
+            out[4*i..4*i + 4].copy_from_slice(&offset.to_be_bytes());
+            let mut starts : Vec<usize> = Vec::new();
+            let mut last_status : Option<u8> = None;
+            for (j, e) in track.events.iter().enumerate(){
+                starts.push(out.len());
+                let mut bytes : Vec<u8> = Vec::new();
+                write_var_len(&mut bytes, e.delta);
+                let status = |s: u8|{s | e.message.channel().unwrap_or(0)};
+                let (status_byte, data) : (Option<u8>, Vec<u8>) = match &e.message{
+                    SeqMessage::Note{note, velocity, duration, ..} => {
+                        let mut d = vec![*note, *velocity];
+                        write_var_len(&mut d, *duration);
+                        (Some(status(0x90)), d)
+                    },
+                    SeqMessage::NoteOff{note, velocity, ..} => (Some(status(0x80)), vec![*note, *velocity]),
+                    SeqMessage::KeyPressure{note, pressure, ..} => (Some(status(0xA0)), vec![*note, *pressure]),
+                    SeqMessage::Controller{controller, value, ..} => (Some(status(0xB0)), vec![*controller, *value]),
+                    SeqMessage::Program{program, ..} => (Some(status(0xC0)), vec![*program]),
+                    SeqMessage::ChannelPressure{pressure, ..} => (Some(status(0xD0)), vec![*pressure]),
+                    SeqMessage::PitchBend{value, ..} => (Some(status(0xE0)), vec![(value & 0x7F) as u8, (value >> 7) as u8 & 0x7F]),
+                    SeqMessage::Tempo(t) => (None, vec![META, META_TEMPO, (t >> 16) as u8, (t >> 8) as u8, *t as u8]),
+                    SeqMessage::LoopStart{number, extra} => (None, vec![META, META_LOOP_START, *number, *extra]),
+                    SeqMessage::EndOfTrack => (None, vec![META, META_END_OF_TRACK]),
+                    SeqMessage::LoopEnd{count, current, start, offset} => {
+                        //written unescaped, the game reads these bytes directly
+                        for b in bytes.iter().chain([META, META_LOOP_END].iter()){
+                            Sequence::push_escaped(&mut out, *b);
+                        }
+                        let offset = match start{
+                            Some(k) => {
+                                assert!(*k <= j, "track {} loop end {} jumps forward to event {}", i, j, k);
+                                (out.len() + 6 - starts[*k]) as u32
+                            },
+                            None => *offset,
+                        };
+                        out.extend_from_slice(&[*count, *current]);
+                        out.extend_from_slice(&offset.to_be_bytes());
+                        continue;
+                    },
+                };
+                if let Some(sb) = status_byte{
+                    //running status only holds if the previous status matches
+                    if !(e.running && last_status == Some(sb)){
+                        bytes.push(sb);
+                    }
+                    last_status = Some(sb);
+                }
+                bytes.extend(data);
+                for b in bytes{
+                    Sequence::push_escaped(&mut out, b);
+                }
+            }
+        }
+        return out
+    }
+
+    /// loop end offsets only matter when their start isn't known
+    fn without_known_offsets(&self) -> Sequence{
+        let mut out = self.clone();
+        for e in out.tracks.iter_mut().flatten().flat_map(|t|{t.events.iter_mut()}){
+            if let SeqMessage::LoopEnd{start: Some(_), offset, ..} = &mut e.message{
+                *offset = 0;
+            }
+        }
+        return out
+    }
+
+    fn push_escaped(out: &mut Vec<u8>, b: u8){
+        out.push(b);
+        if b == BLOCK_CODE{
+            out.push(BLOCK_CODE);
+        }
+    }
+
+    /// every loop whose start is known, in track then file order
+    pub fn loops(&self) -> Vec<SeqLoop>{
+        let mut out : Vec<SeqLoop> = Vec::new();
+        for (i, track) in self.tracks.iter().enumerate(){
+            let track = match track{
+                Some(t) => t,
+                None => continue,
+            };
+            let ticks = track.ticks();
+            for (j, e) in track.events.iter().enumerate(){
+                if let SeqMessage::LoopEnd{count, start: Some(k), ..} = e.message{
+                    //the jump replays the start event's delta, so the loop starts before it
+                    out.push(SeqLoop{track: i, start: ticks[k] - track.events[k].delta, end: ticks[j], count: count});
+                }
+            }
+        }
+        return out
+    }
+
+    /// moves the `n`th loop of its track to new start and end ticks. The loop
+    /// start marker, if it sits right before the start, moves along with it.
+    /// A loop without one gets one, a jump lands after an event's tick so the
+    /// start needs an event of its own on it
+    pub fn set_loop(&mut self, n: usize, new: &SeqLoop){
+        assert!(new.start <= new.end, "loop start {} is after its end {}", new.start, new.end);
+        let track = self.tracks.get_mut(new.track).and_then(|t|{t.as_mut()}).unwrap_or_else(||{panic!("there is no track {}", new.track)});
+        let end_i = track.events.iter().enumerate()
+            .filter(|(_, e)|{matches!(e.message, SeqMessage::LoopEnd{start: Some(_), ..})})
+            .nth(n).map(|(j, _)|{j})
+            .unwrap_or_else(||{panic!("track {} has no loop {}", new.track, n)});
+        let start_i = match track.events[end_i].message{
+            SeqMessage::LoopEnd{start: Some(k), ..} => k,
+            _ => unreachable!(),
+        };
+        let has_marker = start_i > 0 && matches!(track.events[start_i - 1].message, SeqMessage::LoopStart{..});
+
+        //take the markers out, keeping everything else's timing
+        let mut end_event = SeqTrack::remove_event(track, end_i);
+        let start_event = if has_marker {Some(SeqTrack::remove_event(track, start_i - 1))} else {None};
+        if let SeqMessage::LoopEnd{count, ..} = &mut end_event.message{
+            *count = new.count;
+        }
+        //the start goes before anything on its tick, the end before anything on its own
+        let start_at = SeqTrack::insert_index(track, new.start);
+        let marker = start_event.unwrap_or_else(||{
+            //the player skips loop start markers, the number only has to be new
+            let number = track.events.iter()
+                .filter_map(|e|{match e.message {SeqMessage::LoopStart{number, ..} => Some(number.saturating_add(1)), _ => None}})
+                .max().unwrap_or(0);
+            SeqEvent{delta: 0, running: false, message: SeqMessage::LoopStart{number: number, extra: 0xFF}}
+        });
+        SeqTrack::insert_event(track, start_at, new.start, marker);
+        let start = start_at + 1;
+        let end_at = SeqTrack::insert_index(track, new.end).max(start);
+        if let SeqMessage::LoopEnd{start: s, ..} = &mut end_event.message{
+            *s = Some(start);
+        }
+        //later loops pointing past the insertions shift with them
+        for e in track.events.iter_mut(){
+            if let SeqMessage::LoopEnd{start: Some(k), ..} = &mut e.message{
+                *k = SeqTrack::shifted(*k, start_i, end_i, has_marker, start_at, end_at);
+            }
+        }
+        SeqTrack::insert_event(track, end_at, new.end, end_event);
+    }
+
+    /// plays the sequence `scale` times as fast by scaling every tempo event.
+    /// One without any gets one at the start of its first track, scaled from
+    /// the 120 bpm default. Returns how many tempo events there are
+    pub fn scale_tempo(&mut self, scale: f32) -> usize{
+        assert!(scale > 0.0, "tempo scale has to be positive");
+        let scaled = |t: u32| -> u32{
+            let new_t = (t as f32/scale).round();
+            assert!(1.0 <= new_t && new_t <= 0xFFFFFF as f32, "tempo {} would become {}, outside of 24 bits", t, new_t);
+            new_t as u32
+        };
+        let mut count = 0;
+        for e in self.tracks.iter_mut().flatten().flat_map(|t|{t.events.iter_mut()}){
+            if let SeqMessage::Tempo(t) = &mut e.message{
+                *t = scaled(*t);
+                count += 1;
+            }
+        }
+        if count == 0{
+            let track = self.tracks.iter_mut().flatten().next().expect("sequence has no tracks");
+            track.events.insert(0, SeqEvent{delta: 0, running: false, message: SeqMessage::Tempo(scaled(DEFAULT_TEMPO))});
+            for e in track.events.iter_mut(){
+                if let SeqMessage::LoopEnd{start: Some(k), ..} = &mut e.message{
+                    *k += 1;
+                }
+            }
+            count = 1;
+        }
+        return count
+    }
+
+    /// silences every note of track `i` by zeroing its velocity, so its timing,
+    /// loops and controllers stay as they are. Returns how many notes it has
+    pub fn mute_track(&mut self, i: usize) -> usize{
+        let track = self.tracks.get_mut(i).and_then(|t|{t.as_mut()}).unwrap_or_else(||{panic!("there is no track {}", i)});
+        let mut count = 0;
+        for e in track.events.iter_mut(){
+            if let SeqMessage::Note{velocity, ..} = &mut e.message{
+                *velocity = 0;
+                count += 1;
+            }
+        }
+        return count
+    }
+
+    fn write_yaml(&self, out: &mut dyn Write){
+        writeln!(out, "division: {}", self.division).unwrap();
+        let loops = self.loops();
+        if !loops.is_empty(){
+            writeln!(out, "loops:").unwrap();
+            for l in loops.iter(){
+                writeln!(out, "  - {{track: {}, start: {}, end: {}, count: {}}}", l.track, l.start, l.end, l.count).unwrap();
+            }
+        }
+        writeln!(out, "tracks:").unwrap();
+        for (i, track) in self.tracks.iter().enumerate(){
+            let track = match track{
+                Some(t) => t,
+                None => continue,
+            };
+            writeln!(out, "  - index: {}", i).unwrap();
+            writeln!(out, "    events:").unwrap();
+            for (j, e) in track.events.iter().enumerate(){
+                let body = match &e.message{
+                    SeqMessage::Note{channel, note, velocity, duration} => format!("note: [{}, {}, {}, {}]", channel, note, velocity, duration),
+                    SeqMessage::NoteOff{channel, note, velocity} => format!("note_off: [{}, {}, {}]", channel, note, velocity),
+                    SeqMessage::KeyPressure{channel, note, pressure} => format!("key_pressure: [{}, {}, {}]", channel, note, pressure),
+                    SeqMessage::Controller{channel, controller, value} => format!("controller: [{}, {}, {}]", channel, controller, value),
+                    SeqMessage::Program{channel, program} => format!("program: [{}, {}]", channel, program),
+                    SeqMessage::ChannelPressure{channel, pressure} => format!("channel_pressure: [{}, {}]", channel, pressure),
+                    SeqMessage::PitchBend{channel, value} => format!("pitch_bend: [{}, {}]", channel, value),
+                    SeqMessage::Tempo(t) => format!("tempo: {}", t),
+                    SeqMessage::LoopStart{number, extra} => format!("loop_start: [{}, {}]", number, extra),
+                    SeqMessage::LoopEnd{count, current, start: Some(k), ..} => format!("loop_end: {{count: {}, current: {}, start: {}}}", count, current, k),
+                    SeqMessage::LoopEnd{count, current, start: None, offset} => format!("loop_end: {{count: {}, current: {}, offset: {}}}", count, current, offset),
+                    SeqMessage::EndOfTrack => String::from("end_of_track: []"),
+                };
+                let running = if e.running {", running: true"} else {""};
+                writeln!(out, "      - {{delta: {}, {}{}}} # {}", e.delta, body, running, j).unwrap();
+            }
+        }
+    }
+
+    fn from_yaml(doc: &Yaml, path: &Path) -> Sequence{
+        let int = |y: &Yaml, what: &str| -> i64{
+            y.as_i64().unwrap_or_else(||{panic!("{:?}: {} has to be an integer", path, what)})
+        };
+        let args = |y: &Yaml, n: usize, what: &str| -> Vec<i64>{
+            let v = y.as_vec().filter(|v|{v.len() == n}).unwrap_or_else(||{panic!("{:?}: {} needs {} integers", path, what, n)});
+            v.iter().map(|a|{int(a, what)}).collect()
+        };
+        let mut tracks : Vec<Option<SeqTrack>> = vec![None; TRACK_CNT];
+        for t in doc["tracks"].as_vec().unwrap_or_else(||{panic!("{:?}: sequence has no tracks", path)}){
+            let index = int(&t["index"], "track index") as usize;
+            assert!(index < TRACK_CNT, "{:?}: track index {} is past {}", path, index, TRACK_CNT - 1);
+            let events = t["events"].as_vec().unwrap_or_else(||{panic!("{:?}: track {} has no events", path, index)}).iter()
+                .map(|e|{
+                    let (key, value) = e.as_hash().unwrap().iter()
+                        .find(|(k, _)|{!matches!(k.as_str(), Some("delta") | Some("running"))})
+                        .unwrap_or_else(||{panic!("{:?}: track {} has an event with no message", path, index)});
+                    let kind = key.as_str().unwrap_or("");
+                    let a = |n: usize|{args(value, n, kind).into_iter().map(|x|{x as u32}).collect::<Vec<u32>>()};
+                    let message = match kind{
+                        "note" => {let a = a(4); SeqMessage::Note{channel: a[0] as u8, note: a[1] as u8, velocity: a[2] as u8, duration: a[3]}},
+                        "note_off" => {let a = a(3); SeqMessage::NoteOff{channel: a[0] as u8, note: a[1] as u8, velocity: a[2] as u8}},
+                        "key_pressure" => {let a = a(3); SeqMessage::KeyPressure{channel: a[0] as u8, note: a[1] as u8, pressure: a[2] as u8}},
+                        "controller" => {let a = a(3); SeqMessage::Controller{channel: a[0] as u8, controller: a[1] as u8, value: a[2] as u8}},
+                        "program" => {let a = a(2); SeqMessage::Program{channel: a[0] as u8, program: a[1] as u8}},
+                        "channel_pressure" => {let a = a(2); SeqMessage::ChannelPressure{channel: a[0] as u8, pressure: a[1] as u8}},
+                        "pitch_bend" => {let a = a(2); SeqMessage::PitchBend{channel: a[0] as u8, value: a[1] as u16}},
+                        "tempo" => SeqMessage::Tempo(int(value, "tempo") as u32),
+                        "loop_start" => {let a = a(2); SeqMessage::LoopStart{number: a[0] as u8, extra: a[1] as u8}},
+                        "loop_end" => SeqMessage::LoopEnd{
+                            count: int(&value["count"], "loop count") as u8,
+                            current: int(&value["current"], "loop current") as u8,
+                            start: value["start"].as_i64().map(|k|{k as usize}),
+                            offset: value["offset"].as_i64().unwrap_or(0) as u32,
+                        },
+                        "end_of_track" => SeqMessage::EndOfTrack,
+                        x => panic!("{:?}: unknown sequence event {:?}", path, x),
+                    };
+                    if let Some(c) = message.channel(){
+                        assert!(c < 16, "{:?}: channel {} is past 15", path, c);
+                    }
+                    SeqEvent{delta: int(&e["delta"], "delta") as u32, running: e["running"].as_bool().unwrap_or(false), message: message}
+                })
+                .collect();
+            tracks[index] = Some(SeqTrack{events: events});
+        }
+        Sequence{division: int(&doc["division"], "division") as u32, tracks: tracks}
+    }
+}
+
+/// music sequence. Extraction writes the original bytes, a yaml list of every
+/// track's events and a standard midi file; construct uses the bytes unless
+/// the events were edited
+pub struct MidiSeqFile{
+    bytes: Vec<u8>,
+}
+
+impl MidiSeqFile{
+    pub fn from_bytes(in_bytes: &[u8])->MidiSeqFile{
+        MidiSeqFile{bytes: in_bytes.to_vec()}
+    }
+
+    pub fn read(path: &Path) -> MidiSeqFile{
+        if path.extension().unwrap() == "bin"{
+            return MidiSeqFile{bytes: deps::read(path).unwrap()}
+        }
+        let doc = &YamlLoader::load_from_str(&deps::read_to_string(path).expect("could not open yaml")).unwrap()[0];
+        assert_eq!(doc["type"].as_str(), Some("Sequence"), "{:?} is not a sequence", path);
+        let bin = doc["bin"].as_str().map(|b|{deps::read(path.parent().unwrap().join(b)).unwrap()});
+        if doc["tracks"].is_badvalue(){
+            return MidiSeqFile{bytes: bin.unwrap_or_else(||{panic!("{:?}: sequence needs tracks or a bin", path)})}
+        }
+        let mut seq = Sequence::from_yaml(doc, path);
+        //loops edited in ticks move their markers
+        if let Some(loops) = doc["loops"].as_vec(){
+            let current = seq.loops();
+            for (i, l) in loops.iter().enumerate(){
+                let int = |y: &Yaml, what: &str|{y.as_i64().unwrap_or_else(||{panic!("{:?}: loop {} {} has to be an integer", path, i, what)})};
+                let new = SeqLoop{
+                    track: int(&l["track"], "track") as usize,
+                    start: int(&l["start"], "start") as u32,
+                    end: int(&l["end"], "end") as u32,
+                    count: int(&l["count"], "count") as u8,
+                };
+                let old = current.get(i).unwrap_or_else(||{panic!("{:?}: loops can be moved but not added, there are {}", path, current.len())});
+                assert_eq!(old.track, new.track, "{:?}: loop {} belongs to track {}", path, i, old.track);
+                if *old != new{
+                    let n = current[..i].iter().filter(|o|{o.track == new.track}).count();
+                    seq.set_loop(n, &new);
+                }
+            }
+        }
+        //unedited events keep the original, compressed bytes
+        if let Some(bin) = bin{
+            if Sequence::from_bytes(&bin).map(|s|{s.without_known_offsets()}) == Some(seq.without_known_offsets()){
+                return MidiSeqFile{bytes: bin}
+            }
+        }
+        MidiSeqFile{bytes: seq.to_bytes()}
+    }
+}
+
+impl Asset for MidiSeqFile{
+    fn to_bytes(&self)->Vec<u8>{
+        return self.bytes.clone();
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::Midi;
+    }
+
+    fn write(&self, path: &Path, _opts: &ExtractOptions){
+        let base_name = path.file_name().unwrap().to_str().unwrap().trim_end_matches(".seq.yaml");
+        let bin_name = format!("{}.midi.bin", base_name);
+        deps::write(path.parent().unwrap().join(&bin_name), &self.bytes).unwrap();
+        let mut seq_f = deps::create(path).unwrap();
+        writeln!(seq_f, "type: Sequence").unwrap();
+        writeln!(seq_f, "bin: {:?}", bin_name).unwrap();
+        if let Some(seq) = Sequence::from_bytes(&self.bytes){
+            seq.write_yaml(&mut seq_f);
+            deps::write(path.parent().unwrap().join(format!("{}.mid", base_name)), seq.to_midi()).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note() -> SeqMessage{
+        return SeqMessage::Note{channel: 0, note: 60, velocity: 100, duration: 5}
+    }
+
+    fn loop_end(start: usize) -> SeqMessage{
+        return SeqMessage::LoopEnd{count: 0xFF, current: 0, start: Some(start), offset: 0}
+    }
+
+    fn sequence(events: Vec<(u32, SeqMessage)>) -> Sequence{
+        let mut tracks : Vec<Option<SeqTrack>> = vec![None; TRACK_CNT];
+        tracks[0] = Some(SeqTrack{events: events.into_iter().map(|(d, m)|{SeqEvent{delta: d, running: false, message: m}}).collect()});
+        return Sequence{division: 48, tracks: tracks}
+    }
+
+    /// the loops, also after writing the sequence and reading it back
+    fn loops_read_back(seq: &Sequence) -> Vec<SeqLoop>{
+        let loops = seq.loops();
+        assert_eq!(Sequence::from_bytes(&seq.to_bytes()).unwrap().loops(), loops);
+        return loops
+    }
+
+    fn note_ticks(seq: &Sequence) -> Vec<u32>{
+        let track = seq.tracks[0].as_ref().unwrap();
+        return track.ticks().into_iter().zip(track.events.iter()).filter(|(_, e)|{matches!(e.message, SeqMessage::Note{..})}).map(|(t, _)|{t}).collect()
+    }
+
+    #[test]
+    fn moved_loop_without_a_marker_reads_back(){
+        let mut seq = sequence(vec![(0, SeqMessage::Program{channel: 0, program: 1}), (10, note()), (10, note()), (10, note()), (0, loop_end(2)), (0, SeqMessage::EndOfTrack)]);
+        assert_eq!(seq.loops(), vec![SeqLoop{track: 0, start: 10, end: 30, count: 0xFF}]);
+        let new = SeqLoop{track: 0, start: 15, end: 30, count: 2};
+        seq.set_loop(0, &new);
+        assert_eq!(loops_read_back(&seq), vec![new]);
+        assert_eq!(note_ticks(&seq), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn moved_loop_takes_its_marker_along(){
+        let mut seq = sequence(vec![(10, note()), (0, SeqMessage::LoopStart{number: 0, extra: 0xFF}), (10, note()), (10, note()), (0, loop_end(2)), (0, SeqMessage::EndOfTrack)]);
+        assert_eq!(seq.loops(), vec![SeqLoop{track: 0, start: 10, end: 30, count: 0xFF}]);
+        let new = SeqLoop{track: 0, start: 5, end: 20, count: 0xFF};
+        seq.set_loop(0, &new);
+        assert_eq!(loops_read_back(&seq), vec![new]);
+        assert_eq!(note_ticks(&seq), vec![10, 20, 30]);
+        let markers = seq.tracks[0].as_ref().unwrap().events.iter().filter(|e|{matches!(e.message, SeqMessage::LoopStart{..})}).count();
+        assert_eq!(markers, 1);
+    }
+
+    #[test]
+    fn later_loops_keep_their_ticks(){
+        let mut seq = sequence(vec![(0, SeqMessage::Program{channel: 0, program: 1}), (10, note()), (10, note()), (0, loop_end(2)), (10, note()), (10, note()), (0, loop_end(4)), (0, SeqMessage::EndOfTrack)]);
+        let second = SeqLoop{track: 0, start: 20, end: 40, count: 0xFF};
+        assert_eq!(seq.loops()[1], second);
+        let new = SeqLoop{track: 0, start: 5, end: 20, count: 0xFF};
+        seq.set_loop(0, &new);
+        assert_eq!(loops_read_back(&seq), vec![new, second]);
+    }
+
+    #[test]
+    fn shifted_follows_the_removals_then_the_insertions(){
+        //end at 3 and marker at 1 out, marker in at 0 and end in at 4
+        assert_eq!(SeqTrack::shifted(0, 2, 3, true, 0, 4), 1);
+        assert_eq!(SeqTrack::shifted(2, 2, 3, true, 0, 4), 2);
+        assert_eq!(SeqTrack::shifted(5, 2, 3, true, 0, 4), 5);
+        //no marker to take out
+        assert_eq!(SeqTrack::shifted(2, 2, 3, false, 3, 4), 2);
+        assert_eq!(SeqTrack::shifted(4, 2, 3, false, 3, 4), 5);
+    }
+}
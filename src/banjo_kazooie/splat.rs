@@ -0,0 +1,32 @@
+use std::io::{self, Write};
+
+use super::names::NameMap;
+use super::{type_folder, type_name, AssetTable};
+
+// splat segment listing of the asset bin, so the decomp's extraction config
+// can be regenerated from the table. One `group` segment starting at the bin's
+// rom address, with a bin subsegment for the table and one per non-empty
+// entry, named `assets/<type folder>_<uid or name>`.
+
+pub fn write(out: &mut dyn Write, in_bytes: &[u8], rom_base: usize, names: &NameMap) -> io::Result<()>{
+    let table = AssetTable::new(in_bytes);
+    let data_start = rom_base + table.data_start();
+    writeln!(out, "# asset bin segments from bk_asset_tool {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "- name: assets")?;
+    writeln!(out, "  type: group")?;
+    writeln!(out, "  start: 0x{:X}", rom_base)?;
+    writeln!(out, "  subsegments:")?;
+    writeln!(out, "    - [0x{:X}, bin, assets/table] # 0x{:X} entries", rom_base, table.len() + 1)?;
+    for e in table.entries().filter(|e|{e.flags != 4 && e.size != 0}){
+        //entries that don't decode are listed as bin
+        let asset_type = table.try_asset(e.uid).ok().flatten().map(|a|{a.get_type()});
+        let (folder, type_str) = asset_type.as_ref().map_or(("bin", "?".to_string()), |t|{(type_folder(t), type_name(t))});
+        writeln!(out, "    - [0x{:X}, bin, assets/{}] # {}, segment {}, {}0x{:X} bytes",
+            data_start + e.offset, names.symbol(e.uid, folder), type_str, e.segment, if e.compressed {"compressed, "} else {""}, e.size)?;
+    }
+    if in_bytes.len() > table.data_start() + table.data_end(){
+        writeln!(out, "    - [0x{:X}, bin, assets/padding]", data_start + table.data_end())?;
+    }
+    writeln!(out, "- [0x{:X}]", rom_base + in_bytes.len())?;
+    return Ok(())
+}
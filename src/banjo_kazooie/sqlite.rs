@@ -0,0 +1,124 @@
+use std::path::Path;
+
+use rusqlite::types::{ToSql, ToSqlOutput, ValueRef};
+use rusqlite::Connection;
+
+// tables of plain values written out as an SQLite database, by SQLite itself
+// (rusqlite's bundled copy), in one transaction.
+
+pub enum Value{
+    Null,
+    Int(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl From<i64> for Value{
+    fn from(v: i64) -> Value{ Value::Int(v) }
+}
+
+impl From<usize> for Value{
+    fn from(v: usize) -> Value{ Value::Int(v as i64) }
+}
+
+impl From<bool> for Value{
+    fn from(v: bool) -> Value{ Value::Int(v as i64) }
+}
+
+impl From<f64> for Value{
+    fn from(v: f64) -> Value{ Value::Real(v) }
+}
+
+impl From<&str> for Value{
+    fn from(v: &str) -> Value{ Value::Text(v.to_string()) }
+}
+
+impl From<String> for Value{
+    fn from(v: String) -> Value{ Value::Text(v) }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value{
+    fn from(v: Option<T>) -> Value{ v.map_or(Value::Null, |v|{v.into()}) }
+}
+
+pub struct Table{
+    pub name : String,
+    pub columns : Vec<(String, String)>, //name and type
+    pub rows : Vec<Vec<Value>>,
+}
+
+impl Table{
+    pub fn new(name: &str, columns: &[(&str, &str)]) -> Table{
+        return Table{name: name.to_string(), columns: columns.iter().map(|(n, t)|{(n.to_string(), t.to_string())}).collect(), rows: Vec::new()}
+    }
+
+    pub fn push(&mut self, row: Vec<Value>){
+        assert_eq!(row.len(), self.columns.len(), "table {} has {} columns", self.name, self.columns.len());
+        self.rows.push(row);
+    }
+
+    fn sql(&self) -> String{
+        let columns : Vec<String> = self.columns.iter().map(|(n, t)|{format!("{} {}", n, t)}).collect();
+        return format!("CREATE TABLE {}({})", self.name, columns.join(", "))
+    }
+}
+
+impl ToSql for Value{
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>>{
+        return Ok(ToSqlOutput::Borrowed(match self{
+            Value::Null => ValueRef::Null,
+            Value::Int(v) => ValueRef::Integer(*v),
+            Value::Real(v) => ValueRef::Real(*v),
+            Value::Text(s) => ValueRef::Text(s.as_bytes()),
+            Value::Blob(b) => ValueRef::Blob(b),
+        }))
+    }
+}
+
+/// the tables as a new SQLite database at `path`, which has to not exist yet
+pub fn write(path: &Path, tables: &[Table]) -> rusqlite::Result<()>{
+    let mut db = Connection::open(path)?;
+    let tx = db.transaction()?;
+    for t in tables.iter(){
+        tx.execute(&t.sql(), [])?;
+        let marks = vec!["?"; t.columns.len()].join(", ");
+        let mut insert = tx.prepare(&format!("INSERT INTO {} VALUES ({})", t.name, marks))?;
+        for row in t.rows.iter(){
+            insert.execute(rusqlite::params_from_iter(row.iter()))?;
+        }
+    }
+    return tx.commit()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn sqlite_reads_the_tables(){
+        let mut assets = Table::new("assets", &[("uid", "INTEGER"), ("name", "TEXT"), ("size", "REAL"), ("data", "BLOB")]);
+        for i in 0..3000usize{
+            let name : Value = if i % 7 == 0 {Value::Null} else {format!("entry {}", i).into()};
+            assets.push(vec![i.into(), name, (i as f64/2.0).into(), Value::Blob(vec![i as u8; if i == 5 {20000} else {i % 50}])]);
+        }
+        let empty = Table::new("empty", &[("uid", "INTEGER")]);
+        let path = std::env::temp_dir().join(format!("bk_asset_tool_sqlite_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        write(&path, &[assets, empty]).unwrap();
+
+        let db = rusqlite::Connection::open(&path).unwrap();
+        let check : String = db.query_row("PRAGMA integrity_check", [], |r|{r.get(0)}).unwrap();
+        assert_eq!(check, "ok");
+        let (cnt, uids, names) : (i64, i64, i64) = db.query_row("SELECT count(*), sum(uid), count(name) FROM assets", [], |r|{Ok((r.get(0)?, r.get(1)?, r.get(2)?))}).unwrap();
+        assert_eq!((cnt, uids, names), (3000, 2999*3000/2, 3000 - 429));
+        let (name, len) : (String, i64) = db.query_row("SELECT name, length(data) FROM assets WHERE uid = 5", [], |r|{Ok((r.get(0)?, r.get(1)?))}).unwrap();
+        assert_eq!((name.as_str(), len), ("entry 5", 20000));
+        let size : f64 = db.query_row("SELECT size FROM assets WHERE uid = 2999", [], |r|{r.get(0)}).unwrap();
+        assert_eq!(size, 1499.5);
+        let empty_cnt : i64 = db.query_row("SELECT count(*) FROM empty", [], |r|{r.get(0)}).unwrap();
+        assert_eq!(empty_cnt, 0);
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+}
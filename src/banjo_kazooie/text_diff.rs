@@ -0,0 +1,247 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use super::asset::TextLine;
+use super::{type_name, AssetTable};
+
+// side by side diffs of the decoded strings of dialogs and questions, so a
+// translation or script change reads as text instead of bytes. Strings are
+// lined up per section (bottom, top, question, option) by their longest common
+// subsequence, and a changed string has the characters that differ marked:
+// highlighted with color, in [brackets] without.
+
+/// columns for `question 12` and the like
+const LABEL_WIDTH : usize = 12;
+
+pub struct Style{
+    pub color : bool,
+    pub width : usize, //of each side
+}
+
+impl Style{
+    /// from a `--color` of auto, always or never. auto colors when stdout is a
+    /// terminal and NO_COLOR isn't set. The sides split $COLUMNS, or 120
+    pub fn new(color: Option<&str>) -> Result<Style, String>{
+        let color = match color.unwrap_or("auto"){
+            "always" => true,
+            "never" => false,
+            "auto" => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            c => return Err(format!("invalid --color \"{}\"\n try: auto, always or never", c)),
+        };
+        let columns = env::var("COLUMNS").ok().and_then(|c|{c.parse::<usize>().ok()}).unwrap_or(120);
+        return Ok(Style{color: color, width: (columns.saturating_sub(LABEL_WIDTH + 10)/2).max(16)})
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Op{
+    Same,
+    Del(usize),
+    Add(usize),
+}
+
+/// edit script from `a` to `b` along their longest common subsequence, `Del`
+/// and `Add` holding the index into `a` and `b`. For any diff, not just text
+pub(crate) fn ops<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op>{
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev(){
+        for j in (0..b.len()).rev(){
+            lcs[i][j] = if a[i] == b[j] {lcs[i + 1][j + 1] + 1} else {lcs[i + 1][j].max(lcs[i][j + 1])};
+        }
+    }
+    let mut out : Vec<Op> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len(){
+        if i < a.len() && j < b.len() && a[i] == b[j]{
+            out.push(Op::Same);
+            i += 1;
+            j += 1;
+        }
+        else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]){
+            out.push(Op::Del(i));
+            i += 1;
+        }
+        else{
+            out.push(Op::Add(j));
+            j += 1;
+        }
+    }
+    return out
+}
+
+/// characters of a string, true where they're marked as changed
+type Cell = Vec<(char, bool)>;
+
+fn plain(line: &TextLine, cmd: bool) -> Cell{
+    let prefix = if cmd {format!("cmd 0x{:02X}: ", line.cmd)} else {String::new()};
+    return prefix.chars().map(|c|{(c, cmd)}).chain(line.text.chars().map(|c|{(c, false)})).collect()
+}
+
+/// both strings with the characters only one of them has marked. A changed cmd
+/// is written in front of the text
+fn changed(old: &TextLine, new: &TextLine) -> (Cell, Cell){
+    let cmd = old.cmd != new.cmd;
+    let (mut a, mut b) = (plain(old, cmd), plain(new, cmd));
+    let prefix = a.len() - old.text.chars().count();
+    let (a_chars, b_chars) : (Vec<char>, Vec<char>) = (old.text.chars().collect(), new.text.chars().collect());
+    let (mut i, mut j) = (prefix, prefix);
+    for op in ops(&a_chars, &b_chars){
+        match op{
+            Op::Same => {i += 1; j += 1},
+            Op::Del(_) => {a[i].1 = true; i += 1},
+            Op::Add(_) => {b[j].1 = true; j += 1},
+        }
+    }
+    return (a, b)
+}
+
+/// `cell` cut into rows of `style.width` visible characters, each padded out
+fn render(cell: &Cell, color: &str, style: &Style) -> Vec<String>{
+    //without color, marked runs get brackets
+    let mut chars : Vec<(char, bool)> = Vec::new();
+    for (k, (c, marked)) in cell.iter().enumerate(){
+        let before = k > 0 && cell[k - 1].1;
+        let after = k + 1 < cell.len() && cell[k + 1].1;
+        if !style.color && *marked && !before{
+            chars.push(('[', false));
+        }
+        chars.push((*c, *marked));
+        if !style.color && *marked && !after{
+            chars.push((']', false));
+        }
+    }
+    if chars.is_empty(){
+        return vec![" ".repeat(style.width)]
+    }
+    return chars.chunks(style.width)
+        .map(|row|{
+            let mut out = String::new();
+            if style.color{
+                out += color;
+            }
+            let mut on = false;
+            for (c, marked) in row.iter(){
+                if style.color && *marked != on{
+                    out += if *marked {"\x1b[7m"} else {"\x1b[27m"};
+                    on = *marked;
+                }
+                out.push(*c);
+            }
+            if style.color{
+                out += "\x1b[0m";
+            }
+            out + &" ".repeat(style.width - row.len())
+        })
+        .collect()
+}
+
+/// a gutter mark, label and both sides, over as many lines as the longer side
+fn row(mark: char, label: &str, old: Option<&Cell>, new: Option<&Cell>, style: &Style) -> Vec<String>{
+    let (red, green) = ("\x1b[31m", "\x1b[32m");
+    let blank = vec![" ".repeat(style.width)];
+    let a = old.map_or(blank.clone(), |c|{render(c, red, style)});
+    let b = new.map_or(blank, |c|{render(c, green, style)});
+    let mark = match (style.color, mark){
+        (true, '-') => format!("{}-\x1b[0m", red),
+        (true, '+') => format!("{}+\x1b[0m", green),
+        (true, _) => format!("\x1b[33m{}\x1b[0m", mark),
+        (false, _) => mark.to_string(),
+    };
+    return (0..a.len().max(b.len()))
+        .map(|k|{
+            let label = if k == 0 {label} else {""};
+            let side = |s: &Vec<String>|{s.get(k).cloned().unwrap_or(" ".repeat(style.width))};
+            format!("{} {:<w$} {} | {}", mark, label, side(&a), side(&b), w = LABEL_WIDTH).trim_end().to_string()
+        })
+        .collect()
+}
+
+/// one row per string that changed, was removed or was added between `old`
+/// and `new`, with `~`, `-` and `+` in the gutter. Empty if they're the same
+pub fn diff(old: &[TextLine], new: &[TextLine], style: &Style) -> Vec<String>{
+    let mut sections : Vec<&str> = Vec::new();
+    for l in old.iter().chain(new.iter()){
+        if !sections.contains(&l.section){
+            sections.push(l.section);
+        }
+    }
+    let mut out : Vec<String> = Vec::new();
+    for section in sections{
+        let a : Vec<&TextLine> = old.iter().filter(|l|{l.section == section}).collect();
+        let b : Vec<&TextLine> = new.iter().filter(|l|{l.section == section}).collect();
+        let script = ops(&a, &b);
+        //a run of removals and additions pairs up into changes, in order
+        for run in script.split(|op|{matches!(op, Op::Same)}){
+            let dels : Vec<usize> = run.iter().filter_map(|op|{match op {Op::Del(i) => Some(*i), _ => None}}).collect();
+            let adds : Vec<usize> = run.iter().filter_map(|op|{match op {Op::Add(j) => Some(*j), _ => None}}).collect();
+            for k in 0..dels.len().max(adds.len()){
+                match (dels.get(k), adds.get(k)){
+                    (Some(i), Some(j)) => {
+                        let label = if i == j {format!("{} {}", section, j)} else {format!("{} {}->{}", section, i, j)};
+                        let (x, y) = changed(a[*i], b[*j]);
+                        out.extend(row('~', &label, Some(&x), Some(&y), style));
+                    },
+                    (Some(i), None) => out.extend(row('-', &format!("{} {}", section, i), Some(&plain(a[*i], false)), None, style)),
+                    (None, Some(j)) => out.extend(row('+', &format!("{} {}", section, j), None, Some(&plain(b[*j], false)), style)),
+                    (None, None) => (),
+                }
+            }
+        }
+    }
+    return out
+}
+
+/// a header and the string diff of every entry with different bytes in `new`
+/// that decodes to text in both
+pub fn table_diff(old: &AssetTable, new: &AssetTable, style: &Style) -> Vec<String>{
+    let mut out : Vec<String> = Vec::new();
+    for uid in 0..old.len().min(new.len()){
+        match (old.bytes(uid), new.bytes(uid)){
+            (Ok(Some(a)), Ok(Some(b))) if a != b => (),
+            _ => continue,
+        }
+        let (a, b) = match (old.try_asset(uid).ok().flatten(), new.try_asset(uid).ok().flatten()){
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+        if let (Some(x), Some(y)) = (a.text_lines(), b.text_lines()){
+            out.push(format!("0x{:04X}: {}", uid, type_name(&b.get_type())));
+            out.extend(diff(&x, &y, style).into_iter().map(|l|{format!("    {}", l)}));
+        }
+    }
+    return out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> TextLine{
+        return TextLine{section: "bottom", cmd: 0x80, text: text.to_string()}
+    }
+
+    #[test]
+    fn edit_script_follows_the_common_subsequence(){
+        let script = ops(&['a', 'b', 'c', 'd'], &['a', 'c', 'd', 'e']);
+        assert_eq!(script, vec![Op::Same, Op::Del(1), Op::Same, Op::Same, Op::Add(3)]);
+        assert_eq!(ops::<u8>(&[], &[1]), vec![Op::Add(0)]);
+    }
+
+    #[test]
+    fn changed_characters_are_bracketed_without_color(){
+        let style = Style{color: false, width: 40};
+        let out = diff(&[line("HELLO BEAR"), line("GONE")], &[line("HELLO BIRD")], &style);
+        assert_eq!(out.len(), 2);
+        assert!(out[0].starts_with("~ bottom 0"));
+        assert!(out[0].contains("HELLO B[EA]R"));
+        assert!(out[0].contains("HELLO B[I]R[D]"));
+        assert!(out[1].starts_with("- bottom 1"));
+        assert!(diff(&[line("SAME")], &[line("SAME")], &style).is_empty());
+    }
+
+    #[test]
+    fn invalid_color_is_an_error(){
+        assert!(Style::new(Some("never")).is_ok());
+        assert!(Style::new(Some("sometimes")).is_err());
+    }
+}
@@ -0,0 +1,727 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use png;
+
+use super::deps;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ImgFmt{
+    CI4,
+    CI8,
+    I4,
+    I8,
+    RGBA16,
+    RGBA32,
+    IA4,
+    IA8,
+    Unknown(u16),
+}
+
+impl ImgFmt{
+    pub fn from_sprite_code(code: u16) -> ImgFmt{
+        match code{
+            0x0001 => ImgFmt::CI4,
+            0x0004 => ImgFmt::CI8,
+            0x0020 => ImgFmt::I4,
+            0x0040 => ImgFmt::I8,
+            0x0080 => ImgFmt::IA4,
+            0x0100 => ImgFmt::IA8,
+            0x0400 => ImgFmt::RGBA16,
+            0x0800 => ImgFmt::RGBA32,
+            _ => ImgFmt::Unknown(code),
+        }
+    }
+
+    pub fn sprite_code(&self) -> u16{
+        match self{
+            ImgFmt::CI4 => 0x0001,
+            ImgFmt::CI8 => 0x0004,
+            ImgFmt::I4 => 0x0020,
+            ImgFmt::I8 => 0x0040,
+            ImgFmt::IA4 => 0x0080,
+            ImgFmt::IA8 => 0x0100,
+            ImgFmt::RGBA16 => 0x0400,
+            ImgFmt::RGBA32 => 0x0800,
+            ImgFmt::Unknown(code) => *code,
+        }
+    }
+
+    /// format field of a model texture list entry
+    pub fn from_model_code(code: u16) -> ImgFmt{
+        match code{
+            0x0001 => ImgFmt::CI4,
+            0x0002 => ImgFmt::CI8,
+            0x0004 => ImgFmt::RGBA16,
+            0x0008 => ImgFmt::RGBA32,
+            0x0010 => ImgFmt::IA8,
+            _ => ImgFmt::Unknown(code),
+        }
+    }
+
+    pub fn model_code(&self) -> u16{
+        match self{
+            ImgFmt::CI4 => 0x0001,
+            ImgFmt::CI8 => 0x0002,
+            ImgFmt::RGBA16 => 0x0004,
+            ImgFmt::RGBA32 => 0x0008,
+            ImgFmt::IA8 => 0x0010,
+            ImgFmt::Unknown(code) => *code,
+            _ => panic!("{:?} has no model texture format code", self),
+        }
+    }
+
+    pub fn from_name(name: &str) -> ImgFmt{
+        match name{
+            "CI4" => ImgFmt::CI4,
+            "CI8" => ImgFmt::CI8,
+            "I4" => ImgFmt::I4,
+            "I8" => ImgFmt::I8,
+            "RGBA16" => ImgFmt::RGBA16,
+            "RGBA32" => ImgFmt::RGBA32,
+            "IA4" => ImgFmt::IA4,
+            "IA8" => ImgFmt::IA8,
+            _ => panic!("unknown image format \"{}\"", name),
+        }
+    }
+
+    pub fn bits_per_texel(&self) -> usize{
+        match self{
+            ImgFmt::I4 | ImgFmt::IA4 | ImgFmt::CI4 => 4,
+            ImgFmt::I8 | ImgFmt::IA8 | ImgFmt::CI8 => 8,
+            ImgFmt::RGBA16 => 16,
+            ImgFmt::RGBA32 => 32,
+            _=> 0,
+        }
+    }
+}
+
+/// how rgba32 colors are reduced to the 5 bit channels of RGBA16
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Dither{
+    None,
+    Ordered,
+    FloydSteinberg,
+}
+
+impl Dither{
+    pub fn from_name(name: &str) -> Dither{
+        match name{
+            "None" => Dither::None,
+            "Ordered" => Dither::Ordered,
+            "FloydSteinberg" => Dither::FloydSteinberg,
+            _ => panic!("unknown dither mode \"{}\"\n try: None, Ordered, or FloydSteinberg", name),
+        }
+    }
+}
+
+pub struct Texture {
+    texture_type: ImgFmt,
+    w : usize,
+    h : usize,
+
+    palette : Option<Vec<u8>>,
+    pixel_data : Vec<u8>,
+}
+
+impl Texture {
+    pub fn new(texture_type: ImgFmt, w : usize, h : usize, bin : &[u8])->Texture{
+        let palette : Option<Vec<u8>> = match texture_type{
+            ImgFmt::CI4 => Some(bin[0.. 0x20].to_vec()),
+            ImgFmt::CI8 => Some(bin[0.. 0x200].to_vec()),
+            _=> None,
+        };
+        
+        let pixel_data = match texture_type {
+            ImgFmt::CI4 => &bin[0x20..],
+            ImgFmt::CI8 => &bin[0x200..],
+            _ => bin,
+        };
+
+        return Texture{
+            texture_type : texture_type, 
+            w : w,
+            h : h,
+            palette : palette,
+            pixel_data : pixel_data.to_vec(),
+        }
+    }
+
+    /// encode rgba32 pixels, CI formats get a palette of the image's colors
+    pub fn from_rgba32(texture_type: ImgFmt, w : usize, h : usize, rgba32 : &[u8])->Texture{
+        let (palette, pixel_data) = match texture_type{
+            ImgFmt::CI4 => {
+                let pal = Texture::rgba32_to_palette(rgba32, 0x10);
+                (Some(Texture::palette_to_bytes(&pal, 0x10)), Texture::rgba32_to_ci4(rgba32, &pal))
+            },
+            ImgFmt::CI8 => {
+                let pal = Texture::rgba32_to_palette(rgba32, 0x100);
+                (Some(Texture::palette_to_bytes(&pal, 0x100)), Texture::rgba32_to_ci8(rgba32, &pal))
+            },
+            ImgFmt::RGBA16 => (None, Texture::rgba32_to_rgba16(rgba32)),
+            ImgFmt::RGBA32 => (None, rgba32.to_vec()),
+            ImgFmt::I4 => (None, Texture::rgba32_to_i4(rgba32)),
+            ImgFmt::I8 => (None, Texture::rgba32_to_i8(rgba32)),
+            ImgFmt::IA4 => (None, Texture::rgba32_to_ia4(rgba32)),
+            ImgFmt::IA8 => (None, Texture::rgba32_to_ia8(rgba32)),
+            ImgFmt::Unknown(_) => panic!("Image type not implemented yet"),
+        };
+        return Texture{texture_type: texture_type, w: w, h: h, palette: palette, pixel_data: pixel_data}
+    }
+
+    /// palette followed by texels, the layout `Texture::new` reads
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = self.palette.clone().unwrap_or_default();
+        out.extend_from_slice(&self.pixel_data);
+        return out
+    }
+
+    pub fn format(&self) -> ImgFmt{ self.texture_type }
+    pub fn width(&self) -> usize{ self.w }
+    pub fn height(&self) -> usize{ self.h }
+
+    pub fn to_rgba32(&self) -> Vec<u8>{
+        match self.texture_type{
+            ImgFmt::CI4 => 
+            {   
+                match &self.palette{
+                    None => panic!("Expected CI4 palette, but none found"),
+                    Some(pal) => Texture::ci4_to_rgba32(&self.pixel_data, &pal)
+                }
+            },
+            ImgFmt::CI8 => 
+            {   
+                match &self.palette{
+                    None => panic!("Expected CI8 palette, but none found"),
+                    Some(pal) => Texture::ci8_to_rgba32(&self.pixel_data, &pal)
+                }
+            }
+            ImgFmt::RGBA16 => Texture::rgba16_to_rgba32(&self.pixel_data),
+            ImgFmt::RGBA32 => self.pixel_data.clone(),
+            ImgFmt::I4 => Texture::i4_to_rgba32(&self.pixel_data),
+            ImgFmt::I8 => Texture::i8_to_rgba32(&self.pixel_data),
+            ImgFmt::IA4 => Texture::ia4_to_rgba32(&self.pixel_data),
+            ImgFmt::IA8 => Texture::ia8_to_rgba32(&self.pixel_data),
+            _ => {panic!("Image type not implemented yet");},
+
+        }
+    }
+
+    pub fn rgba16_to_rgba32(rgba16 : &[u8])->Vec<u8>{
+        return rgba16.chunks_exact(2)
+            .map(|a|{
+                let val = u16::from_be_bytes([a[0], a[1]]);
+                let r16 = ((val >> 11) & 0x1f) as u8;
+                let g16 = ((val >> 6) & 0x1f) as u8;
+                let b16 = ((val >> 1) & 0x1f) as u8;
+                let a16 = (val & 0x1) as u8;
+
+                let r32 = (r16 << 3) | (r16 >> 3);
+                let g32 = (g16 << 3) | (g16 >> 3);
+                let b32 = (b16 << 3) | (b16 >> 3);
+                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+
+                return [r32, g32, b32, a32]
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn ci4_to_rgba32(ci4 : &[u8], palatte: &[u8])->Vec<u8>{
+        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
+            .map(|a|{
+                let val = u16::from_be_bytes([a[0], a[1]]);
+                let r16 = ((val >> 11) & 0x1f) as u8;
+                let g16 = ((val >> 6) & 0x1f) as u8;
+                let b16 = ((val >> 1) & 0x1f) as u8;
+                let a16 = (val & 0x1) as u8;
+
+                let r32 = (r16 << 3) | (r16 >> 3);
+                let g32 = (g16 << 3) | (g16 >> 3);
+                let b32 = (b16 << 3) | (b16 >> 3);
+                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+
+                [r32, g32, b32, a32]
+            })
+            .collect();
+
+        return ci4
+            .into_iter()
+            .map(|a|{[a >> 4, a & 0xF]}) //cvt to ci8
+            .flatten()
+            .map(|indx|{pal[indx as usize]})
+            .flatten()
+            .collect()
+    }
+    pub fn ci8_to_rgba32(ci8 : &[u8], palatte: &[u8])->Vec<u8>{
+        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
+            .map(|a|{
+                let val = u16::from_be_bytes([a[0], a[1]]);
+                let r16 = ((val >> 11) & 0x1f) as u8;
+                let g16 = ((val >> 6) & 0x1f) as u8;
+                let b16 = ((val >> 1) & 0x1f) as u8;
+                let a16 = (val & 0x1) as u8;
+
+                let r32 = (r16 << 3) | (r16 >> 3);
+                let g32 = (g16 << 3) | (g16 >> 3);
+                let b32 = (b16 << 3) | (b16 >> 3);
+                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+
+                [r32, g32, b32, a32]
+            })
+            .collect();
+
+        return ci8
+            .iter()
+            .map(|indx|{pal[*indx as usize]})
+            .flatten()
+            .collect()
+    }
+
+    pub fn i4_to_rgba32(i_4 : &[u8])->Vec<u8>{
+        return i_4.into_iter()
+            .map(|a|{
+                let val1 = (a & 0xF0) | (a >> 4);
+                let val2 = (a << 4) | (a & 0xF);
+                [val1, val1, val1, 0xFF, val2, val2, val2, 0xFF]
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn i8_to_rgba32(i_8 : &[u8])->Vec<u8>{
+        return i_8.iter()
+            .map(|a|{
+                let val = *a;
+                [val, val, val, 0xFF]
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn ia4_to_rgba32(ia4 : &[u8])->Vec<u8>{
+        return ia4
+            .into_iter()
+            .map(|a|{
+                let i1 = (a & 0xE0) | (a >> 3) | (a >> 6);
+                let a1 = (((a << 3) as i8) >> 7) as u8;
+                let i2 = (a >> 1) & 0x7;
+                let i2 = (i2 << 5) | (i2 << 2) | (i2 >> 1);
+                let a2 = (((a << 7) as i8) >> 7) as u8;
+                [i1, i1, i1, a1, i2, i2, i2, a2]
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn ia8_to_rgba32(ia8 : &[u8])->Vec<u8>{
+        return ia8
+            .iter()
+            .map(|a|{
+                let val = (*a & 0xF0) | (*a >> 4);
+                let alpha = (*a << 4) | (*a & 0xF);
+                [val, val, val, alpha]
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn rgba32_pixel_to_rgba16(px : &[u8])->u16{
+        let r = (px[0] >> 3) as u16;
+        let g = (px[1] >> 3) as u16;
+        let b = (px[2] >> 3) as u16;
+        let a = (px[3] >= 0x80) as u16;
+        return (r << 11) | (g << 6) | (b << 1) | a
+    }
+
+    fn rgba32_pixel_to_intensity(px : &[u8])->u8{
+        return ((px[0] as u16 + px[1] as u16 + px[2] as u16)/3) as u8
+    }
+
+    pub fn rgba32_to_rgba16(rgba32 : &[u8])->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .map(|px|{Texture::rgba32_pixel_to_rgba16(px).to_be_bytes()})
+            .flatten()
+            .collect()
+    }
+
+    /// force alpha to fully opaque or transparent, returns how many pixels were in between
+    pub fn threshold_alpha(rgba32 : &[u8], threshold : u8)->(Vec<u8>, usize){
+        let mut clamped = 0;
+        let out = rgba32.chunks_exact(4)
+            .map(|px|{
+                if px[3] != 0 && px[3] != 0xFF{
+                    clamped += 1;
+                }
+                [px[0], px[1], px[2], if px[3] >= threshold {0xFF} else {0x00}]
+            })
+            .flatten()
+            .collect();
+        return (out, clamped)
+    }
+
+    /// snap the color channels of an image to RGBA16 precision, spreading the rounding error
+    pub fn dither_rgba16(rgba32 : &[u8], w : usize, h : usize, mode : Dither)->Vec<u8>{
+        const BAYER : [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+        let expand = |v : u8|->u8{let q = v >> 3; (q << 3) | (q >> 3)};
+        let mut out = rgba32.to_vec();
+        match mode{
+            Dither::None => {},
+            Dither::Ordered => {
+                for (i, px) in out.chunks_exact_mut(4).enumerate(){
+                    let (x, y) = (i % w, i / w);
+                    for ch in 0..3{
+                        px[ch] = expand(px[ch].saturating_add(BAYER[y % 4][x % 4] / 2));
+                    }
+                }
+            },
+            Dither::FloydSteinberg => {
+                let mut err : Vec<f32> = rgba32.iter().map(|v|{*v as f32}).collect();
+                for y in 0..h{
+                    for x in 0..w{
+                        for ch in 0..3{
+                            let i = 4*(y*w + x) + ch;
+                            let old = err[i].round().max(0.0).min(255.0) as u8;
+                            let new = expand(old);
+                            out[i] = new;
+                            let e = err[i] - new as f32;
+                            let mut spread = |dx : isize, dy : usize, weight : f32|{
+                                let nx = x as isize + dx;
+                                if 0 <= nx && (nx as usize) < w && y + dy < h{
+                                    err[4*((y + dy)*w + nx as usize) + ch] += e*weight;
+                                }
+                            };
+                            spread(1, 0, 7.0/16.0);
+                            spread(-1, 1, 3.0/16.0);
+                            spread(0, 1, 5.0/16.0);
+                            spread(1, 1, 1.0/16.0);
+                        }
+                    }
+                }
+            },
+        }
+        return out
+    }
+
+    pub fn rgba32_to_i4(rgba32 : &[u8])->Vec<u8>{
+        return rgba32.chunks_exact(8)
+            .map(|px|{
+                let i1 = Texture::rgba32_pixel_to_intensity(&px[..4]) >> 4;
+                let i2 = Texture::rgba32_pixel_to_intensity(&px[4..]) >> 4;
+                (i1 << 4) | i2
+            })
+            .collect()
+    }
+
+    pub fn rgba32_to_i8(rgba32 : &[u8])->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .map(|px|{Texture::rgba32_pixel_to_intensity(px)})
+            .collect()
+    }
+
+    pub fn rgba32_to_ia4(rgba32 : &[u8])->Vec<u8>{
+        return rgba32.chunks_exact(8)
+            .map(|px|{
+                let ia1 = ((Texture::rgba32_pixel_to_intensity(&px[..4]) >> 5) << 1) | (px[3] >= 0x80) as u8;
+                let ia2 = ((Texture::rgba32_pixel_to_intensity(&px[4..]) >> 5) << 1) | (px[7] >= 0x80) as u8;
+                (ia1 << 4) | ia2
+            })
+            .collect()
+    }
+
+    pub fn rgba32_to_ia8(rgba32 : &[u8])->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .map(|px|{(Texture::rgba32_pixel_to_intensity(px) & 0xF0) | (px[3] >> 4)})
+            .collect()
+    }
+
+    /// unique RGBA16 colors of an image in order of first appearance
+    pub fn rgba32_to_palette(rgba32 : &[u8], max_colors : usize)->Vec<u16>{
+        let mut palette : Vec<u16> = Vec::new();
+        for px in rgba32.chunks_exact(4){
+            let color = Texture::rgba32_pixel_to_rgba16(px);
+            if !palette.contains(&color){
+                palette.push(color);
+            }
+        }
+        assert!(palette.len() <= max_colors, "image uses {} colors, but the palette only holds {}", palette.len(), max_colors);
+        return palette
+    }
+
+    /// bytes of TMEM a texture can use, CI palettes take the upper half
+    pub fn tmem_available(format : &ImgFmt)->usize{
+        return match format{
+            ImgFmt::CI4 | ImgFmt::CI8 => 0x800,
+            _ => 0x1000,
+        }
+    }
+
+    /// bytes a w x h texture occupies in TMEM, rows are padded to 64 bits
+    pub fn tmem_footprint(w : usize, h : usize, format : &ImgFmt)->usize{
+        let line = (w*format.bits_per_texel() + 63)/64;
+        return 8*line*h
+    }
+
+    /// checks that a single texture load fits the RDP's limits
+    pub fn validate_tmem(w : usize, h : usize, format : &ImgFmt)->Result<(), String>{
+        const MAX_DIM : usize = 1024;
+        if w == 0 || h == 0 || w > MAX_DIM || h > MAX_DIM{
+            return Err(format!("{}x{} {:?} is outside of the 1x1 to {}x{} texture limits", w, h, format, MAX_DIM, MAX_DIM));
+        }
+        if (w*format.bits_per_texel()) % 8 != 0{
+            return Err(format!("{}x{} {:?} rows don't end on a byte boundary", w, h, format));
+        }
+        let footprint = Texture::tmem_footprint(w, h, format);
+        let available = Texture::tmem_available(format);
+        if footprint > available{
+            return Err(format!("{}x{} {:?} = {} bytes > {} available", w, h, format, footprint, available));
+        }
+        return Ok(())
+    }
+
+    /// number of distinct RGBA16 colors in an image
+    pub fn color_count(rgba32 : &[u8])->usize{
+        let mut colors : Vec<u16> = rgba32.chunks_exact(4).map(|px|{Texture::rgba32_pixel_to_rgba16(px)}).collect();
+        colors.sort();
+        colors.dedup();
+        return colors.len()
+    }
+
+    /// reduce an image to at most `max_colors` RGBA16 colors using median cut
+    pub fn quantize(rgba32 : &[u8], max_colors : usize)->Vec<u8>{
+        let mut histogram : HashMap<u16, usize> = HashMap::new();
+        for px in rgba32.chunks_exact(4){
+            *histogram.entry(Texture::rgba32_pixel_to_rgba16(px)).or_insert(0) += 1;
+        }
+        let mut colors : Vec<([u8; 4], usize)> = histogram.into_iter()
+            .map(|(c, n)|{(Texture::rgba16_to_rgba32(&c.to_be_bytes()).try_into().unwrap(), n)})
+            .collect();
+        colors.sort();
+
+        //split the box with the widest channel at its weighted median until we run out of colors
+        let mut boxes : Vec<Vec<([u8; 4], usize)>> = vec![colors];
+        while boxes.len() < max_colors{
+            let widest = boxes.iter()
+                .enumerate()
+                .filter(|(_, b)|{b.len() > 1})
+                .map(|(i, b)|{
+                    let (ch, range) = (0..4).map(|ch|{
+                        let lo = b.iter().map(|(c, _)|{c[ch]}).min().unwrap();
+                        let hi = b.iter().map(|(c, _)|{c[ch]}).max().unwrap();
+                        (ch, hi - lo)
+                    })
+                    .max_by_key(|(_, range)|{*range})
+                    .unwrap();
+                    (i, ch, range)
+                })
+                .max_by_key(|(_, _, range)|{*range});
+            let (i, ch, _) = match widest{
+                Some(w) => w,
+                None => break,
+            };
+            let mut b = boxes.swap_remove(i);
+            b.sort_by_key(|(c, _)|{c[ch]});
+            let total : usize = b.iter().map(|(_, n)|{n}).sum();
+            let mut acc = 0;
+            let mut split = 1;
+            for (j, (_, n)) in b.iter().enumerate(){
+                acc += n;
+                if acc*2 >= total{
+                    split = std::cmp::min(j + 1, b.len() - 1);
+                    break;
+                }
+            }
+            let upper = b.split_off(split);
+            boxes.push(b);
+            boxes.push(upper);
+        }
+
+        let palette : Vec<[u8; 4]> = boxes.iter()
+            .map(|b|{
+                let total : usize = b.iter().map(|(_, n)|{n}).sum();
+                let mut avg = [0u8; 4];
+                for ch in 0..4{
+                    avg[ch] = (b.iter().map(|(c, n)|{c[ch] as usize * n}).sum::<usize>() / total) as u8;
+                }
+                avg
+            })
+            .collect();
+
+        let mut nearest : HashMap<u16, [u8; 4]> = HashMap::new();
+        return rgba32.chunks_exact(4)
+            .map(|px|{
+                *nearest.entry(Texture::rgba32_pixel_to_rgba16(px)).or_insert_with(||{
+                    palette[Texture::nearest_color(&palette, px)]
+                })
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn nearest_color(palette : &[[u8; 4]], px : &[u8])->usize{
+        return (0..palette.len())
+            .min_by_key(|i|{
+                (0..4).map(|ch|{(palette[*i][ch] as i32 - px[ch] as i32).pow(2)}).sum::<i32>()
+            })
+            .unwrap()
+    }
+
+    /// palette indices of the closest colors of an RGBA16 palette
+    pub fn rgba32_to_ci8_nearest(rgba32 : &[u8], palette : &[u8])->Vec<u8>{
+        let colors : Vec<[u8; 4]> = Texture::rgba16_to_rgba32(palette)
+            .chunks_exact(4)
+            .map(|c|{c.try_into().unwrap()})
+            .collect();
+        return rgba32.chunks_exact(4)
+            .map(|px|{Texture::nearest_color(&colors, px) as u8})
+            .collect()
+    }
+
+    /// palette as stored in the binary, zero padded to `entries` colors
+    pub fn palette_to_bytes(palette : &[u16], entries : usize)->Vec<u8>{
+        let mut out : Vec<u8> = palette.iter().map(|c|{c.to_be_bytes()}).flatten().collect();
+        out.resize(2*entries, 0);
+        return out
+    }
+
+    pub fn rgba32_to_ci8(rgba32 : &[u8], palette : &[u16])->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .map(|px|{
+                let color = Texture::rgba32_pixel_to_rgba16(px);
+                palette.iter().position(|c|{*c == color}).expect("color missing from palette") as u8
+            })
+            .collect()
+    }
+
+    pub fn rgba32_to_ci4(rgba32 : &[u8], palette : &[u16])->Vec<u8>{
+        return Texture::rgba32_to_ci8(rgba32, palette)
+            .chunks_exact(2)
+            .map(|a|{(a[0] << 4) | (a[1] & 0xF)})
+            .collect()
+    }
+}
+
+pub fn write_png_rgba32(path: &Path, w: usize, h: usize, rgba32: &[u8]){
+    let texture_f = deps::create(path).unwrap();
+    let ref mut bw = BufWriter::new(texture_f);
+    encode_png_rgba32(bw, w, h, rgba32);
+}
+
+/// png file bytes, for when there's no file system to write to
+pub fn png_rgba32(w: usize, h: usize, rgba32: &[u8]) -> Vec<u8>{
+    let mut out : Vec<u8> = Vec::new();
+    encode_png_rgba32(&mut out, w, h, rgba32);
+    return out
+}
+
+fn encode_png_rgba32<W: Write>(out: W, w: usize, h: usize, rgba32: &[u8]){
+    let mut encoder = png::Encoder::new(out, w as u32, h as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(rgba32).unwrap();
+}
+
+/// nearest neighbour resample
+pub fn resize_rgba32(rgba32: &[u8], w: usize, h: usize, new_w: usize, new_h: usize) -> Vec<u8>{
+    return (0..new_h)
+        .map(|y|{(0..new_w).map(move |x|{(x*w/new_w, y*h/new_h)})})
+        .flatten()
+        .map(|(x, y)|{rgba32[4*(y*w + x)..4*(y*w + x) + 4].to_vec()})
+        .flatten()
+        .collect()
+}
+
+/// C identifier from an asset name, non alphanumerics become underscores
+pub fn c_identifier(name: &str) -> String{
+    return name.chars().map(|c|{if c.is_ascii_alphanumeric() {c} else {'_'}}).collect()
+}
+
+/// bytes as a big endian u64 array, zero padded to 8 bytes
+pub fn write_c_array(out: &mut dyn Write, name: &str, comment: &str, bytes: &[u8]){
+    writeln!(out, "// {}", comment).unwrap();
+    writeln!(out, "u64 {}[] = {{", name).unwrap();
+    for line in bytes.chunks(8*4){
+        let words : Vec<String> = line.chunks(8)
+            .map(|w|{
+                let mut word = [0u8; 8];
+                word[..w.len()].copy_from_slice(w);
+                format!("0x{:016X}", u64::from_be_bytes(word))
+            })
+            .collect();
+        writeln!(out, "    {},", words.join(", ")).unwrap();
+    }
+    writeln!(out, "}};\n").unwrap();
+}
+
+/// rgba32 colors of a raw RGBA16 `.pal.bin` or the pixels of a `.pal.png`, in row order
+pub fn read_palette(path: &Path) -> Vec<u8>{
+    return match path.extension().and_then(|e|{e.to_str()}){
+        Some("bin") => Texture::rgba16_to_rgba32(&deps::read(path).expect("could not read palette")),
+        _ => read_png_rgba32(path).2,
+    }
+}
+
+/// width and height of a png, from its header without decoding the pixels
+pub fn png_size(path: &Path) -> (usize, usize){
+    let decoder = png::Decoder::new(deps::open(path).unwrap_or_else(|_|{panic!("could not open png {:?}", path)}));
+    let reader = decoder.read_info().unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+    return (reader.info().width as usize, reader.info().height as usize)
+}
+
+/// palette indices and rgba32 palette colors of an indexed png, None for other color types
+pub fn read_png_indexed(path: &Path) -> Option<(usize, usize, Vec<u8>, Vec<u8>)>{
+    let mut decoder = png::Decoder::new(deps::open(path).expect("could not open png"));
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info().unwrap();
+    if reader.info().color_type != png::ColorType::Indexed{
+        return None;
+    }
+    let plte = reader.info().palette.as_ref().expect("indexed png without palette").to_vec();
+    let trns = reader.info().trns.as_ref().map(|t|{t.to_vec()}).unwrap_or_default();
+    let colors : Vec<u8> = plte.chunks_exact(3)
+        .enumerate()
+        .map(|(i, c)|{[c[0], c[1], c[2], *trns.get(i).unwrap_or(&0xFF)]})
+        .flatten()
+        .collect();
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    let depth = info.bit_depth as usize;
+    let (w, h) = (info.width as usize, info.height as usize);
+    let indices : Vec<u8> = buf.chunks_exact(info.line_size)
+        .take(h)
+        .map(|line|{
+            (0..w).map(move |x|{
+                let bit = x*depth;
+                (line[bit/8] >> (8 - depth - bit%8)) & (((1u16 << depth) - 1) as u8)
+            })
+        })
+        .flatten()
+        .collect();
+    return Some((w, h, indices, colors))
+}
+
+/// decode any png into 8 bit rgba
+pub fn read_png_rgba32(path: &Path) -> (usize, usize, Vec<u8>){
+    return decode_png_rgba32(&deps::read(path).unwrap_or_else(|_|{panic!("could not open png {:?}", path)}))
+}
+
+/// decode png bytes into 8 bit rgba
+pub fn decode_png_rgba32(bytes: &[u8]) -> (usize, usize, Vec<u8>){
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    let pixels = &buf[..info.line_size * info.height as usize];
+    let rgba32 : Vec<u8> = match info.color_type{
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels.chunks_exact(3).map(|a|{[a[0], a[1], a[2], 0xFF]}).flatten().collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).map(|a|{[a[0], a[0], a[0], a[1]]}).flatten().collect(),
+        png::ColorType::Grayscale => pixels.iter().map(|a|{[*a, *a, *a, 0xFF]}).flatten().collect(),
+        png::ColorType::Indexed => panic!("unexpanded indexed png"),
+    };
+    return (info.width as usize, info.height as usize, rgba32)
+}
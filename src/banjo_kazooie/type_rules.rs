@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::sync::RwLock;
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::{asset, deps};
+
+// how extract tells what an entry is: the first rule whose segment and leading
+// bytes match names the type, and anything no rule matches is Binary. The
+// built in rules are the vanilla game's; a rules file adds more in front of
+// them, so a new find or a fork's own format doesn't need a rebuild:
+//
+//     rules:
+//       - {segment: 4, bytes: "01 05 ?? 05", type: Dialog}
+//       - {segment: [1, 3], bytes: "00 00 00 0C", min_len: 0x38, type: Model}
+//
+// `??` matches any byte. `min_len` defaults to the length of `bytes`.
+
+#[derive(Clone, Debug)]
+pub struct Rule{
+    pub segments : Vec<usize>,
+    pub bytes : Vec<Option<u8>>,
+    pub min_len : usize,
+    pub type_name : String, //as in `asset::TYPE_NAMES`
+}
+
+impl Rule{
+    fn new(segments: &[usize], bytes: &str, type_name: &str) -> Rule{
+        let bytes = parse_bytes(bytes).unwrap();
+        return Rule{segments: segments.to_vec(), min_len: bytes.len(), bytes: bytes, type_name: type_name.to_string()}
+    }
+
+    pub fn matches(&self, segment: usize, in_bytes: &[u8]) -> bool{
+        return self.segments.contains(&segment)
+            && in_bytes.len() >= self.min_len.max(self.bytes.len())
+            && self.bytes.iter().zip(in_bytes.iter()).all(|(p, b)|{p.map_or(true, |p|{p == *b})})
+    }
+
+    fn from_yaml(yaml: &Yaml) -> Result<Rule, String>{
+        let segments = match &yaml["segment"]{
+            Yaml::Integer(s) => vec![*s as usize],
+            Yaml::Array(s) => s.iter().map(|s|{s.as_i64().map(|s|{s as usize}).ok_or("segments are integers".to_string())}).collect::<Result<Vec<usize>, String>>()?,
+            _ => return Err("no segment".to_string()),
+        };
+        let bytes = parse_bytes(yaml["bytes"].as_str().unwrap_or(""))?;
+        let type_name = yaml["type"].as_str().ok_or("no type".to_string())?;
+        if !asset::TYPE_NAMES.contains(&type_name){
+            return Err(format!("unknown type \"{}\"\n try: {}", type_name, asset::TYPE_NAMES.join(", ")));
+        }
+        let min_len = match &yaml["min_len"]{
+            Yaml::Integer(n) => *n as usize,
+            Yaml::BadValue => bytes.len(),
+            n => return Err(format!("min_len should be an integer, not {:?}", n)),
+        };
+        return Ok(Rule{segments: segments, bytes: bytes, min_len: min_len, type_name: type_name.to_string()})
+    }
+}
+
+/// hex bytes split by spaces, `??` for any byte
+pub fn parse_bytes(text: &str) -> Result<Vec<Option<u8>>, String>{
+    return text.split_whitespace()
+        .map(|b|{match b{
+            "??" => Ok(None),
+            b => u8::from_str_radix(b, 16).map(Some).map_err(|_|{format!("\"{}\" is not a hex byte or ??", b)}),
+        }})
+        .collect()
+}
+
+/// the rules the vanilla game's entries are told apart by, in order
+pub fn builtin() -> Vec<Rule>{
+    return vec![
+        Rule::new(&[0], "", "Animation"),
+        Rule::new(&[1, 3], "00 00 00 0B", "Model"),
+        Rule::new(&[1, 3], "", "Sprite"),
+        Rule::new(&[2], "", "LevelSetup"),
+        Rule::new(&[4], "01 01 ?? ?? ??", "QuizQuestion"),
+        Rule::new(&[4], "01 03 00 05 00", "GruntyQuestion"),
+        Rule::new(&[4], "01 03 00", "Dialog"),
+        Rule::new(&[4], "", "DemoInput"),
+        Rule::new(&[5], "", "Model"),
+        Rule::new(&[6], "", "Midi"),
+    ]
+}
+
+static EXTRA : RwLock<Vec<Rule>> = RwLock::new(Vec::new());
+
+/// the rules of a rules file
+pub fn read(path: &Path) -> Vec<Rule>{
+    let text = deps::read_to_string(path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+    let doc = &YamlLoader::load_from_str(&text).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)})[0];
+    let rules = doc["rules"].as_vec().unwrap_or_else(||{panic!("{:?}: no rules list", path)});
+    return rules.iter().enumerate()
+        .map(|(i, r)|{Rule::from_yaml(r).unwrap_or_else(|e|{panic!("{:?}: rule {}: {}", path, i, e)})})
+        .collect()
+}
+
+/// checks `rules` before the built in ones, and any added earlier, from now on
+pub fn add(rules: Vec<Rule>){
+    let mut extra = EXTRA.write().unwrap();
+    let earlier = std::mem::take(&mut *extra);
+    *extra = rules.into_iter().chain(earlier).collect();
+}
+
+/// every rule in the order they're checked
+pub fn rules() -> Vec<Rule>{
+    return EXTRA.read().unwrap().iter().cloned().chain(builtin()).collect()
+}
+
+/// the type the first matching rule names, Binary if none does
+pub fn detect(segment: usize, in_bytes: &[u8]) -> String{
+    return rules().into_iter().find(|r|{r.matches(segment, in_bytes)}).map_or("Binary".to_string(), |r|{r.type_name})
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn builtin_detect(segment: usize, in_bytes: &[u8]) -> String{
+        return builtin().into_iter().find(|r|{r.matches(segment, in_bytes)}).map_or("Binary".to_string(), |r|{r.type_name})
+    }
+
+    //the quiz rule only fixes `01 01`, so an edited answer or category is still
+    //a quiz question, and nothing else the vanilla game has starts that way
+    #[test]
+    fn quiz_rule_only_takes_quiz_questions(){
+        for b in 0..=0xFFu8{
+            assert_eq!(builtin_detect(4, &[0x01, 0x01, b, 0x05, 0x00, 0x03]), "QuizQuestion");
+            assert_eq!(builtin_detect(4, &[0x01, 0x01, 0x02, b, 0x00, 0x03]), "QuizQuestion");
+        }
+        let others : [(usize, &[u8], &str); 6] = [
+            (4, &[0x01, 0x03, 0x00, 0x05, 0x00, 0x03], "GruntyQuestion"),
+            (4, &[0x01, 0x03, 0x00, 0x01, 0x02, 0x00], "Dialog"),
+            (4, &[0x00, 0x00, 0x00, 0x06, 0x10, 0xF0, 0x80, 0x00, 0x05, 0x01], "DemoInput"),
+            (4, &[0x01, 0x02, 0x00, 0x05, 0x00, 0x03], "DemoInput"),
+            (4, &[0x01, 0x01, 0x02, 0x05], "DemoInput"),
+            (1, &[0x01, 0x01, 0x02, 0x05, 0x00, 0x03], "Sprite"),
+        ];
+        for (segment, bytes, expect) in others.iter(){
+            assert_eq!(builtin_detect(*segment, bytes), *expect, "{:02X?}", bytes);
+        }
+    }
+}
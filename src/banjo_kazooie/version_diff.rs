@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+
+use super::asset::TextLine;
+use super::text_diff::{self, Style};
+use super::{type_name, AssetTable};
+
+// diff of two bins from different versions of the game. Entries are paired by
+// content rather than uid: identical bytes first, then the same uid and type if
+// they're still similar, then the most similar entry of the same type. Text
+// assets also get a side by side diff of their strings.
+
+struct Entry{
+    uid : usize,
+    type_name : String,
+    bytes : Vec<u8>,
+    text : Option<Vec<TextLine>>,
+}
+
+fn entries(in_bytes: &[u8]) -> Vec<Entry>{
+    let table = AssetTable::new(in_bytes);
+    return (0..table.len())
+        .filter_map(|uid|{
+            //entries that don't decompress are compared as stored
+            let bytes = table.bytes(uid).or_else(|_|{table.stored_bytes(uid).map(|b|{Some(b.to_vec())})}).ok().flatten()?;
+            //entries that don't decode are compared as bytes
+            let asset = table.try_asset(uid).ok().flatten();
+            Some(Entry{
+                uid: uid,
+                type_name: asset.as_ref().map_or("Binary".to_string(), |a|{type_name(&a.get_type())}),
+                text: asset.as_ref().and_then(|a|{a.text_lines()}),
+                bytes: bytes,
+            })
+        })
+        .collect()
+}
+
+/// every 4 byte window, hashed
+fn grams(bytes: &[u8]) -> HashSet<u32>{
+    return bytes.windows(4).map(|w|{u32::from_be_bytes([w[0], w[1], w[2], w[3]])}).collect()
+}
+
+/// jaccard similarity of the 4 byte windows, 0 to 1
+fn similarity(a: &HashSet<u32>, b: &HashSet<u32>) -> f32{
+    if a.is_empty() && b.is_empty(){
+        return 1.0
+    }
+    return a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
+
+/// one line per difference, with the string diff of text assets indented under
+/// theirs. Pairs below `threshold` similarity count as removed and added
+pub fn diff(old_bytes: &[u8], new_bytes: &[u8], threshold: f32, style: &Style) -> Vec<String>{
+    let (old, new) = (entries(old_bytes), entries(new_bytes));
+    let mut pairs : Vec<(usize, usize, f32)> = Vec::new(); //old index, new index, similarity
+    let mut old_done = vec![false; old.len()];
+    let mut new_done = vec![false; new.len()];
+
+    //identical bytes, the same uid first
+    let mut by_bytes : HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (j, e) in new.iter().enumerate(){
+        by_bytes.entry(&e.bytes).or_insert(Vec::new()).push(j);
+    }
+    for same_uid in [true, false]{
+        for (i, e) in old.iter().enumerate(){
+            if old_done[i]{
+                continue;
+            }
+            let found = by_bytes.get(e.bytes.as_slice()).and_then(|js|{
+                js.iter().copied().find(|j|{!new_done[*j] && (!same_uid || new[*j].uid == e.uid)})
+            });
+            if let Some(j) = found{
+                pairs.push((i, j, 1.0));
+                old_done[i] = true;
+                new_done[j] = true;
+            }
+        }
+    }
+
+    //the rest by similarity, the same uid wins if it's over the threshold.
+    //Jaccard can't be over min/max of the two set sizes, so only new entries of
+    //the same type with a size in range of the old one's are compared
+    let old_grams : Vec<Option<HashSet<u32>>> = old.iter().enumerate().map(|(i, e)|{if old_done[i] {None} else {Some(grams(&e.bytes))}}).collect();
+    let new_grams : Vec<Option<HashSet<u32>>> = new.iter().enumerate().map(|(j, e)|{if new_done[j] {None} else {Some(grams(&e.bytes))}}).collect();
+    let mut by_type : HashMap<&str, Vec<(usize, usize)>> = HashMap::new(); //gram count, new index
+    for (j, g) in new_grams.iter().enumerate().filter_map(|(j, g)|{g.as_ref().map(|g|{(j, g)})}){
+        by_type.entry(new[j].type_name.as_str()).or_insert(Vec::new()).push((g.len(), j));
+    }
+    for v in by_type.values_mut(){
+        v.sort();
+    }
+    let mut candidates : Vec<(usize, usize, f32, bool)> = Vec::new();
+    for (i, a) in old_grams.iter().enumerate().filter_map(|(i, g)|{g.as_ref().map(|g|{(i, g)})}){
+        let same_type = match by_type.get(old[i].type_name.as_str()){
+            Some(v) => v,
+            None => continue,
+        };
+        let (lo, hi) = match threshold > 0.0{
+            //rounded outwards, the similarity check after has the last word
+            true => ((a.len() as f32*threshold).floor() as usize, (a.len() as f32/threshold).ceil() as usize),
+            false => (0, usize::MAX),
+        };
+        let start = same_type.partition_point(|(n, _)|{*n < lo});
+        for (_, j) in same_type[start..].iter().take_while(|(n, _)|{*n <= hi}){
+            let sim = similarity(a, new_grams[*j].as_ref().unwrap());
+            if sim >= threshold{
+                candidates.push((i, *j, sim, old[i].uid == new[*j].uid));
+            }
+        }
+    }
+    candidates.sort_by(|a, b|{b.3.cmp(&a.3).then(b.2.partial_cmp(&a.2).unwrap())});
+    for (i, j, sim, _) in candidates{
+        if !old_done[i] && !new_done[j]{
+            pairs.push((i, j, sim));
+            old_done[i] = true;
+            new_done[j] = true;
+        }
+    }
+
+    //report in uid order
+    let mut out : Vec<(usize, Vec<String>)> = Vec::new();
+    for (i, j, sim) in pairs{
+        let (o, n) = (&old[i], &new[j]);
+        let uids = if o.uid == n.uid {format!("0x{:04X}", o.uid)} else {format!("0x{:04X} -> 0x{:04X}", o.uid, n.uid)};
+        if o.bytes == n.bytes{
+            if o.uid != n.uid{
+                out.push((o.uid, vec![format!("{}: moved", uids)]));
+            }
+            continue;
+        }
+        let mut lines = vec![format!("{}: modified, {}, 0x{:X} -> 0x{:X} bytes, {:.0}% similar", uids, n.type_name, o.bytes.len(), n.bytes.len(), 100.0*sim)];
+        if let (Some(a), Some(b)) = (o.text.as_ref(), n.text.as_ref()){
+            lines.extend(text_diff::diff(a, b, style).into_iter().map(|l|{format!("    {}", l)}));
+        }
+        out.push((o.uid, lines));
+    }
+    for (o, _) in old.iter().zip(old_done.iter()).filter(|(_, done)|{!**done}){
+        out.push((o.uid, vec![format!("0x{:04X}: removed, {}, 0x{:X} bytes", o.uid, o.type_name, o.bytes.len())]));
+    }
+    for (n, _) in new.iter().zip(new_done.iter()).filter(|(_, done)|{!**done}){
+        out.push((n.uid, vec![format!("0x{:04X}: added, {}, 0x{:X} bytes", n.uid, n.type_name, n.bytes.len())]));
+    }
+    out.sort_by_key(|(uid, _)|{*uid});
+    return out.into_iter().flat_map(|(_, lines)|{lines}).collect()
+}
@@ -0,0 +1,95 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use super::asset::TextLine;
+use super::deps;
+
+// the vocabulary of every dialog and question, for proofreading a translation.
+// A word is a run of letters, with apostrophes inside it as in DON'T. Escaped
+// accented letters like \xE9 are letters too, other escapes like \xFD split
+// words. Words are lowercased, so HELLO and
+// Hello are one word.
+
+pub struct Word{
+    pub count : usize,
+    pub uids : Vec<usize>, //in order, each once
+}
+
+/// the byte an escape like \xE9 stands for, read as Latin-1 the way the text
+/// is written, except \xFD: the game draws it as a squiggle, not a ý
+fn escaped_letter(hex: &[char]) -> Option<char>{
+    let b = u8::from_str_radix(&hex.iter().collect::<String>(), 16).ok()?;
+    let c = char::from(b);
+    return if b != 0xFD && c.is_alphabetic() {Some(c)} else {None}
+}
+
+/// the char at `i` with escapes decoded, None for an escape that isn't a
+/// letter, and how many chars it takes
+fn char_at(chars: &[char], i: usize) -> (Option<char>, usize){
+    if chars[i] == '\\' && chars.get(i + 1) == Some(&'x'){
+        if let Some(hex) = chars.get(i + 2..i + 4).filter(|h|{h.iter().all(|h|{h.is_ascii_hexdigit()})}){
+            return (escaped_letter(hex), 4)
+        }
+    }
+    return (Some(chars[i]), 1)
+}
+
+/// the words of one string
+pub fn split(text: &str) -> Vec<String>{
+    let chars : Vec<char> = text.chars().collect();
+    let mut out : Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut i = 0;
+    while i < chars.len(){
+        let (c, len) = char_at(&chars, i);
+        let inner_apostrophe = c == Some('\'') && !word.is_empty() && i + 1 < chars.len() && char_at(&chars, i + 1).0.map_or(false, |n|{n.is_alphabetic()});
+        match c{
+            Some(c) if c.is_alphabetic() || inner_apostrophe => word.extend(c.to_lowercase()),
+            _ if !word.is_empty() => out.push(std::mem::take(&mut word)),
+            _ => (),
+        }
+        i += len;
+    }
+    if !word.is_empty(){
+        out.push(word);
+    }
+    return out
+}
+
+/// every word of the strings of each uid, alphabetically
+pub fn collect(texts: &[(usize, Vec<TextLine>)]) -> BTreeMap<String, Word>{
+    let mut out : BTreeMap<String, Word> = BTreeMap::new();
+    for (uid, lines) in texts.iter(){
+        for w in lines.iter().flat_map(|l|{split(&l.text)}){
+            let word = out.entry(w).or_insert(Word{count: 0, uids: Vec::new()});
+            word.count += 1;
+            if word.uids.last() != Some(uid){
+                word.uids.push(*uid);
+            }
+        }
+    }
+    return out
+}
+
+/// the words of a dictionary file, one per line. Blank lines and lines
+/// starting with # are skipped
+pub fn read_dictionary(path: &Path) -> HashSet<String>{
+    let text = deps::read_to_string(path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+    return text.lines()
+        .map(|l|{l.trim()})
+        .filter(|l|{!l.is_empty() && !l.starts_with('#')})
+        .map(|l|{l.to_lowercase()})
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accented_letters_stay_in_their_word(){
+        assert_eq!(split("CAF\\xC9 cr\\xE8me"), vec!["café", "crème"]);
+        assert_eq!(split("GR\\xDCNTY'S \\xFDJIGGY\\x01ok"), vec!["grünty's", "jiggy", "ok"]);
+        assert_eq!(split("DON'T 'quote'"), vec!["don't", "quote"]);
+    }
+}
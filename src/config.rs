@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use bk_asset_tool::banjo_kazooie::deps;
+
+// project defaults from a bk_asset.toml, so everyday commands don't need every
+// path spelled out. Only top level keys are read. Paths are relative to the
+// file.
+//
+//     rom = "baserom.z64"          # the vanilla rom, bps's default source
+//     bin = "baserom.assets.bin"   # extract's input and construct's output
+//     dir = "assets"               # extract's output and construct's input
+//     threads = 4                  # construct compression and extract png threads
+//     compression = "match"        # fast, match or max
+//     names = "names.yaml"         # name map for commands that take --names
+//     type_rules = "rules.yaml"    # type detection rules, as --type-rules
+//
+// Flags and paths on the command line win over the file.
+
+pub const FILE_NAME : &str = "bk_asset.toml";
+
+#[derive(Default)]
+pub struct Config{
+    pub rom : Option<PathBuf>,
+    pub bin : Option<PathBuf>,
+    pub dir : Option<PathBuf>,
+    pub threads : Option<usize>,
+    pub compression : Option<String>,
+    pub names : Option<PathBuf>,
+    pub type_rules : Option<PathBuf>,
+}
+
+impl Config{
+    /// the file from `--config`, or bk_asset.toml in the working directory if
+    /// there is one. Takes `--config <path>` out of `args`
+    pub fn load(args: &mut Vec<String>) -> Config{
+        let path = match args.iter().position(|a|{a == "--config"}){
+            Some(i) => {
+                assert!(i + 1 < args.len(), "No value provided for --config");
+                let path = PathBuf::from(args.remove(i + 1));
+                args.remove(i);
+                path
+            },
+            None if Path::new(FILE_NAME).is_file() => PathBuf::from(FILE_NAME),
+            None => return Config::default(),
+        };
+        let text = deps::read_to_string(&path).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)});
+        return Config::parse(&text, path.parent().unwrap_or(Path::new(""))).unwrap_or_else(|e|{panic!("{:?}: {}", path, e)})
+    }
+
+    /// reads the keys above, paths joined onto `base`
+    pub fn parse(text: &str, base: &Path) -> Result<Config, String>{
+        let table : toml::Table = text.parse().map_err(|e: toml::de::Error|{e.to_string()})?;
+        let mut config = Config::default();
+        for (key, value) in table.into_iter(){
+            let wrong_type = |want : &str|{format!("{} should be {}", key, want)};
+            match (key.as_str(), value){
+                ("rom", Value::String(s)) => config.rom = Some(base.join(s)),
+                ("bin", Value::String(s)) => config.bin = Some(base.join(s)),
+                ("dir", Value::String(s)) => config.dir = Some(base.join(s)),
+                ("names", Value::String(s)) => config.names = Some(base.join(s)),
+                ("type_rules", Value::String(s)) => config.type_rules = Some(base.join(s)),
+                ("compression", Value::String(s)) => config.compression = Some(s),
+                ("threads", Value::Integer(t)) if t > 0 => config.threads = Some(t as usize),
+                ("rom" | "bin" | "dir" | "names" | "type_rules" | "compression", _) => return Err(wrong_type("a string")),
+                ("threads", _) => return Err(wrong_type("a positive integer")),
+                (k, _) => return Err(format!("unknown key \"{}\"\n try: rom, bin, dir, threads, compression, names or type_rules", k)),
+            }
+        }
+        return Ok(config)
+    }
+}
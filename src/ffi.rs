@@ -0,0 +1,201 @@
+// C interface for linking the tool into editors and build systems. Declared in
+// include/bk_asset_tool.h, which is generated from this file with cbindgen.
+// Nothing here panics across the boundary, failures come back as NULL or -1.
+// Without the `fs` feature, for wasm32, bins only go in and out as bytes.
+
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+#[cfg(feature = "fs")]
+use std::{ffi::CStr, fs, os::raw::c_char, path::Path};
+
+use crate::banjo_kazooie::asset::{self, AssetType};
+use crate::banjo_kazooie::{texture, AssetFolder};
+
+/// an asset bin opened with `bk_bin_open` or `bk_bin_from_bytes`
+pub struct BkAssetBin{
+    folder: AssetFolder,
+}
+
+#[repr(C)]
+pub struct BkAssetInfo{
+    pub compressed: bool,
+    pub flags: u16,
+    pub segment: usize,
+}
+
+fn guard<T>(fail: T, f: impl FnOnce() -> T) -> T{
+    return panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fail)
+}
+
+fn boxed_bytes(bytes: Vec<u8>, out_len: &mut usize) -> *mut u8{
+    let bytes = bytes.into_boxed_slice();
+    *out_len = bytes.len();
+    return Box::into_raw(bytes) as *mut u8
+}
+
+#[cfg(feature = "fs")]
+unsafe fn path_arg<'a>(path: *const c_char) -> &'a Path{
+    return Path::new(CStr::from_ptr(path).to_str().expect("path is not utf-8"))
+}
+
+/// NULL if the file can't be read or decoded. Free with `bk_bin_free`
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_open(path: *const c_char) -> *mut BkAssetBin{
+    if path.is_null() { return ptr::null_mut(); }
+    return guard(ptr::null_mut(), ||{
+        let bytes = fs::read(path_arg(path)).expect("could not read file");
+        Box::into_raw(Box::new(BkAssetBin{folder: AssetFolder::from_bytes(&bytes)}))
+    })
+}
+
+/// copies what it needs, `bytes` can be freed after
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_from_bytes(bytes: *const u8, len: usize) -> *mut BkAssetBin{
+    if bytes.is_null() { return ptr::null_mut(); }
+    return guard(ptr::null_mut(), ||{
+        let bytes = slice::from_raw_parts(bytes, len);
+        Box::into_raw(Box::new(BkAssetBin{folder: AssetFolder::from_bytes(bytes)}))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_free(bin: *mut BkAssetBin){
+    if !bin.is_null(){
+        drop(Box::from_raw(bin));
+    }
+}
+
+/// number of table entries, uids go from 0 up to it
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_asset_count(bin: *const BkAssetBin) -> usize{
+    return bin.as_ref().map_or(0, |b|{b.folder.len()})
+}
+
+/// 0 on success, -1 if the uid is outside the table
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_asset_info(bin: *const BkAssetBin, uid: usize, out: *mut BkAssetInfo) -> c_int{
+    let (bin, out) = match (bin.as_ref(), out.as_mut()){
+        (Some(b), Some(o)) => (b, o),
+        _ => return -1,
+    };
+    return match bin.folder.table_info(uid){
+        Some((compressed, flags, segment)) => {
+            *out = BkAssetInfo{compressed: compressed, flags: flags, segment: segment};
+            0
+        },
+        None => -1,
+    }
+}
+
+/// decompressed bytes of an entry, NULL for empty entries. The length goes in
+/// `out_len`. Free with `bk_bytes_free`
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_asset_bytes(bin: *const BkAssetBin, uid: usize, out_len: *mut usize) -> *mut u8{
+    let (bin, out_len) = match (bin.as_ref(), out_len.as_mut()){
+        (Some(b), Some(l)) => (b, l),
+        _ => return ptr::null_mut(),
+    };
+    return guard(ptr::null_mut(), ||{
+        match bin.folder.asset(uid){
+            Some(a) => boxed_bytes(a.to_bytes(), out_len),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// a buffer to pass bytes in with, for hosts like wasm that can't hand over
+/// their own memory. Free with `bk_bytes_free`
+#[no_mangle]
+pub extern "C" fn bk_alloc(len: usize) -> *mut u8{
+    return Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bk_bytes_free(bytes: *mut u8, len: usize){
+    if !bytes.is_null(){
+        drop(Box::from_raw(slice::from_raw_parts_mut(bytes, len)));
+    }
+}
+
+/// replaces a non-empty entry with decompressed bytes, keeping its flags. 0 on
+/// success, -1 if the uid is empty or outside the table
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_replace_asset(bin: *mut BkAssetBin, uid: usize, bytes: *const u8, len: usize) -> c_int{
+    let bin = match bin.as_mut(){
+        Some(b) if !bytes.is_null() => b,
+        _ => return -1,
+    };
+    let bytes = slice::from_raw_parts(bytes, len);
+    return guard(-1, ||{
+        bin.folder.replace_bytes(uid, bytes);
+        0
+    })
+}
+
+/// builds the bin the same way construct does. 0 on success, -1 on failure
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_write(bin: *mut BkAssetBin, path: *const c_char) -> c_int{
+    let bin = match bin.as_mut(){
+        Some(b) if !path.is_null() => b,
+        _ => return -1,
+    };
+    return guard(-1, ||{
+        let mut out = fs::File::create(path_arg(path)).expect("could not create file");
+        bin.folder.write_to(&mut out).unwrap_or_else(|e|{panic!("could not write file: {}", e)});
+        0
+    })
+}
+
+/// the whole bin, built the same way construct does. Free with `bk_bytes_free`
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_to_bytes(bin: *mut BkAssetBin, out_len: *mut usize) -> *mut u8{
+    let (bin, out_len) = match (bin.as_mut(), out_len.as_mut()){
+        (Some(b), Some(l)) => (b, l),
+        _ => return ptr::null_mut(),
+    };
+    return guard(ptr::null_mut(), ||{boxed_bytes(bin.folder.to_bytes(), out_len)})
+}
+
+/// a dialog or question as the same utf-8 JSON document extract writes, NULL
+/// for other types. Free with `bk_bytes_free`
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_asset_json(bin: *const BkAssetBin, uid: usize, out_len: *mut usize) -> *mut u8{
+    let (bin, out_len) = match (bin.as_ref(), out_len.as_mut()){
+        (Some(b), Some(l)) => (b, l),
+        _ => return ptr::null_mut(),
+    };
+    return guard(ptr::null_mut(), ||{
+        match bin.folder.asset(uid).and_then(|a|{a.to_json()}){
+            Some(doc) => boxed_bytes(doc.into_bytes(), out_len),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// one frame of a sprite as png file bytes, NULL if it isn't a sprite or has
+/// no such frame. Free with `bk_bytes_free`
+#[no_mangle]
+pub unsafe extern "C" fn bk_bin_sprite_png(bin: *const BkAssetBin, uid: usize, frame: usize, out_len: *mut usize) -> *mut u8{
+    let (bin, out_len) = match (bin.as_ref(), out_len.as_mut()){
+        (Some(b), Some(l)) => (b, l),
+        _ => return ptr::null_mut(),
+    };
+    return guard(ptr::null_mut(), ||{
+        let a = match bin.folder.asset(uid){
+            Some(a) if matches!(a.get_type(), AssetType::Sprite(_)) => a,
+            _ => return ptr::null_mut(),
+        };
+        let sprite = asset::Sprite::from_bytes(&a.to_bytes());
+        match sprite.frame.get(frame){
+            Some(f) => {
+                let (w, h, rgba) = f.rgba();
+                boxed_bytes(texture::png_rgba32(w, h, rgba), out_len)
+            },
+            None => ptr::null_mut(),
+        }
+    })
+}
@@ -0,0 +1,2 @@
+pub mod banjo_kazooie;
+pub mod ffi;
@@ -1,10 +1,17 @@
-mod banjo_kazooie;
+use bk_asset_tool::banjo_kazooie;
+use bk_asset_tool::banjo_kazooie::asset::Asset;
 
 use std::env;
 use std::fs::{self, DirBuilder};
-use std::io::Write;
+use std::io::{BufWriter, Write};
+use std::panic;
 use std::path::Path;
 
+mod config;
+use config::Config;
+
+use banjo_kazooie::log;
+
 enum Direction {
     Extract,
     Construct,
@@ -12,40 +19,1140 @@ enum Direction {
 
 fn main() {
     //get inputs
-    let arg1 = env::args().nth(1).expect("No input arguments provided");
-    let direction = match arg1.as_str() {
-        "--extract" | "-e" => Direction::Extract,
-        "--construct" | "-c" => Direction::Construct,
-        _=> panic!("invalid direction \"{}\" provided\n try: --extract, -e, --construct, or -c", arg1),
-    };
-    let in_path = env::args().nth(2).expect("No in path provided");
-    let out_path = env::args().nth(3).expect("No out path provided");
+    let mut args : Vec<String> = env::args().skip(1).collect();
+    //recorded from here on, so the config, --type-rules and --segment-map are
+    //in construct's --emit-deps along with the assets
+    banjo_kazooie::deps::start();
+    let config = Config::load(&mut args);
+    //detection rules on top of the built in ones, for every command that decodes
+    if let Some(p) = take_flag_value(&mut args, "--type-rules").map(|p|{Path::new(&p).to_path_buf()}).or(config.type_rules.clone()){
+        banjo_kazooie::type_rules::add(banjo_kazooie::type_rules::read(&p));
+    }
+    if let Some(p) = take_flag_value(&mut args, "--segment-map"){
+        banjo_kazooie::segment_map::set(banjo_kazooie::segment_map::read(Path::new(&p)));
+    }
+    //extract and construct go through every entry once anyway, so they skip the cache
+    let no_cache = args.iter().any(|a|{a == "--no-cache"});
+    args.retain(|a|{a != "--no-cache"});
+    let whole_bin = ["--extract", "-e", "extract", "--construct", "-c", "construct"].contains(&args.get(0).map_or("", |a|{a.as_str()}));
+    if !no_cache && !whole_bin{
+        banjo_kazooie::cache::set(Some(banjo_kazooie::cache::default_dir()));
+    }
+    let json = match take_flag_value(&mut args, "--log-format").as_deref(){
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(f) => panic!("invalid log format \"{}\" provided\n try: text or json", f),
+    };
+    if !json{
+        return run(&args, &config);
+    }
+
+    //every line is json, so a panic is reported as the error line instead
+    let command = args.get(0).map(|a|{a.as_str()}).unwrap_or("");
+    assert!(["--extract", "-e", "extract", "--construct", "-c", "construct"].contains(&command), "--log-format json is only for extract and construct");
+    log::set_json(true);
+    panic::set_hook(Box::new(|_|{}));
+    if let Err(e) = panic::catch_unwind(||{run(&args, &config)}){
+        log::error(&banjo_kazooie::error::panic_message(&*e));
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String], config: &Config){
+    let arg1 = args.get(0).expect("No input arguments provided");
+    match arg1.as_str() {
+        "--extract" | "-e" | "extract" => convert(Direction::Extract, &args[1..], config),
+        "--construct" | "-c" | "construct" => convert(Direction::Construct, &args[1..], config),
+        "sprite" => sprite_command(&args[1..]),
+        "model" => model_command(&args[1..], config),
+        "anim" => anim_command(&args[1..]),
+        "lvl" => lvl_command(&args[1..]),
+        "demo" => demo_command(&args[1..]),
+        "midi" => midi_command(&args[1..]),
+        "text" => text_command(&args[1..]),
+        "quiz" => quiz_command(&args[1..], config),
+        "validate" => validate(&args[1..]),
+        "migrate" => migrate(&args[1..], config),
+        "dups" => dups(&args[1..]),
+        "verify-manifest" => verify_manifest(&args[1..]),
+        "list" => list(&args[1..]),
+        "extract-one" => extract_one(&args[1..]),
+        "explain" => explain(&args[1..]),
+        "splat" => splat(&args[1..], config),
+        "addrs" => addrs(&args[1..]),
+        "grep-bytes" => grep_bytes(&args[1..]),
+        "bps" => bps(&args[1..], config),
+        "zip" => zip(&args[1..]),
+        "unzip" => unzip(&args[1..]),
+        "detect-segments" => detect_segments(&args[1..]),
+        "version-diff" => version_diff(&args[1..]),
+        "dep-graph" => dep_graph(&args[1..], config),
+        "export-db" => export_db(&args[1..], config),
+        "roundtrip-check" => roundtrip_check(&args[1..]),
+        "gen-fixtures" => gen_fixtures(&args[1..]),
+        "check-fixtures" => check_fixtures(&args[1..]),
+        "cache-clear" => cache_clear(),
+        _=> panic!("invalid command \"{}\" provided\n try: extract, --extract, -e, construct, --construct, -c, list, extract-one, explain, splat, addrs, grep-bytes, detect-segments, bps, zip, unzip, validate, migrate, verify-manifest, dups, version-diff, dep-graph, export-db, roundtrip-check, gen-fixtures, check-fixtures, cache-clear, sprite, model, anim, lvl, demo, midi, text, or quiz", arg1),
+    };
+}
+
+fn convert(direction: Direction, args: &[String], config: &Config){
+    //paths left out come from the config
+    let positional = args.iter().take(2).take_while(|a|{!a.starts_with("--")}).count();
+    let path_str = |p : &Path|{p.to_string_lossy().into_owned()};
+    let (config_in, config_out) = match direction{
+        Direction::Extract => (config.bin.as_deref().map(path_str), config.dir.as_deref().map(path_str)),
+        Direction::Construct => (config.dir.as_ref().map(|d|{path_str(&d.join("assets.yaml"))}), config.bin.as_deref().map(path_str)),
+    };
+    //with an archive, the folder is a temporary one it's packed from or unpacked to
+    let archive_flag = match direction {Direction::Extract => "--archive", Direction::Construct => "--from-archive"};
+    let archive = args.iter().position(|a|{a == archive_flag}).map(|i|{args.get(i + 1).expect("No archive path provided").clone()});
+    //removed when it's dropped, on a panic too
+    let temp_dir = archive.as_ref().map(|_|{tempfile::Builder::new().prefix("bk_asset_tool_").tempdir().expect("Could not create a temporary folder")});
+    //an overlay is the assets.yaml, read on top of --base
+    let overlay = args.iter().position(|a|{a == "--overlay"}).filter(|_|{matches!(direction, Direction::Construct)}).map(|i|{args.get(i + 1).expect("No overlay provided").clone()});
+    assert!(overlay.is_none() || archive.is_none(), "--overlay and --from-archive both replace the assets.yaml, pick one");
+    let arg = |i: usize|{args.get(i).filter(|_|{positional > i}).cloned()};
+    let (in_arg, out_arg) = match (&direction, temp_dir.as_ref().map(|t|{t.path()}), overlay.as_ref()){
+        (Direction::Construct, None, Some(o)) => (Some(o.clone()), arg(0)),
+        (_, None, _) => (arg(0), arg(1)),
+        (Direction::Extract, Some(t), _) => (arg(0), Some(path_str(t))),
+        (Direction::Construct, Some(t), _) => (Some(path_str(&t.join("assets.yaml"))), arg(0)),
+    };
+    let in_path = &in_arg.or(config_in).expect("No in path provided");
+    let out_path = &out_arg.or(config_out).expect("No out path provided");
+
+    //optional flags, over the config's defaults
+    let mut extract_opts = banjo_kazooie::asset::ExtractOptions::new();
+    let mut import_opts = banjo_kazooie::asset::ImportOptions::new();
+    if let Some(c) = config.compression.as_ref(){
+        import_opts.compression = banjo_kazooie::asset::Compression::from_str(c);
+    }
+    import_opts.threads = config.threads.unwrap_or(1);
+    extract_opts.threads = import_opts.threads;
+    if let Some(names) = config.names.as_ref(){
+        extract_opts.names = banjo_kazooie::names::NameMap::read(names);
+        import_opts.names = extract_opts.names.clone();
+    }
+    let mut dry_run = false;
+    let mut base_path : Option<String> = None;
+    let mut color : Option<String> = None;
+    let mut permissive = false;
+    let mut manifest_path : Option<String> = None;
+    let mut bps_path : Option<String> = None;
+    let mut bps_source : Option<String> = None;
+    let mut deps_path : Option<String> = None;
+    let mut map_path : Option<String> = None;
+    let mut rom_base = 0;
+    let mut flags = args.iter().skip(positional);
+    while let Some(flag) = flags.next(){
+        match flag.as_str(){
+            "--text-format" => {
+                extract_opts.text_format = match flags.next().expect("No text format provided").as_str(){
+                    "yaml" => banjo_kazooie::asset::TextFormat::Yaml,
+                    "json" => banjo_kazooie::asset::TextFormat::Json,
+                    x => panic!("invalid text format \"{}\" provided\n try: yaml or json", x),
+                };
+            },
+            "--apng" => extract_opts.apng_delay_ms = Some(extract_opts.apng_delay_ms.unwrap_or(100)),
+            "--frame-delay" => {
+                let delay = flags.next().expect("No frame delay provided").parse::<u16>().expect("frame delay must be a number of milliseconds");
+                extract_opts.apng_delay_ms = Some(delay);
+            },
+            "--c-arrays" => extract_opts.c_arrays = true,
+            "--split-index" => extract_opts.split_index = true,
+            "--archive" | "--from-archive" if flag == archive_flag => {flags.next();},
+            "--overlay" if overlay.is_some() => {flags.next();},
+            "--names" => {
+                extract_opts.names = banjo_kazooie::names::NameMap::read(Path::new(flags.next().expect("No name map provided")));
+                import_opts.names = extract_opts.names.clone();
+            },
+            "--dry-run" => dry_run = true,
+            "--base" => base_path = Some(flags.next().expect("No base bin or assets.yaml provided").clone()),
+            "--color" => color = Some(flags.next().expect("No color mode provided").clone()),
+            "--permissive" => permissive = true,
+            "--bps" => bps_path = Some(flags.next().expect("No patch path provided").clone()),
+            "--bps-source" => bps_source = Some(flags.next().expect("No patch source provided").clone()),
+            "--manifest" => manifest_path = Some(flags.next().expect("No manifest path provided").clone()),
+            "--emit-deps" => deps_path = Some(flags.next().expect("No deps path provided").clone()),
+            "--address-map" => map_path = Some(flags.next().expect("No address map path provided").clone()),
+            "--rom-base" => rom_base = parse_int(flags.next().expect("No rom base provided")),
+            "--actor-names" => extract_opts.actor_names = Some(Path::new(flags.next().expect("No actor name table provided")).to_path_buf()),
+            "--strict-colors" => import_opts.strict_colors = true,
+            "--patch" => import_opts.patches.push(Path::new(flags.next().expect("No patch provided")).to_path_buf()),
+            "--asset-align" => import_opts.asset_align = Some(parse_int(flags.next().expect("No alignment provided"))),
+            "--bin-align" => import_opts.bin_align = Some(parse_int(flags.next().expect("No alignment provided"))),
+            "--compression" => import_opts.compression = banjo_kazooie::asset::Compression::from_str(flags.next().expect("No compression provided")),
+            "--threads" => {
+                import_opts.threads = parse_int(flags.next().expect("No thread count provided")).max(1);
+                extract_opts.threads = import_opts.threads;
+            },
+            "--fill" => import_opts.fill = Some(parse_int(flags.next().expect("No fill byte provided")) as u8),
+            "--alpha-threshold" => {
+                import_opts.alpha_threshold = flags.next().expect("No alpha threshold provided").parse::<u8>().expect("alpha threshold must be 0-255");
+            },
+            _=> panic!("unknown flag \"{}\" provided", flag),
+        }
+    }
+    assert!(overlay.is_none() || base_path.is_some(), "--overlay is read on top of --base, give the vanilla bin or assets.yaml with --base");
+    let style = banjo_kazooie::text_diff::Style::new(color.as_deref()).unwrap_or_else(|e|{log::error(&e); std::process::exit(1)});
     
+    let command = match direction {Direction::Extract => "extract", Direction::Construct => "construct"};
+    log::progress("start", &[("command", log::string(command)), ("in", log::string(in_path)), ("out", log::string(out_path))]);
     match direction {
         Direction::Extract => {
             // open asset binary
             assert!(fs::metadata(&in_path).unwrap().is_file());
-            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let in_bytes = map_input(in_path);
             
+            //an earlier extraction into the same archive is unpacked, so what's
+            //kept from an earlier extraction is kept from it
+            if let Some(archive) = archive.as_ref().filter(|a|{Path::new(a).is_file()}){
+                banjo_kazooie::archive::extract_to(&map_input(archive), Path::new(out_path)).unwrap_or_else(|e|{panic!("{}: {}", archive, e)});
+            }
+
+            //type overrides from an earlier extraction into the same folder
+            let old_yaml = Path::new(&out_path).join("assets.yaml");
+            let overrides = if old_yaml.is_file() {banjo_kazooie::type_overrides(&old_yaml, &extract_opts.names)} else {Vec::new()};
+
             // parse binary
-            let af = banjo_kazooie::AssetFolder::from_bytes(&in_bytes);
+            let (mut af, errors) = banjo_kazooie::AssetFolder::from_bytes_with(&in_bytes, &overrides, permissive).unwrap_or_else(|e| panic!("{}", e));
+            for e in errors.iter(){
+                log::warning(e.uid(), &format!("{}, written as Binary", e));
+            }
+            log::progress("read", &[("entries", af.len().to_string())]);
+
+            //notes and tags from the earlier extraction go back where they were
+            if old_yaml.is_file(){
+                af.keep_notes(&old_yaml, &extract_opts.names);
+            }
 
             //create output
             DirBuilder::new().recursive(true).create(&out_path).unwrap();
             assert!(fs::metadata(&out_path).unwrap().is_dir());
-            af.write(Path::new(&out_path));
+            af.write(Path::new(&out_path), &extract_opts);
+            if let Some(archive) = archive.as_ref(){
+                let mut out = BufWriter::new(fs::File::create(archive).expect("Could not create archive"));
+                let count = banjo_kazooie::archive::write_dir(&mut out, Path::new(out_path)).expect("Could not write archive");
+                out.flush().unwrap();
+                log::progress("archive", &[("path", log::string(archive)), ("files", count.to_string())]);
+            }
+            log::progress("summary", &[("entries", af.len().to_string()), ("warnings", log::warnings().to_string())]);
 
         }
         Direction::Construct => {
+            if let Some(archive) = archive.as_ref(){
+                let bytes = banjo_kazooie::deps::read(archive).expect("Could not read archive");
+                banjo_kazooie::archive::extract_to(&bytes, temp_dir.as_ref().unwrap().path()).unwrap_or_else(|e|{panic!("{}: {}", archive, e)});
+            }
             assert!(fs::metadata(&in_path).unwrap().is_file());
-            let mut af = banjo_kazooie::AssetFolder::new();
-            af.read(Path::new(&in_path));
+            //with a base bin or extraction, assets.yaml only needs the entries that changed
+            let mut af = match base_path.as_ref(){
+                Some(base_path) if base_path.ends_with(".yaml") => {
+                    let mut af = banjo_kazooie::AssetFolder::new();
+                    af.read(Path::new(base_path), &import_opts);
+                    af
+                },
+                Some(base_path) => banjo_kazooie::AssetFolder::from_base(&banjo_kazooie::deps::read(base_path).expect("Could not read base bin")),
+                None => banjo_kazooie::AssetFolder::new(),
+            };
+            af.read(Path::new(&in_path), &import_opts);
+            let problems = af.validate();
+            if !problems.is_empty(){
+                for p in problems.iter(){
+                    log::error(p);
+                }
+                panic!("{} problems would crash or soft lock the game, nothing was written", problems.len());
+            }
+            let mut read_paths = banjo_kazooie::deps::finish();
+            if let Some(t) = temp_dir.as_ref(){
+                //the unpacked files come from the archive, which is already listed
+                read_paths.retain(|p|{!p.starts_with(t.path())});
+            }
+            log::progress("read", &[("entries", af.len().to_string()), ("files", read_paths.len().to_string())]);
+            if let Some(deps_path) = deps_path{
+                let mut out = BufWriter::new(fs::File::create(&deps_path).expect("Could not create deps file"));
+                banjo_kazooie::deps::write_depfile(&mut out, Path::new(&out_path), &read_paths).expect("Could not write deps file");
+            }
 
-            let mut decomp_buffer = af.to_bytes();
-            decomp_buffer.resize((decomp_buffer.len() + 15) & !15, 0);
-            let mut out_bin = fs::File::create(&out_path).expect("Could create output bin");
-            out_bin.write_all(&decomp_buffer).unwrap();
+            //compare with the bin that would be overwritten instead of writing it
+            if dry_run{
+                let new_bytes = af.to_bytes();
+                //a bin that isn't built yet is compared as one with no entries
+                let ref_map = Path::new(out_path).is_file().then(||{map_input(out_path)});
+                let empty_bytes = banjo_kazooie::AssetFolder::new().to_bytes();
+                let ref_bytes : &[u8] = ref_map.as_deref().unwrap_or(&empty_bytes);
+                let (ref_table, new_table) = (banjo_kazooie::AssetTable::new(ref_bytes), banjo_kazooie::AssetTable::new(&new_bytes));
+                let changes = ref_table.changes(&new_table);
+                for c in changes.iter(){
+                    log::info(c, "change", &[("message", log::string(c))]);
+                }
+                let text_changes = banjo_kazooie::text_diff::table_diff(&ref_table, &new_table, &style);
+                //the side by side diff is for reading, json only gets the changes above
+                if !text_changes.is_empty() && !log::is_json(){
+                    println!("text changes:");
+                    for c in text_changes.iter(){
+                        println!("{}", c);
+                    }
+                }
+                let text = format!("{} entries changed, 0x{:X} -> 0x{:X} bytes", changes.len(), ref_bytes.len(), new_bytes.len());
+                log::info(&text, "summary", &[("dry_run", "true".to_string()), ("changed", changes.len().to_string()), ("old_bytes", ref_bytes.len().to_string()), ("new_bytes", new_bytes.len().to_string()), ("warnings", log::warnings().to_string())]);
+                return;
+            }
+
+            let mut out_bin = BufWriter::new(fs::File::create(&out_path).expect("Could create output bin"));
+            let out_len = af.write_to(&mut out_bin).unwrap_or_else(|e|{panic!("Could not write output bin: {}", e)});
+            out_bin.flush().unwrap();
+            drop(out_bin);
+            if let Some(manifest_path) = manifest_path{
+                //hashed from the written file, so it records what's on disk
+                banjo_kazooie::manifest::write(Path::new(&manifest_path), &map_input(out_path));
+            }
+            if let Some(bps_path) = bps_path{
+                //from the vanilla bin, which --base already is if it's a bin
+                let source = bps_source.or(base_path.filter(|b|{!b.ends_with(".yaml")})).expect("--bps needs the vanilla bin, from --bps-source or --base");
+                write_bps(&map_input(&source), &map_input(out_path), &bps_path);
+            }
+            if let Some(map_path) = map_path{
+                let mut out = BufWriter::new(fs::File::create(&map_path).expect("Could not create address map"));
+                banjo_kazooie::address_map::write(&mut out, &map_input(out_path), rom_base).expect("Could not write address map");
+            }
+            if let Some(size) = af.rom_size().filter(|s|{out_len > *s}){
+                log::warning(None, &format!("{} is 0x{:X} bytes, 0x{:X} more than the 0x{:X} rom_size", out_path, out_len, out_len - size, size));
+            }
+            let rom_size = af.rom_size().map_or("null".to_string(), |s|{s.to_string()});
+            log::progress("summary", &[("entries", af.len().to_string()), ("bytes", out_len.to_string()), ("rom_size", rom_size), ("warnings", log::warnings().to_string())]);
 
         }
     }
 }
+
+/// reads an extracted folder the same way construct does, without writing anything
+fn validate(args: &[String]){
+    let in_path = args.get(0).expect("No in path provided");
+    assert!(fs::metadata(&in_path).unwrap().is_file());
+    let mut af = banjo_kazooie::AssetFolder::new();
+    af.read(Path::new(&in_path), &banjo_kazooie::asset::ImportOptions::new());
+    let problems = af.validate();
+    for p in problems.iter(){
+        println!("error: {}", p);
+    }
+    if !problems.is_empty(){
+        println!("{} problems", problems.len());
+        std::process::exit(1);
+    }
+    println!("{} is valid", in_path);
+}
+
+/// upgrades an extracted folder's assets.yaml, and the files it lists, to the
+/// current schema
+fn migrate(args: &[String], config: &Config){
+    let yaml_path = match args.get(0){
+        Some(p) => Path::new(p).to_path_buf(),
+        None => config.dir.as_ref().map(|d|{d.join("assets.yaml")}).expect("No assets.yaml provided"),
+    };
+    let report = banjo_kazooie::migrate::migrate(&yaml_path).unwrap_or_else(|e|{panic!("{}", e)});
+    for line in report.iter(){
+        println!("{}", line);
+    }
+    if report.is_empty(){
+        println!("{:?} is already schema version {}", yaml_path, banjo_kazooie::migrate::SCHEMA_VERSION);
+    }
+}
+
+/// maps the input bin instead of reading it, so only the parts used get loaded
+fn map_input(path: &str) -> memmap2::Mmap{
+    let file = fs::File::open(path).expect("Could not read file");
+    //the bin isn't expected to change while the tool runs
+    return unsafe { memmap2::Mmap::map(&file) }.expect("Could not map file")
+}
+
+/// prints the asset table without decompressing anything
+fn list(args: &[String]){
+    let in_path = args.get(0).expect("No in path provided");
+    let in_bytes = map_input(in_path);
+    let table = banjo_kazooie::AssetTable::new(&in_bytes);
+    for e in table.entries().filter(|e|{e.flags != 4}){
+        println!("0x{:04X}: segment {}, flags 0x{:04X}, {}, 0x{:X} bytes", e.uid, e.segment, e.flags, if e.compressed {"compressed"} else {"uncompressed"}, e.size);
+    }
+}
+
+/// decodes and writes a single asset the same way extract does
+fn extract_one(args: &[String]){
+    let mut args = args.to_vec();
+    let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No uid provided"));
+    let mut extract_opts = banjo_kazooie::asset::ExtractOptions::new();
+    if let Some(fmt) = take_flag_value(&mut args, "--text-format"){
+        extract_opts.text_format = match fmt.as_str(){
+            "yaml" => banjo_kazooie::asset::TextFormat::Yaml,
+            "json" => banjo_kazooie::asset::TextFormat::Json,
+            x => panic!("invalid text format \"{}\" provided\n try: yaml or json", x),
+        };
+    }
+    let in_path = args.get(0).expect("No in path provided");
+    let out_path = Path::new(args.get(1).expect("No out path provided"));
+    let in_bytes = map_input(in_path);
+    let asset = banjo_kazooie::AssetTable::new(&in_bytes).asset(uid).expect("uid is an empty table entry");
+    DirBuilder::new().recursive(true).create(out_path).unwrap();
+    let (type_str, relative_path) = banjo_kazooie::write_asset(asset.as_ref(), uid, out_path, &extract_opts);
+    println!("0x{:04X}: {} {}", uid, type_str, relative_path);
+}
+
+/// hex dump of one entry's decompressed bytes, annotated with its parser's fields
+fn explain(args: &[String]){
+    let mut args = args.to_vec();
+    let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No uid provided"));
+    let full = args.iter().any(|a|{a == "--full"});
+    args.retain(|a|{a != "--full"});
+    let in_path = args.get(0).expect("No in path provided");
+    let in_bytes = map_input(in_path);
+    let table = banjo_kazooie::AssetTable::new(&in_bytes);
+    let bytes = table.bytes(uid).unwrap_or_else(|e|{panic!("{}", e)}).expect("uid is an empty table entry");
+    let asset_type = table.asset(uid).unwrap().get_type();
+    let e = table.entry(uid).unwrap_or_else(|e|{panic!("{}", e)});
+    println!("0x{:04X}: {}, segment {}, 0x{:X} bytes", uid, banjo_kazooie::type_name(&asset_type), e.segment, bytes.len());
+    let fields = banjo_kazooie::explain::fields(&asset_type, &bytes);
+    print!("{}", banjo_kazooie::explain::dump(&bytes, &fields, if full {usize::MAX} else {0x20}));
+}
+
+/// splat segment listing of the bin's entries, to stdout without an out path
+fn splat(args: &[String], config: &Config){
+    let mut args = args.to_vec();
+    let rom_base = take_flag_value(&mut args, "--rom-base").map_or(0, |b|{parse_int(&b)});
+    let names = names_flag(&mut args, config);
+    let in_path = args.get(0).expect("No in path provided");
+    let in_bytes = map_input(in_path);
+    match args.get(1){
+        Some(out_path) => {
+            let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create output yaml"));
+            banjo_kazooie::splat::write(&mut out, &in_bytes, rom_base, &names).unwrap();
+        },
+        None => banjo_kazooie::splat::write(&mut std::io::stdout(), &in_bytes, rom_base, &names).unwrap(),
+    }
+}
+
+/// bin offset and rom address of every entry, to stdout without an out path
+fn addrs(args: &[String]){
+    let mut args = args.to_vec();
+    let rom_base = take_flag_value(&mut args, "--rom-base").map_or(0, |b|{parse_int(&b)});
+    let in_path = args.get(0).expect("No in path provided");
+    let in_bytes = map_input(in_path);
+    match args.get(1){
+        Some(out_path) => {
+            let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create address map"));
+            banjo_kazooie::address_map::write(&mut out, &in_bytes, rom_base).unwrap();
+        },
+        None => banjo_kazooie::address_map::write(&mut std::io::stdout(), &in_bytes, rom_base).unwrap(),
+    }
+}
+
+/// empties the decompressed entry cache
+fn cache_clear(){
+    let dir = banjo_kazooie::cache::default_dir();
+    let removed = banjo_kazooie::cache::clear(&dir).unwrap_or_else(|e|{panic!("{:?}: {}", dir, e)});
+    println!("removed {} cached entries from {:?}", removed, dir);
+}
+
+/// every uid and offset of the decompressed data a byte pattern is at, exits
+/// with 1 when there are none, like grep
+fn grep_bytes(args: &[String]){
+    let text = args.get(0).expect("No pattern provided");
+    let in_path = args.get(1).expect("No in path provided");
+    let pattern = banjo_kazooie::byte_search::parse(text).unwrap_or_else(|e|{panic!("invalid pattern: {}", e)});
+    let in_bytes = map_input(in_path);
+    let matches = banjo_kazooie::byte_search::search(&banjo_kazooie::AssetTable::new(&in_bytes), &pattern);
+    for m in matches.iter(){
+        let bytes : Vec<String> = m.bytes.iter().map(|b|{format!("{:02X}", b)}).collect();
+        println!("0x{:04X}+0x{:X}: {}", m.uid, m.offset, bytes.join(" "));
+    }
+    let mut uids : Vec<usize> = matches.iter().map(|m|{m.uid}).collect();
+    uids.dedup();
+    eprintln!("{} matches in {} entries", matches.len(), uids.len());
+    if matches.is_empty(){
+        std::process::exit(1);
+    }
+}
+
+/// a BPS patch between any two files, like whole roms. The source can be left
+/// out for the config's rom
+fn bps(args: &[String], config: &Config){
+    let (source_path, rest) = match (args.len(), config.rom.as_ref()){
+        (2, Some(rom)) => (rom.to_string_lossy().into_owned(), args),
+        _ => (args.get(0).expect("No source path provided").clone(), args.get(1..).unwrap_or(&[])),
+    };
+    let target_path = rest.get(0).expect("No target path provided");
+    let out_path = rest.get(1).expect("No patch path provided");
+    write_bps(&map_input(&source_path), &map_input(target_path), out_path);
+}
+
+fn write_bps(source: &[u8], target: &[u8], out_path: &str){
+    let patch = banjo_kazooie::bps::diff(source, target);
+    //an encoder bug would otherwise only show up for whoever applies it
+    assert!(banjo_kazooie::bps::apply(source, &patch).as_deref() == Ok(target), "the patch doesn't apply back to the same bytes");
+    fs::write(out_path, &patch).expect("Could not write patch");
+    log::info(&format!("wrote {}, 0x{:X} bytes", out_path, patch.len()), "bps", &[("path", log::string(out_path)), ("bytes", patch.len().to_string())]);
+}
+
+/// compresses any file the way the game's are, `--overlay` without the 11 72
+/// header. Writes <input>.z without an out path
+fn zip(args: &[String]){
+    let mut args = args.to_vec();
+    let overlay = args.iter().any(|a|{a == "--overlay"});
+    args.retain(|a|{a != "--overlay"});
+    let in_path = args.get(0).expect("No in path provided");
+    let out_path = args.get(1).cloned().unwrap_or(format!("{}.z", in_path));
+    let bytes = map_input(in_path);
+    let mut zipped = rarezip::bk::zip(&bytes);
+    if overlay{
+        zipped.drain(..6);
+    }
+    fs::write(&out_path, &zipped).expect("Could not write compressed file");
+    println!("{}: 0x{:X} bytes to 0x{:X}", out_path, bytes.len(), zipped.len());
+}
+
+/// decompresses a file compressed the way the game's are, `--overlay` for one
+/// without the 11 72 header. Writes <input> without its .z, or <input>.unzipped
+fn unzip(args: &[String]){
+    let mut args = args.to_vec();
+    let overlay = args.iter().any(|a|{a == "--overlay"});
+    args.retain(|a|{a != "--overlay"});
+    let in_path = args.get(0).expect("No in path provided");
+    let out_path = args.get(1).cloned().unwrap_or_else(||{
+        in_path.strip_suffix(".z").map_or(format!("{}.unzipped", in_path), |p|{p.to_string()})
+    });
+    let bytes = map_input(in_path);
+    let unzipped = match overlay{
+        //overlays don't say how big they are, the stream ends itself
+        true => miniz_oxide::inflate::decompress_to_vec(&bytes).unwrap_or_else(|e|{panic!("{} isn't a deflate stream: {:?}", in_path, e)}),
+        false => {
+            assert!(bytes.starts_with(&[0x11, 0x72]) && bytes.len() >= 6, "{} doesn't start with the 11 72 header, try --overlay", in_path);
+            let len = u32::from_be_bytes(bytes[2..6].try_into().unwrap()) as usize;
+            let unzipped = rarezip::bk::unzip(&bytes);
+            assert_eq!(unzipped.len(), len, "{} decompresses to 0x{:X} bytes, its header says 0x{:X}", in_path, unzipped.len(), len);
+            unzipped
+        },
+    };
+    fs::write(&out_path, &unzipped).expect("Could not write decompressed file");
+    println!("{}: 0x{:X} bytes to 0x{:X}", out_path, bytes.len(), unzipped.len());
+}
+
+/// the segment boundaries the bin's entries are read with, as a segment map
+fn detect_segments(args: &[String]){
+    let in_path = args.get(0).expect("No in path provided");
+    let in_bytes = map_input(in_path);
+    let table = banjo_kazooie::AssetTable::new(&in_bytes);
+    match args.get(1){
+        Some(out_path) => {
+            let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create segment map"));
+            banjo_kazooie::segment_map::write(&mut out, &table).unwrap();
+        },
+        None => banjo_kazooie::segment_map::write(&mut std::io::stdout(), &table).unwrap(),
+    }
+}
+
+/// checks a bin against a construct --manifest
+fn verify_manifest(args: &[String]){
+    let in_path = args.get(0).expect("No in path provided");
+    let manifest_path = args.get(1).expect("No manifest path provided");
+    let errors = banjo_kazooie::manifest::verify(Path::new(manifest_path), &map_input(in_path));
+    for e in errors.iter(){
+        println!("{}", e);
+    }
+    if !errors.is_empty(){
+        std::process::exit(1);
+    }
+    println!("{} matches {}", in_path, manifest_path);
+}
+
+/// entries added, removed, moved or changed between two versions of the bin
+fn version_diff(args: &[String]){
+    let mut args = args.to_vec();
+    let threshold = take_flag_value(&mut args, "--threshold").map_or(0.5, |t|{t.parse::<f32>().expect("threshold must be a number from 0 to 1")});
+    let style = banjo_kazooie::text_diff::Style::new(take_flag_value(&mut args, "--color").as_deref()).unwrap_or_else(|e|{log::error(&e); std::process::exit(1)});
+    let old_path = args.get(0).expect("No old bin provided");
+    let new_path = args.get(1).expect("No new bin provided");
+    let changes = banjo_kazooie::version_diff::diff(&map_input(old_path), &map_input(new_path), threshold, &style);
+    for c in changes.iter(){
+        println!("{}", c);
+    }
+}
+
+/// which assets reference which, as DOT or JSON by the out path's extension, or
+/// with --uid, every asset that depends on that one
+fn dep_graph(args: &[String], config: &Config){
+    let mut args = args.to_vec();
+    let actor_models = match take_flag_value(&mut args, "--actor-models"){
+        Some(p) => banjo_kazooie::dep_graph::read_actor_models(Path::new(&p)),
+        None => std::collections::HashMap::new(),
+    };
+    let names = names_flag(&mut args, config);
+    let uid = take_flag_value(&mut args, "--uid").map(|u|{parse_int(&u)});
+    let in_path = args.get(0).expect("No in path provided");
+    let graph = banjo_kazooie::dep_graph::DepGraph::new(&map_input(in_path), &actor_models, &names);
+    for w in graph.warnings.iter(){
+        eprintln!("warning: {}", w);
+    }
+    if let Some(uid) = uid{
+        let dependents = graph.dependents(&banjo_kazooie::dep_graph::Node::Asset(uid));
+        println!("0x{:04X}: {} dependent asset(s)", uid, dependents.len());
+        for n in dependents.iter(){
+            println!("  {}", n.id());
+        }
+        return;
+    }
+    match args.get(1){
+        Some(out_path) => {
+            let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create graph file"));
+            match Path::new(out_path).extension().and_then(|e|{e.to_str()}){
+                Some("json") => graph.write_json(&mut out).unwrap(),
+                _ => graph.write_dot(&mut out).unwrap(),
+            }
+        },
+        None => graph.write_dot(&mut std::io::stdout()).unwrap(),
+    }
+}
+
+/// lists table entries with the same decompressed bytes. Only a report,
+/// construct can't share one blob between them, see `AssetFolder::duplicates`
+fn dups(args: &[String]){
+    let in_path = args.get(0).expect("No in path provided");
+    let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+    let af = banjo_kazooie::AssetFolder::from_bytes(&in_bytes);
+    let groups = af.duplicates();
+    let mut dup_bytes = 0;
+    for uids in groups.iter(){
+        let size = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uids[0]).unwrap().len();
+        dup_bytes += size*(uids.len() - 1);
+        let uid_strs : Vec<String> = uids.iter().map(|u|{format!("0x{:04X}", u)}).collect();
+        println!("{} (0x{:X} bytes)", uid_strs.join(", "), size);
+    }
+    println!("{} groups, 0x{:X} decompressed bytes in repeats", groups.len(), dup_bytes);
+}
+
+/// every entry, string, sprite frame and model as an SQLite database
+#[cfg(feature = "sqlite")]
+fn export_db(args: &[String], config: &Config){
+    let mut args = args.to_vec();
+    let names = names_flag(&mut args, config);
+    let in_path = args.get(0).expect("No in path provided");
+    let out_path = args.get(1).expect("No out path provided");
+    let in_bytes = map_input(in_path);
+    let tables = banjo_kazooie::asset_db::tables(&banjo_kazooie::AssetTable::new(&in_bytes), &names);
+    //a new database, not tables added to an old one
+    if Path::new(out_path).exists(){
+        fs::remove_file(out_path).expect("Could not replace database");
+    }
+    banjo_kazooie::sqlite::write(Path::new(out_path), &tables).unwrap_or_else(|e|{panic!("Could not write database: {}", e)});
+    let counts : Vec<String> = tables.iter().map(|t|{format!("{} {}", t.rows.len(), t.name)}).collect();
+    println!("{}", counts.join(", "));
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn export_db(_args: &[String], _config: &Config){
+    panic!("export-db needs bk_asset_tool built with the sqlite feature");
+}
+
+fn roundtrip_check(args: &[String]){
+    let mut args = args.to_vec();
+    let cases = take_flag_value(&mut args, "--cases").map_or(0x100, |c|{parse_int(&c)});
+    let seed = take_flag_value(&mut args, "--seed").map_or(0, |s|{parse_int(&s)}) as u64;
+    let mut failed = false;
+    for (name, result) in banjo_kazooie::roundtrip::check_all(seed, cases){
+        match result{
+            Ok(()) => println!("{}: {} cases ok", name, cases),
+            Err(e) => {
+                println!("{}: failed at {}", name, e);
+                failed = true;
+            },
+        }
+    }
+    if failed{
+        std::process::exit(1);
+    }
+}
+
+/// samples a vanilla bin's entries of each type into a fixture folder
+fn gen_fixtures(args: &[String]){
+    let mut args = args.to_vec();
+    let per_type = take_flag_value(&mut args, "--per-type").map_or(3, |n|{parse_int(&n)});
+    let in_path = args.get(0).expect("No in path provided");
+    let out_path = Path::new(args.get(1).map_or("tests/fixtures", |p|{p.as_str()}));
+    let written = banjo_kazooie::fixtures::generate(&map_input(in_path), out_path, per_type);
+    println!("wrote {} fixtures to {:?}", written, out_path);
+}
+
+fn check_fixtures(args: &[String]){
+    let dir = Path::new(args.get(0).map_or("tests/fixtures", |p|{p.as_str()}));
+    let errors = banjo_kazooie::fixtures::check(dir);
+    for e in errors.iter(){
+        println!("{}", e);
+    }
+    if !errors.is_empty(){
+        std::process::exit(1);
+    }
+    println!("all fixtures in {:?} match", dir);
+}
+
+fn sprite_command(args: &[String]){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "sheet" => {
+            let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No --uid provided"));
+            let in_path = args.get(0).expect("No in path provided");
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            let sprite = banjo_kazooie::asset::Sprite::from_bytes(&bytes);
+            sprite.write_sheet(out_path, &out_path.with_extension("json"));
+        },
+        "fonts" => {
+            let mut shape = banjo_kazooie::font::FontShape::new();
+            if let Some(n) = take_flag_value(&mut args, "--min-glyphs"){
+                shape.min_glyphs = parse_int(&n);
+            }
+            if let Some(n) = take_flag_value(&mut args, "--max-glyph-size"){
+                shape.max_glyph_size = parse_int(&n);
+            }
+            let in_path = args.get(0).expect("No in path provided");
+            for uid in banjo_kazooie::font::candidates(&map_input(in_path), shape){
+                println!("0x{:04X}", uid);
+            }
+        },
+        "font-export" => {
+            let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No --uid provided"));
+            let widths = take_flag_value(&mut args, "--widths").map(|p|{fs::read(&p).unwrap_or_else(|e|{panic!("{}: {}", p, e)})});
+            let in_path = args.get(0).expect("No in path provided");
+            let out_dir = Path::new(args.get(1).expect("No out folder provided"));
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&map_input(in_path), uid).expect("uid is an empty table entry");
+            let sprite = banjo_kazooie::asset::Sprite::from_bytes(&bytes);
+            if !banjo_kazooie::font::is_font(&sprite, banjo_kazooie::font::FontShape::new()){
+                eprintln!("warning: 0x{:04X} doesn't look like a font, exporting its frames as glyphs anyway", uid);
+            }
+            if widths.is_none(){
+                eprintln!("warning: no --widths table given, fill in the advances in font_{:04X}.widths.yaml before font-build", uid);
+            }
+            banjo_kazooie::font::export(&sprite, widths.as_deref(), out_dir, &format!("font_{:04X}", uid));
+        },
+        "font-build" => {
+            let yaml_path = Path::new(args.get(0).expect("No font yaml provided"));
+            let widths_path = Path::new(args.get(1).expect("No widths yaml provided"));
+            let out_path = Path::new(args.get(2).expect("No out path provided"));
+            for w in banjo_kazooie::font::build(yaml_path, widths_path, out_path).iter(){
+                eprintln!("warning: {}", w);
+            }
+        },
+        _=> panic!("invalid sprite command \"{}\" provided\n try: sheet, fonts, font-export or font-build", sub_cmd),
+    }
+}
+
+fn model_command(args: &[String], config: &Config){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "export" => {
+            let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No --uid provided"));
+            let format = take_flag_value(&mut args, "--format").unwrap_or(String::from("obj"));
+            let names = names_flag(&mut args, config);
+            let in_path = args.get(0).expect("No in path provided");
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            let model = banjo_kazooie::asset::Model::try_from_bytes(&bytes).unwrap_or_else(|e|{panic!("uid 0x{:04X} is not a model that can be read: {}", uid, e)});
+            assert!(model.header.is_some(), "uid 0x{:04X} is not a model", uid);
+            match format.as_str(){
+                "obj" => model.write_obj(out_path).unwrap_or_else(|e|{panic!("uid 0x{:04X}: {}", uid, e)}),
+                "gfx" => {
+                    let cmds = model.gfx().unwrap_or_else(|e|{panic!("uid 0x{:04X}: {}", uid, e)});
+                    fs::write(out_path, banjo_kazooie::gfx::disassemble(&cmds)).expect("Could not write gfx");
+                },
+                "c" => {
+                    let mut out_f = fs::File::create(out_path).expect("Could not create output file");
+                    model.write_c(&mut out_f, &names.symbol(uid, "model")).unwrap_or_else(|e|{panic!("uid 0x{:04X}: {}", uid, e)});
+                },
+                x => panic!("invalid export format \"{}\" provided\n try: obj, gfx or c", x),
+            }
+        },
+        "retexture" => {
+            let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No --uid provided"));
+            let slot = parse_int(&take_flag_value(&mut args, "--slot").expect("No --slot provided"));
+            let png_path = Path::new(args.get(0).expect("No png provided"));
+            let in_path = args.get(1).expect("No in path provided");
+            let out_path = args.get(2).expect("No out path provided");
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            let mut model = banjo_kazooie::asset::Model::try_from_bytes(&bytes).unwrap_or_else(|e|{panic!("uid 0x{:04X} is not a model that can be read: {}", uid, e)});
+            assert!(model.header.is_some(), "uid 0x{:04X} is not a model", uid);
+            let entry = model.replace_texture(slot, png_path, &banjo_kazooie::asset::ImportOptions::new()).unwrap_or_else(|e|{panic!("uid 0x{:04X}: {}", uid, e)});
+            //every other entry keeps the bytes it has in the bin
+            let mut af = banjo_kazooie::AssetFolder::from_base(&in_bytes);
+            af.replace_bytes(uid, &model.to_bytes());
+            fs::write(out_path, af.to_bytes()).expect("Could not write output bin");
+            println!("0x{:04X}: texture {}, {:?} {}x{} at 0x{:X}", uid, slot, entry.format, entry.w, entry.h, entry.offset);
+        },
+        _=> panic!("invalid model command \"{}\" provided\n try: export or retexture", sub_cmd),
+    }
+}
+
+fn anim_command(args: &[String]){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "retime" => {
+            let scale = take_flag_value(&mut args, "--scale").expect("No --scale provided").parse::<f32>().expect("scale must be a number");
+            let uid = take_flag_value(&mut args, "--uid").map(|u|{parse_int(&u)});
+            let in_path = Path::new(args.get(0).expect("No in path provided"));
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            let mut anim = anim_input(in_path, uid);
+            let dropped = anim.retime(scale);
+            if dropped > 0{
+                eprintln!("warning: {} keyframes merged into neighbours on the same frame", dropped);
+            }
+            anim.write(out_path, &banjo_kazooie::asset::ExtractOptions::new());
+        },
+        "export-curves" => {
+            let uid = take_flag_value(&mut args, "--uid").map(|u|{parse_int(&u)});
+            let in_path = Path::new(args.get(0).expect("No in path provided"));
+            let out_dir = Path::new(args.get(1).expect("No out folder provided"));
+            let anim = anim_input(in_path, uid);
+            let bones = anim.write_curves(out_dir);
+            println!("{} bones, frames {} to {}", bones, anim.start_frame, anim.end_frame);
+        },
+        _=> panic!("invalid anim command \"{}\" provided\n try: retime or export-curves", sub_cmd),
+    }
+}
+
+/// an animation from an asset bin with --uid, or an extracted .anim.yaml
+fn anim_input(in_path: &Path, uid: Option<usize>) -> banjo_kazooie::asset::Animation{
+    return match uid{
+        Some(uid) => {
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            banjo_kazooie::asset::Animation::from_bytes(&bytes)
+        },
+        None => banjo_kazooie::asset::Animation::read(in_path),
+    }
+}
+
+fn lvl_command(args: &[String]){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "check" => {
+            //every level setup has to decode and rebuild byte for byte
+            let in_path = args.get(0).expect("No in path provided");
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let uids = banjo_kazooie::AssetFolder::from_bytes(&in_bytes).level_setup_uids();
+            let failed : Vec<usize> = uids.iter()
+                .filter(|uid|{
+                    let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, **uid).unwrap();
+                    banjo_kazooie::asset::LevelSetup::from_bytes(&bytes).is_raw()
+                })
+                .copied()
+                .collect();
+            for uid in failed.iter(){
+                println!("0x{:04X}: does not decode", uid);
+            }
+            println!("{} of {} level setups round-trip", uids.len() - failed.len(), uids.len());
+            if !failed.is_empty(){
+                std::process::exit(1);
+            }
+        },
+        "export-preview" => {
+            let uid = take_flag_value(&mut args, "--uid").map(|u|{parse_int(&u)});
+            let map_uid = take_flag_value(&mut args, "--map").map(|u|{parse_int(&u)});
+            let names = match take_flag_value(&mut args, "--actor-names"){
+                Some(p) => banjo_kazooie::names::NameMap::read(Path::new(&p)),
+                None => banjo_kazooie::names::NameMap::new(),
+            };
+            let in_path = Path::new(args.get(0).expect("No in path provided"));
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            let setup = setup_input(in_path, uid);
+            setup.write_preview_obj(out_path, &names);
+            //the map model goes next to it in the same space
+            if let Some(map_uid) = map_uid{
+                let in_bytes : Vec<u8> = fs::read(in_path).expect("--map needs an asset bin as input");
+                let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, map_uid).expect("map uid is an empty table entry");
+                let model = banjo_kazooie::asset::Model::from_bytes(&bytes);
+                assert!(model.header.is_some(), "uid 0x{:04X} is not a model", map_uid);
+                model.write_obj(&out_path.with_extension("map.obj")).unwrap_or_else(|e|{panic!("uid 0x{:04X}: {}", map_uid, e)});
+            }
+        },
+        "export-map" => {
+            let uid = take_flag_value(&mut args, "--uid").map(|u|{parse_int(&u)});
+            let names = match take_flag_value(&mut args, "--actor-names"){
+                Some(p) => banjo_kazooie::names::NameMap::read(Path::new(&p)),
+                None => banjo_kazooie::names::NameMap::new(),
+            };
+            let in_path = Path::new(args.get(0).expect("No in path provided"));
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            let setup = setup_input(in_path, uid);
+            let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create svg"));
+            banjo_kazooie::map_svg::write(&mut out, &setup, &names).unwrap();
+        },
+        "export-scene" => {
+            let map = take_flag_value(&mut args, "--map").map(|m|{parse_int(&m)});
+            let setup_uid = take_flag_value(&mut args, "--setup").map(|u|{parse_int(&u)})
+                .or(map.map(|m|{banjo_kazooie::scene::MAP_SETUP_BASE + m}));
+            let mut model_uids : Vec<usize> = Vec::new();
+            while let Some(m) = take_flag_value(&mut args, "--model"){
+                model_uids.push(parse_int(&m));
+            }
+            let names = match take_flag_value(&mut args, "--actor-names"){
+                Some(p) => banjo_kazooie::names::NameMap::read(Path::new(&p)),
+                None => banjo_kazooie::names::NameMap::new(),
+            };
+            let in_path = args.get(0).expect("No in path provided");
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            assert!(setup_uid.is_some() || !model_uids.is_empty(), "No --map, --setup or --model provided");
+            let in_bytes = map_input(in_path);
+            let setup = setup_uid.map(|uid|{
+                let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).unwrap_or_else(||{panic!("setup 0x{:04X} is an empty table entry", uid)});
+                let setup = banjo_kazooie::asset::LevelSetup::from_bytes(&bytes);
+                assert!(!setup.is_raw(), "0x{:04X} is not a level setup that decodes, try --setup <uid>", uid);
+                setup
+            });
+            let models : Vec<(usize, banjo_kazooie::asset::Model)> = model_uids.iter()
+                .map(|uid|{
+                    let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, *uid).unwrap_or_else(||{panic!("model 0x{:04X} is an empty table entry", uid)});
+                    let model = banjo_kazooie::asset::Model::from_bytes(&bytes);
+                    assert!(model.header.is_some(), "uid 0x{:04X} is not a model", uid);
+                    (*uid, model)
+                })
+                .collect();
+            let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create gltf"));
+            banjo_kazooie::scene::write(&mut out, &models, setup.as_ref(), &names).unwrap();
+        },
+        _=> panic!("invalid lvl command \"{}\" provided\n try: check, export-preview, export-map or export-scene", sub_cmd),
+    }
+}
+
+/// a level setup from an asset bin with --uid, or an extracted .lvl_setup.yaml
+fn setup_input(in_path: &Path, uid: Option<usize>) -> banjo_kazooie::asset::LevelSetup{
+    let setup = match uid{
+        Some(uid) => {
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            banjo_kazooie::asset::LevelSetup::from_bytes(&bytes)
+        },
+        None => banjo_kazooie::asset::LevelSetup::read(in_path),
+    };
+    assert!(!setup.is_raw(), "level setup could not be decoded");
+    return setup
+}
+
+fn quiz_command(args: &[String], config: &Config){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "report" => {
+            //every question with its answer, from a bin or an extracted folder
+            let names = names_flag(&mut args, config);
+            let in_path = args.get(0).expect("No in path provided");
+            let questions : Vec<banjo_kazooie::quiz::Question> = match in_path.ends_with(".yaml"){
+                true => {
+                    let mut af = banjo_kazooie::AssetFolder::new();
+                    af.read(Path::new(in_path), &banjo_kazooie::asset::ImportOptions::new());
+                    (0..af.len()).filter_map(|uid|{af.asset(uid).and_then(|a|{banjo_kazooie::quiz::Question::from_asset(uid, a)})}).collect()
+                },
+                false => {
+                    let in_bytes = map_input(in_path);
+                    banjo_kazooie::AssetTable::new(&in_bytes).assets().filter_map(|(uid, a)|{banjo_kazooie::quiz::Question::from_asset(uid, a.as_ref())}).collect()
+                },
+            };
+            match args.get(1){
+                Some(out_path) => {
+                    let mut out = BufWriter::new(fs::File::create(out_path).expect("Could not create report"));
+                    banjo_kazooie::quiz::write_report(&mut out, &questions, &names).expect("Could not write report");
+                },
+                None => banjo_kazooie::quiz::write_report(&mut std::io::stdout(), &questions, &names).unwrap(),
+            }
+            let problems : Vec<String> = questions.iter().flat_map(|q|{q.problems()}).collect();
+            for p in problems.iter(){
+                eprintln!("error: {}", p);
+            }
+            if !problems.is_empty(){
+                std::process::exit(1);
+            }
+        },
+        _=> panic!("invalid quiz command \"{}\" provided\n try: report", sub_cmd),
+    }
+}
+
+fn midi_command(args: &[String]){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "edit" => {
+            let uid = parse_int(&take_flag_value(&mut args, "--uid").expect("No --uid provided"));
+            let tempo_scale = take_flag_value(&mut args, "--tempo-scale").map(|s|{s.parse::<f32>().expect("tempo scale must be a number")});
+            let mut muted : Vec<usize> = Vec::new();
+            while let Some(t) = take_flag_value(&mut args, "--mute-track"){
+                muted.push(parse_int(&t));
+            }
+            assert!(tempo_scale.is_some() || !muted.is_empty(), "nothing to edit, give --tempo-scale or --mute-track");
+            let in_path = args.get(0).expect("No in path provided");
+            let out_path = args.get(1).expect("No out path provided");
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            let mut seq = banjo_kazooie::sequence::Sequence::from_bytes(&bytes).unwrap_or_else(||{panic!("uid 0x{:04X} is not a sequence", uid)});
+            if let Some(scale) = tempo_scale{
+                let count = seq.scale_tempo(scale);
+                println!("0x{:04X}: {} tempo events scaled by {}", uid, count, scale);
+            }
+            for t in muted.iter(){
+                let count = seq.mute_track(*t);
+                println!("0x{:04X}: track {} muted, {} notes", uid, t, count);
+            }
+            //every other entry keeps the bytes it has in the bin
+            let mut af = banjo_kazooie::AssetFolder::from_base(&in_bytes);
+            af.replace_bytes(uid, &seq.to_bytes());
+            fs::write(out_path, af.to_bytes()).expect("Could not write output bin");
+        },
+        _=> panic!("invalid midi command \"{}\" provided\n try: edit", sub_cmd),
+    }
+}
+
+fn text_command(args: &[String]){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "words" => {
+            let counts = args.iter().any(|a|{a == "--counts"});
+            args.retain(|a|{a != "--counts"});
+            let dictionary = take_flag_value(&mut args, "--dictionary").map(|p|{banjo_kazooie::words::read_dictionary(Path::new(&p))});
+            let in_path = args.get(0).expect("No in path provided");
+            //every text asset's strings, from a bin or an extracted folder
+            let texts : Vec<(usize, Vec<banjo_kazooie::asset::TextLine>)> = match in_path.ends_with(".yaml"){
+                true => {
+                    let mut af = banjo_kazooie::AssetFolder::new();
+                    af.read(Path::new(in_path), &banjo_kazooie::asset::ImportOptions::new());
+                    (0..af.len()).filter_map(|uid|{af.asset(uid).and_then(|a|{a.text_lines()}).map(|l|{(uid, l)})}).collect()
+                },
+                false => {
+                    let in_bytes = map_input(in_path);
+                    banjo_kazooie::AssetTable::new(&in_bytes).assets().filter_map(|(uid, a)|{a.text_lines().map(|l|{(uid, l)})}).collect()
+                },
+            };
+            let words = banjo_kazooie::words::collect(&texts);
+            let dictionary = match dictionary{
+                Some(d) => d,
+                None => {
+                    //one per line, ready to pipe into a spell checker
+                    for (w, word) in words.iter(){
+                        match counts{
+                            true => println!("{}\t{}", w, word.count),
+                            false => println!("{}", w),
+                        }
+                    }
+                    return;
+                },
+            };
+            let unknown : Vec<(&String, &banjo_kazooie::words::Word)> = words.iter().filter(|(w, _)|{!dictionary.contains(*w)}).collect();
+            for (w, word) in unknown.iter(){
+                let uids : Vec<String> = word.uids.iter().map(|u|{format!("0x{:04X}", u)}).collect();
+                println!("{}: {}", w, uids.join(", "));
+            }
+            eprintln!("{} of {} words aren't in the dictionary", unknown.len(), words.len());
+            if !unknown.is_empty(){
+                std::process::exit(1);
+            }
+        },
+        _=> panic!("invalid text command \"{}\" provided\n try: words", sub_cmd),
+    }
+}
+
+fn demo_command(args: &[String]){
+    let mut args = args.to_vec();
+    let sub_cmd = args.remove(0);
+    match sub_cmd.as_str() {
+        "to-m64" => {
+            let demo = read_demo(&mut args);
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            fs::write(out_path, demo.to_m64()).expect("Could not write m64");
+        },
+        "stats" => {
+            let demo = read_demo(&mut args);
+            let frames = demo.frame_count();
+            println!("{} inputs, {} frames ({:.2}s at 60fps), flag 0x{:02X}", demo.inputs.len(), frames, frames as f64 / 60.0, demo.frame1_flag());
+            for (name, held, presses) in demo.button_usage().iter().filter(|b|{b.1 != 0}){
+                println!("  {:<8} held {} frames, pressed {} times", name, held, presses);
+            }
+            let errors = demo.validate();
+            for e in errors.iter(){
+                println!("error: {}", e);
+            }
+            if !errors.is_empty(){
+                std::process::exit(1);
+            }
+        },
+        "from-m64" => {
+            let flag = take_flag_value(&mut args, "--flag").map(|f|{parse_int(&f) as u8}).unwrap_or(0);
+            let in_path = Path::new(args.get(0).expect("No in path provided"));
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let demo = banjo_kazooie::asset::DemoButtonFile::from_m64(&in_bytes, flag).unwrap_or_else(|e|{panic!("{:?}: {}", in_path, e)});
+            demo.write(out_path, &banjo_kazooie::asset::ExtractOptions::new());
+        },
+        "cut" => {
+            let frames = take_flag_value(&mut args, "--frames").expect("No frame range provided");
+            let (start, end) = frames.split_once("..").expect("frame range must look like start..end");
+            let demo = read_demo(&mut args);
+            let start = if start.is_empty() {0} else {parse_int(start)};
+            let end = if end.is_empty() {demo.frame_count()} else {parse_int(end)};
+            assert!(start < end, "frame range {} is empty", frames);
+            let out_path = Path::new(args.get(1).expect("No out path provided"));
+            demo.cut(start, end).write(out_path, &banjo_kazooie::asset::ExtractOptions::new());
+        },
+        "concat" => {
+            //every input but the last is appended in order, the first's flag is kept
+            assert!(args.len() >= 3, "concat needs at least two demos and an out path");
+            let out_path = Path::new(args.last().unwrap());
+            let demo = args[1..args.len() - 1].iter()
+                .fold(banjo_kazooie::asset::DemoButtonFile::read(Path::new(&args[0])), |acc, p|{
+                    acc.concat(&banjo_kazooie::asset::DemoButtonFile::read(Path::new(p)))
+                });
+            demo.write(out_path, &banjo_kazooie::asset::ExtractOptions::new());
+        },
+        _=> panic!("invalid demo command \"{}\" provided\n try: to-m64, from-m64, stats, cut, or concat", sub_cmd),
+    }
+}
+
+/// an asset bin with --uid, or an extracted .demo
+fn read_demo(args: &mut Vec<String>) -> banjo_kazooie::asset::DemoButtonFile{
+    let uid = take_flag_value(args, "--uid").map(|u|{parse_int(&u)});
+    let in_path = Path::new(args.get(0).expect("No in path provided"));
+    return match uid{
+        Some(uid) => {
+            let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
+            let bytes = banjo_kazooie::AssetFolder::asset_bytes(&in_bytes, uid).expect("uid is an empty table entry");
+            banjo_kazooie::asset::DemoButtonFile::from_bytes(&bytes)
+        },
+        None => banjo_kazooie::asset::DemoButtonFile::read(in_path),
+    }
+}
+
+/// removes `name <value>` from the argument list and returns the value
+fn take_flag_value(args: &mut Vec<String>, name: &str) -> Option<String>{
+    let i = args.iter().position(|a|{a == name})?;
+    assert!(i + 1 < args.len(), "No value provided for {}", name);
+    let value = args.remove(i + 1);
+    args.remove(i);
+    return Some(value)
+}
+
+/// the name map from --names, or the config's
+fn names_flag(args: &mut Vec<String>, config: &Config) -> banjo_kazooie::names::NameMap{
+    return match take_flag_value(args, "--names").map(|p|{Path::new(&p).to_path_buf()}).or(config.names.clone()){
+        Some(p) => banjo_kazooie::names::NameMap::read(&p),
+        None => banjo_kazooie::names::NameMap::new(),
+    }
+}
+
+/// decimal or 0x prefixed hex
+fn parse_int(s: &str) -> usize{
+    return match s.strip_prefix("0x").or(s.strip_prefix("0X")){
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse::<usize>(),
+    }.expect("could not parse number")
+}
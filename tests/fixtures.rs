@@ -0,0 +1,10 @@
+use std::path::Path;
+
+use bk_asset_tool::banjo_kazooie::fixtures;
+
+#[test]
+fn fixtures_decode_and_rebuild(){
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let errors = fixtures::check(&dir);
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+}